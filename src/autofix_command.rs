@@ -1,54 +1,196 @@
+use crate::junitparser::{self, JUnitParserError, JUnitResultParser};
 use crate::llm::ProviderConfig;
+use crate::pipeline::{FixTarget, PipelineOptions};
+use crate::report::{BaselineDelta, ReportError, RunReport, TestOutcome, TestReport};
 use crate::test_command::{TestCommand, TestCommandError};
-use crate::xcresultparser::{XCResultParser, XCResultParserError, XCResultSummary};
+use crate::tools::TestRunnerTool;
+use crate::xcresultparser::{
+    TestFailure, XCResultParser, XCResultParserError, XCResultSummary, XCTestResults,
+    merge_failures,
+};
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, IsTerminal};
 use std::path::PathBuf;
+use std::time::{Instant, SystemTime};
 
 #[derive(Debug, thiserror::Error)]
 pub enum AutofixError {
     #[error("Failed to parse XCResult: {0}")]
     XCResultParseError(#[from] XCResultParserError),
 
+    #[error("Failed to parse JUnit results: {0}")]
+    JUnitParseError(#[from] JUnitParserError),
+
     #[error("No test failures found")]
     NoTestFailures,
 
     #[error("Failed to get test details: {0}")]
     TestCommandError(#[from] TestCommandError),
+
+    #[error("Failed to write report: {0}")]
+    Report(#[from] ReportError),
+}
+
+/// Overall progress of a batch run, drawn on stderr before each test
+///
+/// Only shown for interactive terminals, so logs and piped output stay clean.
+struct BatchProgress {
+    total: usize,
+    enabled: bool,
+}
+
+impl BatchProgress {
+    /// Width of the bar in characters
+    const WIDTH: usize = 30;
+
+    fn new(total: usize) -> Self {
+        Self {
+            total,
+            enabled: total > 1 && io::stderr().is_terminal(),
+        }
+    }
+
+    /// Draw the progress after the tests in `reports` were processed
+    fn update(&self, reports: &[TestReport]) {
+        if self.enabled {
+            eprintln!("{}\n", self.render(reports));
+        }
+    }
+
+    /// E.g. `[██████░░░░░░] test 3 of 10 · 1 fixed · 1 gave up`
+    fn render(&self, reports: &[TestReport]) -> String {
+        let done = reports.len();
+        let filled = Self::WIDTH * done / self.total.max(1);
+        let fixed = reports.iter().filter(|report| report.is_fixed()).count();
+        let gave_up = reports
+            .iter()
+            .filter(|report| report.outcome == TestOutcome::GaveUp)
+            .count();
+        format!(
+            "[{}{}] test {} of {} · {} fixed · {} gave up",
+            "█".repeat(filled),
+            "░".repeat(Self::WIDTH - filled),
+            done,
+            self.total,
+            fixed,
+            gave_up
+        )
+    }
 }
 
 pub struct AutofixCommand {
     test_result_path: PathBuf,
+    /// Bundles of further CI shards, fixed together with `test_result_path`
+    additional_test_results: Vec<PathBuf>,
     workspace_path: PathBuf,
-    knightrider_mode: bool,
+    fix_target: FixTarget,
     verbose: bool,
     provider_config: ProviderConfig,
+    baseline_report: bool,
+    report_file: Option<PathBuf>,
+    options: PipelineOptions,
+    exclude: Vec<Regex>,
+    include_unexpected_passes: bool,
 }
 
 impl AutofixCommand {
     pub fn new(
         test_result_path: PathBuf,
         workspace_path: PathBuf,
-        knightrider_mode: bool,
+        fix_target: FixTarget,
         verbose: bool,
         provider_config: ProviderConfig,
     ) -> Self {
         Self {
             test_result_path,
+            additional_test_results: Vec::new(),
             workspace_path,
-            knightrider_mode,
+            fix_target,
             verbose,
             provider_config,
+            baseline_report: false,
+            report_file: None,
+            options: PipelineOptions::default(),
+            exclude: Vec::new(),
+            include_unexpected_passes: false,
+        }
+    }
+
+    /// Also fix the failures of these bundles, e.g. the other shards of a sharded CI run
+    pub fn with_additional_test_results(mut self, test_results: Vec<PathBuf>) -> Self {
+        self.additional_test_results = test_results;
+        self
+    }
+
+    /// All bundles to fix, the primary one first
+    fn test_result_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.test_result_path).chain(&self.additional_test_results)
+    }
+
+    /// Skip tests whose name or identifier matches any of the given patterns
+    pub fn with_exclude(mut self, exclude: Vec<Regex>) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    /// Whether a failure matches one of the `--exclude` patterns
+    fn is_excluded(&self, failure: &TestFailure) -> bool {
+        self.exclude.iter().any(|pattern| {
+            pattern.is_match(&failure.test_name)
+                || pattern.is_match(&failure.test_identifier_string)
+                || pattern.is_match(&failure.test_identifier_url)
+        })
+    }
+
+    /// Also fix expected failures that no longer fail, instead of skipping them
+    pub fn with_include_unexpected_passes(mut self, include: bool) -> Self {
+        self.include_unexpected_passes = include;
+        self
+    }
+
+    /// Why a failure should not be handed to the pipeline, if it shouldn't
+    fn skip_reason(&self, failure: &TestFailure, skipped: &HashSet<&str>) -> Option<&'static str> {
+        if self.options.cancellation.is_cancelled() {
+            Some("the run was cancelled")
+        } else if skipped.contains(failure.test_identifier_url.as_str()) {
+            Some("test was skipped")
+        } else if self.is_excluded(failure) {
+            Some("excluded test")
+        } else if failure.is_unexpected_pass() && !self.include_unexpected_passes {
+            Some("expected failure now passes; use --include-unexpected-passes to fix it")
+        } else {
+            None
         }
     }
 
-    /// Execute the autofix command for iOS
-    pub async fn execute_ios(&self) -> Result<(), AutofixError> {
+    /// Apply optional pipeline settings to every test run
+    pub fn with_options(mut self, options: PipelineOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Compare the fresh test results against the original xcresult after the run
+    pub fn with_baseline_report(mut self, enabled: bool) -> Self {
+        self.baseline_report = enabled;
+        self
+    }
+
+    /// Write the structured run report as JSON to the given path at the end of the run
+    pub fn with_report_file(mut self, report_file: Option<PathBuf>) -> Self {
+        self.report_file = report_file;
+        self
+    }
+
+    /// Execute the autofix command for iOS, returning the report of the run
+    pub async fn execute_ios(&self) -> Result<RunReport, AutofixError> {
         println!("Running autofix for iOS...");
 
         if self.verbose {
-            println!(
-                "  [DEBUG] Test result path: {}",
-                self.test_result_path.display()
-            );
+            for path in self.test_result_paths() {
+                println!("  [DEBUG] Test result path: {}", path.display());
+            }
             println!(
                 "  [DEBUG] Workspace path: {}",
                 self.workspace_path.display()
@@ -56,55 +198,164 @@ impl AutofixCommand {
         }
         println!();
 
-        // Parse the xcresult file
+        let run_started = SystemTime::now();
+        let mut report = RunReport::new(&self.provider_config);
+        match self.process_failures(&mut report, run_started).await {
+            Ok(()) => {
+                self.write_report(&mut report, run_started)?;
+                Ok(report)
+            }
+            Err(e) => {
+                // Keep what was done before the failure
+                report.error = Some(e.to_string());
+                if let Err(write_error) = self.write_report(&mut report, run_started) {
+                    eprintln!("⚠️  {}", write_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Parse the test results and run the pipeline for each failed test, adding to `report`
+    async fn process_failures(
+        &self,
+        report: &mut RunReport,
+        run_started: SystemTime,
+    ) -> Result<(), AutofixError> {
+        // Parse the xcresult bundles and JUnit files
+        let parse_started = Instant::now();
         let parser = XCResultParser::new();
-        let summary = parser.parse(&self.test_result_path)?;
+        let mut summaries = Vec::new();
+        let mut all_tests = Vec::new();
+        for path in self.test_result_paths() {
+            if junitparser::is_junit(path) {
+                // Skipped tests are counted in the summary but never listed as failures
+                let summary = JUnitResultParser::new().parse(path)?;
+                if !self.additional_test_results.is_empty() {
+                    println!("📦 {}", path.display());
+                }
+                self.print_summary(&summary, None);
+                summaries.push((path.clone(), summary));
+                continue;
+            }
+
+            let summary = parser.parse(path)?;
+            // Per-test results only refine the summary, so a failure here is not fatal
+            let tests = match parser.parse_tests(path) {
+                Ok(tests) => Some(tests),
+                Err(e) => {
+                    if self.verbose {
+                        println!("  [DEBUG] Could not read per-test results: {}", e);
+                    }
+                    None
+                }
+            };
 
-        // Display summary information
-        self.print_summary(&summary);
+            // Display summary information
+            if !self.additional_test_results.is_empty() {
+                println!("📦 {}", path.display());
+            }
+            self.print_summary(&summary, tests.as_ref());
+
+            summaries.push((path.clone(), summary));
+            all_tests.extend(tests);
+        }
+        let parse_duration = parse_started.elapsed();
+
+        // A test failing in several shards is fixed once
+        let failures = merge_failures(&summaries);
+        let failed_tests: Vec<TestFailure> = failures
+            .iter()
+            .map(|merged| merged.failure.clone())
+            .collect();
+
+        let skipped: HashSet<&str> = all_tests
+            .iter()
+            .flat_map(|tests| tests.test_cases_with_result("Skipped"))
+            .filter_map(|node| node.node_identifier_url.as_deref())
+            .collect();
+
+        report.timings.record("parse_xcresult", parse_duration);
 
         // Process failed tests
-        if summary.failed_tests > 0 {
+        if !failures.is_empty() {
             if self.verbose {
-                Self::print_failed_tests(&summary);
+                Self::print_failed_tests(&failed_tests);
             }
 
             // Process each failed test
             println!(
                 "Processing {} failed test{}...",
-                summary.failed_tests,
-                if summary.failed_tests == 1 { "" } else { "s" }
+                failures.len(),
+                if failures.len() == 1 { "" } else { "s" }
             );
             println!();
 
-            for (index, failure) in summary.test_failures.iter().enumerate() {
+            let progress = BatchProgress::new(failures.len());
+            for (index, merged) in failures.iter().enumerate() {
+                let failure = &merged.failure;
                 println!("═══════════════════════════════════════════════════════════");
                 println!(
                     "Processing test {}/{}: {}",
                     index + 1,
-                    summary.failed_tests,
+                    failures.len(),
                     failure.test_name
                 );
                 println!("═══════════════════════════════════════════════════════════");
 
+                if let Some(reason) = self.skip_reason(failure, &skipped) {
+                    println!("⏭️  Skipping: {}", reason);
+                    println!();
+                    report.add_test(TestReport::skipped(&failure.test_identifier_url));
+                    progress.update(&report.tests);
+                    continue;
+                }
+
                 if self.verbose {
                     println!("  [DEBUG] Target: {}", failure.target_name);
                     println!("  [DEBUG] Test ID: {}", failure.test_identifier_string);
+                    println!("  [DEBUG] Bundle: {}", merged.bundle.display());
                 }
                 println!();
 
-                // Use test command to get detailed information
+                // Use test command to get detailed information from the bundle the test failed in
                 let test_cmd = TestCommand::new(
-                    self.test_result_path.clone(),
+                    merged.bundle.clone(),
                     self.workspace_path.clone(),
                     failure.test_identifier_url.clone(),
-                    self.knightrider_mode,
+                    self.fix_target,
                     self.verbose,
                     self.provider_config.clone(),
-                );
+                )
+                .with_options(self.options.clone());
 
-                test_cmd.execute_ios_silent().await?;
+                // An error only ends this test; the remaining ones are still fixed
+                match test_cmd.execute_ios_silent().await {
+                    Ok(test_report) => report.add_test(test_report),
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        report.add_test(e.report(&failure.test_identifier_url));
+                    }
+                }
                 println!();
+                progress.update(&report.tests);
+            }
+
+            if self.baseline_report {
+                let fresh_results = self.collect_fresh_results(run_started);
+                let delta = BaselineDelta::compute(&failed_tests, &fresh_results);
+                delta.print();
+                report.baseline_delta = Some(delta);
+            }
+
+            // Each test already printed its own breakdown
+            if report.tests.len() > 1 && !report.timings.is_empty() {
+                println!("Total time per step and tool:");
+                report.timings.print();
+            }
+            if self.verbose && report.tests.len() > 1 && !report.tool_metrics.is_empty() {
+                println!("Total tool calls:");
+                report.tool_metrics.print();
             }
         } else {
             return Err(AutofixError::NoTestFailures);
@@ -113,8 +364,76 @@ impl AutofixCommand {
         Ok(())
     }
 
+    /// Write the run report to the configured report file, if any
+    fn write_report(
+        &self,
+        report: &mut RunReport,
+        started: SystemTime,
+    ) -> Result<(), AutofixError> {
+        if let Some(report_file) = &self.report_file {
+            report.set_duration(started.elapsed().unwrap_or_default());
+            report.write_to(report_file)?;
+            println!("📄 Report written to: {}", report_file.display());
+        }
+        Ok(())
+    }
+
+    /// Re-parse the xcresult bundles generated by the test runner during this run
+    ///
+    /// Returns `(test_identifier_url, passed)` pairs ordered from oldest to newest bundle.
+    fn collect_fresh_results(&self, since: SystemTime) -> Vec<(String, bool)> {
+        let runs_dir = self.workspace_path.join(TestRunnerTool::RUNS_DIR);
+        let Ok(entries) = fs::read_dir(&runs_dir) else {
+            return Vec::new();
+        };
+
+        let mut bundles: Vec<(SystemTime, PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let bundle = entry.path().join("test/result.xcresult");
+                let modified = fs::metadata(&bundle).and_then(|m| m.modified()).ok()?;
+                (modified >= since).then_some((modified, bundle))
+            })
+            .collect();
+        bundles.sort();
+
+        // One call per bundle lists every test it ran, passing or failing
+        let parser = XCResultParser::new();
+        let mut results = Vec::new();
+
+        for (_, bundle) in bundles {
+            if self.verbose {
+                println!("  [DEBUG] Re-parsing {}", bundle.display());
+            }
+
+            match parser.parse_tests(&bundle) {
+                Ok(tests) => {
+                    for (result, passed) in [("Passed", true), ("Failed", false)] {
+                        results.extend(
+                            tests
+                                .test_cases_with_result(result)
+                                .into_iter()
+                                .filter_map(|node| {
+                                    Some((node.node_identifier_url.clone()?, passed))
+                                }),
+                        );
+                    }
+                }
+                Err(e) => println!("⚠️  Could not read {}: {}", bundle.display(), e),
+            }
+        }
+
+        results
+    }
+
     /// Print the test summary
-    fn print_summary(&self, summary: &XCResultSummary) {
+    fn print_summary(&self, summary: &XCResultSummary, tests: Option<&XCTestResults>) {
+        let unexpected_passes: Vec<&TestFailure> = summary
+            .test_failures
+            .iter()
+            .filter(|failure| failure.is_unexpected_pass())
+            .collect();
+
         if self.verbose {
             println!("Test Summary:");
             println!("  Title: {}", summary.title);
@@ -123,21 +442,46 @@ impl AutofixCommand {
             println!("  Passed: {}", summary.passed_tests);
             println!("  Failed: {}", summary.failed_tests);
             println!("  Skipped: {}", summary.skipped_tests);
+            println!("  Expected failures: {}", summary.expected_failures);
+            println!(
+                "  Expected failures now passing: {}",
+                unexpected_passes.len()
+            );
+            if let Some(tests) = tests {
+                for (label, result) in [
+                    ("Skipped", "Skipped"),
+                    ("Expected failure", "Expected Failure"),
+                ] {
+                    for node in tests.test_cases_with_result(result) {
+                        println!("    {}: {}", label, node.name);
+                    }
+                }
+            }
             println!();
         } else {
             // In non-verbose mode, just show the key stats
             println!(
-                "Tests: {} passed, {} failed, {} skipped",
-                summary.passed_tests, summary.failed_tests, summary.skipped_tests
+                "Tests: {} passed, {} failed, {} skipped, {} expected failures",
+                summary.passed_tests,
+                summary.failed_tests,
+                summary.skipped_tests,
+                summary.expected_failures
             );
             println!();
         }
+
+        for failure in &unexpected_passes {
+            println!("⚠️  Expected failure now passes: {}", failure.test_name);
+        }
+        if !unexpected_passes.is_empty() {
+            println!();
+        }
     }
 
     /// Print the list of failed tests
-    fn print_failed_tests(summary: &XCResultSummary) {
+    fn print_failed_tests(failures: &[TestFailure]) {
         println!("Failed Tests:");
-        for (index, failure) in summary.test_failures.iter().enumerate() {
+        for (index, failure) in failures.iter().enumerate() {
             println!("  {}. {}", index + 1, failure.test_name);
             println!("     Target: {}", failure.target_name);
             println!("     Test ID: {}", failure.test_identifier_string);
@@ -163,7 +507,7 @@ mod tests {
         let cmd = AutofixCommand::new(
             PathBuf::from("tests/fixtures/sample.xcresult"),
             PathBuf::from("path/to/workspace"),
-            false,
+            FixTarget::Test,
             false,
             config,
         );
@@ -175,13 +519,143 @@ mod tests {
         assert_eq!(cmd.workspace_path, PathBuf::from("path/to/workspace"));
     }
 
+    #[test]
+    fn test_batch_progress_counts_outcomes() {
+        let progress = BatchProgress {
+            total: 4,
+            enabled: false,
+        };
+        let reports = vec![
+            TestReport {
+                test_passed: Some(true),
+                ..TestReport::default()
+            },
+            TestReport {
+                outcome: TestOutcome::GaveUp,
+                ..TestReport::default()
+            },
+        ];
+
+        assert_eq!(
+            progress.render(&reports),
+            format!(
+                "[{}{}] test 2 of 4 · 1 fixed · 1 gave up",
+                "█".repeat(15),
+                "░".repeat(15)
+            )
+        );
+    }
+
+    #[test]
+    fn test_exclude_matches_name_and_identifier() {
+        let cmd = AutofixCommand::new(
+            PathBuf::from("tests/fixtures/sample.xcresult"),
+            PathBuf::from("path/to/workspace"),
+            FixTarget::Test,
+            false,
+            ProviderConfig::default(),
+        )
+        .with_exclude(vec![
+            Regex::new("^testFlaky").unwrap(),
+            Regex::new("/PaymentTests/").unwrap(),
+        ]);
+
+        let failure = |name: &str, class: &str| TestFailure {
+            test_identifier: 1,
+            test_identifier_string: format!("{}/{}", class, name),
+            test_identifier_url: format!(
+                "test://com.apple.xcode/App/AppUITests/{}/{}",
+                class, name
+            ),
+            test_name: name.to_string(),
+            target_name: "AppUITests".to_string(),
+            failure_text: "failed".to_string(),
+        };
+
+        assert!(cmd.is_excluded(&failure("testFlakyLogin()", "LoginTests")));
+        assert!(cmd.is_excluded(&failure("testCheckout()", "PaymentTests")));
+        assert!(!cmd.is_excluded(&failure("testLogin()", "LoginTests")));
+    }
+
+    #[test]
+    fn test_skipped_and_unexpected_passes_are_not_fixed() {
+        let json = fs::read_to_string("tests/fixtures/test_results_tests.json").unwrap();
+        let tests: XCTestResults = serde_json::from_str(&json).unwrap();
+        let skipped: HashSet<&str> = tests
+            .test_cases_with_result("Skipped")
+            .iter()
+            .filter_map(|node| node.node_identifier_url.as_deref())
+            .collect();
+
+        let failure = |name: &str, failure_text: &str| TestFailure {
+            test_identifier: 1,
+            test_identifier_string: format!("AutoFixSamplerUITests/{}()", name),
+            test_identifier_url: format!(
+                "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/{}",
+                name
+            ),
+            test_name: format!("{}()", name),
+            target_name: "AutoFixSamplerUITests".to_string(),
+            failure_text: failure_text.to_string(),
+        };
+        let cmd = || {
+            AutofixCommand::new(
+                PathBuf::from("tests/fixtures/sample.xcresult"),
+                PathBuf::from("path/to/workspace"),
+                FixTarget::Test,
+                false,
+                ProviderConfig::default(),
+            )
+        };
+
+        let flipped = failure("testOnboarding", "Expected failure but none recorded");
+        assert_eq!(
+            cmd().skip_reason(&failure("testExample", "Failed to tap button"), &skipped),
+            None
+        );
+        assert_eq!(
+            cmd().skip_reason(&failure("testCamera", "Test skipped"), &skipped),
+            Some("test was skipped")
+        );
+        assert!(cmd().skip_reason(&flipped, &skipped).is_some());
+        assert_eq!(
+            cmd()
+                .with_include_unexpected_passes(true)
+                .skip_reason(&flipped, &skipped),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_run_still_writes_report() {
+        let dir = std::env::temp_dir().join(format!("autofix-run-{}", uuid::Uuid::new_v4()));
+        let report_file = dir.join("report.json");
+        let cmd = AutofixCommand::new(
+            dir.join("missing.xcresult"),
+            dir.clone(),
+            FixTarget::Test,
+            false,
+            ProviderConfig::default(),
+        )
+        .with_report_file(Some(report_file.clone()));
+
+        let error = cmd.execute_ios().await.unwrap_err();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&report_file).unwrap()).unwrap();
+        assert_eq!(json["error"], error.to_string());
+        assert_eq!(json["tests"].as_array().unwrap().len(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[tokio::test]
     async fn test_execute_ios_with_fixture() {
         let config = ProviderConfig::default();
         let cmd = AutofixCommand::new(
             PathBuf::from("tests/fixtures/sample.xcresult"),
             PathBuf::from("path/to/workspace"),
-            false,
+            FixTarget::Test,
             false,
             config,
         );
@@ -194,8 +668,10 @@ mod tests {
         if let Err(e) = result {
             match e {
                 AutofixError::XCResultParseError(_) => {}
+                AutofixError::JUnitParseError(_) => {}
                 AutofixError::NoTestFailures => {}
                 AutofixError::TestCommandError(_) => {}
+                AutofixError::Report(_) => {}
             }
         }
     }
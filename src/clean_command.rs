@@ -0,0 +1,190 @@
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the directory holding autofix artifacts
+const ARTIFACTS_DIR: &str = ".autofix";
+
+/// User configuration inside `.autofix` that cleaning keeps
+const KEPT_FILES: &[&str] = &["tools.json", "protected.toml"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum CleanError {
+    #[error("Failed to remove {path}: {source}")]
+    Remove { path: PathBuf, source: io::Error },
+
+    #[error("Not removing anything without confirmation; pass --yes to clean non-interactively")]
+    ConfirmationRequired,
+}
+
+/// Removes the `.autofix` artifacts of past runs: temp dirs, test runner output,
+/// transcripts and HTTP traces
+pub struct CleanCommand {
+    /// `.autofix` directories to clean, e.g. in the workspace and the current directory
+    artifact_dirs: Vec<PathBuf>,
+    assume_yes: bool,
+}
+
+impl CleanCommand {
+    /// Clean the `.autofix` directories inside `roots`; the same directory is cleaned once
+    pub fn new(roots: &[PathBuf], assume_yes: bool) -> Self {
+        let canonical = |dir: &Path| fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let mut artifact_dirs: Vec<PathBuf> = Vec::new();
+        for root in roots {
+            let dir = root.join(ARTIFACTS_DIR);
+            if !artifact_dirs
+                .iter()
+                .any(|known| canonical(known) == canonical(&dir))
+            {
+                artifact_dirs.push(dir);
+            }
+        }
+        Self {
+            artifact_dirs,
+            assume_yes,
+        }
+    }
+
+    /// Remove the artifacts, asking first unless `--yes`, and return the bytes freed
+    pub fn execute(&self) -> Result<u64, CleanError> {
+        let entries = self.removable_entries();
+        if entries.is_empty() {
+            println!("✨ Nothing to clean");
+            return Ok(0);
+        }
+
+        let total: u64 = entries.iter().map(|(_, size)| size).sum();
+        for (path, size) in &entries {
+            println!("  {}  {}", format_size(*size), path.display());
+        }
+        if !self.confirm(entries.len(), total)? {
+            println!("Nothing removed");
+            return Ok(0);
+        }
+
+        for (path, _) in &entries {
+            let result = if path.is_dir() {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            };
+            result.map_err(|source| CleanError::Remove {
+                path: path.clone(),
+                source,
+            })?;
+        }
+        println!("🧹 Freed {}", format_size(total));
+        Ok(total)
+    }
+
+    /// Entries of the artifact directories with their sizes, configuration left out
+    fn removable_entries(&self) -> Vec<(PathBuf, u64)> {
+        let mut entries: Vec<(PathBuf, u64)> = self
+            .artifact_dirs
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flat_map(|read_dir| read_dir.flatten())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_none_or(|name| !KEPT_FILES.contains(&name))
+            })
+            .map(|entry| {
+                let path = entry.path();
+                let size = disk_usage(&path);
+                (path, size)
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    fn confirm(&self, count: usize, total: u64) -> Result<bool, CleanError> {
+        if self.assume_yes {
+            return Ok(true);
+        }
+        if !io::stdin().is_terminal() {
+            return Err(CleanError::ConfirmationRequired);
+        }
+        print!(
+            "Remove {} entr{} ({})? [y/N] ",
+            count,
+            if count == 1 { "y" } else { "ies" },
+            format_size(total)
+        );
+        let _ = io::stdout().flush();
+        let mut answer = String::new();
+        let _ = io::stdin().lock().read_line(&mut answer);
+        Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+    }
+}
+
+/// Size of a file or directory tree, not following symlinks
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .into_iter()
+        .flat_map(|read_dir| read_dir.flatten())
+        .map(|entry| disk_usage(&entry.path()))
+        .sum()
+}
+
+/// Human-readable size, e.g. `12.3 MB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_clean_removes_artifacts_and_keeps_config() {
+        let workspace = std::env::temp_dir().join(format!("autofix-clean-{}", Uuid::new_v4()));
+        let artifacts = workspace.join(ARTIFACTS_DIR);
+        let run_dir = artifacts
+            .join("test-runner-tool")
+            .join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&run_dir).unwrap();
+        fs::write(run_dir.join("build.log"), vec![b'x'; 2000]).unwrap();
+        fs::write(artifacts.join("trace.jsonl"), "{}\n").unwrap();
+        fs::write(artifacts.join("tools.json"), r#"{"tools": []}"#).unwrap();
+        fs::write(artifacts.join("protected.toml"), "paths = []\n").unwrap();
+
+        // The same directory given twice is cleaned once
+        let command = CleanCommand::new(&[workspace.clone(), workspace.clone()], true);
+        assert_eq!(command.execute().unwrap(), 2003);
+
+        assert!(!artifacts.join("test-runner-tool").exists());
+        assert!(!artifacts.join("trace.jsonl").exists());
+        assert!(artifacts.join("tools.json").exists());
+        assert!(artifacts.join("protected.toml").exists());
+        assert_eq!(command.execute().unwrap(), 0);
+
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GB");
+    }
+}
@@ -0,0 +1,68 @@
+// Selects the Xcode used by every spawned Xcode tool
+//
+// With several Xcodes installed, `xcrun` on PATH may resolve to a different
+// one than the project needs. Xcode tools honor `DEVELOPER_DIR`, so each
+// `xcrun`/`xcodebuild` invocation is created through `command()`, which sets
+// it from `--developer-dir` or the inherited environment variable.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Environment variable Xcode tools read to pick the developer directory
+pub const DEVELOPER_DIR_VAR: &str = "DEVELOPER_DIR";
+
+static DEVELOPER_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use `path` (e.g. `/Applications/Xcode-16.2.app/Contents/Developer`) for all Xcode tools
+///
+/// Only the first call has an effect.
+pub fn set(path: PathBuf) {
+    let _ = DEVELOPER_DIR.set(path);
+}
+
+/// The developer directory from `--developer-dir`, falling back to `DEVELOPER_DIR`
+pub fn get() -> Option<PathBuf> {
+    DEVELOPER_DIR.get().cloned().or_else(|| {
+        std::env::var_os(DEVELOPER_DIR_VAR)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+/// A `Command` for an Xcode tool such as `xcrun` or `xcodebuild`, bound to the selected Xcode
+pub fn command(program: impl AsRef<OsStr>) -> Command {
+    command_with(program, get().as_deref())
+}
+
+/// A `Command` for an Xcode tool bound to `developer_dir` instead of the selected Xcode
+pub fn command_with(program: impl AsRef<OsStr>, developer_dir: Option<&Path>) -> Command {
+    let mut command = Command::new(program);
+    if let Some(developer_dir) = developer_dir {
+        command.env(DEVELOPER_DIR_VAR, developer_dir);
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_developer_dir_is_set_on_spawned_command() {
+        let developer_dir = Path::new("/Applications/Xcode-16.2.app/Contents/Developer");
+
+        let command = command_with("xcrun", Some(developer_dir));
+        let envs: Vec<_> = command.get_envs().collect();
+
+        assert_eq!(
+            envs,
+            vec![(
+                OsStr::new(DEVELOPER_DIR_VAR),
+                Some(developer_dir.as_os_str())
+            )]
+        );
+        assert_eq!(command_with("xcrun", None).get_envs().count(), 0);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::llm::ProviderConfig;
+use crate::pipeline::{AutofixPipeline, FixTarget, PipelineError, PipelineOptions, PromptEstimate};
+use crate::xctestresultdetailparser::{XCTestResultDetailParser, XCTestResultDetailParserError};
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EstimateError {
+    #[error("Failed to parse test details: {0}")]
+    ParseError(#[from] XCTestResultDetailParserError),
+
+    #[error("Failed to build the prompt: {0}")]
+    PipelineError(#[from] PipelineError),
+}
+
+/// Prints the prompt for a test and what sending it would cost, without calling the provider
+pub struct EstimateCommand {
+    test_result_path: PathBuf,
+    workspace_path: PathBuf,
+    test_id: String,
+    fix_target: FixTarget,
+    verbose: bool,
+    provider_config: ProviderConfig,
+    options: PipelineOptions,
+}
+
+impl EstimateCommand {
+    pub fn new(
+        test_result_path: PathBuf,
+        workspace_path: PathBuf,
+        test_id: String,
+        fix_target: FixTarget,
+        verbose: bool,
+        provider_config: ProviderConfig,
+    ) -> Self {
+        Self {
+            test_result_path,
+            workspace_path,
+            test_id,
+            fix_target,
+            verbose,
+            provider_config,
+            options: PipelineOptions::default(),
+        }
+    }
+
+    /// Apply optional pipeline settings, e.g. disabled tools that change the prompt size
+    pub fn with_options(mut self, options: PipelineOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Execute the estimate command for iOS
+    pub fn execute_ios(&self) -> Result<(), EstimateError> {
+        let detail =
+            XCTestResultDetailParser::new().parse(&self.test_result_path, &self.test_id)?;
+
+        let pipeline = AutofixPipeline::new(
+            &self.test_result_path,
+            &self.workspace_path,
+            self.fix_target,
+            self.verbose,
+            self.provider_config.clone(),
+        )?
+        .with_options(self.options.clone());
+        let estimate = pipeline.estimate(&detail)?;
+
+        println!("Prompt:");
+        println!("─────────────────────────────────────────");
+        println!("{}", estimate.prompt);
+        println!("─────────────────────────────────────────");
+        println!();
+        println!("{}", Self::render_summary(&estimate, &self.provider_config));
+        Ok(())
+    }
+
+    /// Token and cost estimate for the first request
+    fn render_summary(estimate: &PromptEstimate, provider_config: &ProviderConfig) -> String {
+        let mut lines = vec![format!(
+            "Provider: {:?} ({})",
+            provider_config.provider_type, provider_config.model
+        )];
+        match &estimate.snapshot_path {
+            Some(path) => lines.push(format!(
+                "Estimated input tokens: ~{} (prompt and tools: {}, snapshot {}: {})",
+                estimate.input_tokens(),
+                estimate.prompt_tokens,
+                path.display(),
+                estimate.image_tokens
+            )),
+            None => lines.push(format!(
+                "Estimated input tokens: ~{} (prompt and tools, no snapshot)",
+                estimate.input_tokens()
+            )),
+        }
+
+        match provider_config
+            .provider_type
+            .input_cost_per_million_tokens(&provider_config.model)
+        {
+            Some(price) => lines.push(format!(
+                "Estimated cost of the first request: ~${:.4} (at ${:.2} per million input tokens)",
+                estimate.input_tokens() as f64 * price / 1_000_000.0,
+                price
+            )),
+            None => lines.push(format!(
+                "Estimated cost: unknown, no price on record for {}",
+                provider_config.model
+            )),
+        }
+        lines.push(
+            "Each further iteration resends the growing conversation, so a full run costs a multiple of this."
+                .to_string(),
+        );
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ProviderType;
+
+    fn estimate(snapshot_path: Option<PathBuf>) -> PromptEstimate {
+        PromptEstimate {
+            prompt: "Fix the test".to_string(),
+            image_tokens: if snapshot_path.is_some() { 1600 } else { 0 },
+            snapshot_path,
+            prompt_tokens: 2400,
+        }
+    }
+
+    #[test]
+    fn test_render_summary_with_known_price() {
+        let config = ProviderConfig::default_for_provider(ProviderType::Claude);
+
+        let summary =
+            EstimateCommand::render_summary(&estimate(Some(PathBuf::from("shot.png"))), &config);
+
+        assert!(summary.contains("Estimated input tokens: ~4000"));
+        assert!(summary.contains("snapshot shot.png: 1600"));
+        // 4000 tokens at $3 per million
+        assert!(summary.contains("~$0.0120"));
+    }
+
+    #[test]
+    fn test_render_summary_with_unknown_price() {
+        let mut config = ProviderConfig::default_for_provider(ProviderType::Groq);
+        config.model = "gemma2-9b-it".to_string();
+
+        let summary = EstimateCommand::render_summary(&estimate(None), &config);
+
+        assert!(summary.contains("Estimated input tokens: ~2400 (prompt and tools, no snapshot)"));
+        assert!(summary.contains("unknown, no price on record for gemma2-9b-it"));
+    }
+}
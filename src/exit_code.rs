@@ -0,0 +1,205 @@
+// Process exit codes
+//
+// CI pipelines branch on how a run ended, so every way out of `main` maps onto
+// one of these codes. The scheme is documented in the README's "Exit Codes".
+
+use crate::autofix_command::AutofixError;
+use crate::clean_command::CleanError;
+use crate::estimate_command::EstimateError;
+use crate::list_failures_command::ListFailuresError;
+use crate::pipeline::{PipelineError, PipelineOutcome};
+use crate::report::{TestOutcome, TestReport};
+use crate::test_command::TestCommandError;
+use crate::verify_command::VerifyError;
+
+/// How a run ended, as the process exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Every targeted test was fixed (or there was nothing else to do)
+    Success = 0,
+    /// Any error not covered by a more specific code
+    Error = 1,
+    /// At least one test was not fixed: the model gave up, looped or ran out of iterations
+    NotFixed = 2,
+    /// xcresulttool or xcodebuild failed
+    Infrastructure = 3,
+    /// Invalid flags or environment, or the provider rejected the configuration
+    Configuration = 4,
+    /// The xcresult bundles contain no failures to fix
+    NoFailures = 5,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Exit the process with this code
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code())
+    }
+
+    /// Code for a finished run from the reports of its tests
+    ///
+    /// A test counts as fixed when the model completed and its last test run passed.
+    pub fn for_reports(reports: &[TestReport]) -> Self {
+        let not_fixed = reports
+            .iter()
+            .any(|report| report.outcome != TestOutcome::Skipped && !report.is_fixed());
+        if not_fixed {
+            ExitCode::NotFixed
+        } else {
+            ExitCode::Success
+        }
+    }
+}
+
+impl From<&PipelineOutcome> for ExitCode {
+    fn from(outcome: &PipelineOutcome) -> Self {
+        match outcome {
+            PipelineOutcome::Fixed => ExitCode::Success,
+            _ => ExitCode::NotFixed,
+        }
+    }
+}
+
+impl From<&PipelineError> for ExitCode {
+    fn from(error: &PipelineError) -> Self {
+        match error {
+            PipelineError::AttachmentError(_)
+            | PipelineError::TestInfrastructureError(_)
+            | PipelineError::BuildFailed(_) => ExitCode::Infrastructure,
+            PipelineError::ProviderConfiguration(_) | PipelineError::Scheme(_) => {
+                ExitCode::Configuration
+            }
+            PipelineError::CreateDirectoryError(_)
+            | PipelineError::FileLocatorError(_)
+            | PipelineError::AnthropicApiError(_) => ExitCode::Error,
+        }
+    }
+}
+
+impl From<&TestCommandError> for ExitCode {
+    fn from(error: &TestCommandError) -> Self {
+        match error {
+            TestCommandError::ParseError(_) | TestCommandError::JUnitParseError(_) => {
+                ExitCode::Infrastructure
+            }
+            TestCommandError::PipelineError { source, .. } => source.into(),
+            TestCommandError::Report(_) => ExitCode::Error,
+            TestCommandError::ProtectedPaths(_) => ExitCode::Configuration,
+        }
+    }
+}
+
+impl From<&AutofixError> for ExitCode {
+    fn from(error: &AutofixError) -> Self {
+        match error {
+            AutofixError::XCResultParseError(_) | AutofixError::JUnitParseError(_) => {
+                ExitCode::Infrastructure
+            }
+            AutofixError::NoTestFailures => ExitCode::NoFailures,
+            AutofixError::TestCommandError(e) => e.into(),
+            AutofixError::Report(_) => ExitCode::Error,
+        }
+    }
+}
+
+impl From<&EstimateError> for ExitCode {
+    fn from(error: &EstimateError) -> Self {
+        match error {
+            EstimateError::ParseError(_) => ExitCode::Infrastructure,
+            EstimateError::PipelineError(e) => e.into(),
+        }
+    }
+}
+
+impl From<&ListFailuresError> for ExitCode {
+    fn from(error: &ListFailuresError) -> Self {
+        match error {
+            ListFailuresError::XCResultParseError(_) => ExitCode::Infrastructure,
+            ListFailuresError::Serialize(_) => ExitCode::Error,
+        }
+    }
+}
+
+impl From<&CleanError> for ExitCode {
+    fn from(error: &CleanError) -> Self {
+        match error {
+            CleanError::Remove { .. } => ExitCode::Error,
+            CleanError::ConfirmationRequired => ExitCode::Configuration,
+        }
+    }
+}
+
+impl From<&VerifyError> for ExitCode {
+    fn from(error: &VerifyError) -> Self {
+        match error {
+            VerifyError::MissingScheme(_) | VerifyError::Scheme(_) => ExitCode::Configuration,
+            VerifyError::Infrastructure(_) => ExitCode::Infrastructure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(outcome: TestOutcome, test_passed: Option<bool>) -> TestReport {
+        TestReport {
+            outcome,
+            test_passed,
+            ..TestReport::default()
+        }
+    }
+
+    #[test]
+    fn test_exit_code_for_reports() {
+        assert_eq!(
+            ExitCode::for_reports(&[
+                report(TestOutcome::Completed, Some(true)),
+                report(TestOutcome::Skipped, None),
+            ]),
+            ExitCode::Success
+        );
+        assert_eq!(
+            ExitCode::for_reports(&[
+                report(TestOutcome::Completed, Some(true)),
+                report(TestOutcome::GaveUp, Some(false)),
+            ]),
+            ExitCode::NotFixed
+        );
+        // Completing without a passing test run is not a fix
+        assert_eq!(
+            ExitCode::for_reports(&[report(TestOutcome::NotFixed, Some(false))]),
+            ExitCode::NotFixed
+        );
+        // Neither is completing without running the test at all
+        assert_eq!(
+            ExitCode::for_reports(&[report(TestOutcome::Unverified, None)]),
+            ExitCode::NotFixed
+        );
+        assert_eq!(ExitCode::from(&PipelineOutcome::Fixed), ExitCode::Success);
+        assert_eq!(
+            ExitCode::from(&PipelineOutcome::Unverified),
+            ExitCode::NotFixed
+        );
+        assert_eq!(
+            ExitCode::from(&PipelineOutcome::BudgetExhausted),
+            ExitCode::NotFixed
+        );
+    }
+
+    #[test]
+    fn test_exit_code_for_errors() {
+        assert_eq!(ExitCode::from(&AutofixError::NoTestFailures).code(), 5);
+        let build_failed = PipelineError::BuildFailed("error: cannot find 'x'".to_string());
+        assert_eq!(ExitCode::from(&build_failed), ExitCode::Infrastructure);
+        let auth = PipelineError::ProviderConfiguration(crate::llm::LLMError::AuthenticationError);
+        let error = TestCommandError::PipelineError {
+            report: Box::new(TestReport::failed("test://a", auth.to_string())),
+            source: auth,
+        };
+        assert_eq!(ExitCode::from(&error), ExitCode::Configuration);
+    }
+}
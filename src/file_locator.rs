@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum FileLocatorError {
+    #[error("Invalid test identifier URL: {0}")]
+    InvalidTestIdentifierUrl(String),
+
+    #[error("File not found for class: {0}")]
+    FileNotFound(String),
+
+    #[error(
+        "No test file found for class {class_name}: {} match the name but contain no XCTest or Swift Testing code (no `import XCTest`, `XCTestCase` subclass or `@Test`)",
+        display_paths(candidates)
+    )]
+    NotATestFile {
+        class_name: String,
+        candidates: Vec<PathBuf>,
+    },
+
+    #[error("Search for {file_name} stopped after {searched_dirs} directories: {reason}")]
+    SearchTimedOut {
+        file_name: String,
+        searched_dirs: usize,
+        reason: String,
+    },
+
+    #[error("Invalid test file index {0}: {1}")]
+    InvalidIndex(PathBuf, String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Comma-separated list of `paths` for error messages
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Finds the source file declaring a failing test
+///
+/// `XCWorkspaceFileLocator` searches an Xcode workspace for `<Class>.swift` or `<Class>.m`;
+/// other platforms and project layouts plug in their own implementation.
+pub trait FileLocator: Send + Sync {
+    /// Path of the file containing the test with `test_identifier_url`
+    fn locate_file(&self, test_identifier_url: &str) -> Result<PathBuf, FileLocatorError>;
+}
+
+/// Resolves test classes through an index file instead of searching the workspace
+///
+/// The index is a JSON object mapping test class names to file paths, relative
+/// to the workspace unless absolute:
+/// `{"LoginTests": "app/src/androidTest/java/com/example/LoginTests.kt"}`
+pub struct IndexFileLocator {
+    index_path: PathBuf,
+    workspace_path: PathBuf,
+}
+
+impl IndexFileLocator {
+    pub fn new<P: AsRef<Path>>(index_path: P, workspace_path: P) -> Self {
+        Self {
+            index_path: index_path.as_ref().to_path_buf(),
+            workspace_path: workspace_path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl FileLocator for IndexFileLocator {
+    fn locate_file(&self, test_identifier_url: &str) -> Result<PathBuf, FileLocatorError> {
+        let class_name = test_class_name(test_identifier_url)?;
+        let contents = fs::read_to_string(&self.index_path)?;
+        let index: HashMap<String, PathBuf> = serde_json::from_str(&contents)
+            .map_err(|e| FileLocatorError::InvalidIndex(self.index_path.clone(), e.to_string()))?;
+
+        match index.get(&class_name) {
+            Some(path) => Ok(self.workspace_path.join(path)),
+            None => Err(FileLocatorError::FileNotFound(class_name)),
+        }
+    }
+}
+
+/// Extract the class name from a test identifier URL
+/// The class name is the second-to-last component (before the test method name)
+///
+/// Example: "test://com.apple.xcode/MyApp/MyUITests/Features/Login/Screens/LoginScreenTests/testLoginFlow"
+/// Returns: "LoginScreenTests"
+pub fn test_class_name(test_identifier_url: &str) -> Result<String, FileLocatorError> {
+    let parts = parse_test_identifier_url(test_identifier_url)?;
+
+    // We need at least: scheme, project, target, class, testMethod = 5 parts
+    if parts.len() < 5 {
+        return Err(FileLocatorError::InvalidTestIdentifierUrl(
+            test_identifier_url.to_string(),
+        ));
+    }
+
+    // The class name is the second-to-last component (before the test method)
+    let class_name = &parts[parts.len() - 2];
+
+    Ok(class_name.to_string())
+}
+
+/// Parse the test identifier URL into parts
+fn parse_test_identifier_url(url: &str) -> Result<Vec<String>, FileLocatorError> {
+    // Remove the "test://" prefix
+    let without_prefix = url
+        .strip_prefix("test://")
+        .ok_or_else(|| FileLocatorError::InvalidTestIdentifierUrl(url.to_string()))?;
+
+    // Split by '/' and collect parts
+    let parts: Vec<String> = without_prefix.split('/').map(|s| s.to_string()).collect();
+
+    if parts.is_empty() {
+        return Err(FileLocatorError::InvalidTestIdentifierUrl(url.to_string()));
+    }
+
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_parse_test_identifier_url() {
+        let url = "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/testExample";
+
+        let parts = parse_test_identifier_url(url).unwrap();
+
+        assert_eq!(parts.len(), 5);
+        assert_eq!(parts[0], "com.apple.xcode");
+        assert_eq!(parts[1], "AutoFixSampler");
+        assert_eq!(parts[2], "AutoFixSamplerUITests");
+        assert_eq!(parts[3], "AutoFixSamplerUITests");
+        assert_eq!(parts[4], "testExample");
+    }
+
+    #[test]
+    fn test_parse_invalid_url() {
+        let url = "invalid://url";
+
+        let result = parse_test_identifier_url(url);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_index_locator_resolves_class_from_index() {
+        let workspace = std::env::temp_dir().join(format!("file-locator-{}", Uuid::new_v4()));
+        fs::create_dir_all(&workspace).unwrap();
+        let index_path = workspace.join("test-files.json");
+        fs::write(
+            &index_path,
+            r#"{"LoginTests": "app/src/androidTest/java/com/example/LoginTests.kt"}"#,
+        )
+        .unwrap();
+
+        let locator = IndexFileLocator::new(&index_path, &workspace);
+
+        assert_eq!(
+            locator
+                .locate_file("test://com.apple.xcode/App/AppUITests/LoginTests/testLogin")
+                .unwrap(),
+            workspace.join("app/src/androidTest/java/com/example/LoginTests.kt")
+        );
+        assert!(matches!(
+            locator.locate_file("test://com.apple.xcode/App/AppUITests/SignUpTests/testSignUp"),
+            Err(FileLocatorError::FileNotFound(class_name)) if class_name == "SignUpTests"
+        ));
+
+        // Clean up
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+}
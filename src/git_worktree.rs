@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Files with uncommitted changes in the git working tree `workspace` lives in
+///
+/// Paths are canonical, so they can be compared with `Path::canonicalize` of a
+/// file. Untracked files count as uncommitted. Deleted files are left out, and
+/// the set is empty when `workspace` isn't inside a git repository or git isn't
+/// available.
+pub fn uncommitted_files(workspace: &Path) -> HashSet<PathBuf> {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(workspace)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| output.stdout)
+    };
+    let (Some(toplevel), Some(status)) = (
+        git(&["rev-parse", "--show-toplevel"]),
+        git(&["status", "--porcelain", "-z", "--untracked-files=all"]),
+    ) else {
+        return HashSet::new();
+    };
+    let toplevel = PathBuf::from(String::from_utf8_lossy(&toplevel).trim_end());
+
+    // Entries are `XY path`, and renames and copies are followed by their source path
+    let mut entries = status.split(|&byte| byte == 0);
+    let mut files = HashSet::new();
+    while let Some(entry) = entries.next() {
+        let Some(path) = entry.get(3..) else {
+            continue;
+        };
+        if matches!(entry[0], b'R' | b'C') {
+            entries.next();
+        }
+        if let Ok(path) = toplevel
+            .join(String::from_utf8_lossy(path).as_ref())
+            .canonicalize()
+        {
+            files.insert(path);
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use uuid::Uuid;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args([
+                "-c",
+                "user.name=autofix",
+                "-c",
+                "user.email=autofix@example.com",
+            ])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_detects_modified_and_clean_files() {
+        if Command::new("git").arg("--version").output().is_err() {
+            return;
+        }
+
+        let repo = std::env::temp_dir().join(format!("autofix-git-{}", Uuid::new_v4()));
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q"]);
+        fs::write(repo.join("Clean.swift"), "let a = 1\n").unwrap();
+        fs::write(repo.join("Dirty.swift"), "let b = 1\n").unwrap();
+        fs::write(repo.join("Renamed.swift"), "let e = 1\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "init"]);
+
+        fs::write(repo.join("Dirty.swift"), "let b = 2\n").unwrap();
+        fs::write(repo.join("New.swift"), "let c = 1\n").unwrap();
+
+        fs::create_dir_all(repo.join("App")).unwrap();
+        fs::write(repo.join("App/Nested.swift"), "let d = 1\n").unwrap();
+        git(&repo, &["mv", "Renamed.swift", "Moved.swift"]);
+
+        // Paths are relative to the repository, not the directory asked about
+        let dirty = uncommitted_files(&repo.join("App"));
+        let dirty_file = |name: &str| dirty.contains(&repo.join(name).canonicalize().unwrap());
+        assert!(!dirty_file("Clean.swift"));
+        assert!(dirty_file("Dirty.swift"));
+        assert!(dirty_file("New.swift"));
+        assert!(dirty_file("App/Nested.swift"));
+        assert!(dirty_file("Moved.swift"));
+        assert_eq!(dirty.len(), 4);
+
+        // Clean up
+        fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn test_outside_git_repository_is_clean() {
+        let dir = std::env::temp_dir().join(format!("autofix-nogit-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("File.swift"), "let a = 1\n").unwrap();
+
+        // temp_dir() is not normally inside a repository
+        if Command::new("git")
+            .arg("-C")
+            .arg(&dir)
+            .args(["rev-parse", "--git-dir"])
+            .output()
+            .is_ok_and(|output| !output.status.success())
+        {
+            assert!(uncommitted_files(&dir).is_empty());
+        }
+
+        // Clean up
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,397 @@
+use crate::xcresultparser::{TestFailure, XCResultSummary};
+use crate::xctestresultdetailparser::{FailureDetail, XCTestResultDetail};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// `/path/to/LoginTests.swift:28`, where JUnit reporters put the failure location
+static LOCATION_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"([^\s:/]+\.swift):(\d+)").unwrap());
+
+#[derive(Debug, thiserror::Error)]
+pub enum JUnitParserError {
+    #[error("Failed to read JUnit file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid JUnit XML: {0}")]
+    Parse(String),
+
+    #[error("Test '{0}' not found in the JUnit results")]
+    TestNotFound(String),
+}
+
+impl From<quick_xml::Error> for JUnitParserError {
+    fn from(error: quick_xml::Error) -> Self {
+        JUnitParserError::Parse(error.to_string())
+    }
+}
+
+impl From<quick_xml::events::attributes::AttrError> for JUnitParserError {
+    fn from(error: quick_xml::events::attributes::AttrError) -> Self {
+        JUnitParserError::Parse(error.to_string())
+    }
+}
+
+/// Whether `path` is a JUnit XML file rather than an xcresult bundle
+///
+/// Bundles are directories; a file counts as JUnit when it ends in `.xml` or starts with `<`.
+pub fn is_junit(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+    path.extension().is_some_and(|extension| extension == "xml")
+        || fs::read_to_string(path).is_ok_and(|content| content.trim_start().starts_with('<'))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Passed,
+    Failed(String),
+    Skipped,
+}
+
+/// A `<testcase>` with the suite it's in
+#[derive(Debug, Clone, PartialEq)]
+struct TestCase {
+    suite: String,
+    class_name: String,
+    name: String,
+    time: f64,
+    outcome: Outcome,
+}
+
+impl TestCase {
+    /// Test target and class, from a `classname` like `AppUITests.LoginTests`
+    fn target_and_class(&self) -> (&str, &str) {
+        match self.class_name.rsplit_once('.') {
+            Some((target, class)) => (target, class),
+            None if !self.suite.is_empty() && self.suite != self.class_name => {
+                (&self.suite, &self.class_name)
+            }
+            None => (&self.class_name, &self.class_name),
+        }
+    }
+
+    /// Identifier in xcresult's `test://com.apple.xcode/{scheme}/{target}/{class}/{method}` form
+    ///
+    /// JUnit doesn't name the scheme, so the target stands in for it; the scheme check
+    /// before each run picks the actual scheme.
+    fn identifier_url(&self) -> String {
+        let (target, class) = self.target_and_class();
+        format!(
+            "test://com.apple.xcode/{}/{}/{}/{}",
+            target, target, class, self.name
+        )
+    }
+
+    fn failure_text(&self) -> Option<&str> {
+        match &self.outcome {
+            Outcome::Failed(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// Reads test results from JUnit XML, as produced by xcbeautify, xcpretty, fastlane
+/// or other CI tooling, into the shapes the xcresult parsers return
+pub struct JUnitResultParser;
+
+impl JUnitResultParser {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Summary of the JUnit file at `path`, with one `TestFailure` per failed test case
+    pub fn parse<P: AsRef<Path>>(&self, path: P) -> Result<XCResultSummary, JUnitParserError> {
+        let cases = parse_test_cases(&read(path.as_ref())?)?;
+        Ok(summary(&cases))
+    }
+
+    /// Detail of the test with identifier URL `test_id`, its failures located where the
+    /// message names a Swift file
+    pub fn parse_detail<P: AsRef<Path>>(
+        &self,
+        path: P,
+        test_id: &str,
+    ) -> Result<XCTestResultDetail, JUnitParserError> {
+        let cases = parse_test_cases(&read(path.as_ref())?)?;
+        cases
+            .iter()
+            .find(|case| case.identifier_url() == test_id)
+            .map(detail)
+            .ok_or_else(|| JUnitParserError::TestNotFound(test_id.to_string()))
+    }
+}
+
+impl Default for JUnitResultParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read(path: &Path) -> Result<String, JUnitParserError> {
+    fs::read_to_string(path).map_err(|source| JUnitParserError::Read {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<String, JUnitParserError> {
+    Ok(match element.try_get_attribute(name)? {
+        Some(attribute) => attribute
+            .unescape_value()
+            .map_err(JUnitParserError::from)?
+            .into_owned(),
+        None => String::new(),
+    })
+}
+
+/// Every `<testcase>` of the document, in order
+fn parse_test_cases(xml: &str) -> Result<Vec<TestCase>, JUnitParserError> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut suites: Vec<String> = Vec::new();
+    let mut cases = Vec::new();
+    let mut current: Option<TestCase> = None;
+    // Inside a <failure> or <error>, whose text is appended to the failure message
+    let mut in_failure = false;
+
+    loop {
+        let event = reader.read_event()?;
+        let is_empty = matches!(event, Event::Empty(_));
+        match event {
+            Event::Start(element) | Event::Empty(element) => match element.name().as_ref() {
+                b"testsuite" if !is_empty => suites.push(attribute(&element, "name")?),
+                b"testcase" => {
+                    let case = TestCase {
+                        suite: suites.last().cloned().unwrap_or_default(),
+                        class_name: attribute(&element, "classname")?,
+                        name: attribute(&element, "name")?,
+                        time: attribute(&element, "time")?.parse().unwrap_or_default(),
+                        outcome: Outcome::Passed,
+                    };
+                    if is_empty {
+                        cases.push(case);
+                    } else {
+                        current = Some(case);
+                    }
+                }
+                b"failure" | b"error" => {
+                    let message = attribute(&element, "message")?;
+                    if let Some(case) = current.as_mut() {
+                        case.outcome = Outcome::Failed(message);
+                    }
+                    in_failure = !is_empty;
+                }
+                b"skipped" => {
+                    if let Some(case) = current.as_mut() {
+                        case.outcome = Outcome::Skipped;
+                    }
+                }
+                _ => {}
+            },
+            Event::Text(text) if in_failure => {
+                let text = text.unescape().map_err(JUnitParserError::from)?;
+                append_failure_text(current.as_mut(), &text);
+            }
+            Event::CData(text) if in_failure => {
+                append_failure_text(current.as_mut(), &String::from_utf8_lossy(&text));
+            }
+            Event::End(element) => match element.name().as_ref() {
+                b"testsuite" => {
+                    suites.pop();
+                }
+                b"testcase" => cases.extend(current.take()),
+                b"failure" | b"error" => in_failure = false,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(cases)
+}
+
+fn append_failure_text(case: Option<&mut TestCase>, text: &str) {
+    if let Some(TestCase {
+        outcome: Outcome::Failed(message),
+        ..
+    }) = case
+    {
+        let text = text.trim();
+        if !text.is_empty() && !message.contains(text) {
+            if !message.is_empty() {
+                message.push('\n');
+            }
+            message.push_str(text);
+        }
+    }
+}
+
+fn summary(cases: &[TestCase]) -> XCResultSummary {
+    let count = |outcome: fn(&Outcome) -> bool| {
+        cases.iter().filter(|case| outcome(&case.outcome)).count() as u32
+    };
+    let failed_tests = count(|outcome| matches!(outcome, Outcome::Failed(_)));
+    let test_failures = cases
+        .iter()
+        .filter_map(|case| Some((case, case.failure_text()?)))
+        .enumerate()
+        .map(|(index, (case, failure_text))| {
+            let (target, class) = case.target_and_class();
+            TestFailure {
+                test_identifier: index as u32 + 1,
+                test_identifier_string: format!("{}/{}", class, case.name),
+                test_identifier_url: case.identifier_url(),
+                test_name: case.name.clone(),
+                target_name: target.to_string(),
+                failure_text: failure_text.to_string(),
+            }
+        })
+        .collect();
+
+    XCResultSummary {
+        title: "JUnit test results".to_string(),
+        result: if failed_tests > 0 { "Failed" } else { "Passed" }.to_string(),
+        environment_description: String::new(),
+        start_time: 0.0,
+        finish_time: 0.0,
+        total_test_count: cases.len() as u32,
+        passed_tests: count(|outcome| *outcome == Outcome::Passed),
+        failed_tests,
+        skipped_tests: count(|outcome| *outcome == Outcome::Skipped),
+        expected_failures: 0,
+        devices_and_configurations: Vec::new(),
+        test_failures,
+        statistics: Vec::new(),
+        top_insights: Vec::new(),
+        extra: serde_json::Map::new(),
+    }
+}
+
+fn detail(case: &TestCase) -> XCTestResultDetail {
+    let failures = case
+        .failure_text()
+        .map(|text| {
+            let location = LOCATION_PATTERN.captures(text);
+            vec![FailureDetail {
+                message: text.lines().next().unwrap_or_default().to_string(),
+                file: location.as_ref().map(|captures| captures[1].to_string()),
+                line: location.and_then(|captures| captures[2].parse().ok()),
+            }]
+        })
+        .unwrap_or_default();
+    let (_, class) = case.target_and_class();
+
+    XCTestResultDetail {
+        test_identifier: format!("{}/{}", class, case.name),
+        test_identifier_url: case.identifier_url(),
+        test_name: case.name.clone(),
+        test_result: match case.outcome {
+            Outcome::Passed => "Passed",
+            Outcome::Failed(_) => "Failed",
+            Outcome::Skipped => "Skipped",
+        }
+        .to_string(),
+        test_description: String::new(),
+        start_time: 0.0,
+        duration: format!("{:.2}s", case.time),
+        duration_in_seconds: case.time,
+        has_media_attachments: false,
+        has_performance_metrics: false,
+        devices: Vec::new(),
+        test_plan_configurations: Vec::new(),
+        test_runs: Vec::new(),
+        failures,
+        performance_metrics: Vec::new(),
+        extra: serde_json::Map::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    const JUNIT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="AppUITests.xctest" tests="4" failures="2">
+  <testsuite name="AppUITests.LoginTests" tests="3" failures="1">
+    <testcase classname="AppUITests.LoginTests" name="testLogin" time="4.2">
+      <failure message="XCTAssertTrue failed - Login button not found">/Users/ci/App/AppUITests/LoginTests.swift:28</failure>
+    </testcase>
+    <testcase classname="AppUITests.LoginTests" name="testLogout" time="2.0"/>
+    <testcase classname="AppUITests.LoginTests" name="testCamera" time="0.1">
+      <skipped message="Requires a camera"/>
+    </testcase>
+  </testsuite>
+  <testsuite name="CartTests" tests="1" errors="1">
+    <testcase classname="CartTests" name="testTotal()" time="0.5">
+      <error message="Expectation failed: total == 42"><![CDATA[CartTests.swift:12: total was 41]]></error>
+    </testcase>
+  </testsuite>
+</testsuites>"#;
+
+    #[test]
+    fn test_summary_lists_failed_cases() {
+        let summary = summary(&parse_test_cases(JUNIT).unwrap());
+
+        assert_eq!(summary.result, "Failed");
+        assert_eq!(summary.total_test_count, 4);
+        assert_eq!(summary.passed_tests, 1);
+        assert_eq!(summary.failed_tests, 2);
+        assert_eq!(summary.skipped_tests, 1);
+
+        let failure = &summary.test_failures[0];
+        assert_eq!(
+            failure.test_identifier_url,
+            "test://com.apple.xcode/AppUITests/AppUITests/LoginTests/testLogin"
+        );
+        assert_eq!(failure.test_identifier_string, "LoginTests/testLogin");
+        assert_eq!(failure.target_name, "AppUITests");
+        assert_eq!(
+            failure.failure_text,
+            "XCTAssertTrue failed - Login button not found\n/Users/ci/App/AppUITests/LoginTests.swift:28"
+        );
+        // Without a target in the class name, the suite is taken as the target
+        assert_eq!(summary.test_failures[1].target_name, "CartTests");
+        assert_eq!(summary.test_failures[1].test_name, "testTotal()");
+    }
+
+    #[test]
+    fn test_detail_locates_failure() {
+        let path = std::env::temp_dir().join(format!("autofix-junit-{}.xml", Uuid::new_v4()));
+        fs::write(&path, JUNIT).unwrap();
+        assert!(is_junit(&path));
+
+        let detail = JUnitResultParser::new()
+            .parse_detail(
+                &path,
+                "test://com.apple.xcode/AppUITests/AppUITests/LoginTests/testLogin",
+            )
+            .unwrap();
+        assert_eq!(detail.test_result, "Failed");
+        assert_eq!(detail.duration_in_seconds, 4.2);
+        assert_eq!(
+            detail.failures,
+            vec![FailureDetail {
+                message: "XCTAssertTrue failed - Login button not found".to_string(),
+                file: Some("LoginTests.swift".to_string()),
+                line: Some(28),
+            }]
+        );
+        assert!(matches!(
+            JUnitResultParser::new().parse_detail(&path, "test://com.apple.xcode/App/App/X/y"),
+            Err(JUnitParserError::TestNotFound(_))
+        ));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
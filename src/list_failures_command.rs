@@ -0,0 +1,173 @@
+use crate::xcresultparser::{XCResultParser, XCResultParserError, XCResultSummary};
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ListFailuresError {
+    #[error("Failed to parse XCResult: {0}")]
+    XCResultParseError(#[from] XCResultParserError),
+
+    #[error("Failed to serialize failures: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// Output format for `list-failures`
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ListFormat {
+    Json,
+    Table,
+}
+
+/// A failing test as printed by `list-failures`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FailureEntry {
+    pub name: String,
+    pub identifier_url: String,
+    pub target: String,
+    pub failure_text: String,
+}
+
+/// Prints the failing tests of an xcresult bundle without contacting any LLM
+pub struct ListFailuresCommand {
+    test_result_path: PathBuf,
+    format: ListFormat,
+}
+
+impl ListFailuresCommand {
+    pub fn new(test_result_path: PathBuf, format: ListFormat) -> Self {
+        Self {
+            test_result_path,
+            format,
+        }
+    }
+
+    /// Execute the list-failures command for iOS
+    pub fn execute_ios(&self) -> Result<(), ListFailuresError> {
+        let summary = XCResultParser::new().parse(&self.test_result_path)?;
+        println!("{}", Self::render(&summary, self.format)?);
+        Ok(())
+    }
+
+    /// Render the failures of `summary` in the requested format
+    fn render(summary: &XCResultSummary, format: ListFormat) -> Result<String, ListFailuresError> {
+        let entries: Vec<FailureEntry> = summary
+            .test_failures
+            .iter()
+            .map(|failure| FailureEntry {
+                name: failure.test_name.clone(),
+                identifier_url: failure.test_identifier_url.clone(),
+                target: failure.target_name.clone(),
+                failure_text: failure.failure_text.clone(),
+            })
+            .collect();
+
+        match format {
+            ListFormat::Json => Ok(serde_json::to_string_pretty(&entries)?),
+            ListFormat::Table => Ok(Self::render_table(&entries)),
+        }
+    }
+
+    fn render_table(entries: &[FailureEntry]) -> String {
+        if entries.is_empty() {
+            return "No failing tests".to_string();
+        }
+
+        let name_width = entries
+            .iter()
+            .map(|entry| entry.name.len())
+            .chain(std::iter::once("TEST".len()))
+            .max()
+            .unwrap_or_default();
+        let target_width = entries
+            .iter()
+            .map(|entry| entry.target.len())
+            .chain(std::iter::once("TARGET".len()))
+            .max()
+            .unwrap_or_default();
+
+        let mut lines = vec![format!(
+            "{:name_width$}  {:target_width$}  FAILURE",
+            "TEST", "TARGET"
+        )];
+        for entry in entries {
+            // Keep one row per test even for multi-line failure messages
+            let failure = entry.failure_text.lines().next().unwrap_or_default();
+            lines.push(format!(
+                "{:name_width$}  {:target_width$}  {}",
+                entry.name, entry.target, failure
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary_with_failures() -> XCResultSummary {
+        serde_json::from_value(serde_json::json!({
+            "title": "Test - App",
+            "result": "Failed",
+            "environmentDescription": "App · iPhone 16",
+            "startTime": 0.0,
+            "finishTime": 1.0,
+            "totalTestCount": 3,
+            "passedTests": 1,
+            "failedTests": 2,
+            "skippedTests": 0,
+            "expectedFailures": 0,
+            "devicesAndConfigurations": [],
+            "testFailures": [
+                {
+                    "testIdentifier": 1,
+                    "testIdentifierString": "LoginTests/testLogin()",
+                    "testIdentifierURL": "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin",
+                    "testName": "testLogin()",
+                    "targetName": "AppUITests",
+                    "failureText": "Button not found\nsecond line"
+                },
+                {
+                    "testIdentifier": 2,
+                    "testIdentifierString": "LoginTests/testLogout()",
+                    "testIdentifierURL": "test://com.apple.xcode/App/AppUITests/LoginTests/testLogout",
+                    "testName": "testLogout()",
+                    "targetName": "AppUITests",
+                    "failureText": "Timed out"
+                }
+            ],
+            "statistics": [],
+            "topInsights": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_json() {
+        let output =
+            ListFailuresCommand::render(&summary_with_failures(), ListFormat::Json).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(parsed.as_array().unwrap().len(), 2);
+        assert_eq!(parsed[0]["name"], "testLogin()");
+        assert_eq!(
+            parsed[0]["identifier_url"],
+            "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin"
+        );
+        assert_eq!(parsed[0]["target"], "AppUITests");
+        assert_eq!(parsed[1]["failure_text"], "Timed out");
+    }
+
+    #[test]
+    fn test_render_table() {
+        let output =
+            ListFailuresCommand::render(&summary_with_failures(), ListFormat::Table).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("TEST"));
+        assert!(lines[1].contains("testLogin()"));
+        assert!(lines[1].ends_with("Button not found"));
+        assert!(lines[2].contains("Timed out"));
+    }
+}
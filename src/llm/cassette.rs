@@ -0,0 +1,429 @@
+// Recorded provider conversations, enabled with --record-cassette / --replay-cassette
+//
+// Recording wraps the real provider and writes every request with its response
+// to a JSON file. Replaying serves those responses back without any network
+// call (and without an API key), matched on a hash of the request, so a run
+// can be reproduced exactly for bug reports, demos and pipeline tests.
+
+use super::provider_trait::collect_stream;
+use super::{
+    LLMError, LLMProvider, LLMRequest, LLMResponse, Message, ProviderCapabilities, ProviderConfig,
+    ProviderType,
+};
+use async_trait::async_trait;
+use futures::stream::{self, Stream};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+
+static CASSETTE: OnceLock<Arc<Cassette>> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum CassetteError {
+    #[error("Failed to read cassette {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("Failed to parse cassette {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+/// Whether a cassette is being written or served
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// The recorded provider and its request/response pairs, as stored on disk
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Tape {
+    provider: ProviderType,
+    model: String,
+    capabilities: ProviderCapabilities,
+    max_context_length: u32,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Interaction {
+    request_hash: String,
+    request: LLMRequest,
+    response: LLMResponse,
+}
+
+/// A cassette file being recorded or replayed
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    tape: Mutex<Tape>,
+    /// Interactions already served, so repeated identical requests get the later answers
+    served: Mutex<Vec<bool>>,
+}
+
+impl Cassette {
+    /// Start an empty cassette at `path`, creating parent directories as needed
+    pub fn record(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let cassette = Self {
+            path: path.to_path_buf(),
+            mode: Mode::Record,
+            tape: Mutex::new(Tape::default()),
+            served: Mutex::new(Vec::new()),
+        };
+        cassette.save()?;
+        Ok(cassette)
+    }
+
+    /// Load a recorded cassette to serve its responses
+    pub fn replay(path: &Path) -> Result<Self, CassetteError> {
+        let json = fs::read_to_string(path).map_err(|source| CassetteError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let tape: Tape = serde_json::from_str(&json).map_err(|source| CassetteError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            mode: Mode::Replay,
+            served: Mutex::new(vec![false; tape.interactions.len()]),
+            tape: Mutex::new(tape),
+        })
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&*self.tape.lock().unwrap())?;
+        fs::write(&self.path, json)
+    }
+
+    /// Append an interaction and rewrite the file, so an aborted run keeps what it recorded
+    fn append(&self, request: &LLMRequest, response: &LLMResponse) {
+        self.tape.lock().unwrap().interactions.push(Interaction {
+            request_hash: request_hash(request),
+            request: request.clone(),
+            response: response.clone(),
+        });
+        if let Err(e) = self.save() {
+            eprintln!(
+                "⚠️  Failed to write cassette {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    /// The first recorded response to `request` that wasn't served yet
+    fn lookup(&self, request: &LLMRequest) -> Result<LLMResponse, LLMError> {
+        let hash = request_hash(request);
+        let tape = self.tape.lock().unwrap();
+        let mut served = self.served.lock().unwrap();
+        let index = tape
+            .interactions
+            .iter()
+            .enumerate()
+            .position(|(index, interaction)| interaction.request_hash == hash && !served[index])
+            .ok_or_else(|| {
+                LLMError::ConfigurationError(format!(
+                    "cassette {} has no recorded response for request {}; record it again with --record-cassette",
+                    self.path.display(),
+                    hash
+                ))
+            })?;
+        served[index] = true;
+        Ok(tape.interactions[index].response.clone())
+    }
+}
+
+/// Record all provider calls of this process to `path`
+pub fn enable_recording(path: &Path) -> io::Result<()> {
+    let _ = CASSETTE.set(Arc::new(Cassette::record(path)?));
+    Ok(())
+}
+
+/// Serve all provider calls of this process from the cassette at `path`
+pub fn enable_replay(path: &Path) -> Result<(), CassetteError> {
+    let _ = CASSETTE.set(Arc::new(Cassette::replay(path)?));
+    Ok(())
+}
+
+/// Whether provider calls are served from a cassette instead of the network
+pub fn is_replaying() -> bool {
+    CASSETTE
+        .get()
+        .is_some_and(|cassette| cassette.mode == Mode::Replay)
+}
+
+/// `provider` wrapped to record its calls, if a cassette is being recorded
+pub fn wrap(provider: Box<dyn LLMProvider>, model: &str) -> Box<dyn LLMProvider> {
+    match CASSETTE.get() {
+        Some(cassette) if cassette.mode == Mode::Record => Box::new(CassetteProvider::recording(
+            cassette.clone(),
+            provider,
+            model,
+        )),
+        _ => provider,
+    }
+}
+
+/// Run and test run directories, named by a fresh UUID each run
+static UUID_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}").unwrap()
+});
+
+/// FNV-1a hash of the request, stable across runs and Rust versions
+///
+/// Only what a re-run reproduces is hashed: streaming doesn't change the answer, so a
+/// response recorded with `--stream` also serves the same request without it, and the
+/// UUIDs of temporary directories that tool results mention are masked.
+fn request_hash(request: &LLMRequest) -> String {
+    let request = LLMRequest {
+        messages: request
+            .messages
+            .iter()
+            .map(|message| Message {
+                content: UUID_PATTERN
+                    .replace_all(&message.content, "<uuid>")
+                    .into_owned(),
+                ..message.clone()
+            })
+            .collect(),
+        stream: false,
+        ..request.clone()
+    };
+    let json = serde_json::to_string(&request).unwrap_or_default();
+    let hash = json.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Provider that records the calls of the real provider, or replays them without one
+pub struct CassetteProvider {
+    cassette: Arc<Cassette>,
+    /// The real provider while recording
+    inner: Option<Box<dyn LLMProvider>>,
+}
+
+impl CassetteProvider {
+    /// Record the calls of `inner`, noting the provider and model in the cassette
+    pub fn recording(cassette: Arc<Cassette>, inner: Box<dyn LLMProvider>, model: &str) -> Self {
+        {
+            let mut tape = cassette.tape.lock().unwrap();
+            if tape.interactions.is_empty() {
+                tape.provider = inner.provider_type();
+                tape.model = model.to_string();
+                tape.capabilities = inner.capabilities();
+                tape.max_context_length = inner.max_context_length();
+            }
+        }
+        Self {
+            cassette,
+            inner: Some(inner),
+        }
+    }
+
+    /// Serve the responses recorded in `cassette`
+    pub fn replaying(cassette: Arc<Cassette>) -> Self {
+        Self {
+            cassette,
+            inner: None,
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for CassetteProvider {
+    /// Replay the cassette enabled with `enable_replay`
+    fn new(_config: ProviderConfig) -> Result<Self, LLMError> {
+        match CASSETTE.get() {
+            Some(cassette) if cassette.mode == Mode::Replay => {
+                Ok(Self::replaying(cassette.clone()))
+            }
+            _ => Err(LLMError::ConfigurationError(
+                "no cassette is being replayed".to_string(),
+            )),
+        }
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        match &self.inner {
+            Some(inner) => inner.provider_type(),
+            None => self.cassette.tape.lock().unwrap().provider,
+        }
+    }
+
+    async fn complete(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        let Some(inner) = &self.inner else {
+            return self.cassette.lookup(&request);
+        };
+        let response = inner.complete(request.clone()).await?;
+        self.cassette.append(&request, &response);
+        Ok(response)
+    }
+
+    /// Streams arrive as a single chunk holding the whole response
+    async fn complete_stream(
+        &self,
+        request: LLMRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<LLMResponse, LLMError>> + Send>>, LLMError> {
+        let response = match &self.inner {
+            Some(inner) => {
+                let response =
+                    collect_stream(inner.complete_stream(request.clone()).await?).await?;
+                self.cassette.append(&request, &response);
+                response
+            }
+            None => self.cassette.lookup(&request)?,
+        };
+        Ok(Box::pin(stream::once(async { Ok(response) })))
+    }
+
+    fn estimate_tokens(&self, request: &LLMRequest) -> u32 {
+        match &self.inner {
+            Some(inner) => inner.estimate_tokens(request),
+            // Rough heuristic: 4 characters = 1 token
+            None => (serde_json::to_string(request).unwrap_or_default().len() / 4) as u32,
+        }
+    }
+
+    fn validate_config(_config: &ProviderConfig) -> Result<(), LLMError> {
+        Ok(())
+    }
+
+    fn max_context_length(&self) -> u32 {
+        match &self.inner {
+            Some(inner) => inner.max_context_length(),
+            None => self.cassette.tape.lock().unwrap().max_context_length,
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LLMError> {
+        match &self.inner {
+            Some(inner) => inner.list_models().await,
+            None => Ok(vec![self.cassette.tape.lock().unwrap().model.clone()]),
+        }
+    }
+
+    async fn check_credentials(&self) -> Result<(), LLMError> {
+        match &self.inner {
+            Some(inner) => inner.check_credentials().await,
+            None => Ok(()),
+        }
+    }
+
+    async fn check_ready(&self) -> Result<(), LLMError> {
+        match &self.inner {
+            Some(inner) => inner.check_ready().await,
+            None => Ok(()),
+        }
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        match &self.inner {
+            Some(inner) => inner.capabilities(),
+            None => self.cassette.tape.lock().unwrap().capabilities,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MessageRole;
+    use crate::llm::mock_provider::MockProvider;
+
+    fn request(text: &str) -> LLMRequest {
+        LLMRequest {
+            system_prompt: None,
+            messages: vec![Message {
+                role: MessageRole::User,
+                content: text.to_string(),
+            }],
+            tools: Vec::new(),
+            max_tokens: Some(1024),
+            temperature: Some(0.7),
+            stream: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recorded_cassette_replays_without_provider() {
+        let path = std::env::temp_dir()
+            .join(format!("autofix-cassette-{}", uuid::Uuid::new_v4()))
+            .join("run.json");
+
+        let recorder = CassetteProvider::recording(
+            Arc::new(Cassette::record(&path).unwrap()),
+            Box::new(MockProvider::with_responses(vec![
+                MockProvider::text("Reading the test"),
+                MockProvider::text("Reading the test again"),
+                MockProvider::text("Done"),
+            ])),
+            "claude-sonnet-4-5",
+        );
+        for text in ["Fix the test", "Fix the test", "Test passed"] {
+            recorder.complete(request(text)).await.unwrap();
+        }
+
+        let player = CassetteProvider::replaying(Arc::new(Cassette::replay(&path).unwrap()));
+        assert_eq!(player.provider_type(), ProviderType::Claude);
+        assert_eq!(player.capabilities(), ProviderCapabilities::default());
+
+        // Identical requests get their answers in recorded order, streamed or not
+        let replayed = player.complete(request("Fix the test")).await.unwrap();
+        assert_eq!(replayed.content.as_deref(), Some("Reading the test"));
+        let streamed = collect_stream(
+            player
+                .complete_stream(LLMRequest {
+                    stream: true,
+                    ..request("Fix the test")
+                })
+                .await
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(streamed.content.as_deref(), Some("Reading the test again"));
+        let replayed = player.complete(request("Test passed")).await.unwrap();
+        assert_eq!(replayed.content.as_deref(), Some("Done"));
+
+        match player.complete(request("Fix the test")).await {
+            Err(LLMError::ConfigurationError(message)) => {
+                assert!(message.contains("has no recorded response"))
+            }
+            other => panic!("expected a cassette miss, got {:?}", other),
+        }
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_request_hash_ignores_run_directories() {
+        let run = |id: &str| {
+            request(&format!(
+                "Test failed, see .autofix/test-runner-tool/{}/test/result.xcresult",
+                id
+            ))
+        };
+        assert_eq!(
+            request_hash(&run("3F2504E0-4F89-11D3-9A0C-0305E82C3301")),
+            request_hash(&run("8d1c2a6e-5b7f-4c1a-9e3d-2f6a7b8c9d0e"))
+        );
+        assert_ne!(
+            request_hash(&run("3F2504E0-4F89-11D3-9A0C-0305E82C3301")),
+            request_hash(&request("Test passed"))
+        );
+    }
+}
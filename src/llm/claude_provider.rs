@@ -1,14 +1,17 @@
 // Claude AI provider implementation
 
+use super::config::ModelLimits;
 use super::{
     LLMError, LLMRequest, LLMResponse, MessageRole, ProviderConfig, ProviderType, StopReason,
-    TokenUsage, ToolCall, ToolDefinition,
+    TokenUsage, ToolCall, ToolDefinition, context_length_error, estimated_output_tokens,
 };
 use crate::llm::provider_trait::LLMProvider;
+use crate::llm::retry::{self, RetryPolicy};
+use crate::llm::{http_client, http_trace};
 use crate::rate_limiter::RateLimiter;
 use anthropic_sdk::{
     Anthropic, ContentBlock, ContentBlockParam, MessageContent, MessageCreateBuilder,
-    StopReason as AnthropicStopReason, Tool as AnthropicTool, ToolChoice,
+    MessageCreateParams, StopReason as AnthropicStopReason, Tool as AnthropicTool, ToolChoice,
 };
 use async_trait::async_trait;
 use futures::stream::Stream;
@@ -16,14 +19,227 @@ use std::pin::Pin;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// HTTP status Anthropic returns when the API is temporarily overloaded
+const OVERLOADED_STATUS: u16 = 529;
+
+/// Messages API version sent with requests made without the SDK
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Smallest thinking budget the API accepts
+const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// Model name prefixes of Claude models with extended thinking
+const THINKING_MODEL_PREFIXES: &[&str] = &[
+    "claude-3-7-sonnet",
+    "claude-sonnet-4",
+    "claude-opus-4",
+    "claude-haiku-4",
+];
+
+/// Limits of `model`; models missing from the known models get conservative ones
+pub fn model_limits(model: &str) -> ModelLimits {
+    if let Some(limits) = ProviderType::Claude.model_limits(model) {
+        return limits;
+    }
+    let (context_window, max_output_tokens) =
+        if model.contains("sonnet") || model.contains("haiku") || model.contains("opus") {
+            (200_000, 8_192)
+        } else {
+            // Older models
+            (100_000, 4_096)
+        };
+    ModelLimits {
+        context_window,
+        max_output_tokens,
+    }
+}
+
+/// Whether `model` supports extended thinking
+pub fn supports_thinking(model: &str) -> bool {
+    THINKING_MODEL_PREFIXES
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
+}
+
+/// Remove thinking blocks from a raw Messages API response, returning their text
+///
+/// Redacted thinking is encrypted and dropped without text.
+fn take_thinking_blocks(response: &mut serde_json::Value) -> Option<String> {
+    let blocks = response.get_mut("content")?.as_array_mut()?;
+    let mut thinking = Vec::new();
+    blocks.retain(|block| match block["type"].as_str() {
+        Some("thinking") => {
+            thinking.extend(block["thinking"].as_str().map(str::to_string));
+            false
+        }
+        Some("redacted_thinking") => false,
+        _ => true,
+    });
+
+    let thinking = thinking.join("\n");
+    (!thinking.is_empty()).then_some(thinking)
+}
+
 /// Claude provider implementation
 pub struct ClaudeProvider {
     config: ProviderConfig,
     client: Anthropic,
+    /// Client for requests sent without the SDK, trusting `AUTOFIX_CA_BUNDLE`
+    http: reqwest::Client,
     rate_limiter: Arc<Mutex<RateLimiter>>,
+    /// The warning about a too large `max_tokens` was printed
+    max_tokens_warned: std::sync::Once,
 }
 
 impl ClaudeProvider {
+    /// `requested` output tokens, lowered to what the model can produce next to the
+    /// thinking budget, with a warning the first time that happens
+    fn clamp_max_tokens(&self, requested: u32) -> u32 {
+        let max_output_tokens = model_limits(&self.config.model).max_output_tokens;
+        let thinking_budget = self.config.thinking_budget.unwrap_or(0);
+        let available = max_output_tokens.saturating_sub(thinking_budget);
+        if requested <= available {
+            return requested;
+        }
+
+        self.max_tokens_warned.call_once(|| {
+            println!(
+                "⚠️  max_tokens {} is more than {} allows ({} output tokens{}), using {}",
+                requested,
+                self.config.model,
+                max_output_tokens,
+                if thinking_budget > 0 {
+                    format!(" including the thinking budget of {}", thinking_budget)
+                } else {
+                    String::new()
+                },
+                available
+            );
+        });
+        available
+    }
+
+    /// Convert an Anthropic SDK error into an LLMError, removing potential API keys
+    fn convert_error(&self, error: anthropic_sdk::AnthropicError) -> LLMError {
+        let error_msg = format!("{}", error);
+        let sanitized = error_msg
+            .replace(self.config.api_key(), "[REDACTED]")
+            .replace("sk-ant-", "[REDACTED]");
+
+        if matches!(error, anthropic_sdk::AnthropicError::InvalidApiKey) {
+            return LLMError::AuthenticationError;
+        }
+        match error.status_code() {
+            Some(OVERLOADED_STATUS) => LLMError::Overloaded {
+                message: sanitized,
+                retry_after: None,
+            },
+            Some(401) => LLMError::AuthenticationError,
+            Some(429) => LLMError::RateLimitError {
+                message: sanitized,
+                retry_after: None,
+            },
+            Some(status @ 500..) => LLMError::ServerError { status },
+            _ => http_client::tls_error(&sanitized).unwrap_or_else(|| {
+                context_length_error(&sanitized).unwrap_or(LLMError::InvalidRequest(sanitized))
+            }),
+        }
+    }
+
+    /// JSON body of `params` with extended thinking enabled
+    ///
+    /// Thinking tokens count against `max_tokens`, so the budget is added on top
+    /// of it. Temperature can't be combined with thinking and is left out.
+    fn thinking_request_body(
+        params: &MessageCreateParams,
+        budget_tokens: u32,
+    ) -> Result<serde_json::Value, LLMError> {
+        let mut body = serde_json::to_value(params)
+            .map_err(|e| LLMError::InvalidRequest(format!("Failed to build request: {}", e)))?;
+        if let Some(body) = body.as_object_mut() {
+            body.remove("temperature");
+            body.insert(
+                "max_tokens".to_string(),
+                serde_json::json!(params.max_tokens + budget_tokens),
+            );
+            body.insert(
+                "thinking".to_string(),
+                serde_json::json!({"type": "enabled", "budget_tokens": budget_tokens}),
+            );
+        }
+        Ok(body)
+    }
+
+    /// Send a request without the SDK, returning the message and its thinking text
+    ///
+    /// The SDK knows neither the `thinking` parameter nor thinking blocks, and its
+    /// HTTP client can't be given the `AUTOFIX_CA_BUNDLE` certificates. The thinking
+    /// blocks are taken out of the response before the SDK parses it.
+    async fn create_raw(
+        &self,
+        body: &serde_json::Value,
+    ) -> Result<(anthropic_sdk::Message, Option<String>), LLMError> {
+        let url = self.messages_url();
+        let trace = http_trace::start(self.config.provider_type, &url, body);
+        let result = self.post_json(&url, body).await;
+        http_trace::finish(trace, &result, &[self.config.api_key()]);
+        let mut value = result?;
+
+        let thinking = take_thinking_blocks(&mut value);
+        let message = serde_json::from_value(value)
+            .map_err(|e| LLMError::InvalidRequest(format!("Invalid response: {}", e)))?;
+        Ok((message, thinking))
+    }
+
+    /// POST `body` as JSON to `url`, returning the JSON response
+    async fn post_json(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, LLMError> {
+        let response = self
+            .http
+            .post(url)
+            .header("x-api-key", self.config.api_key())
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(body)
+            .send()
+            .await
+            .map_err(http_client::send_error)?;
+
+        let status = response.status().as_u16();
+        let retry_after = retry::retry_after(response.headers());
+        let text = response.text().await?;
+        if !(200..300).contains(&status) {
+            // Sanitize error message to remove potential API keys
+            let sanitized = text
+                .replace(self.config.api_key(), "[REDACTED]")
+                .replace("sk-ant-", "[REDACTED]");
+            return Err(match status {
+                OVERLOADED_STATUS => LLMError::Overloaded {
+                    message: sanitized,
+                    retry_after,
+                },
+                401 => LLMError::AuthenticationError,
+                429 => LLMError::RateLimitError {
+                    message: sanitized,
+                    retry_after,
+                },
+                500.. => LLMError::ServerError { status },
+                _ => {
+                    context_length_error(&sanitized).unwrap_or(LLMError::InvalidRequest(sanitized))
+                }
+            });
+        }
+
+        serde_json::from_str(&text)
+            .map_err(|e| LLMError::InvalidRequest(format!("Invalid response: {}", e)))
+    }
+
+    fn messages_url(&self) -> String {
+        format!("{}/v1/messages", self.config.api_base)
+    }
+
     /// Convert tool definitions to Claude format
     fn convert_tools(&self, tools: &[ToolDefinition]) -> Result<Vec<AnthropicTool>, LLMError> {
         tools
@@ -95,6 +311,7 @@ impl ClaudeProvider {
             } else {
                 Some(content)
             },
+            thinking: None,
             tool_calls,
             stop_reason,
             usage,
@@ -122,7 +339,9 @@ impl LLMProvider for ClaudeProvider {
         Ok(Self {
             config,
             client,
+            http: http_client::client()?,
             rate_limiter,
+            max_tokens_warned: std::sync::Once::new(),
         })
     }
 
@@ -130,6 +349,21 @@ impl LLMProvider for ClaudeProvider {
         ProviderType::Claude
     }
 
+    async fn list_models(&self) -> Result<Vec<String>, LLMError> {
+        let models = self
+            .client
+            .models()
+            .list(None)
+            .await
+            .map_err(|e| self.convert_error(e))?;
+        Ok(models.data.into_iter().map(|model| model.id).collect())
+    }
+
+    /// Listing models costs no tokens but needs a valid key
+    async fn check_credentials(&self) -> Result<(), LLMError> {
+        self.list_models().await.map(|_| ())
+    }
+
     async fn complete(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
         // Estimate tokens and check rate limiter
         let estimated_tokens = self.estimate_tokens(&request);
@@ -142,7 +376,7 @@ impl LLMProvider for ClaudeProvider {
         }
 
         // Determine max_tokens - required parameter
-        let max_tokens = request.max_tokens.unwrap_or(4096);
+        let max_tokens = self.clamp_max_tokens(request.max_tokens.unwrap_or(4096));
 
         // Build request with model and max_tokens (both required in constructor)
         let mut builder = MessageCreateBuilder::new(&self.config.model, max_tokens);
@@ -176,22 +410,40 @@ impl LLMProvider for ClaudeProvider {
             builder = builder.temperature(temperature);
         }
 
-        // Send request
-        let response = self
-            .client
-            .messages()
-            .create(builder.build())
-            .await
-            .map_err(|e| {
-                // Sanitize error message to remove potential API keys
-                let error_msg = format!("{}", e);
-                let sanitized = error_msg
-                    .replace(self.config.api_key(), "[REDACTED]")
-                    .replace("sk-ant-", "[REDACTED]");
-                LLMError::InvalidRequest(sanitized)
-            })?;
-
-        // Record actual usage
+        // Send request, backing off while the API is rate limited or overloaded
+        let params = builder.build();
+        let policy = RetryPolicy::from_config(&self.config);
+        let (response, thinking) = match self.config.thinking_budget {
+            Some(budget_tokens) => {
+                let body = Self::thinking_request_body(&params, budget_tokens)?;
+                retry::with_retries(policy, || self.create_raw(&body)).await?
+            }
+            None if http_client::ca_bundle_path().is_some() => {
+                let body = serde_json::to_value(&params).map_err(|e| {
+                    LLMError::InvalidRequest(format!("Failed to build request: {}", e))
+                })?;
+                retry::with_retries(policy, || self.create_raw(&body)).await?
+            }
+            None => {
+                let response = retry::with_retries(policy, || async {
+                    let trace =
+                        http_trace::start(self.config.provider_type, &self.messages_url(), &params);
+                    let result = self
+                        .client
+                        .messages()
+                        .create(params.clone())
+                        .await
+                        .map_err(|e| self.convert_error(e));
+                    http_trace::finish(trace, &result, &[self.config.api_key()]);
+                    result
+                })
+                .await?;
+                (response, None)
+            }
+        };
+
+        // Record actual usage (failed attempts never reach this point, so they
+        // don't count against the token window)
         {
             let limiter = self.rate_limiter.lock().await;
             limiter.record_usage(
@@ -200,7 +452,9 @@ impl LLMProvider for ClaudeProvider {
         }
 
         // Convert to LLMResponse
-        self.convert_response(response)
+        let mut llm_response = self.convert_response(response)?;
+        llm_response.thinking = thinking;
+        Ok(llm_response)
     }
 
     async fn complete_stream(
@@ -235,7 +489,7 @@ impl LLMProvider for ClaudeProvider {
             .sum();
 
         // Estimate output tokens
-        let output_tokens = request.max_tokens.unwrap_or(1000);
+        let output_tokens = estimated_output_tokens(request);
 
         input_tokens + tool_tokens + output_tokens
     }
@@ -270,20 +524,35 @@ impl LLMProvider for ClaudeProvider {
             )));
         }
 
+        // Check extended thinking can be used with the model
+        if let Some(budget_tokens) = config.thinking_budget {
+            if !supports_thinking(&config.model) {
+                return Err(LLMError::ConfigurationError(format!(
+                    "Extended thinking is not supported by {}",
+                    config.model
+                )));
+            }
+            if budget_tokens < MIN_THINKING_BUDGET {
+                return Err(LLMError::ConfigurationError(format!(
+                    "Thinking budget must be at least {} tokens",
+                    MIN_THINKING_BUDGET
+                )));
+            }
+            // The answer has to fit next to the thinking in the output limit
+            let max_output_tokens = model_limits(&config.model).max_output_tokens;
+            if budget_tokens >= max_output_tokens {
+                return Err(LLMError::ConfigurationError(format!(
+                    "Thinking budget of {} tokens leaves no room for an answer: {} produces at most {} output tokens",
+                    budget_tokens, config.model, max_output_tokens
+                )));
+            }
+        }
+
         Ok(())
     }
 
     fn max_context_length(&self) -> u32 {
-        // Claude Sonnet 4 and Haiku 3.5 have 200k context
-        if self.config.model.contains("sonnet")
-            || self.config.model.contains("haiku")
-            || self.config.model.contains("opus")
-        {
-            200000
-        } else {
-            // Default for older models
-            100000
-        }
+        model_limits(&self.config.model).context_window
     }
 
     fn supports_streaming(&self) -> bool {
@@ -294,3 +563,160 @@ impl LLMProvider for ClaudeProvider {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thinking_request_replaces_temperature_with_budget() {
+        let params = MessageCreateBuilder::new("claude-sonnet-4", 1024)
+            .user(MessageContent::Text("Fix the test".to_string()))
+            .temperature(0.7)
+            .build();
+
+        let body = ClaudeProvider::thinking_request_body(&params, 4096).unwrap();
+
+        assert!(body.get("temperature").is_none());
+        assert_eq!(body["max_tokens"], 1024 + 4096);
+        assert_eq!(
+            body["thinking"],
+            serde_json::json!({"type": "enabled", "budget_tokens": 4096})
+        );
+    }
+
+    #[test]
+    fn test_thinking_blocks_are_kept_apart_from_the_answer() {
+        let mut response = serde_json::json!({
+            "content": [
+                {"type": "thinking", "thinking": "The selector is stale.", "signature": "sig"},
+                {"type": "redacted_thinking", "data": "encrypted"},
+                {"type": "text", "text": "I'll update the selector."}
+            ]
+        });
+
+        let thinking = take_thinking_blocks(&mut response);
+
+        assert_eq!(thinking.as_deref(), Some("The selector is stale."));
+        assert_eq!(
+            response["content"],
+            serde_json::json!([{"type": "text", "text": "I'll update the selector."}])
+        );
+    }
+
+    #[test]
+    fn test_thinking_budget_requires_thinking_model() {
+        let config = |model: &str| {
+            ProviderConfig::builder()
+                .provider_type(ProviderType::Claude)
+                .api_key("sk-ant-test")
+                .model(model)
+                .thinking_budget(2048)
+                .build()
+                .unwrap()
+        };
+
+        assert!(ClaudeProvider::validate_config(&config("claude-sonnet-4-20250514")).is_ok());
+        assert!(matches!(
+            ClaudeProvider::validate_config(&config("claude-3-5-haiku-20241022")),
+            Err(LLMError::ConfigurationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_model_limits_of_known_models() {
+        assert_eq!(
+            model_limits("claude-sonnet-4-20250514"),
+            ModelLimits {
+                context_window: 200_000,
+                max_output_tokens: 64_000
+            }
+        );
+        assert_eq!(
+            model_limits("claude-opus-4-1-20250805").max_output_tokens,
+            32_000
+        );
+        assert_eq!(
+            model_limits("claude-opus-4-5-20251101").max_output_tokens,
+            64_000
+        );
+        assert_eq!(
+            model_limits("claude-3-5-haiku-20241022").max_output_tokens,
+            8_192
+        );
+        assert_eq!(
+            model_limits("claude-3-haiku-20240307").max_output_tokens,
+            4_096
+        );
+        assert_eq!(model_limits("claude-2.1").context_window, 100_000);
+    }
+
+    #[test]
+    fn test_max_tokens_is_clamped_to_the_model_output_limit() {
+        let provider = |model: &str, thinking_budget: Option<u32>| {
+            let mut builder = ProviderConfig::builder()
+                .provider_type(ProviderType::Claude)
+                .api_key("sk-ant-test")
+                .model(model);
+            if let Some(budget) = thinking_budget {
+                builder = builder.thinking_budget(budget);
+            }
+            ClaudeProvider::new(builder.build().unwrap()).unwrap()
+        };
+
+        let haiku = provider("claude-3-5-haiku-20241022", None);
+        assert_eq!(haiku.clamp_max_tokens(4096), 4096);
+        assert_eq!(haiku.clamp_max_tokens(20_000), 8_192);
+
+        let sonnet = provider("claude-sonnet-4-20250514", Some(60_000));
+        assert_eq!(sonnet.clamp_max_tokens(4096), 4_000);
+    }
+
+    #[test]
+    fn test_thinking_budget_must_leave_room_for_an_answer() {
+        let config = ProviderConfig::builder()
+            .provider_type(ProviderType::Claude)
+            .api_key("sk-ant-test")
+            .model("claude-opus-4-1-20250805")
+            .thinking_budget(32_000)
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            ClaudeProvider::validate_config(&config),
+            Err(LLMError::ConfigurationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejected_key_is_an_authentication_error() {
+        let provider = ClaudeProvider::new(
+            ProviderConfig::builder()
+                .provider_type(ProviderType::Claude)
+                .api_key("sk-ant-test")
+                .model("claude-sonnet-4-20250514")
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            provider.convert_error(anthropic_sdk::AnthropicError::Authentication {
+                message: "invalid x-api-key".to_string(),
+                status: 401,
+            }),
+            LLMError::AuthenticationError
+        ));
+        assert!(matches!(
+            provider.convert_error(anthropic_sdk::AnthropicError::InvalidApiKey),
+            LLMError::AuthenticationError
+        ));
+        assert!(matches!(
+            provider.convert_error(anthropic_sdk::AnthropicError::RateLimit {
+                message: "rate limited".to_string(),
+                status: 429,
+            }),
+            LLMError::RateLimitError { .. }
+        ));
+    }
+}
@@ -1,9 +1,55 @@
 // Provider configuration types
 
+use super::LLMError;
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use std::env;
 
+/// Context window and output token limit of a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelLimits {
+    pub context_window: u32,
+    pub max_output_tokens: u32,
+}
+
+/// A well-known model of a provider, listed by `autofix models`
+#[derive(Debug, Clone, Copy)]
+pub struct KnownModel {
+    /// Name as passed to `--model`
+    pub name: &'static str,
+    pub note: &'static str,
+    /// `None` where they depend on the installation, as with Ollama
+    pub limits: Option<ModelLimits>,
+}
+
+impl KnownModel {
+    /// Name without a `-latest` or `-YYYYMMDD` suffix, shared by the model's versions
+    fn family(&self) -> &'static str {
+        if let Some(family) = self.name.strip_suffix("-latest") {
+            return family;
+        }
+        match self.name.rsplit_once('-') {
+            Some((family, date))
+                if date.len() == 8 && date.bytes().all(|byte| byte.is_ascii_digit()) =>
+            {
+                family
+            }
+            _ => self.name,
+        }
+    }
+}
+
+const fn limits(context_window: u32, max_output_tokens: u32) -> Option<ModelLimits> {
+    Some(ModelLimits {
+        context_window,
+        max_output_tokens,
+    })
+}
+
+const fn known(name: &'static str, note: &'static str, limits: Option<ModelLimits>) -> KnownModel {
+    KnownModel { name, note, limits }
+}
+
 /// Supported LLM provider types
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -12,6 +58,7 @@ pub enum ProviderType {
     #[default]
     Claude,
     OpenAI,
+    Groq,
     Ollama,
 }
 
@@ -21,12 +68,220 @@ impl ProviderType {
         match s.to_lowercase().as_str() {
             "claude" => Ok(ProviderType::Claude),
             "openai" => Ok(ProviderType::OpenAI),
+            "groq" => Ok(ProviderType::Groq),
             "ollama" => Ok(ProviderType::Ollama),
             _ => Err(format!("Unknown provider type: {}", s)),
         }
     }
+
+    /// All supported providers
+    pub const ALL: [ProviderType; 4] = [
+        ProviderType::Claude,
+        ProviderType::OpenAI,
+        ProviderType::Groq,
+        ProviderType::Ollama,
+    ];
+
+    /// Name as passed to `--provider`
+    pub fn name(self) -> &'static str {
+        match self {
+            ProviderType::Claude => "claude",
+            ProviderType::OpenAI => "openai",
+            ProviderType::Groq => "groq",
+            ProviderType::Ollama => "ollama",
+        }
+    }
+
+    /// Environment variable holding the API key, if the provider needs one
+    pub fn api_key_var(self) -> Option<&'static str> {
+        match self {
+            ProviderType::Claude => Some("ANTHROPIC_API_KEY"),
+            ProviderType::OpenAI => Some("OPENAI_API_KEY"),
+            ProviderType::Groq => Some("GROQ_API_KEY"),
+            ProviderType::Ollama => None,
+        }
+    }
+
+    /// Well-known models for `--model`, with a short note and their limits
+    ///
+    /// Not exhaustive - any model the endpoint serves can be used.
+    pub fn known_models(self) -> &'static [KnownModel] {
+        match self {
+            ProviderType::Claude => &[
+                known(
+                    "claude-sonnet-4",
+                    "balanced, default",
+                    limits(200_000, 64_000),
+                ),
+                known("claude-opus-4-5", "most capable", limits(200_000, 64_000)),
+                known(
+                    "claude-opus-4",
+                    "slower and pricier",
+                    limits(200_000, 32_000),
+                ),
+                known(
+                    "claude-haiku-4-5",
+                    "fast, extended thinking",
+                    limits(200_000, 64_000),
+                ),
+                known(
+                    "claude-3-7-sonnet-latest",
+                    "older, extended thinking",
+                    limits(200_000, 64_000),
+                ),
+                known("claude-3-5-sonnet-latest", "older", limits(200_000, 8_192)),
+                known(
+                    "claude-3-5-haiku-latest",
+                    "fast and cheap",
+                    limits(200_000, 8_192),
+                ),
+                known(
+                    "claude-3-opus-latest",
+                    "older, 4k output",
+                    limits(200_000, 4_096),
+                ),
+                known(
+                    "claude-3-haiku-20240307",
+                    "oldest and cheapest",
+                    limits(200_000, 4_096),
+                ),
+            ],
+            ProviderType::OpenAI => &[
+                known("gpt-4", "default", limits(8_192, 8_192)),
+                known(
+                    "gpt-4o",
+                    "multimodal, 128k context",
+                    limits(128_000, 16_384),
+                ),
+                known("gpt-4-turbo", "128k context", limits(128_000, 4_096)),
+                known(
+                    "gpt-3.5-turbo",
+                    "fast and cheap, no images",
+                    limits(16_385, 4_096),
+                ),
+            ],
+            ProviderType::Groq => &[
+                known(
+                    "llama-3.3-70b-versatile",
+                    "default, 128k context",
+                    limits(131_072, 32_768),
+                ),
+                known(
+                    "llama-3.1-8b-instant",
+                    "fastest, 128k context",
+                    limits(131_072, 131_072),
+                ),
+                known("mixtral-8x7b-32768", "32k context", limits(32_768, 32_768)),
+            ],
+            // Limits depend on the local model file and its settings
+            ProviderType::Ollama => &[
+                known("llama2", "default", None),
+                known("llama3", "general purpose", None),
+                known("codellama", "code focused", None),
+                known("mistral", "32k context", None),
+                known("llava", "vision, can read snapshots", None),
+            ],
+        }
+    }
+
+    /// Limits of `model` from the known models, if it is one of them or a version of one
+    ///
+    /// A known model covers the names starting with it once a `-latest` or date suffix
+    /// is dropped, e.g. `claude-3-5-haiku-latest` covers `claude-3-5-haiku-20241022`.
+    /// The longest match wins, so `claude-opus-4-5-20251101` gets `claude-opus-4-5`'s
+    /// limits rather than `claude-opus-4`'s.
+    pub fn model_limits(self, model: &str) -> Option<ModelLimits> {
+        self.known_models()
+            .iter()
+            .filter(|known| model.starts_with(known.family()))
+            .max_by_key(|known| known.family().len())
+            .and_then(|known| known.limits)
+    }
+
+    /// Reject a model that clearly belongs to another provider, e.g. `gpt-4o` with Claude
+    ///
+    /// Stricter than the providers' `validate_config`, to catch a mixed up `--provider`
+    /// or `--model` before any request. Only Anthropic serves Claude models and only
+    /// OpenAI serves GPT and o-series models; any other name may be hosted by an
+    /// OpenAI-compatible endpoint, Groq or Ollama, so it passes there.
+    pub fn check_model(self, model: &str) -> Result<(), String> {
+        let owner = exclusive_provider(model);
+        let mismatch = match self {
+            ProviderType::Claude => owner != Some(ProviderType::Claude),
+            _ => owner.is_some_and(|owner| owner != self),
+        };
+        if !mismatch {
+            return Ok(());
+        }
+        let suggestion = match owner {
+            Some(owner) => format!("use --provider {}, ", owner.name()),
+            None => String::new(),
+        };
+        Err(format!(
+            "Model '{}' is not served by {}; {}pick one of `autofix models --provider {}` or pass --allow-model-mismatch if your endpoint remaps model names",
+            model,
+            self.name(),
+            suggestion,
+            self.name()
+        ))
+    }
+
+    /// List price in USD per million input tokens for `model`, if known
+    ///
+    /// Prices change; this is meant for rough estimates only. Local Ollama models are free.
+    pub fn input_cost_per_million_tokens(self, model: &str) -> Option<f64> {
+        // More specific prefixes first, e.g. gpt-4o-mini before gpt-4o before gpt-4
+        let prices: &[(&str, f64)] = match self {
+            ProviderType::Claude => &[
+                ("claude-opus", 15.0),
+                ("claude-3-opus", 15.0),
+                ("claude-sonnet", 3.0),
+                ("claude-3-5-sonnet", 3.0),
+                ("claude-3-7-sonnet", 3.0),
+                ("claude-haiku-4", 1.0),
+                ("claude-haiku", 0.8),
+                ("claude-3-5-haiku", 0.8),
+                ("claude-3-haiku", 0.25),
+            ],
+            ProviderType::OpenAI => &[
+                ("gpt-4o-mini", 0.15),
+                ("gpt-4o", 2.5),
+                ("gpt-4.1-mini", 0.4),
+                ("gpt-4.1", 2.0),
+                ("gpt-4-turbo", 10.0),
+                ("gpt-4", 30.0),
+                ("gpt-3.5-turbo", 0.5),
+            ],
+            ProviderType::Groq => &[
+                ("llama-3.3-70b", 0.59),
+                ("llama-3.1-8b", 0.05),
+                ("mixtral-8x7b", 0.24),
+            ],
+            ProviderType::Ollama => return Some(0.0),
+        };
+        prices
+            .iter()
+            .find(|(prefix, _)| model.starts_with(prefix))
+            .map(|(_, price)| *price)
+    }
 }
 
+/// Provider that alone serves `model`, judging by its name
+///
+/// `gpt-oss` models are open-weight and served by many, so they belong to no one.
+fn exclusive_provider(model: &str) -> Option<ProviderType> {
+    let model = model.to_lowercase();
+    let o_series = model.starts_with('o') && model[1..].starts_with(|c: char| c.is_ascii_digit());
+    if model.starts_with("claude") {
+        Some(ProviderType::Claude)
+    } else if model.starts_with("gpt-oss") {
+        None
+    } else if model.starts_with("gpt-") || model.starts_with("chatgpt-") || o_series {
+        Some(ProviderType::OpenAI)
+    } else {
+        None
+    }
+}
 
 /// Configuration for an LLM provider
 #[derive(Debug, Clone)]
@@ -38,82 +293,67 @@ pub struct ProviderConfig {
     pub timeout_secs: u64,
     pub max_retries: u32,
     pub rate_limit_tpm: Option<u32>,
+    /// Token budget for extended thinking on models that support it, off when `None`
+    pub thinking_budget: Option<u32>,
+    /// Pull the configured Ollama model when it's missing instead of failing
+    pub ollama_auto_pull: bool,
 }
 
 impl ProviderConfig {
-    /// Create a new provider configuration
-    #[allow(dead_code)] // Not currently used but part of public API
-    pub fn new(
-        provider_type: ProviderType,
-        api_key: String,
-        api_base: String,
-        model: String,
-    ) -> Self {
-        Self {
-            provider_type,
-            api_key: SecretString::new(api_key),
-            api_base,
-            model,
-            timeout_secs: 30,
-            max_retries: 3,
-            rate_limit_tpm: None,
-        }
+    /// Start building a configuration; fields left unset take the provider's defaults
+    pub fn builder() -> ProviderConfigBuilder {
+        ProviderConfigBuilder::default()
     }
 
-    /// Load configuration from environment variables
-    pub fn from_env() -> Result<Self, String> {
+    /// Load configuration for `provider_type` from environment variables
+    ///
+    /// The provider itself comes from `--provider`, `AUTOFIX_PROVIDER` or the project config.
+    pub fn from_env(provider_type: ProviderType) -> Result<Self, String> {
         // Load .env file if present (ignore errors if file doesn't exist)
         let _ = dotenvy::dotenv();
 
-        // Determine provider type
-        let provider_str = env::var("AUTOFIX_PROVIDER").unwrap_or_else(|_| "claude".to_string());
-        let provider_type = ProviderType::from_str(&provider_str)?;
+        let mut builder = Self::builder().provider_type(provider_type);
 
-        // Get API key based on provider
-        let api_key = match provider_type {
-            ProviderType::Claude => env::var("ANTHROPIC_API_KEY")
-                .map_err(|_| "ANTHROPIC_API_KEY not set".to_string())?,
-            ProviderType::OpenAI => {
-                env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY not set".to_string())?
+        // Get API key based on provider (Ollama only needs one behind an authenticating proxy)
+        match provider_type.api_key_var() {
+            Some(var) => {
+                builder = builder.api_key(env::var(var).map_err(|_| format!("{} not set", var))?)
             }
-            ProviderType::Ollama => {
-                // Ollama doesn't require an API key
-                "ollama".to_string()
+            None => {
+                if let Ok(api_key) = env::var("OLLAMA_API_KEY") {
+                    builder = builder.api_key(api_key);
+                }
             }
-        };
+        }
 
-        // Get default values for this provider
-        let defaults = Self::default_for_provider(provider_type);
-
-        // Override with environment variables if present
-        let api_base = env::var("AUTOFIX_API_BASE").unwrap_or(defaults.api_base);
-        let model = env::var("AUTOFIX_MODEL").unwrap_or(defaults.model);
-        let timeout_secs = env::var("AUTOFIX_TIMEOUT_SECS")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(defaults.timeout_secs);
-        let max_retries = env::var("AUTOFIX_MAX_RETRIES")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(defaults.max_retries);
-        let rate_limit_tpm = env::var("AUTOFIX_RATE_LIMIT_TPM")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .or(defaults.rate_limit_tpm);
-
-        Ok(Self {
-            provider_type,
-            api_key: SecretString::new(api_key),
-            api_base,
-            model,
-            timeout_secs,
-            max_retries,
-            rate_limit_tpm,
-        })
+        // Override the provider's defaults with environment variables if present
+        fn parsed<T: std::str::FromStr>(var: &str) -> Option<T> {
+            env::var(var).ok().and_then(|s| s.parse().ok())
+        }
+        if let Ok(api_base) = env::var("AUTOFIX_API_BASE") {
+            builder = builder.api_base(api_base);
+        }
+        if let Ok(model) = env::var("AUTOFIX_MODEL") {
+            builder = builder.model(model);
+        }
+        if let Some(timeout_secs) = parsed("AUTOFIX_TIMEOUT_SECS") {
+            builder = builder.timeout_secs(timeout_secs);
+        }
+        if let Some(max_retries) = parsed("AUTOFIX_MAX_RETRIES") {
+            builder = builder.max_retries(max_retries);
+        }
+        if let Some(rate_limit_tpm) = parsed("AUTOFIX_RATE_LIMIT_TPM") {
+            builder = builder.rate_limit_tpm(rate_limit_tpm);
+        }
+        if let Some(thinking_budget) = parsed("AUTOFIX_THINKING_BUDGET") {
+            builder = builder.thinking_budget(thinking_budget);
+        }
+
+        builder.build().map_err(|e| e.to_string())
     }
 
     /// Get default configuration values for a provider
-    fn default_for_provider(provider_type: ProviderType) -> Self {
+    pub fn default_for_provider(provider_type: ProviderType) -> Self {
         match provider_type {
             ProviderType::Claude => Self {
                 provider_type,
@@ -123,6 +363,8 @@ impl ProviderConfig {
                 timeout_secs: 30,
                 max_retries: 3,
                 rate_limit_tpm: Some(30000),
+                thinking_budget: None,
+                ollama_auto_pull: false,
             },
             ProviderType::OpenAI => Self {
                 provider_type,
@@ -132,6 +374,19 @@ impl ProviderConfig {
                 timeout_secs: 30,
                 max_retries: 3,
                 rate_limit_tpm: Some(90000),
+                thinking_budget: None,
+                ollama_auto_pull: false,
+            },
+            ProviderType::Groq => Self {
+                provider_type,
+                api_key: SecretString::new("".to_string()),
+                api_base: "https://api.groq.com/openai/v1".to_string(),
+                model: "llama-3.3-70b-versatile".to_string(),
+                timeout_secs: 30,
+                max_retries: 3,
+                rate_limit_tpm: Some(12000),
+                thinking_budget: None,
+                ollama_auto_pull: false,
             },
             ProviderType::Ollama => Self {
                 provider_type,
@@ -141,6 +396,8 @@ impl ProviderConfig {
                 timeout_secs: 120, // Local models may be slower
                 max_retries: 3,
                 rate_limit_tpm: None, // No rate limit for local
+                thinking_budget: None,
+                ollama_auto_pull: false,
             },
         }
     }
@@ -151,8 +408,423 @@ impl ProviderConfig {
     }
 }
 
+/// Builder for a `ProviderConfig`, from `ProviderConfig::builder()`
+///
+/// Only the provider type is required; everything else defaults to
+/// `ProviderConfig::default_for_provider`. `build` checks the result.
+#[derive(Debug, Default)]
+pub struct ProviderConfigBuilder {
+    provider_type: Option<ProviderType>,
+    api_key: Option<String>,
+    api_base: Option<String>,
+    model: Option<String>,
+    timeout_secs: Option<u64>,
+    max_retries: Option<u32>,
+    rate_limit_tpm: Option<u32>,
+    thinking_budget: Option<u32>,
+}
+
+impl ProviderConfigBuilder {
+    pub fn provider_type(mut self, provider_type: ProviderType) -> Self {
+        self.provider_type = Some(provider_type);
+        self
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// API base URL, normalized by `build` like `AUTOFIX_API_BASE`
+    pub fn api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = Some(api_base.into());
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn timeout_secs(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = Some(timeout_secs);
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    pub fn rate_limit_tpm(mut self, rate_limit_tpm: u32) -> Self {
+        self.rate_limit_tpm = Some(rate_limit_tpm);
+        self
+    }
+
+    pub fn thinking_budget(mut self, thinking_budget: u32) -> Self {
+        self.thinking_budget = Some(thinking_budget);
+        self
+    }
+
+    /// The configuration, with unset fields filled in from the provider's defaults
+    pub fn build(self) -> Result<ProviderConfig, LLMError> {
+        let provider_type = self.provider_type.ok_or_else(|| {
+            LLMError::ConfigurationError("A provider type is required".to_string())
+        })?;
+        let defaults = ProviderConfig::default_for_provider(provider_type);
+
+        let api_key = self
+            .api_key
+            .unwrap_or_else(|| defaults.api_key().to_string());
+        if api_key.is_empty()
+            && let Some(var) = provider_type.api_key_var()
+        {
+            return Err(LLMError::ConfigurationError(format!(
+                "An API key is required for {:?}; set {}",
+                provider_type, var
+            )));
+        }
+        let api_base = match self.api_base {
+            Some(api_base) => normalize_api_base(provider_type, &api_base)?,
+            None => defaults.api_base,
+        };
+        let model = self.model.unwrap_or(defaults.model);
+        if model.trim().is_empty() {
+            return Err(LLMError::ConfigurationError(
+                "Model name must not be empty".to_string(),
+            ));
+        }
+        let timeout_secs = self.timeout_secs.unwrap_or(defaults.timeout_secs);
+        if timeout_secs == 0 {
+            return Err(LLMError::ConfigurationError(
+                "Timeout must be at least one second".to_string(),
+            ));
+        }
+
+        Ok(ProviderConfig {
+            provider_type,
+            api_key: SecretString::new(api_key),
+            api_base,
+            model,
+            timeout_secs,
+            max_retries: self.max_retries.unwrap_or(defaults.max_retries),
+            rate_limit_tpm: self.rate_limit_tpm.or(defaults.rate_limit_tpm),
+            thinking_budget: self.thinking_budget.or(defaults.thinking_budget),
+            ollama_auto_pull: defaults.ollama_auto_pull,
+        })
+    }
+}
+
+/// Normalize a user-supplied API base URL into the shape the provider's client expects
+///
+/// Trailing slashes are stripped. OpenAI-compatible bases given as a bare host get `/v1`
+/// appended, Ollama bases must end in `/v1` and Anthropic bases are kept at the host
+/// root because the SDK adds `/v1/...` itself. Custom paths (e.g. Azure deployments)
+/// are left alone for OpenAI.
+pub fn normalize_api_base(provider_type: ProviderType, api_base: &str) -> Result<String, LLMError> {
+    let example = ProviderConfig::default_for_provider(provider_type).api_base;
+    let invalid = |reason: &str| {
+        LLMError::ConfigurationError(format!(
+            "Invalid AUTOFIX_API_BASE '{}' for {:?}: {}. Expected something like {}",
+            api_base, provider_type, reason, example
+        ))
+    };
+
+    let trimmed = api_base.trim().trim_end_matches('/');
+    let Some((scheme, rest)) = trimmed.split_once("://") else {
+        return Err(invalid("missing http:// or https://"));
+    };
+    if scheme != "http" && scheme != "https" {
+        return Err(invalid("must use http or https"));
+    }
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if host.is_empty() {
+        return Err(invalid("missing host"));
+    }
+    if ["/chat/completions", "/completions", "/messages"]
+        .iter()
+        .any(|endpoint| path.ends_with(endpoint))
+    {
+        return Err(invalid("use the base URL without the endpoint path"));
+    }
+
+    let path = match provider_type {
+        ProviderType::Claude => path.strip_suffix("/v1").unwrap_or(path).to_string(),
+        ProviderType::OpenAI | ProviderType::Groq => {
+            if path.is_empty() || path == "/openai" {
+                format!("{}/v1", path)
+            } else {
+                path.to_string()
+            }
+        }
+        ProviderType::Ollama => match path {
+            // The native API lives under /api; autofix talks to the OpenAI-compatible one
+            "" | "/api" => "/v1".to_string(),
+            _ if path.ends_with("/v1") => path.to_string(),
+            _ => return Err(invalid("Ollama's OpenAI-compatible API lives under /v1")),
+        },
+    };
+
+    Ok(format!("{}://{}{}", scheme, host, path))
+}
+
 impl Default for ProviderConfig {
     fn default() -> Self {
         Self::default_for_provider(ProviderType::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_model_is_listed_as_known() {
+        for provider_type in ProviderType::ALL {
+            let default_model = ProviderConfig::default_for_provider(provider_type).model;
+            assert!(
+                provider_type
+                    .known_models()
+                    .iter()
+                    .any(|known| known.name == default_model),
+                "{:?} default {} missing from known models",
+                provider_type,
+                default_model
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_models_match_their_provider() {
+        for provider_type in ProviderType::ALL {
+            for known in provider_type.known_models() {
+                assert_eq!(provider_type.check_model(known.name), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_model_limits_cover_versions_of_known_models() {
+        let claude = |model| ProviderType::Claude.model_limits(model).unwrap();
+        assert_eq!(
+            claude("claude-sonnet-4-20250514"),
+            ModelLimits {
+                context_window: 200_000,
+                max_output_tokens: 64_000
+            }
+        );
+        assert_eq!(claude("claude-opus-4-1-20250805").max_output_tokens, 32_000);
+        assert_eq!(claude("claude-opus-4-5-20251101").max_output_tokens, 64_000);
+        assert_eq!(claude("claude-3-5-haiku-20241022").max_output_tokens, 8_192);
+        assert_eq!(claude("claude-3-haiku-20240307").max_output_tokens, 4_096);
+        assert_eq!(ProviderType::Claude.model_limits("claude-2.1"), None);
+
+        assert_eq!(
+            ProviderType::OpenAI
+                .model_limits("gpt-4o-mini")
+                .unwrap()
+                .max_output_tokens,
+            16_384
+        );
+        assert_eq!(ProviderType::Ollama.model_limits("llama3"), None);
+    }
+
+    #[test]
+    fn test_openai_model_with_claude_is_rejected() {
+        let error = ProviderType::Claude.check_model("gpt-4o").unwrap_err();
+        assert!(error.contains("use --provider openai"), "{}", error);
+        assert!(ProviderType::Claude.check_model("o3-mini").is_err());
+    }
+
+    #[test]
+    fn test_open_weight_model_with_claude_is_rejected() {
+        let error = ProviderType::Claude.check_model("llama2").unwrap_err();
+        assert!(!error.contains("use --provider"), "{}", error);
+        assert!(error.contains("--allow-model-mismatch"), "{}", error);
+    }
+
+    #[test]
+    fn test_claude_model_with_openai_is_rejected() {
+        let error = ProviderType::OpenAI
+            .check_model("claude-sonnet-4")
+            .unwrap_err();
+        assert!(error.contains("use --provider claude"), "{}", error);
+        // OpenAI-compatible endpoints host open-weight models
+        assert_eq!(
+            ProviderType::OpenAI.check_model("meta-llama/Llama-3.3-70B-Instruct-Turbo"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_claude_and_openai_models_with_groq_are_rejected() {
+        assert!(ProviderType::Groq.check_model("claude-opus-4").is_err());
+        assert!(ProviderType::Groq.check_model("gpt-4").is_err());
+    }
+
+    #[test]
+    fn test_claude_and_openai_models_with_ollama_are_rejected() {
+        assert!(
+            ProviderType::Ollama
+                .check_model("claude-3-5-haiku-latest")
+                .is_err()
+        );
+        assert!(ProviderType::Ollama.check_model("gpt-4o").is_err());
+        assert_eq!(ProviderType::Ollama.check_model("gpt-oss:20b"), Ok(()));
+    }
+
+    #[test]
+    fn test_input_cost_matches_most_specific_model() {
+        assert_eq!(
+            ProviderType::OpenAI.input_cost_per_million_tokens("gpt-4o-mini-2024-07-18"),
+            Some(0.15)
+        );
+        assert_eq!(
+            ProviderType::OpenAI.input_cost_per_million_tokens("gpt-4"),
+            Some(30.0)
+        );
+        assert_eq!(
+            ProviderType::Claude.input_cost_per_million_tokens("claude-sonnet-4"),
+            Some(3.0)
+        );
+        assert_eq!(
+            ProviderType::Ollama.input_cost_per_million_tokens("codellama"),
+            Some(0.0)
+        );
+        assert_eq!(
+            ProviderType::Groq.input_cost_per_million_tokens("gemma2-9b-it"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_builder_fills_in_provider_defaults() {
+        let config = ProviderConfig::builder()
+            .provider_type(ProviderType::OpenAI)
+            .api_key("sk-test")
+            .model("gpt-4o")
+            .rate_limit_tpm(10000)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.api_key(), "sk-test");
+        assert_eq!(config.model, "gpt-4o");
+        assert_eq!(config.rate_limit_tpm, Some(10000));
+        assert_eq!(config.api_base, "https://api.openai.com/v1");
+        assert_eq!(config.timeout_secs, 30);
+        assert_eq!(config.max_retries, 3);
+
+        // Ollama needs neither an API key nor a model
+        let config = ProviderConfig::builder()
+            .provider_type(ProviderType::Ollama)
+            .api_base("http://gpu-box:11434")
+            .build()
+            .unwrap();
+        assert_eq!(config.model, "llama2");
+        assert_eq!(config.api_base, "http://gpu-box:11434/v1");
+        assert_eq!(config.timeout_secs, 120);
+    }
+
+    #[test]
+    fn test_builder_validates() {
+        let error = |builder: ProviderConfigBuilder| builder.build().unwrap_err().to_string();
+
+        assert_eq!(
+            error(ProviderConfig::builder().model("gpt-4")),
+            "Configuration error: A provider type is required"
+        );
+        assert_eq!(
+            error(ProviderConfig::builder().provider_type(ProviderType::Claude)),
+            "Configuration error: An API key is required for Claude; set ANTHROPIC_API_KEY"
+        );
+        let groq = || {
+            ProviderConfig::builder()
+                .provider_type(ProviderType::Groq)
+                .api_key("gsk_test")
+        };
+        assert!(error(groq().model(" ")).contains("Model name"));
+        assert!(error(groq().timeout_secs(0)).contains("Timeout"));
+        assert!(error(groq().api_base("api.groq.com")).contains("missing http"));
+    }
+
+    #[test]
+    fn test_normalize_openai_api_base() {
+        let normalize = |base| normalize_api_base(ProviderType::OpenAI, base).unwrap();
+
+        assert_eq!(
+            normalize("https://api.openai.com/v1/"),
+            "https://api.openai.com/v1"
+        );
+        assert_eq!(
+            normalize("https://api.together.xyz"),
+            "https://api.together.xyz/v1"
+        );
+        // Custom deployment paths are kept
+        assert_eq!(
+            normalize("https://res.openai.azure.com/openai/deployments/gpt4/"),
+            "https://res.openai.azure.com/openai/deployments/gpt4"
+        );
+        assert!(matches!(
+            normalize_api_base(
+                ProviderType::OpenAI,
+                "https://api.openai.com/v1/chat/completions"
+            ),
+            Err(LLMError::ConfigurationError(_))
+        ));
+        assert!(normalize_api_base(ProviderType::OpenAI, "api.openai.com/v1").is_err());
+    }
+
+    #[test]
+    fn test_normalize_groq_api_base() {
+        let normalize = |base| normalize_api_base(ProviderType::Groq, base).unwrap();
+
+        assert_eq!(
+            normalize("https://api.groq.com/openai"),
+            "https://api.groq.com/openai/v1"
+        );
+        assert_eq!(
+            normalize("https://api.groq.com/openai/v1//"),
+            "https://api.groq.com/openai/v1"
+        );
+    }
+
+    #[test]
+    fn test_normalize_ollama_api_base() {
+        let normalize = |base| normalize_api_base(ProviderType::Ollama, base).unwrap();
+
+        assert_eq!(
+            normalize("http://localhost:11434"),
+            "http://localhost:11434/v1"
+        );
+        assert_eq!(
+            normalize("http://localhost:11434/api/"),
+            "http://localhost:11434/v1"
+        );
+        assert_eq!(
+            normalize("http://gpu-box:11434/v1/"),
+            "http://gpu-box:11434/v1"
+        );
+        assert!(normalize_api_base(ProviderType::Ollama, "http://localhost:11434/chat").is_err());
+    }
+
+    #[test]
+    fn test_normalize_claude_api_base() {
+        let normalize = |base| normalize_api_base(ProviderType::Claude, base).unwrap();
+
+        assert_eq!(
+            normalize("https://api.anthropic.com/"),
+            "https://api.anthropic.com"
+        );
+        assert_eq!(
+            normalize("https://api.anthropic.com/v1"),
+            "https://api.anthropic.com"
+        );
+        assert!(
+            normalize_api_base(
+                ProviderType::Claude,
+                "https://api.anthropic.com/v1/messages"
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// Groq provider implementation
+// Reuses the OpenAI provider since Groq exposes an OpenAI-compatible endpoint
+
+use super::{LLMError, LLMRequest, LLMResponse, OpenAIProvider, ProviderConfig, ProviderType};
+use crate::llm::provider_trait::LLMProvider;
+use async_trait::async_trait;
+use futures::stream::Stream;
+use std::pin::Pin;
+
+/// Groq provider implementation
+/// Low-latency inference for open models, well suited for many cheap exploration turns
+pub struct GroqProvider {
+    config: ProviderConfig,
+    inner: OpenAIProvider,
+}
+
+#[async_trait]
+impl LLMProvider for GroqProvider {
+    fn new(config: ProviderConfig) -> Result<Self, LLMError> {
+        // Validate configuration
+        Self::validate_config(&config)?;
+
+        let inner = OpenAIProvider::with_config(config.clone())?;
+
+        Ok(Self { config, inner })
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Groq
+    }
+
+    async fn complete(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        self.inner.complete(request).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LLMError> {
+        self.inner.list_models().await
+    }
+
+    async fn check_credentials(&self) -> Result<(), LLMError> {
+        self.inner.check_credentials().await
+    }
+
+    async fn complete_stream(
+        &self,
+        request: LLMRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<LLMResponse, LLMError>> + Send>>, LLMError> {
+        self.inner.complete_stream(request).await
+    }
+
+    fn estimate_tokens(&self, request: &LLMRequest) -> u32 {
+        self.inner.estimate_tokens(request)
+    }
+
+    fn validate_config(config: &ProviderConfig) -> Result<(), LLMError> {
+        // Check provider type
+        if config.provider_type != ProviderType::Groq {
+            return Err(LLMError::ConfigurationError(
+                "Invalid provider type for Groq provider".to_string(),
+            ));
+        }
+
+        // Check API key is not empty
+        if config.api_key().is_empty() {
+            return Err(LLMError::ConfigurationError(
+                "API key is required for Groq provider (set GROQ_API_KEY)".to_string(),
+            ));
+        }
+
+        // Check endpoint is HTTPS
+        if !config.api_base.starts_with("https://") {
+            return Err(LLMError::ConfigurationError(
+                "Groq API endpoint must use HTTPS".to_string(),
+            ));
+        }
+
+        // Check model is not empty
+        if config.model.is_empty() {
+            return Err(LLMError::ConfigurationError(
+                "Model name is required for Groq provider".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn max_context_length(&self) -> u32 {
+        // Return context length based on model name
+        if self.config.model.contains("mixtral") {
+            32768
+        } else if self.config.model.contains("gemma") {
+            8192
+        } else if self.config.model.contains("llama-3.1")
+            || self.config.model.contains("llama-3.3")
+            || self.config.model.contains("llama-4")
+        {
+            131072
+        } else if self.config.model.contains("llama3") {
+            8192
+        } else {
+            // Default for unknown models
+            8192
+        }
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn supports_tools(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groq_config(api_key: &str, model: &str) -> ProviderConfig {
+        // An empty key is rejected by the builder, so tests of validation set it directly
+        ProviderConfig {
+            api_key: secrecy::SecretString::new(api_key.to_string()),
+            model: model.to_string(),
+            ..ProviderConfig::default_for_provider(ProviderType::Groq)
+        }
+    }
+
+    #[test]
+    fn test_validate_config_requires_api_key() {
+        let result = GroqProvider::validate_config(&groq_config("", "llama-3.3-70b-versatile"));
+        assert!(matches!(result, Err(LLMError::ConfigurationError(_))));
+
+        let result =
+            GroqProvider::validate_config(&groq_config("gsk_test", "llama-3.3-70b-versatile"));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_max_context_length_per_model() {
+        let provider =
+            GroqProvider::new(groq_config("gsk_test", "llama-3.3-70b-versatile")).unwrap();
+        assert_eq!(provider.max_context_length(), 131072);
+
+        let provider = GroqProvider::new(groq_config("gsk_test", "mixtral-8x7b-32768")).unwrap();
+        assert_eq!(provider.max_context_length(), 32768);
+        assert_eq!(provider.provider_type(), ProviderType::Groq);
+    }
+
+    #[test]
+    fn test_rejected_key_is_an_authentication_error() {
+        // Groq's response to a revoked key
+        let error: async_openai::error::ApiError = serde_json::from_value(serde_json::json!({
+            "message": "Invalid API Key",
+            "type": "invalid_request_error",
+            "code": "invalid_api_key"
+        }))
+        .unwrap();
+
+        assert!(crate::llm::openai_provider::is_auth_error(
+            &async_openai::error::OpenAIError::ApiError(error)
+        ));
+    }
+}
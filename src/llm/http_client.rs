@@ -0,0 +1,125 @@
+// HTTP clients for provider calls
+//
+// reqwest honors HTTPS_PROXY, HTTP_PROXY and NO_PROXY on its own. Behind a
+// proxy that intercepts TLS, AUTOFIX_CA_BUNDLE names a PEM file whose
+// certificates are trusted in addition to the system's root certificates.
+
+use super::LLMError;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// Variable naming a PEM file with additional root certificates
+pub const CA_BUNDLE_VAR: &str = "AUTOFIX_CA_BUNDLE";
+
+/// What OpenSSL, rustls and Security.framework report for an untrusted certificate chain
+const TLS_VERIFICATION_MESSAGES: &[&str] = &[
+    "certificate verify failed",
+    "unable to get local issuer certificate",
+    "self signed certificate",
+    "self-signed certificate",
+    "unknownissuer",
+    "invalid peer certificate",
+    "certificate was not trusted",
+    "certificate is not trusted",
+];
+
+/// Path of the `AUTOFIX_CA_BUNDLE` file, if one is configured
+pub fn ca_bundle_path() -> Option<PathBuf> {
+    std::env::var_os(CA_BUNDLE_VAR)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Contents of the `AUTOFIX_CA_BUNDLE` file, if one is configured
+fn ca_bundle() -> Result<Option<Vec<u8>>, LLMError> {
+    let Some(path) = ca_bundle_path() else {
+        return Ok(None);
+    };
+    fs::read(&path).map(Some).map_err(|e| {
+        LLMError::ConfigurationError(format!(
+            "Failed to read {} {}: {}",
+            CA_BUNDLE_VAR,
+            path.display(),
+            e
+        ))
+    })
+}
+
+fn invalid_bundle(error: impl Error) -> LLMError {
+    LLMError::ConfigurationError(format!(
+        "{} is not a PEM certificate bundle: {}",
+        CA_BUNDLE_VAR, error
+    ))
+}
+
+fn build_error(error: impl Error) -> LLMError {
+    LLMError::ConfigurationError(format!("Failed to create HTTP client: {}", error))
+}
+
+/// Client for the requests the providers send themselves
+pub fn client() -> Result<reqwest::Client, LLMError> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(pem) = ca_bundle()? {
+        for certificate in reqwest::Certificate::from_pem_bundle(&pem).map_err(invalid_bundle)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    builder.build().map_err(build_error)
+}
+
+/// Client for async-openai, which is built on a newer reqwest
+pub fn openai_client() -> Result<reqwest_012::Client, LLMError> {
+    let mut builder = reqwest_012::Client::builder();
+    if let Some(pem) = ca_bundle()? {
+        for certificate in
+            reqwest_012::Certificate::from_pem_bundle(&pem).map_err(invalid_bundle)?
+        {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    builder.build().map_err(build_error)
+}
+
+/// `error` with all of its causes, which is where TLS failures are reported
+pub fn describe(error: &(dyn Error + 'static)) -> String {
+    let mut messages = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(cause) = source {
+        messages.push(cause.to_string());
+        source = cause.source();
+    }
+    messages.join(": ")
+}
+
+/// `TlsVerification` if `message` reports an untrusted certificate chain
+pub fn tls_error(message: &str) -> Option<LLMError> {
+    let lowercase = message.to_lowercase();
+    TLS_VERIFICATION_MESSAGES
+        .iter()
+        .any(|pattern| lowercase.contains(pattern))
+        .then(|| LLMError::TlsVerification(message.to_string()))
+}
+
+/// A request that could not be sent, as `TlsVerification` if the certificate check failed
+pub fn send_error(error: reqwest::Error) -> LLMError {
+    tls_error(&describe(&error)).unwrap_or_else(|| error.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_untrusted_certificate_is_a_tls_error() {
+        let message = "error sending request for url (https://api.anthropic.com/v1/messages): error trying to connect: error:0A000086:SSL routines:tls_post_process_server_certificate:certificate verify failed:ssl/statem/statem_clnt.c:1889: (unable to get local issuer certificate)";
+        match tls_error(message) {
+            Some(error @ LLMError::TlsVerification(_)) => {
+                assert!(error.to_string().contains(CA_BUNDLE_VAR))
+            }
+            other => panic!("expected a TLS verification error, got {:?}", other),
+        }
+        assert!(tls_error("invalid peer certificate: UnknownIssuer").is_some());
+        assert!(tls_error("error trying to connect: Connection refused").is_none());
+    }
+}
@@ -0,0 +1,188 @@
+// Wire-level log of provider calls, enabled with --trace-http
+//
+// Each call becomes one JSON line with the request body as sent and the
+// response body as received (or the error), so a misbehaving provider can be
+// reported with the exact exchange. Secrets are redacted and images truncated
+// before anything is written.
+
+use super::{LLMError, ProviderType};
+use crate::redact::redact;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+static HTTP_TRACE: OnceLock<HttpTrace> = OnceLock::new();
+
+/// A trace file receiving one line per provider call
+pub struct HttpTrace {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+/// A provider call in flight, recorded once its response arrives
+pub struct TraceCall {
+    provider: ProviderType,
+    url: String,
+    request: serde_json::Value,
+    started: Instant,
+}
+
+#[derive(Serialize)]
+struct TraceEntry<'a> {
+    timestamp: u64,
+    provider: ProviderType,
+    url: &'a str,
+    duration_ms: u128,
+    request: &'a serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl HttpTrace {
+    /// Create a new trace file in `dir`
+    pub fn create(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+        let path = dir.join(format!("http-{}.jsonl", timestamp));
+        let file = File::create(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `call` with its outcome, redacting `secrets`
+    fn write<T: Serialize>(
+        &self,
+        call: TraceCall,
+        response: &Result<T, LLMError>,
+        secrets: &[&str],
+    ) {
+        let (response, error) = match response {
+            Ok(response) => (serde_json::to_value(response).ok(), None),
+            Err(error) => (None, Some(error.to_string())),
+        };
+        let entry = TraceEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            provider: call.provider,
+            url: &call.url,
+            duration_ms: call.started.elapsed().as_millis(),
+            request: &call.request,
+            response,
+            error,
+        };
+        let Ok(line) = serde_json::to_string(&entry) else {
+            return;
+        };
+
+        // Tracing must never fail a run
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "{}", redact(&line, secrets));
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Trace all provider calls of this process to a new file in `dir`, returning its path
+pub fn enable(dir: &Path) -> io::Result<PathBuf> {
+    let trace = HttpTrace::create(dir)?;
+    let path = trace.path().to_path_buf();
+    let _ = HTTP_TRACE.set(trace);
+    Ok(path)
+}
+
+/// Begin tracing a call to `url`, or `None` when tracing is off
+pub fn start(provider: ProviderType, url: &str, request: &impl Serialize) -> Option<TraceCall> {
+    HTTP_TRACE.get()?;
+    Some(TraceCall {
+        provider,
+        url: url.to_string(),
+        request: serde_json::to_value(request).unwrap_or_default(),
+        started: Instant::now(),
+    })
+}
+
+/// Record the outcome of a call begun with `start`
+pub fn finish<T: Serialize>(
+    call: Option<TraceCall>,
+    response: &Result<T, LLMError>,
+    secrets: &[&str],
+) {
+    if let (Some(call), Some(trace)) = (call, HTTP_TRACE.get()) {
+        trace.write(call, response, secrets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_trace_redacts_secrets_and_images() {
+        let dir = std::env::temp_dir().join(format!("http-trace-{}", Uuid::new_v4()));
+        let trace = HttpTrace::create(&dir).unwrap();
+        let call = TraceCall {
+            provider: ProviderType::OpenAI,
+            url: "https://api.openai.com/v1/chat/completions".to_string(),
+            request: serde_json::json!({
+                "model": "gpt-4o",
+                "messages": [{"role": "user", "content": format!(
+                    "data:image/png;base64,{}", "iVBO".repeat(200)
+                )}]
+            }),
+            started: Instant::now(),
+        };
+
+        trace.write(
+            call,
+            &Ok(serde_json::json!({"choices": [], "echo": "sk-test-secret"})),
+            &["sk-test-secret"],
+        );
+        trace.write::<serde_json::Value>(
+            TraceCall {
+                provider: ProviderType::Claude,
+                url: "https://api.anthropic.com/v1/messages".to_string(),
+                request: serde_json::json!({}),
+                started: Instant::now(),
+            },
+            &Err(LLMError::ServerError { status: 500 }),
+            &[],
+        );
+
+        let contents = fs::read_to_string(trace.path()).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["provider"], "openai");
+        assert_eq!(lines[0]["response"]["echo"], "[REDACTED]");
+        assert!(
+            lines[0]["request"]["messages"][0]["content"]
+                .as_str()
+                .unwrap()
+                .ends_with("[base64 data, 800 bytes omitted]")
+        );
+        assert_eq!(lines[1]["error"], "Server error: status 500");
+        assert!(lines[1].get("response").is_none());
+
+        // Clean up
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+// Scripted provider for tests
+// Returns canned responses (or errors) in order and records every request it receives
+
+use super::{
+    LLMError, LLMRequest, LLMResponse, ProviderCapabilities, ProviderConfig, ProviderType,
+    StopReason,
+};
+use super::{TokenUsage, ToolCall};
+use crate::llm::provider_trait::LLMProvider;
+use async_trait::async_trait;
+use futures::stream::{Stream, StreamExt};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+/// Responses still to be returned and requests received so far
+#[derive(Default)]
+pub struct MockState {
+    pub responses: Mutex<VecDeque<Result<LLMResponse, LLMError>>>,
+    pub requests: Mutex<Vec<LLMRequest>>,
+    /// How many of the requests went through `complete_stream`
+    pub stream_calls: Mutex<usize>,
+}
+
+/// Provider that replays scripted responses
+///
+/// Keep a clone of `state` to inspect the recorded requests after handing the
+/// provider to a pipeline.
+pub struct MockProvider {
+    pub state: Arc<MockState>,
+    capabilities: ProviderCapabilities,
+    /// Streams hang after their text instead of finishing
+    stall_streams: bool,
+}
+
+impl MockProvider {
+    /// Create a provider that returns `responses` in order
+    pub fn with_responses(responses: Vec<LLMResponse>) -> Self {
+        Self::with_results(responses.into_iter().map(Ok).collect())
+    }
+
+    /// Create a provider that returns `results`, errors included, in order
+    pub fn with_results(results: Vec<Result<LLMResponse, LLMError>>) -> Self {
+        let state = MockState {
+            responses: Mutex::new(results.into()),
+            requests: Mutex::new(Vec::new()),
+            stream_calls: Mutex::new(0),
+        };
+        Self {
+            state: Arc::new(state),
+            capabilities: ProviderCapabilities::default(),
+            stall_streams: false,
+        }
+    }
+
+    /// Pretend to be a model with limited capabilities
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Hang streamed responses after their text, like a connection that stops sending
+    pub fn with_stalled_streams(mut self) -> Self {
+        self.stall_streams = true;
+        self
+    }
+
+    /// Response that asks for a single tool call
+    pub fn tool_call(id: &str, name: &str, input: serde_json::Value) -> LLMResponse {
+        LLMResponse {
+            content: None,
+            thinking: None,
+            tool_calls: vec![ToolCall {
+                id: id.to_string(),
+                name: name.to_string(),
+                input,
+            }],
+            stop_reason: StopReason::ToolUse,
+            usage: TokenUsage::new(100, 10),
+        }
+    }
+
+    /// Response that ends the turn with plain text
+    pub fn text(text: &str) -> LLMResponse {
+        LLMResponse {
+            content: Some(text.to_string()),
+            thinking: None,
+            tool_calls: vec![],
+            stop_reason: StopReason::EndTurn,
+            usage: TokenUsage::new(100, 10),
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MockProvider {
+    fn new(_config: ProviderConfig) -> Result<Self, LLMError> {
+        Ok(Self::with_responses(vec![]))
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Claude
+    }
+
+    async fn complete(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        self.state.requests.lock().unwrap().push(request);
+        self.state
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| {
+                Err(LLMError::InvalidRequest(
+                    "No scripted response left".to_string(),
+                ))
+            })
+    }
+
+    /// Replays the next scripted response as a text delta followed by the rest
+    async fn complete_stream(
+        &self,
+        request: LLMRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<LLMResponse, LLMError>> + Send>>, LLMError> {
+        if !self.capabilities.streaming {
+            return Err(LLMError::StreamingNotSupported);
+        }
+        *self.state.stream_calls.lock().unwrap() += 1;
+        let mut rest = self.complete(request).await?;
+        let text = LLMResponse {
+            content: rest.content.take(),
+            thinking: rest.thinking.take(),
+            tool_calls: vec![],
+            stop_reason: StopReason::EndTurn,
+            usage: TokenUsage::default(),
+        };
+        if self.stall_streams {
+            return Ok(Box::pin(
+                futures::stream::iter(vec![Ok(text)]).chain(futures::stream::pending()),
+            ));
+        }
+        Ok(Box::pin(futures::stream::iter(vec![Ok(text), Ok(rest)])))
+    }
+
+    fn estimate_tokens(&self, _request: &LLMRequest) -> u32 {
+        0
+    }
+
+    fn validate_config(_config: &ProviderConfig) -> Result<(), LLMError> {
+        Ok(())
+    }
+
+    fn max_context_length(&self) -> u32 {
+        200000
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities
+    }
+}
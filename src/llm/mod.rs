@@ -1,20 +1,34 @@
 // LLM Provider abstraction module
-// Provides a unified interface for multiple LLM providers (Claude, OpenAI, Ollama)
+// Provides a unified interface for multiple LLM providers (Claude, OpenAI, Groq, Ollama)
 
+pub mod cassette;
 pub mod claude_provider;
 pub mod config;
+pub mod groq_provider;
+pub mod http_client;
+pub mod http_trace;
+#[cfg(test)]
+pub mod mock_provider;
 pub mod ollama_provider;
 pub mod openai_provider;
 pub mod provider_trait;
+pub mod retry;
+pub mod token_counter;
 
 // Re-export core types
+pub use cassette::CassetteProvider;
 pub use claude_provider::ClaudeProvider;
 pub use config::{ProviderConfig, ProviderType};
+pub use groq_provider::GroqProvider;
 pub use ollama_provider::OllamaProvider;
 pub use openai_provider::OpenAIProvider;
 pub use provider_trait::LLMProvider;
+pub use token_counter::TokenCounter;
 
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+use std::time::Duration;
 use thiserror::Error;
 
 /// A message in a conversation
@@ -48,6 +62,9 @@ pub struct LLMRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMResponse {
     pub content: Option<String>,
+    /// Reasoning a thinking model produced before its answer, kept apart from `content`
+    #[serde(default)]
+    pub thinking: Option<String>,
     pub tool_calls: Vec<ToolCall>,
     pub stop_reason: StopReason,
     pub usage: TokenUsage,
@@ -70,7 +87,9 @@ pub struct ToolCall {
 }
 
 /// Token usage metrics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Usages add up with `+` and `+=`, which recompute the total from the sums.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TokenUsage {
     pub input_tokens: u32,
     pub output_tokens: u32,
@@ -85,6 +104,62 @@ impl TokenUsage {
             total_tokens: input_tokens + output_tokens,
         }
     }
+
+    /// No tokens, the starting point for summing usage
+    pub fn zero() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl std::ops::Add for TokenUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(
+            self.input_tokens + other.input_tokens,
+            self.output_tokens + other.output_tokens,
+        )
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl std::fmt::Display for TokenUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} tokens ({} in / {} out)",
+            self.total_tokens, self.input_tokens, self.output_tokens
+        )
+    }
+}
+
+/// What a provider/model combination can handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderCapabilities {
+    /// Function/tool calling
+    pub tools: bool,
+    /// Image input such as simulator snapshots
+    pub images: bool,
+    /// Streaming responses
+    pub streaming: bool,
+    /// A separate system prompt
+    pub system_prompt: bool,
+}
+
+impl Default for ProviderCapabilities {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            images: true,
+            streaming: true,
+            system_prompt: true,
+        }
+    }
 }
 
 /// Reason why LLM generation stopped
@@ -105,23 +180,169 @@ pub enum LLMError {
     #[error("Authentication failed: invalid API key")]
     AuthenticationError,
 
-    #[error("Rate limit exceeded: {0}")]
-    RateLimitError(String),
+    /// `retry_after` is the delay the server asked for, if it named one
+    #[error("Rate limit exceeded: {message}")]
+    RateLimitError {
+        message: String,
+        retry_after: Option<Duration>,
+    },
 
     #[error("Network error: {0}")]
-    NetworkError(#[from] reqwest::Error),
+    NetworkError(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// The server's certificate chain isn't trusted, e.g. behind a proxy that intercepts TLS
+    #[error(
+        "TLS certificate verification failed: {0}. Behind a proxy that intercepts TLS, set AUTOFIX_CA_BUNDLE to a PEM file with its root certificate"
+    )]
+    TlsVerification(String),
 
     #[error("Server error: status {status}")]
     ServerError { status: u16 },
 
+    #[error("Provider overloaded: {message}")]
+    Overloaded {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
 
+    /// The prompt doesn't fit the model's context window; either count may be unknown
+    #[error("Context length exceeded: {requested:?} tokens requested, limit {limit:?}")]
+    ContextLengthExceeded {
+        limit: Option<u32>,
+        requested: Option<u32>,
+    },
+
     #[error("Streaming not supported by this provider")]
     StreamingNotSupported,
 
     #[error("Configuration error: {0}")]
     ConfigurationError(String),
+
+    /// The run was cancelled while the request was in flight, so it was dropped
+    #[error("Request cancelled")]
+    Cancelled,
+}
+
+// Held boxed, as async-openai fails with errors of a newer reqwest
+impl From<reqwest::Error> for LLMError {
+    fn from(error: reqwest::Error) -> Self {
+        LLMError::NetworkError(Box::new(error))
+    }
+}
+
+impl LLMError {
+    /// Whether the same request may succeed when sent again later
+    ///
+    /// Rate limits, overloaded servers, 5xx responses and network failures are
+    /// transient; authentication, invalid requests and configuration are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LLMError::RateLimitError { .. }
+            | LLMError::Overloaded { .. }
+            | LLMError::ServerError { .. }
+            | LLMError::NetworkError(_) => true,
+            LLMError::AuthenticationError
+            | LLMError::TlsVerification(_)
+            | LLMError::InvalidRequest(_)
+            | LLMError::ContextLengthExceeded { .. }
+            | LLMError::StreamingNotSupported
+            | LLMError::ConfigurationError(_)
+            | LLMError::Cancelled => false,
+        }
+    }
+
+    /// Delay the server asked for before the next attempt, if it named one
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            LLMError::RateLimitError { retry_after, .. }
+            | LLMError::Overloaded { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Anthropic: "prompt is too long: 208000 tokens > 200000 maximum"
+static PROMPT_TOO_LONG_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"prompt is too long: (\d+) tokens > (\d+) maximum").unwrap());
+
+/// OpenAI and Groq: "maximum context length is 128000 tokens. However, your messages resulted in 130000 tokens"
+static MAXIMUM_CONTEXT_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"maximum context length is (\d+) tokens(?:.*?(?:resulted in|requested) (\d+) tokens)?",
+    )
+    .unwrap()
+});
+
+/// Output tokens `estimate_tokens` counts for a response
+///
+/// `max_tokens` is an upper bound that responses rarely come near; counting all of a
+/// model's output limit would hold up the rate limiter for tokens never produced.
+pub fn estimated_output_tokens(request: &LLMRequest) -> u32 {
+    const TYPICAL_OUTPUT_TOKENS: u32 = 1000;
+    request
+        .max_tokens
+        .map_or(TYPICAL_OUTPUT_TOKENS, |max_tokens| {
+            max_tokens.min(TYPICAL_OUTPUT_TOKENS)
+        })
+}
+
+/// Recognize a provider's context-window error in its (sanitized) error message
+///
+/// Returns `None` for every other error, which callers keep mapping as before.
+pub fn context_length_error(message: &str) -> Option<LLMError> {
+    let number = |m: Option<regex::Match>| m.and_then(|m| m.as_str().parse().ok());
+    if let Some(captures) = PROMPT_TOO_LONG_PATTERN.captures(message) {
+        return Some(LLMError::ContextLengthExceeded {
+            limit: number(captures.get(2)),
+            requested: number(captures.get(1)),
+        });
+    }
+    if let Some(captures) = MAXIMUM_CONTEXT_PATTERN.captures(message) {
+        return Some(LLMError::ContextLengthExceeded {
+            limit: number(captures.get(1)),
+            requested: number(captures.get(2)),
+        });
+    }
+    // Error codes and messages without counts (OpenAI/Groq code, Ollama wording)
+    let lower = message.to_lowercase();
+    [
+        "context_length_exceeded",
+        "exceeds the context length",
+        "context length exceeded",
+    ]
+    .iter()
+    .any(|marker| lower.contains(marker))
+    .then_some(LLMError::ContextLengthExceeded {
+        limit: None,
+        requested: None,
+    })
+}
+
+/// Check the configured API key with a cheap request, so a rejected key ends the
+/// run before any slow work
+///
+/// A rejected key is reported as a `ConfigurationError` naming the variable it came from.
+pub async fn check_credentials(config: &ProviderConfig) -> Result<(), LLMError> {
+    let provider = ProviderFactory::create(config.clone())?;
+    match provider.check_credentials().await {
+        Err(LLMError::AuthenticationError) => Err(LLMError::ConfigurationError(
+            invalid_key_message(config.provider_type),
+        )),
+        result => result,
+    }
+}
+
+/// What to tell the user when the provider rejects their API key
+fn invalid_key_message(provider_type: ProviderType) -> String {
+    // Ollama only needs a key behind an authenticating proxy
+    let variable = provider_type.api_key_var().unwrap_or("OLLAMA_API_KEY");
+    format!(
+        "{} appears invalid or expired: the {:?} API rejected it. Check the key or create a new one",
+        variable, provider_type
+    )
 }
 
 /// Factory for creating LLM providers
@@ -129,20 +350,142 @@ pub struct ProviderFactory;
 
 impl ProviderFactory {
     /// Create a provider from configuration
+    ///
+    /// Replaying a cassette needs no provider at all; recording one wraps the provider.
     pub fn create(config: ProviderConfig) -> Result<Box<dyn LLMProvider>, LLMError> {
-        match config.provider_type {
+        if cassette::is_replaying() {
+            return Ok(Box::new(CassetteProvider::new(config)?));
+        }
+
+        let model = config.model.clone();
+        let provider: Box<dyn LLMProvider> = match config.provider_type {
             ProviderType::Claude => {
                 ClaudeProvider::validate_config(&config)?;
-                Ok(Box::new(ClaudeProvider::new(config)?))
+                Box::new(ClaudeProvider::new(config)?)
             }
             ProviderType::OpenAI => {
                 OpenAIProvider::validate_config(&config)?;
-                Ok(Box::new(OpenAIProvider::new(config)?))
+                Box::new(OpenAIProvider::new(config)?)
+            }
+            ProviderType::Groq => {
+                GroqProvider::validate_config(&config)?;
+                Box::new(GroqProvider::new(config)?)
             }
             ProviderType::Ollama => {
                 OllamaProvider::validate_config(&config)?;
-                Ok(Box::new(OllamaProvider::new(config)?))
+                Box::new(OllamaProvider::new(config)?)
             }
+        };
+        Ok(cassette::wrap(provider, &model))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_usage_adds_up() {
+        let mut usage = TokenUsage::zero();
+        usage += TokenUsage::new(1200, 80);
+        usage += TokenUsage::new(300, 20);
+        assert_eq!(usage, TokenUsage::new(1500, 100));
+        assert_eq!(usage.total_tokens, 1600);
+
+        // The total is recomputed rather than summed, so it can't drift from its parts
+        let inconsistent = TokenUsage {
+            input_tokens: 10,
+            output_tokens: 5,
+            total_tokens: 0,
+        };
+        assert_eq!(TokenUsage::zero() + inconsistent, TokenUsage::new(10, 5));
+    }
+
+    #[test]
+    fn test_token_usage_display() {
+        assert_eq!(
+            TokenUsage::new(1500, 100).to_string(),
+            "1600 tokens (1500 in / 100 out)"
+        );
+        assert_eq!(TokenUsage::zero().to_string(), "0 tokens (0 in / 0 out)");
+    }
+
+    fn counts(message: &str) -> Option<(Option<u32>, Option<u32>)> {
+        match context_length_error(message)? {
+            LLMError::ContextLengthExceeded { limit, requested } => Some((limit, requested)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_context_length_error_detection() {
+        assert_eq!(
+            counts(
+                r#"{"type":"invalid_request_error","message":"prompt is too long: 208000 tokens > 200000 maximum"}"#
+            ),
+            Some((Some(200000), Some(208000)))
+        );
+        assert_eq!(
+            counts(
+                "This model's maximum context length is 128000 tokens. However, your messages resulted in 130512 tokens. Please reduce the length of the messages."
+            ),
+            Some((Some(128000), Some(130512)))
+        );
+        assert_eq!(
+            counts(
+                r#"{"error":{"message":"Please reduce the length of the messages or completion.","code":"context_length_exceeded"}}"#
+            ),
+            Some((None, None))
+        );
+        assert_eq!(
+            counts("Ollama error: the input length exceeds the context length"),
+            Some((None, None))
+        );
+        assert_eq!(counts("model not found"), None);
+    }
+
+    #[test]
+    fn test_invalid_key_message_names_the_variable() {
+        assert!(
+            invalid_key_message(ProviderType::Claude)
+                .starts_with("ANTHROPIC_API_KEY appears invalid or expired")
+        );
+        assert!(invalid_key_message(ProviderType::Ollama).starts_with("OLLAMA_API_KEY"));
+    }
+
+    #[test]
+    fn test_retryable_error_classification() {
+        let network_error = reqwest::Client::new().get("not a url").build().unwrap_err();
+        let rate_limit = LLMError::RateLimitError {
+            message: "slow down".to_string(),
+            retry_after: Some(Duration::from_secs(20)),
+        };
+        let overloaded = LLMError::Overloaded {
+            message: "529 Overloaded".to_string(),
+            retry_after: None,
+        };
+
+        assert!(rate_limit.is_retryable());
+        assert_eq!(rate_limit.retry_after(), Some(Duration::from_secs(20)));
+        assert!(overloaded.is_retryable());
+        assert_eq!(overloaded.retry_after(), None);
+        assert!(LLMError::ServerError { status: 503 }.is_retryable());
+        assert!(LLMError::from(network_error).is_retryable());
+
+        for error in [
+            LLMError::AuthenticationError,
+            LLMError::TlsVerification("self signed certificate".to_string()),
+            LLMError::InvalidRequest("bad tool schema".to_string()),
+            LLMError::ContextLengthExceeded {
+                limit: None,
+                requested: None,
+            },
+            LLMError::StreamingNotSupported,
+            LLMError::ConfigurationError("missing API key".to_string()),
+            LLMError::Cancelled,
+        ] {
+            assert!(!error.is_retryable(), "{} should not be retried", error);
+            assert_eq!(error.retry_after(), None);
         }
     }
 }
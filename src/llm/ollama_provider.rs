@@ -2,14 +2,19 @@
 // Reuses async-openai client since Ollama is OpenAI-compatible
 
 use super::{
-    LLMError, LLMRequest, LLMResponse, MessageRole, ProviderConfig, ProviderType, StopReason,
-    TokenUsage, ToolCall, ToolDefinition,
+    LLMError, LLMRequest, LLMResponse, MessageRole, ProviderCapabilities, ProviderConfig,
+    ProviderType, StopReason, TokenUsage, ToolCall, ToolDefinition, context_length_error,
+    estimated_output_tokens,
 };
+use crate::llm::openai_provider::{is_auth_error, transient_error};
 use crate::llm::provider_trait::LLMProvider;
+use crate::llm::retry::{self, RetryPolicy};
+use crate::llm::{http_client, http_trace};
 use crate::rate_limiter::RateLimiter;
 use async_openai::{
     Client,
     config::OpenAIConfig,
+    error::OpenAIError,
     types::{
         ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
         ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
@@ -19,6 +24,7 @@ use async_openai::{
 };
 use async_trait::async_trait;
 use futures::stream::Stream;
+use serde::Deserialize;
 use std::pin::Pin;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -28,10 +34,133 @@ use tokio::sync::Mutex;
 pub struct OllamaProvider {
     config: ProviderConfig,
     client: Client<OpenAIConfig>,
+    /// Client for the native API, trusting `AUTOFIX_CA_BUNDLE` like `client`
+    http: reqwest::Client,
     rate_limiter: Arc<Mutex<RateLimiter>>,
 }
 
+/// Response of Ollama's `/api/tags`: the models pulled locally
+#[derive(Debug, Deserialize)]
+struct Tags {
+    #[serde(default)]
+    models: Vec<Tag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tag {
+    name: String,
+}
+
+/// Convert an async-openai error from a chat request into an LLMError
+fn convert_error(error: OpenAIError) -> LLMError {
+    if is_auth_error(&error) {
+        return LLMError::AuthenticationError;
+    }
+    let error_msg = http_client::describe(&error);
+    if let Some(error) = transient_error(error, &error_msg) {
+        return error;
+    }
+    context_length_error(&error_msg)
+        .or_else(|| http_client::tls_error(&error_msg))
+        .unwrap_or_else(|| LLMError::InvalidRequest(format!("Ollama error: {}", error_msg)))
+}
+
+/// Whether `model` is among the `pulled` models; an untagged name means `:latest`
+fn is_pulled(pulled: &[String], model: &str) -> bool {
+    let latest = format!("{}:latest", model);
+    pulled
+        .iter()
+        .any(|name| name == model || (!model.contains(':') && *name == latest))
+}
+
+/// Whether `api_base` points at this machine
+fn is_local(api_base: &str) -> bool {
+    let authority = api_base
+        .split_once("://")
+        .map_or(api_base, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let host = match authority.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+fn missing_model_error(model: &str) -> LLMError {
+    LLMError::ConfigurationError(format!(
+        "Ollama model '{}' is not pulled. Run `ollama pull {}`, or pass --ollama-auto-pull to pull it automatically",
+        model, model
+    ))
+}
+
 impl OllamaProvider {
+    /// Root of Ollama's native API, e.g. `http://localhost:11434` for `http://localhost:11434/v1`
+    fn native_api_base(&self) -> &str {
+        self.config
+            .api_base
+            .strip_suffix("/v1")
+            .unwrap_or(&self.config.api_base)
+    }
+
+    /// Request to Ollama's native API, authenticated when an API key is configured
+    fn native_request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}", self.native_api_base(), path));
+        match self.config.api_key() {
+            "" | "ollama" => request,
+            api_key => request.bearer_auth(api_key),
+        }
+    }
+
+    /// Models pulled on the server, from the native `/api/tags`
+    async fn tags(&self) -> Result<Tags, LLMError> {
+        let base = self.native_api_base();
+        let unreachable = |e: reqwest::Error| {
+            let error_msg = http_client::describe(&e);
+            http_client::tls_error(&error_msg).unwrap_or_else(|| {
+                LLMError::ConfigurationError(format!(
+                    "Ollama is not reachable at {}: {}. Start it with `ollama serve`",
+                    base, error_msg
+                ))
+            })
+        };
+        let response = self
+            .native_request(reqwest::Method::GET, "/api/tags")
+            .send()
+            .await
+            .map_err(unreachable)?;
+        // An authenticating proxy in front of Ollama rejected the key
+        if matches!(response.status().as_u16(), 401 | 403) {
+            return Err(LLMError::AuthenticationError);
+        }
+        response.json().await.map_err(unreachable)
+    }
+
+    /// Pull `model`, waiting until the download finished
+    async fn pull(&self, model: &str) -> Result<(), LLMError> {
+        println!(
+            "⬇️  Pulling Ollama model '{}', this may take a while...",
+            model
+        );
+        let response = self
+            .native_request(reqwest::Method::POST, "/api/pull")
+            .json(&serde_json::json!({ "model": model, "stream": false }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(LLMError::ConfigurationError(format!(
+                "Failed to pull Ollama model '{}': {}",
+                model,
+                response.text().await.unwrap_or_default().trim()
+            )));
+        }
+        println!("✓ Pulled '{}'", model);
+        Ok(())
+    }
+
     /// Convert tool definitions to Ollama format (same as OpenAI)
     fn convert_tools(&self, tools: &[ToolDefinition]) -> Result<Vec<ChatCompletionTool>, LLMError> {
         tools
@@ -111,11 +240,25 @@ impl OllamaProvider {
             } else {
                 Some(content)
             },
+            thinking: None,
             tool_calls,
             stop_reason,
             usage,
         })
     }
+
+    /// Heads-up for an endpoint on another machine, which receives test code and snapshots
+    ///
+    /// Returned rather than printed, so the caller shows it once per run instead of
+    /// once per provider created.
+    pub fn remote_endpoint_warning(config: &ProviderConfig) -> Option<String> {
+        (config.provider_type == ProviderType::Ollama && !is_local(&config.api_base)).then(|| {
+            format!(
+                "Using a remote Ollama endpoint ({}); test code and snapshots are sent to that host",
+                config.api_base
+            )
+        })
+    }
 }
 
 #[async_trait]
@@ -125,7 +268,8 @@ impl LLMProvider for OllamaProvider {
         Self::validate_config(&config)?;
 
         // Create OpenAI-compatible client for Ollama
-        // Ollama doesn't require authentication, but async-openai needs a key
+        // Ollama doesn't require authentication, but async-openai needs a key; a real key
+        // is sent as a bearer token for Ollama behind an authenticating proxy
         let api_key = if config.api_key().is_empty() || config.api_key() == "ollama" {
             "ollama".to_string()
         } else {
@@ -136,7 +280,8 @@ impl LLMProvider for OllamaProvider {
             .with_api_key(api_key)
             .with_api_base(&config.api_base);
 
-        let client = Client::with_config(openai_config);
+        let client =
+            Client::with_config(openai_config).with_http_client(http_client::openai_client()?);
 
         // Create rate limiter (often unlimited for local usage)
         let rate_limiter = Arc::new(Mutex::new(RateLimiter::for_provider(
@@ -147,6 +292,7 @@ impl LLMProvider for OllamaProvider {
         Ok(Self {
             config,
             client,
+            http: http_client::client()?,
             rate_limiter,
         })
     }
@@ -225,7 +371,7 @@ impl LLMProvider for OllamaProvider {
 
         // Add parameters
         if let Some(max_tokens) = request.max_tokens {
-            request_builder.max_tokens(max_tokens as u16);
+            request_builder.max_tokens(u16::try_from(max_tokens).unwrap_or(u16::MAX));
         }
         if let Some(temperature) = request.temperature {
             request_builder.temperature(temperature);
@@ -236,10 +382,27 @@ impl LLMProvider for OllamaProvider {
             .map_err(|e| LLMError::InvalidRequest(format!("Failed to build request: {}", e)))?;
 
         // Send request to local Ollama instance
-        let response = self.client.chat().create(chat_request).await.map_err(|e| {
-            let error_msg = format!("{}", e);
-            LLMError::InvalidRequest(format!("Ollama error: {}", error_msg))
-        })?;
+        // "ollama" is a placeholder, not a secret - redacting it would garble the trace
+        let secrets: &[&str] = match self.config.api_key() {
+            "" | "ollama" => &[],
+            api_key => &[api_key],
+        };
+        let response = retry::with_retries(RetryPolicy::from_config(&self.config), || async {
+            let trace = http_trace::start(
+                self.config.provider_type,
+                &format!("{}/chat/completions", self.config.api_base),
+                &chat_request,
+            );
+            let result = self
+                .client
+                .chat()
+                .create(chat_request.clone())
+                .await
+                .map_err(convert_error);
+            http_trace::finish(trace, &result, secrets);
+            result
+        })
+        .await?;
 
         // Record actual usage (if rate limiting is enabled)
         if should_rate_limit
@@ -254,6 +417,41 @@ impl LLMProvider for OllamaProvider {
         self.convert_response(response)
     }
 
+    /// Only Ollama behind an authenticating proxy has a key to check
+    async fn check_credentials(&self) -> Result<(), LLMError> {
+        match self.config.api_key() {
+            "" | "ollama" => Ok(()),
+            _ => self.tags().await.map(|_| ()),
+        }
+    }
+
+    async fn check_ready(&self) -> Result<(), LLMError> {
+        let tags = self.tags().await?;
+        let pulled: Vec<String> = tags.models.into_iter().map(|tag| tag.name).collect();
+
+        if is_pulled(&pulled, &self.config.model) {
+            Ok(())
+        } else if self.config.ollama_auto_pull {
+            self.pull(&self.config.model).await
+        } else {
+            Err(missing_model_error(&self.config.model))
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LLMError> {
+        let response = self.client.models().list().await.map_err(|e| {
+            if is_auth_error(&e) {
+                return LLMError::AuthenticationError;
+            }
+            // Sanitize error message to remove potential API keys
+            let error_msg = format!("{}", e);
+            LLMError::InvalidRequest(error_msg.replace(self.config.api_key(), "[REDACTED]"))
+        })?;
+        let mut models: Vec<String> = response.data.into_iter().map(|model| model.id).collect();
+        models.sort();
+        Ok(models)
+    }
+
     async fn complete_stream(
         &self,
         _request: LLMRequest,
@@ -291,7 +489,7 @@ impl LLMProvider for OllamaProvider {
         };
 
         // Estimate output tokens
-        let output_tokens = request.max_tokens.unwrap_or(1000);
+        let output_tokens = estimated_output_tokens(request);
 
         input_tokens + tool_tokens + output_tokens
     }
@@ -307,14 +505,18 @@ impl LLMProvider for OllamaProvider {
         // API key is optional for Ollama (local usage)
         // Just check that it's not required to be set
 
-        // Check endpoint is localhost
-        if !config.api_base.starts_with("http://localhost:")
-            && !config.api_base.starts_with("http://127.0.0.1:")
-        {
-            return Err(LLMError::ConfigurationError(
-                "Ollama endpoint must be localhost (http://localhost:11434/v1 or similar)"
-                    .to_string(),
-            ));
+        // Check endpoint is an http(s) URL; remote hosts work, see `remote_endpoint_warning`
+        let has_host = ["http://", "https://"].iter().any(|scheme| {
+            config
+                .api_base
+                .strip_prefix(scheme)
+                .is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'))
+        });
+        if !has_host {
+            return Err(LLMError::ConfigurationError(format!(
+                "Ollama endpoint must be an http(s) URL like http://localhost:11434/v1, got '{}'",
+                config.api_base
+            )));
         }
 
         // Check model is not empty
@@ -357,4 +559,118 @@ impl LLMProvider for OllamaProvider {
         // Models like codellama and mistral may support function calling
         false
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // Only multimodal models can look at snapshots
+        let vision_models = [
+            "llava",
+            "bakllava",
+            "vision",
+            "moondream",
+            "minicpm-v",
+            "gemma3",
+            "qwen2.5vl",
+        ];
+        ProviderCapabilities {
+            tools: self.supports_tools(),
+            images: vision_models
+                .iter()
+                .any(|family| self.config.model.contains(family)),
+            streaming: self.supports_streaming(),
+            system_prompt: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_model_names_pull_command() {
+        let pulled = vec!["llama3.1:latest".to_string(), "llava:13b".to_string()];
+
+        assert!(is_pulled(&pulled, "llama3.1"));
+        assert!(is_pulled(&pulled, "llava:13b"));
+        assert!(!is_pulled(&pulled, "llava"));
+        assert!(!is_pulled(&pulled, "llama3.1:70b"));
+        assert_eq!(
+            missing_model_error("llava").to_string(),
+            "Configuration error: Ollama model 'llava' is not pulled. Run `ollama pull llava`, or pass --ollama-auto-pull to pull it automatically"
+        );
+    }
+
+    #[test]
+    fn test_remote_endpoints_pass_validation() {
+        let config = |api_base: &str| ProviderConfig {
+            api_base: api_base.to_string(),
+            ..ProviderConfig::default_for_provider(ProviderType::Ollama)
+        };
+        assert!(OllamaProvider::validate_config(&config("http://gpu-box:11434/v1")).is_ok());
+        assert!(OllamaProvider::validate_config(&config("https://ollama.example.com/v1")).is_ok());
+        assert!(OllamaProvider::validate_config(&config("ftp://gpu-box/v1")).is_err());
+        assert!(OllamaProvider::validate_config(&config("http:///v1")).is_err());
+
+        assert!(is_local("http://localhost:11434/v1"));
+        assert!(is_local("http://[::1]:11434/v1"));
+        assert!(!is_local("https://ollama.example.com/v1"));
+        assert!(
+            OllamaProvider::remote_endpoint_warning(&config("http://localhost:11434/v1")).is_none()
+        );
+        assert!(
+            OllamaProvider::remote_endpoint_warning(&config("http://gpu-box:11434/v1"))
+                .is_some_and(|warning| warning.contains("gpu-box"))
+        );
+    }
+
+    #[test]
+    fn test_rejected_key_is_an_authentication_error() {
+        let api_error = |r#type: &str| {
+            OpenAIError::ApiError(async_openai::error::ApiError {
+                message: "unauthorized".to_string(),
+                r#type: Some(r#type.to_string()),
+                param: None,
+                code: None,
+            })
+        };
+
+        assert!(matches!(
+            convert_error(api_error("unauthorized")),
+            LLMError::AuthenticationError
+        ));
+        assert!(matches!(
+            convert_error(api_error("api_error")),
+            LLMError::InvalidRequest(_)
+        ));
+    }
+
+    #[test]
+    fn test_transient_errors_are_retryable() {
+        let api_error = |r#type: &str| {
+            OpenAIError::ApiError(async_openai::error::ApiError {
+                message: "try again later".to_string(),
+                r#type: Some(r#type.to_string()),
+                param: None,
+                code: None,
+            })
+        };
+
+        assert!(matches!(
+            convert_error(api_error("rate_limit_exceeded")),
+            LLMError::RateLimitError { .. }
+        ));
+        assert!(matches!(
+            convert_error(api_error("server_error")),
+            LLMError::ServerError { .. }
+        ));
+        // A request that never reached the server
+        let connection_error = reqwest_012::Client::new()
+            .get("not a url")
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            convert_error(OpenAIError::Reqwest(connection_error)),
+            LLMError::NetworkError(_)
+        ));
+    }
 }
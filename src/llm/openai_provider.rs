@@ -2,18 +2,22 @@
 
 use super::{
     LLMError, LLMRequest, LLMResponse, MessageRole, ProviderConfig, ProviderType, StopReason,
-    TokenUsage, ToolCall, ToolDefinition,
+    TokenUsage, ToolCall, ToolDefinition, context_length_error, estimated_output_tokens,
 };
 use crate::llm::provider_trait::LLMProvider;
+use crate::llm::retry::{self, RetryPolicy};
+use crate::llm::{http_client, http_trace};
 use crate::rate_limiter::RateLimiter;
 use async_openai::{
     Client,
     config::OpenAIConfig,
+    error::OpenAIError,
     types::{
         ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
         ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
         ChatCompletionTool, ChatCompletionToolChoiceOption, ChatCompletionToolType,
-        CreateChatCompletionRequestArgs, FinishReason, FunctionObjectArgs,
+        CreateChatCompletionRequest, CreateChatCompletionRequestArgs, CreateChatCompletionResponse,
+        FinishReason, FunctionObjectArgs,
     },
 };
 use async_trait::async_trait;
@@ -22,10 +26,90 @@ use std::pin::Pin;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// Model name prefixes of OpenAI reasoning models
+const REASONING_MODEL_PREFIXES: &[&str] = &["o1", "o3", "o4", "gpt-5"];
+
+/// Extra output tokens a reasoning model may spend on reasoning before its answer
+const REASONING_TOKEN_ALLOWANCE: u32 = 8192;
+
+/// Whether `model` is a reasoning model, which rejects `temperature` and
+/// takes `max_completion_tokens` instead of `max_tokens`
+pub fn is_reasoning_model(model: &str) -> bool {
+    REASONING_MODEL_PREFIXES
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
+}
+
+/// Error codes and types OpenAI-compatible APIs use for a rejected API key
+const AUTH_ERROR_CODES: &[&str] = &[
+    "invalid_api_key",
+    "invalid_authentication",
+    "authentication_error",
+    "unauthorized",
+];
+
+/// Error codes and types OpenAI-compatible APIs use for a rate limit
+const RATE_LIMIT_ERROR_CODES: &[&str] = &["rate_limit_exceeded"];
+
+/// Error codes and types OpenAI-compatible APIs use for a failure on their side
+const SERVER_ERROR_CODES: &[&str] = &["server_error"];
+
+/// Whether the code or type of an async-openai error object is one of `codes`
+fn has_error_code(error: &OpenAIError, codes: &[&str]) -> bool {
+    let OpenAIError::ApiError(api_error) = error else {
+        return false;
+    };
+    let code = api_error.code.as_ref().and_then(|code| code.as_str());
+    [code, api_error.r#type.as_deref()]
+        .into_iter()
+        .flatten()
+        .any(|value| codes.contains(&value))
+}
+
+/// Whether an async-openai error reports a rejected API key
+///
+/// async-openai drops the HTTP status of error responses, so this goes by the
+/// code and type of the error object instead.
+pub(super) fn is_auth_error(error: &OpenAIError) -> bool {
+    match error {
+        OpenAIError::Reqwest(e) => e.status().is_some_and(|status| status.as_u16() == 401),
+        _ => has_error_code(error, AUTH_ERROR_CODES),
+    }
+}
+
+/// Rate limits, server errors and failed connections of async-openai as the
+/// retryable `LLMError`s, `None` for anything else
+///
+/// `message` is the error's description with secrets removed.
+pub(super) fn transient_error(error: OpenAIError, message: &str) -> Option<LLMError> {
+    let rate_limit = || LLMError::RateLimitError {
+        message: message.to_string(),
+        retry_after: None,
+    };
+    if has_error_code(&error, RATE_LIMIT_ERROR_CODES) {
+        return Some(rate_limit());
+    }
+    if has_error_code(&error, SERVER_ERROR_CODES) {
+        // The status is gone, but it was a 5xx
+        return Some(LLMError::ServerError { status: 500 });
+    }
+    match error {
+        OpenAIError::Reqwest(e) => Some(match e.status().map(|status| status.as_u16()) {
+            Some(429) => rate_limit(),
+            Some(status @ 500..) => LLMError::ServerError { status },
+            _ => http_client::tls_error(message)
+                .unwrap_or_else(|| LLMError::NetworkError(Box::new(e))),
+        }),
+        _ => None,
+    }
+}
+
 /// OpenAI provider implementation
 pub struct OpenAIProvider {
     config: ProviderConfig,
     client: Client<OpenAIConfig>,
+    /// Client for raw requests, trusting `AUTOFIX_CA_BUNDLE` like `client`
+    http: reqwest::Client,
     rate_limiter: Arc<Mutex<RateLimiter>>,
 }
 
@@ -98,7 +182,7 @@ impl OpenAIProvider {
                 usage_info.completion_tokens,
             )
         } else {
-            TokenUsage::new(0, 0)
+            TokenUsage::zero()
         };
 
         Ok(LLMResponse {
@@ -107,54 +191,18 @@ impl OpenAIProvider {
             } else {
                 Some(content)
             },
+            thinking: None,
             tool_calls,
             stop_reason,
             usage,
         })
     }
-}
-
-#[async_trait]
-impl LLMProvider for OpenAIProvider {
-    fn new(config: ProviderConfig) -> Result<Self, LLMError> {
-        // Validate configuration
-        Self::validate_config(&config)?;
-
-        // Create OpenAI client with custom endpoint
-        let openai_config = OpenAIConfig::new()
-            .with_api_key(config.api_key())
-            .with_api_base(&config.api_base);
-
-        let client = Client::with_config(openai_config);
-
-        // Create rate limiter
-        let rate_limiter = Arc::new(Mutex::new(RateLimiter::for_provider(
-            config.provider_type,
-            config.rate_limit_tpm,
-        )));
-
-        Ok(Self {
-            config,
-            client,
-            rate_limiter,
-        })
-    }
-
-    fn provider_type(&self) -> ProviderType {
-        ProviderType::OpenAI
-    }
-
-    async fn complete(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
-        // Estimate tokens and check rate limiter
-        let estimated_tokens = self.estimate_tokens(&request);
-        {
-            let limiter = self.rate_limiter.lock().await;
-            if let Err(wait_duration) = limiter.check_and_wait(estimated_tokens as usize) {
-                // Wait for rate limit to reset
-                tokio::time::sleep(wait_duration).await;
-            }
-        }
 
+    /// Build the chat completion request for `request`
+    fn build_chat_request(
+        &self,
+        request: &LLMRequest,
+    ) -> Result<CreateChatCompletionRequest, LLMError> {
         // Build messages
         let mut messages: Vec<ChatCompletionRequestMessage> = Vec::new();
 
@@ -209,25 +257,198 @@ impl LLMProvider for OpenAIProvider {
                 .tool_choice(ChatCompletionToolChoiceOption::Auto);
         }
 
-        // Add parameters
-        if let Some(max_tokens) = request.max_tokens {
-            request_builder.max_tokens(max_tokens as u16);
-        }
-        if let Some(temperature) = request.temperature {
-            request_builder.temperature(temperature);
+        // Add parameters - reasoning models reject temperature and get their token limit
+        // as max_completion_tokens in `reasoning_request_body`
+        if !is_reasoning_model(&self.config.model) {
+            if let Some(max_tokens) = request.max_tokens {
+                request_builder.max_tokens(u16::try_from(max_tokens).unwrap_or(u16::MAX));
+            }
+            if let Some(temperature) = request.temperature {
+                request_builder.temperature(temperature);
+            }
         }
 
-        let chat_request = request_builder
+        request_builder
             .build()
+            .map_err(|e| LLMError::InvalidRequest(format!("Failed to build request: {}", e)))
+    }
+
+    /// JSON body for a reasoning model, with the output limit as `max_completion_tokens`
+    ///
+    /// Hidden reasoning counts against that limit, so it is raised by
+    /// `REASONING_TOKEN_ALLOWANCE` to leave the answer its requested length.
+    fn reasoning_request_body(
+        chat_request: &CreateChatCompletionRequest,
+        max_tokens: Option<u32>,
+    ) -> Result<serde_json::Value, LLMError> {
+        let mut body = serde_json::to_value(chat_request)
             .map_err(|e| LLMError::InvalidRequest(format!("Failed to build request: {}", e)))?;
+        if let Some(max_tokens) = max_tokens {
+            body["max_completion_tokens"] =
+                serde_json::json!(max_tokens + REASONING_TOKEN_ALLOWANCE);
+        }
+        Ok(body)
+    }
 
-        // Send request
-        let response = self.client.chat().create(chat_request).await.map_err(|e| {
+    /// Send a raw chat completion request, returning the response and any reasoning text
+    ///
+    /// OpenAI keeps o-series reasoning hidden, but compatible endpoints serving
+    /// reasoning models return it as `reasoning_content` or `reasoning`.
+    async fn create_raw(
+        &self,
+        body: &serde_json::Value,
+    ) -> Result<(CreateChatCompletionResponse, Option<String>), LLMError> {
+        let url = self.chat_completions_url();
+        let trace = http_trace::start(self.config.provider_type, &url, body);
+        let result = self.post_json(&url, body).await;
+        http_trace::finish(trace, &result, &[self.config.api_key()]);
+        let value = result?;
+
+        let message = &value["choices"][0]["message"];
+        let thinking = ["reasoning_content", "reasoning"]
+            .iter()
+            .find_map(|field| message.get(field)?.as_str())
+            .filter(|thinking| !thinking.is_empty())
+            .map(str::to_string);
+        let response = serde_json::from_value(value)
+            .map_err(|e| LLMError::InvalidRequest(format!("Invalid response: {}", e)))?;
+        Ok((response, thinking))
+    }
+
+    /// Convert an async-openai error into an LLMError, removing potential API keys
+    fn convert_error(&self, error: OpenAIError) -> LLMError {
+        if is_auth_error(&error) {
+            return LLMError::AuthenticationError;
+        }
+        let error_msg = http_client::describe(&error);
+        let sanitized = error_msg.replace(self.config.api_key(), "[REDACTED]");
+        if let Some(error) = transient_error(error, &sanitized) {
+            return error;
+        }
+        context_length_error(&sanitized)
+            .or_else(|| http_client::tls_error(&sanitized))
+            .unwrap_or(LLMError::InvalidRequest(sanitized))
+    }
+
+    /// POST `body` as JSON to `url`, returning the JSON response
+    async fn post_json(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, LLMError> {
+        let response = self
+            .http
+            .post(url)
+            .bearer_auth(self.config.api_key())
+            .json(body)
+            .send()
+            .await
+            .map_err(http_client::send_error)?;
+
+        let status = response.status().as_u16();
+        let retry_after = retry::retry_after(response.headers());
+        let text = response.text().await?;
+        if !(200..300).contains(&status) {
             // Sanitize error message to remove potential API keys
-            let error_msg = format!("{}", e);
-            let sanitized = error_msg.replace(self.config.api_key(), "[REDACTED]");
-            LLMError::InvalidRequest(sanitized)
-        })?;
+            let sanitized = text.replace(self.config.api_key(), "[REDACTED]");
+            return Err(match status {
+                401 => LLMError::AuthenticationError,
+                429 => LLMError::RateLimitError {
+                    message: sanitized,
+                    retry_after,
+                },
+                500.. => LLMError::ServerError { status },
+                _ => {
+                    context_length_error(&sanitized).unwrap_or(LLMError::InvalidRequest(sanitized))
+                }
+            });
+        }
+
+        serde_json::from_str(&text)
+            .map_err(|e| LLMError::InvalidRequest(format!("Invalid response: {}", e)))
+    }
+
+    fn chat_completions_url(&self) -> String {
+        format!("{}/chat/completions", self.config.api_base)
+    }
+
+    /// Create a provider for any OpenAI-compatible endpoint without OpenAI-specific validation
+    ///
+    /// Used by providers that reuse the OpenAI machinery (e.g. Groq) after validating
+    /// their own configuration.
+    pub(super) fn with_config(config: ProviderConfig) -> Result<Self, LLMError> {
+        // Create OpenAI client with custom endpoint
+        let openai_config = OpenAIConfig::new()
+            .with_api_key(config.api_key())
+            .with_api_base(&config.api_base);
+
+        let client =
+            Client::with_config(openai_config).with_http_client(http_client::openai_client()?);
+
+        // Create rate limiter
+        let rate_limiter = Arc::new(Mutex::new(RateLimiter::for_provider(
+            config.provider_type,
+            config.rate_limit_tpm,
+        )));
+
+        Ok(Self {
+            config,
+            client,
+            http: http_client::client()?,
+            rate_limiter,
+        })
+    }
+}
+
+#[async_trait]
+impl LLMProvider for OpenAIProvider {
+    fn new(config: ProviderConfig) -> Result<Self, LLMError> {
+        // Validate configuration
+        Self::validate_config(&config)?;
+
+        Self::with_config(config)
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::OpenAI
+    }
+
+    async fn complete(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        // Estimate tokens and check rate limiter
+        let estimated_tokens = self.estimate_tokens(&request);
+        {
+            let limiter = self.rate_limiter.lock().await;
+            if let Err(wait_duration) = limiter.check_and_wait(estimated_tokens as usize) {
+                // Wait for rate limit to reset
+                tokio::time::sleep(wait_duration).await;
+            }
+        }
+
+        // Reasoning models take parameters async-openai doesn't know yet, so they get raw JSON
+        let chat_request = self.build_chat_request(&request)?;
+        let policy = RetryPolicy::from_config(&self.config);
+        let (response, thinking) = if is_reasoning_model(&self.config.model) {
+            let body = Self::reasoning_request_body(&chat_request, request.max_tokens)?;
+            retry::with_retries(policy, || self.create_raw(&body)).await?
+        } else {
+            let response = retry::with_retries(policy, || async {
+                let trace = http_trace::start(
+                    self.config.provider_type,
+                    &self.chat_completions_url(),
+                    &chat_request,
+                );
+                let result = self
+                    .client
+                    .chat()
+                    .create(chat_request.clone())
+                    .await
+                    .map_err(|e| self.convert_error(e));
+                http_trace::finish(trace, &result, &[self.config.api_key()]);
+                result
+            })
+            .await?;
+            (response, None)
+        };
 
         // Record actual usage
         {
@@ -240,7 +461,26 @@ impl LLMProvider for OpenAIProvider {
         }
 
         // Convert to LLMResponse
-        self.convert_response(response)
+        let mut llm_response = self.convert_response(response)?;
+        llm_response.thinking = thinking;
+        Ok(llm_response)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LLMError> {
+        let response = self
+            .client
+            .models()
+            .list()
+            .await
+            .map_err(|e| self.convert_error(e))?;
+        let mut models: Vec<String> = response.data.into_iter().map(|model| model.id).collect();
+        models.sort();
+        Ok(models)
+    }
+
+    /// Listing models costs no tokens but needs a valid key
+    async fn check_credentials(&self) -> Result<(), LLMError> {
+        self.list_models().await.map(|_| ())
     }
 
     async fn complete_stream(
@@ -275,7 +515,7 @@ impl LLMProvider for OpenAIProvider {
             .sum();
 
         // Estimate output tokens
-        let output_tokens = request.max_tokens.unwrap_or(1000);
+        let output_tokens = estimated_output_tokens(request);
 
         input_tokens + tool_tokens + output_tokens
     }
@@ -314,7 +554,10 @@ impl LLMProvider for OpenAIProvider {
 
     fn max_context_length(&self) -> u32 {
         // Return context length based on model name
-        if self.config.model.contains("gpt-4-turbo") || self.config.model.contains("gpt-4o") {
+        if is_reasoning_model(&self.config.model) {
+            200000
+        } else if self.config.model.contains("gpt-4-turbo") || self.config.model.contains("gpt-4o")
+        {
             128000
         } else if self.config.model.contains("gpt-4") {
             8192
@@ -334,3 +577,124 @@ impl LLMProvider for OpenAIProvider {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::Message;
+
+    fn openai_provider(model: &str) -> OpenAIProvider {
+        OpenAIProvider::new(
+            ProviderConfig::builder()
+                .provider_type(ProviderType::OpenAI)
+                .api_key("sk-test")
+                .model(model)
+                .build()
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn sample_request() -> LLMRequest {
+        LLMRequest {
+            system_prompt: None,
+            messages: vec![Message {
+                role: MessageRole::User,
+                content: "Fix the test".to_string(),
+            }],
+            tools: vec![],
+            max_tokens: Some(1024),
+            temperature: Some(0.7),
+            stream: false,
+        }
+    }
+
+    #[test]
+    fn test_reasoning_model_request_omits_temperature() {
+        let provider = openai_provider("o1");
+        let chat_request = provider.build_chat_request(&sample_request()).unwrap();
+        let body = OpenAIProvider::reasoning_request_body(&chat_request, Some(1024)).unwrap();
+
+        assert_eq!(body["model"], "o1");
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("max_tokens").is_none());
+        assert_eq!(
+            body["max_completion_tokens"],
+            1024 + REASONING_TOKEN_ALLOWANCE
+        );
+
+        // Other models keep the classic parameters
+        let chat_request = openai_provider("gpt-4o")
+            .build_chat_request(&sample_request())
+            .unwrap();
+        assert_eq!(chat_request.temperature, Some(0.7));
+        assert_eq!(chat_request.max_tokens, Some(1024));
+        assert!(is_reasoning_model("o3-mini"));
+        assert!(!is_reasoning_model("gpt-4o"));
+    }
+
+    #[test]
+    fn test_rejected_key_is_an_authentication_error() {
+        let provider = openai_provider("gpt-4o");
+        let api_error = |code: Option<&str>, message: &str| {
+            OpenAIError::ApiError(async_openai::error::ApiError {
+                message: message.to_string(),
+                r#type: Some("invalid_request_error".to_string()),
+                param: None,
+                code: code.map(|code| serde_json::json!(code)),
+            })
+        };
+
+        assert!(matches!(
+            provider.convert_error(api_error(
+                Some("invalid_api_key"),
+                "Incorrect API key provided: sk-test"
+            )),
+            LLMError::AuthenticationError
+        ));
+        match provider.convert_error(api_error(None, "Unknown parameter: sk-test")) {
+            LLMError::InvalidRequest(message) => assert!(!message.contains("sk-test")),
+            other => panic!("expected an invalid request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_is_retryable() {
+        let error = OpenAIError::ApiError(async_openai::error::ApiError {
+            message: "Rate limit reached for gpt-4o".to_string(),
+            r#type: Some("requests".to_string()),
+            param: None,
+            code: Some(serde_json::json!("rate_limit_exceeded")),
+        });
+
+        let error = openai_provider("gpt-4o").convert_error(error);
+        assert!(matches!(error, LLMError::RateLimitError { .. }));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_server_error_is_retryable() {
+        let error = OpenAIError::ApiError(async_openai::error::ApiError {
+            message: "The server had an error while processing your request".to_string(),
+            r#type: Some("server_error".to_string()),
+            param: None,
+            code: None,
+        });
+
+        let error = openai_provider("gpt-4o").convert_error(error);
+        assert!(matches!(error, LLMError::ServerError { status: 500 }));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_unsent_request_is_a_network_error() {
+        let error = reqwest_012::Client::new()
+            .get("not a url")
+            .build()
+            .unwrap_err();
+
+        let error = openai_provider("gpt-4o").convert_error(OpenAIError::Reqwest(error));
+        assert!(matches!(error, LLMError::NetworkError(_)));
+        assert!(error.is_retryable());
+    }
+}
@@ -1,8 +1,11 @@
 // LLM Provider trait - unified interface for all LLM providers
 
-use super::{LLMError, LLMRequest, LLMResponse, ProviderConfig, ProviderType};
+use super::{
+    LLMError, LLMRequest, LLMResponse, ProviderCapabilities, ProviderConfig, ProviderType,
+    StopReason, TokenCounter, TokenUsage,
+};
 use async_trait::async_trait;
-use futures::stream::Stream;
+use futures::stream::{Stream, StreamExt};
 use std::pin::Pin;
 
 /// Trait that all LLM providers must implement
@@ -20,7 +23,9 @@ pub trait LLMProvider: Send + Sync {
     async fn complete(&self, request: LLMRequest) -> Result<LLMResponse, LLMError>;
 
     /// Send a request and get a streaming response
-    #[allow(dead_code)] // Streaming not yet used in pipeline but implemented in providers
+    ///
+    /// Each item is a delta: text to append, new tool calls and the usage it
+    /// adds. The last item carries the stop reason.
     async fn complete_stream(
         &self,
         request: LLMRequest,
@@ -48,4 +53,114 @@ pub trait LLMProvider: Send + Sync {
     fn supports_tools(&self) -> bool {
         true // Default: most providers support tools
     }
+
+    /// List the models served by the provider's endpoint
+    async fn list_models(&self) -> Result<Vec<String>, LLMError> {
+        Err(LLMError::InvalidRequest(
+            "Listing models is not supported by this provider".to_string(),
+        ))
+    }
+
+    /// Check the configured API key with a cheap request, before any slow work starts
+    ///
+    /// A rejected key is reported as `LLMError::AuthenticationError`.
+    async fn check_credentials(&self) -> Result<(), LLMError> {
+        Ok(())
+    }
+
+    /// Check that the provider can serve the configured model, before a run starts
+    async fn check_ready(&self) -> Result<(), LLMError> {
+        Ok(())
+    }
+
+    /// Features the configured model supports, so the pipeline can degrade gracefully
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            tools: self.supports_tools(),
+            streaming: self.supports_streaming(),
+            ..ProviderCapabilities::default()
+        }
+    }
+}
+
+/// Merge the deltas of a streaming response into one response
+pub async fn collect_stream(
+    stream: Pin<Box<dyn Stream<Item = Result<LLMResponse, LLMError>> + Send>>,
+) -> Result<LLMResponse, LLMError> {
+    let mut response = LLMResponse {
+        content: None,
+        thinking: None,
+        tool_calls: Vec::new(),
+        stop_reason: StopReason::EndTurn,
+        usage: TokenUsage::zero(),
+    };
+    collect_stream_into(stream, &mut response, None).await?;
+    Ok(response)
+}
+
+/// Merge the deltas of a streaming response into `response` as they arrive
+///
+/// Text and thinking deltas are added to `token_counter` as provisional output.
+/// Dropping the future stops reading the stream and keeps what was merged so far,
+/// including the usage of the deltas received.
+pub async fn collect_stream_into(
+    mut stream: Pin<Box<dyn Stream<Item = Result<LLMResponse, LLMError>> + Send>>,
+    response: &mut LLMResponse,
+    token_counter: Option<&TokenCounter>,
+) -> Result<(), LLMError> {
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if let Some(token_counter) = token_counter {
+            for text in chunk.content.iter().chain(&chunk.thinking) {
+                token_counter.record_stream_chunk(text);
+            }
+        }
+        if let Some(text) = chunk.content {
+            response.content.get_or_insert_default().push_str(&text);
+        }
+        if let Some(text) = chunk.thinking {
+            response.thinking.get_or_insert_default().push_str(&text);
+        }
+        response.tool_calls.extend(chunk.tool_calls);
+        response.stop_reason = chunk.stop_reason;
+        response.usage += chunk.usage;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stream_chunks_count_as_provisional_output() {
+        let chunk = |text: &str| {
+            Ok(LLMResponse {
+                content: Some(text.to_string()),
+                thinking: None,
+                tool_calls: Vec::new(),
+                stop_reason: StopReason::EndTurn,
+                usage: TokenUsage::zero(),
+            })
+        };
+        let stream = futures::stream::iter(vec![chunk("Let me look at "), chunk("the view.")]);
+        let mut response = LLMResponse {
+            content: None,
+            thinking: None,
+            tool_calls: Vec::new(),
+            stop_reason: StopReason::EndTurn,
+            usage: TokenUsage::zero(),
+        };
+        let token_counter = TokenCounter::default();
+
+        collect_stream_into(Box::pin(stream), &mut response, Some(&token_counter))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.content.as_deref(),
+            Some("Let me look at the view.")
+        );
+        assert_eq!(token_counter.usage().output_tokens, 4 + 3);
+    }
 }
@@ -0,0 +1,255 @@
+// Shared retry policy for provider requests
+//
+// Which errors are worth another attempt is decided by LLMError::is_retryable:
+// rate limits, overloaded servers, 5xx responses and network failures are
+// retried, everything else is returned at once. The delay doubles after each
+// attempt unless the server asked for a specific one.
+
+use super::{LLMError, ProviderConfig};
+use reqwest::header::HeaderMap;
+use std::future::Future;
+use std::time::Duration;
+
+/// Delay before the first retry (doubled on each retry)
+const INITIAL_DELAY: Duration = Duration::from_secs(5);
+
+/// Upper bound for the backoff delay between two retries
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Fewest attempts while the server reports being overloaded, which takes longer
+/// to clear up than a rate limit
+const MIN_OVERLOADED_ATTEMPTS: u32 = 6;
+
+/// How often and how patiently a failed request is retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included
+    pub max_attempts: u32,
+    /// Attempts in total while the server reports being overloaded
+    pub overloaded_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Policy allowing `max_retries` (`AUTOFIX_MAX_RETRIES`) retries after the first attempt
+    pub fn from_config(config: &ProviderConfig) -> Self {
+        Self {
+            max_attempts: config.max_retries + 1,
+            overloaded_attempts: (config.max_retries + 1).max(MIN_OVERLOADED_ATTEMPTS),
+            initial_delay: INITIAL_DELAY,
+            max_delay: MAX_DELAY,
+        }
+    }
+
+    /// Attempts in total for a request failing with `error`
+    fn attempts_for(&self, error: &LLMError) -> u32 {
+        match error {
+            LLMError::Overloaded { .. } => self.overloaded_attempts,
+            _ => self.max_attempts,
+        }
+    }
+}
+
+/// Run `operation`, retrying it with backoff while it fails with a retryable error
+///
+/// A delay named by the server (`LLMError::retry_after`) takes precedence over
+/// the backoff. Non-retryable errors and success are returned immediately.
+pub async fn with_retries<T, F, Fut>(policy: RetryPolicy, mut operation: F) -> Result<T, LLMError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, LLMError>>,
+{
+    let mut backoff = policy.initial_delay;
+    let mut attempt = 1;
+
+    loop {
+        match operation().await {
+            Err(error) if error.is_retryable() && attempt < policy.attempts_for(&error) => {
+                let delay = error.retry_after().unwrap_or(backoff);
+                println!(
+                    "⏳ {}, retrying in {:?} (attempt {}/{})...",
+                    error,
+                    delay,
+                    attempt + 1,
+                    policy.attempts_for(&error)
+                );
+                tokio::time::sleep(delay).await;
+                backoff = (backoff * 2).min(policy.max_delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Delay a response asks for in its `retry-after-ms` or `retry-after` header
+///
+/// Only delays in (milli)seconds are understood; an HTTP date is ignored.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let number = |name: &str| -> Option<f64> {
+        let value: f64 = headers.get(name)?.to_str().ok()?.trim().parse().ok()?;
+        (value.is_finite() && value >= 0.0).then_some(value)
+    };
+    number("retry-after-ms")
+        .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+        .or_else(|| number("retry-after").map(Duration::from_secs_f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock_provider::MockProvider;
+    use crate::llm::provider_trait::LLMProvider;
+    use crate::llm::{LLMRequest, Message, MessageRole};
+    use std::time::Instant;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            overloaded_attempts: max_attempts,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+        }
+    }
+
+    fn request() -> LLMRequest {
+        LLMRequest {
+            system_prompt: None,
+            messages: vec![Message {
+                role: MessageRole::User,
+                content: "Fix the test".to_string(),
+            }],
+            tools: vec![],
+            max_tokens: None,
+            temperature: None,
+            stream: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retryable_errors_are_retried_until_success() {
+        let provider = MockProvider::with_results(vec![
+            Err(LLMError::RateLimitError {
+                message: "slow down".to_string(),
+                retry_after: None,
+            }),
+            Err(LLMError::ServerError { status: 503 }),
+            Ok(MockProvider::text("done")),
+        ]);
+
+        let response = with_retries(policy(4), || provider.complete(request()))
+            .await
+            .unwrap();
+
+        assert_eq!(response.content.as_deref(), Some("done"));
+        assert_eq!(provider.state.requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_errors_are_returned_at_once() {
+        let provider = MockProvider::with_results(vec![
+            Err(LLMError::AuthenticationError),
+            Ok(MockProvider::text("done")),
+        ]);
+
+        let result = with_retries(policy(4), || provider.complete(request())).await;
+
+        assert!(matches!(result, Err(LLMError::AuthenticationError)));
+        assert_eq!(provider.state.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let overloaded = || {
+            Err(LLMError::Overloaded {
+                message: "529 Overloaded".to_string(),
+                retry_after: None,
+            })
+        };
+        let provider = MockProvider::with_results(vec![overloaded(), overloaded(), overloaded()]);
+
+        let result = with_retries(policy(2), || provider.complete(request())).await;
+
+        assert!(matches!(result, Err(LLMError::Overloaded { .. })));
+        assert_eq!(provider.state.requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_overloaded_server_gets_its_own_attempts() {
+        let overloaded = || {
+            Err(LLMError::Overloaded {
+                message: "529 Overloaded".to_string(),
+                retry_after: None,
+            })
+        };
+        let provider = MockProvider::with_results(vec![
+            overloaded(),
+            overloaded(),
+            Ok(MockProvider::text("done")),
+        ]);
+        let policy = RetryPolicy {
+            overloaded_attempts: 3,
+            ..policy(1)
+        };
+
+        with_retries(policy, || provider.complete(request()))
+            .await
+            .unwrap();
+
+        assert_eq!(provider.state.requests.lock().unwrap().len(), 3);
+        assert_eq!(
+            RetryPolicy::from_config(&ProviderConfig::default()).overloaded_attempts,
+            MIN_OVERLOADED_ATTEMPTS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_delay_takes_precedence_over_backoff() {
+        let provider = MockProvider::with_results(vec![
+            Err(LLMError::RateLimitError {
+                message: "slow down".to_string(),
+                retry_after: Some(Duration::from_millis(50)),
+            }),
+            Ok(MockProvider::text("done")),
+        ]);
+
+        let started = Instant::now();
+        with_retries(policy(2), || provider.complete(request()))
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_retry_after_headers() {
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", "20")])),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            retry_after(&headers(&[
+                ("retry-after", "20"),
+                ("retry-after-ms", "1500")
+            ])),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            retry_after(&headers(&[(
+                "retry-after",
+                "Wed, 21 Oct 2026 07:28:00 GMT"
+            )])),
+            None
+        );
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+}
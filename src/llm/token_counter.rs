@@ -0,0 +1,80 @@
+use super::TokenUsage;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Live count of the tokens used so far in a run, shared between pipelines
+///
+/// Final usage from provider responses is added with `record_usage`. While a
+/// response streams in, `record_stream_chunk` adds provisional output tokens
+/// so the readout moves; they are replaced by the exact count once the
+/// response's usage is recorded.
+#[derive(Debug, Default)]
+pub struct TokenCounter {
+    input_tokens: AtomicU32,
+    output_tokens: AtomicU32,
+    /// Output tokens of the response currently streaming, not yet confirmed
+    streamed_tokens: AtomicU32,
+}
+
+impl TokenCounter {
+    /// Add the usage reported with a complete response
+    pub fn record_usage(&self, usage: &TokenUsage) {
+        self.input_tokens
+            .fetch_add(usage.input_tokens, Ordering::Relaxed);
+        self.output_tokens
+            .fetch_add(usage.output_tokens, Ordering::Relaxed);
+        self.streamed_tokens.store(0, Ordering::Relaxed);
+    }
+
+    /// Add a streamed text chunk, estimated at four characters per token
+    pub fn record_stream_chunk(&self, text: &str) {
+        let tokens = text.len().div_ceil(4) as u32;
+        self.streamed_tokens.fetch_add(tokens, Ordering::Relaxed);
+    }
+
+    /// Provisional output tokens of the response currently streaming
+    pub fn streamed_tokens(&self) -> u32 {
+        self.streamed_tokens.load(Ordering::Relaxed)
+    }
+
+    /// Tokens used so far, including provisional streamed output
+    pub fn usage(&self) -> TokenUsage {
+        TokenUsage::new(
+            self.input_tokens.load(Ordering::Relaxed),
+            self.output_tokens.load(Ordering::Relaxed)
+                + self.streamed_tokens.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl fmt::Display for TokenCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let usage = self.usage();
+        write!(
+            f,
+            "{} tokens so far ({} in / {} out)",
+            usage.total_tokens, usage.input_tokens, usage.output_tokens
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streamed_tokens_are_replaced_by_final_usage() {
+        let counter = TokenCounter::default();
+        counter.record_usage(&TokenUsage::new(1000, 50));
+
+        counter.record_stream_chunk("Let me look at ");
+        counter.record_stream_chunk("the view.");
+        assert_eq!(counter.usage().output_tokens, 50 + 4 + 3);
+
+        counter.record_usage(&TokenUsage::new(1200, 20));
+        let usage = counter.usage();
+        assert_eq!(usage.input_tokens, 2200);
+        assert_eq!(usage.output_tokens, 70);
+        assert_eq!(counter.to_string(), "2270 tokens so far (2200 in / 70 out)");
+    }
+}
@@ -1,19 +1,52 @@
 mod autofix_command;
+mod clean_command;
+mod developer_dir;
+mod estimate_command;
+mod exit_code;
+mod file_locator;
+mod git_worktree;
+mod junitparser;
+mod list_failures_command;
 mod llm;
+mod models_command;
 mod pipeline;
+mod project_config;
 mod rate_limiter;
+mod redact;
+mod report;
+#[cfg(feature = "serve")]
+mod serve;
+mod snapshot_image;
+mod source_language;
 mod test_command;
 mod tools;
+mod verify_command;
+mod workspace_path;
 mod xc_test_result_attachment_handler;
 mod xc_workspace_file_locator;
 mod xcresultparser;
+mod xcresulttool;
 mod xctestresultdetailparser;
 
 use autofix_command::AutofixCommand;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use clean_command::CleanCommand;
+use estimate_command::EstimateCommand;
+use exit_code::ExitCode;
+use list_failures_command::{ListFailuresCommand, ListFormat};
 use llm::ProviderType;
+use models_command::ModelsCommand;
+use pipeline::{
+    DEFAULT_SWITCH_AFTER, Editor, FixTarget, LadderPolicy, ModelLadder, PipelineOptions, ReplInput,
+};
+use project_config::Config;
+use source_language::SourceLanguage;
+use std::fs;
+use std::io::IsTerminal;
 use std::path::PathBuf;
+use std::time::Duration;
 use test_command::TestCommand;
+use verify_command::VerifyCommand;
 
 /// A tool to automatically fix failing UI tests
 #[derive(Parser, Debug)]
@@ -28,30 +61,266 @@ struct Args {
     #[arg(short = 'a', long, conflicts_with = "ios", global = true)]
     android: bool,
 
-    /// Path to the test result file (xcresult for iOS)
-    #[arg(long, required_if_eq("ios", "true"), global = true)]
-    test_result: Option<PathBuf>,
+    /// Path to the test result file (xcresult bundle or JUnit XML for iOS), or a directory of bundles; repeat for sharded runs [env: AUTOFIX_TEST_RESULT]
+    #[arg(long, global = true)]
+    test_result: Vec<PathBuf>,
 
-    /// Path to the workspace/project
-    #[arg(long, required_if_eq("ios", "true"), global = true)]
+    /// Path to the workspace/project (required for iOS unless listing failures) [env: AUTOFIX_WORKSPACE]
+    #[arg(long, global = true)]
     workspace: Option<PathBuf>,
 
-    /// Enable Knight Rider mode: AI agent with tools to automatically fix code
-    #[arg(long, global = true)]
+    /// Xcode developer directory for xcrun and xcodebuild, e.g. /Applications/Xcode-16.2.app/Contents/Developer [env: DEVELOPER_DIR]
+    #[arg(long, value_name = "PATH", global = true)]
+    developer_dir: Option<PathBuf>,
+
+    /// Load environment variables from this dotenv file before .env (repeatable; later files win, the real environment wins over all)
+    #[arg(long, value_name = "PATH", global = true)]
+    env_file: Vec<PathBuf>,
+
+    /// What the model changes: the app (the test is right), the test (the app is right), both (minimal app changes, assertions kept), or auto to decide per test
+    #[arg(long, value_enum, default_value_t = FixTarget::Test, global = true)]
+    fix_target: FixTarget,
+
+    /// Deprecated alias for --fix-target app
+    #[arg(long, conflicts_with = "fix_target", global = true)]
     knightrider: bool,
 
+    /// Shorthand for --fix-target both
+    #[arg(long, conflicts_with_all = ["fix_target", "knightrider"], global = true)]
+    fix_app_and_test: bool,
+
     /// Enable verbose mode: print detailed debug information
     #[arg(short = 'v', long, global = true)]
     verbose: bool,
 
-    /// LLM provider to use (claude, openai, ollama)
-    #[arg(long, default_value = "claude", global = true)]
-    provider: String,
+    /// LLM provider to use (claude, openai, groq, ollama) [default: AUTOFIX_PROVIDER or claude]
+    #[arg(long, global = true)]
+    provider: Option<String>,
 
     /// Model to use (overrides provider default)
     #[arg(long, global = true)]
     model: Option<String>,
 
+    /// Use --model (or the --escalate/--deescalate models) even if it looks like another provider's, e.g. behind a proxy that remaps model names
+    #[arg(long, global = true)]
+    allow_model_mismatch: bool,
+
+    /// Sampling temperature of the fix conversation [env: AUTOFIX_TEMPERATURE] [default: 0.7]
+    #[arg(long, global = true, value_name = "T")]
+    temperature: Option<f32>,
+
+    /// Most model turns per test before giving up [env: AUTOFIX_MAX_ITERATIONS] [default: 20]
+    #[arg(long, global = true, value_name = "N")]
+    max_iterations: Option<u32>,
+
+    /// Stop once the run has used this many tokens, input and output of all tests together [default: unlimited]
+    #[arg(long, global = true, value_name = "TOKENS")]
+    token_budget: Option<u32>,
+
+    /// Comma-separated models from cheap to strong; start with the first and move to the next when one stalls
+    #[arg(long, global = true, value_name = "MODELS", value_delimiter = ',', conflicts_with_all = ["model", "deescalate"])]
+    escalate: Vec<String>,
+
+    /// Comma-separated models from strong to cheap; start with the first and move to the next when one stalls
+    #[arg(
+        long,
+        global = true,
+        value_name = "MODELS",
+        value_delimiter = ',',
+        conflicts_with = "model"
+    )]
+    deescalate: Vec<String>,
+
+    /// Iterations without the test failing less before --escalate/--deescalate switch models (default: 6)
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    switch_after: Option<u64>,
+
+    /// Pull the Ollama model with `ollama pull` if it isn't available yet, instead of failing
+    #[arg(long, global = true)]
+    ollama_auto_pull: bool,
+
+    /// Token budget for Claude extended thinking [default: AUTOFIX_THINKING_BUDGET or off]
+    #[arg(long, global = true, value_name = "TOKENS")]
+    thinking_budget: Option<u32>,
+
+    /// After fixing, compare the fresh test results against the original xcresult
+    #[arg(long)]
+    baseline_report: bool,
+
+    /// Write the structured run report as JSON to this path at the end of the run
+    #[arg(long, global = true)]
+    report_file: Option<PathBuf>,
+
+    /// Write a markdown transcript of each conversation to .autofix/transcripts
+    #[arg(long, global = true)]
+    transcript: bool,
+
+    /// Write each iteration's request to <DIR> as JSON and markdown (secrets redacted)
+    #[arg(long, global = true, value_name = "DIR")]
+    dump_prompt: Option<PathBuf>,
+
+    /// Log raw provider requests and responses to .autofix/http-traces in the workspace (secrets redacted)
+    #[arg(long, global = true)]
+    trace_http: bool,
+
+    /// Show absolute file paths in output instead of paths relative to the workspace (reports stay relative)
+    #[arg(long, global = true)]
+    absolute_paths: bool,
+
+    /// Record every provider request and response to this file, for replaying the run later
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        conflicts_with = "replay_cassette"
+    )]
+    record_cassette: Option<PathBuf>,
+
+    /// Answer provider requests from a recorded cassette instead of the network (no API key needed)
+    #[arg(long, global = true, value_name = "PATH")]
+    replay_cassette: Option<PathBuf>,
+
+    /// Disable a tool for this run (repeatable), e.g. test_runner for a run without xcodebuild
+    #[arg(
+        long = "disable-tool",
+        value_name = "NAME",
+        value_parser = ["directory_inspector", "code_editor", "test_runner"],
+        global = true
+    )]
+    disabled_tools: Vec<String>,
+
+    /// When the model gives up, ask it for a handoff note for a human engineer
+    #[arg(long, global = true)]
+    summarize_on_giveup: bool,
+
+    /// Chat with the model about the failure: after every turn, type a message to steer it
+    #[arg(long, global = true)]
+    interactive_repl: bool,
+
+    /// Summarize older turns once a request is estimated above this many tokens [default: never]
+    #[arg(long, global = true, value_name = "TOKENS")]
+    compact_history_tokens: Option<usize>,
+
+    /// Editor to open at the failing assertion when the model gives up [env: AUTOFIX_EDITOR] [default: xcode]
+    #[arg(long, value_enum, global = true)]
+    editor: Option<Editor>,
+
+    /// Also fix expected failures (XCTExpectFailure) that no longer fail instead of skipping them
+    #[arg(long, global = true)]
+    include_unexpected_passes: bool,
+
+    /// Keep only the N most recent run directories in .autofix/tmp and .autofix/test-runner-tool [default: keep all]
+    #[arg(long, value_name = "COUNT", value_parser = clap::value_parser!(u32).range(1..), global = true)]
+    temp_retention: Option<u32>,
+
+    /// Back up edited files into the run directory and restore them when the test is not fixed (no git needed)
+    #[arg(long, global = true)]
+    revert_unfixed: bool,
+
+    /// Write a successful fix to FILE as a patch for git apply instead of editing the working tree
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        conflicts_with = "revert_unfixed"
+    )]
+    patch_only: Option<PathBuf>,
+
+    /// JSON file mapping test classes to their files, for layouts the workspace search can't handle
+    #[arg(long, global = true, value_name = "FILE")]
+    test_file_index: Option<PathBuf>,
+
+    /// Simulator OS version to run tests on, e.g. 18.5, when several runtimes are installed
+    #[arg(long, global = true, value_name = "VERSION")]
+    os_version: Option<String>,
+
+    /// xcodebuild -destination to run tests on (repeatable), e.g. "platform=iOS Simulator,name=iPhone SE (3rd generation)" [default: iPhone 17 Pro]
+    #[arg(
+        long,
+        global = true,
+        value_name = "SPECIFIER",
+        conflicts_with = "os_version"
+    )]
+    destination: Vec<String>,
+
+    /// Extra argument appended to the test runner's xcodebuild call (repeatable), e.g. CODE_SIGNING_ALLOWED=NO
+    #[arg(long, global = true, value_name = "ARG", allow_hyphen_values = true)]
+    xcodebuild_arg: Vec<String>,
+
+    /// Stream model responses (falls back to complete responses where unsupported)
+    #[arg(long, global = true)]
+    stream: bool,
+
+    /// Downscale snapshots whose width or height exceeds this many pixels (default: 1568)
+    #[arg(long, global = true, value_name = "PX", value_parser = clap::value_parser!(u32).range(1..))]
+    max_image_dimension: Option<u32>,
+
+    /// Erase the simulator before every test run and disable parallel testing, so no state from other tests bleeds in
+    #[arg(long, global = true)]
+    isolate: bool,
+
+    /// Target or class prefix the model's test runs are restricted to (repeatable), e.g. AppUITests/LoginTests
+    #[arg(long, global = true, value_name = "PREFIX")]
+    allow_testing: Vec<String>,
+
+    /// Cut tool results sent to the model to this many bytes; the full result stays readable in pages (default: 65536)
+    #[arg(long, global = true, value_name = "BYTES", value_parser = clap::value_parser!(u64).range(1024..))]
+    max_tool_result_bytes: Option<u64>,
+
+    /// Kill a test run after this many seconds (default: 1800)
+    #[arg(long, global = true, value_name = "SECS")]
+    test_timeout: Option<u64>,
+
+    /// Give up waiting for other test runs to release the simulator after this many seconds (default: 3600)
+    #[arg(long, global = true, value_name = "SECS")]
+    simulator_lock_timeout: Option<u64>,
+
+    /// Scheme to run tests with, instead of the one named in the test identifier
+    #[arg(long, global = true, value_name = "NAME")]
+    scheme: Option<String>,
+
+    /// Don't check the scheme and that the workspace builds (xcodebuild build-for-testing) before fixing
+    #[arg(long, global = true)]
+    skip_preflight: bool,
+
+    /// Offer the model the custom tools from --custom-tools; they run shell commands in the workspace
+    #[arg(long, global = true)]
+    allow_custom_tools: bool,
+
+    /// JSON file defining custom tools [default: <workspace>/.autofix/tools.json]
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        requires = "allow_custom_tools"
+    )]
+    custom_tools: Option<PathBuf>,
+
+    /// Directory name to skip when searching for test files (repeatable), e.g. Vendor
+    #[arg(long, global = true, value_name = "NAME")]
+    ignore_dir: Vec<String>,
+
+    /// Language of the UI tests, for the test file search and the prompt's code samples [default: from the test file's extension]
+    #[arg(long, value_enum, global = true)]
+    language: Option<SourceLanguage>,
+
+    /// Don't ask before editing files with uncommitted changes; note them in the report instead
+    #[arg(short = 'y', long, global = true)]
+    yes: bool,
+
+    /// Skip tests whose name or identifier matches this regex (repeatable)
+    #[arg(long, value_name = "REGEX", value_parser = regex::Regex::new, global = true)]
+    exclude: Vec<regex::Regex>,
+
+    /// Refuse code edits to files matching this glob, relative to the workspace (repeatable)
+    #[arg(long, value_name = "GLOB", value_parser = glob::Pattern::new, global = true)]
+    exclude_path: Vec<glob::Pattern>,
+
+    /// Serve pipeline events over HTTP/SSE on the given address (e.g. 127.0.0.1:7878)
+    #[cfg(feature = "serve")]
+    #[arg(long)]
+    serve: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -64,36 +333,364 @@ enum Commands {
         #[arg(short = 't', long)]
         test_id: String,
     },
+    /// Print the prompt for a test and its estimated token count and cost without calling the API
+    Estimate {
+        /// Test ID to build the prompt for
+        #[arg(short = 't', long)]
+        test_id: String,
+    },
+    /// Print the failing tests of the test result without running any LLM
+    ListFailures {
+        /// Output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: ListFormat,
+    },
+    /// Print default, known and (if reachable) live models per provider
+    Models {
+        /// Only print the built-in defaults without contacting any endpoint
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Remove the .autofix artifacts in the workspace and the current directory (asks unless --yes)
+    Clean,
+    /// Run a test once and report whether it passes, e.g. after fixing it by hand (no LLM call)
+    Verify {
+        /// Test ID to run, e.g. ShopUITests/LoginTests/testLogin with --scheme
+        #[arg(short = 't', long)]
+        test_id: String,
+    },
+}
+
+/// Read a path from `var`, treating an unset or empty variable as absent
+fn path_from_env(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Replace directories holding `.xcresult` bundles (e.g. CI shard artifacts) by the bundles
+fn expand_test_results(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        let is_bundle = path
+            .extension()
+            .is_some_and(|extension| extension == "xcresult");
+        let mut bundles: Vec<PathBuf> = match fs::read_dir(path) {
+            Ok(entries) if !is_bundle => entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|entry| {
+                    entry
+                        .extension()
+                        .is_some_and(|extension| extension == "xcresult")
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        if bundles.is_empty() {
+            expanded.push(path.clone());
+        } else {
+            bundles.sort();
+            expanded.extend(bundles);
+        }
+    }
+    expanded
+}
+
+/// The only `--test-result` bundle, for commands that read a single one
+fn single_test_result(test_results: &[PathBuf], command: &str) -> PathBuf {
+    match test_results {
+        [path] => path.clone(),
+        [] => {
+            eprintln!("Error: --test-result (or AUTOFIX_TEST_RESULT) is required for iOS");
+            ExitCode::Configuration.exit();
+        }
+        _ => {
+            eprintln!(
+                "Error: {} reads a single --test-result, got {}",
+                command,
+                test_results.len()
+            );
+            ExitCode::Configuration.exit();
+        }
+    }
+}
+
+/// Load the `--env-file`s so that later files win over earlier ones
+///
+/// dotenvy never overrides variables that are already set, so loading the
+/// files last to first gives later files precedence while the real environment
+/// keeps precedence over all of them.
+fn load_env_files(env_files: &[PathBuf]) -> Result<(), String> {
+    if let Some(missing) = env_files.iter().find(|path| !path.is_file()) {
+        return Err(format!("--env-file {} does not exist", missing.display()));
+    }
+    for path in env_files.iter().rev() {
+        dotenvy::from_path(path)
+            .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+    }
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    // Usage errors exit with the configuration code; --help and --version with 0
+    let mut args = Args::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        if e.use_stderr() {
+            ExitCode::Configuration.exit();
+        }
+        ExitCode::Success.exit();
+    });
 
-    // Load provider configuration from environment
-    let mut provider_config = match llm::ProviderConfig::from_env() {
-        Ok(config) => config,
-        Err(e) => {
-            eprintln!("Error: Failed to load provider configuration: {}", e);
-            std::process::exit(1);
+    // Explicit env files take precedence over the implicit .env
+    if let Err(e) = load_env_files(&args.env_file) {
+        eprintln!("Error: {}", e);
+        ExitCode::Configuration.exit();
+    }
+
+    // Paths may come from a previous CI step; the flags take precedence
+    let _ = dotenvy::dotenv();
+    if args.test_result.is_empty() {
+        args.test_result
+            .extend(path_from_env("AUTOFIX_TEST_RESULT"));
+    }
+    args.test_result = expand_test_results(&args.test_result);
+    args.workspace = args
+        .workspace
+        .or_else(|| path_from_env("AUTOFIX_WORKSPACE"));
+
+    if let Some(developer_dir) = &args.developer_dir {
+        if !developer_dir.is_dir() {
+            eprintln!(
+                "Error: --developer-dir {} is not a directory",
+                developer_dir.display()
+            );
+            ExitCode::Configuration.exit();
+        }
+        developer_dir::set(developer_dir.clone());
+    }
+    if args.verbose
+        && let Some(developer_dir) = developer_dir::get()
+    {
+        println!("  [DEBUG] Developer directory: {}", developer_dir.display());
+    }
+
+    if args.absolute_paths {
+        workspace_path::show_absolute();
+    }
+
+    if args.trace_http {
+        let trace_dir = args
+            .workspace
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".autofix/http-traces");
+        match llm::http_trace::enable(&trace_dir) {
+            Ok(path) => println!("🔍 Tracing provider HTTP traffic to {}", path.display()),
+            Err(e) => {
+                eprintln!("Error: Failed to create HTTP trace file: {}", e);
+                ExitCode::Error.exit();
+            }
+        }
+    }
+
+    if let Some(path) = &args.record_cassette {
+        match llm::cassette::enable_recording(path) {
+            Ok(()) => println!("📼 Recording provider calls to {}", path.display()),
+            Err(e) => {
+                eprintln!("Error: Failed to create cassette {}: {}", path.display(), e);
+                ExitCode::Error.exit();
+            }
         }
+    }
+    if let Some(path) = &args.replay_cassette {
+        match llm::cassette::enable_replay(path) {
+            Ok(()) => println!("📼 Replaying provider calls from {}", path.display()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::Configuration.exit();
+            }
+        }
+    }
+
+    let cli_provider = args.provider.as_deref().map(|provider| {
+        ProviderType::from_str(provider).unwrap_or_else(|e| {
+            eprintln!("Error: Invalid provider '{}': {}", provider, e);
+            eprintln!("Valid providers: claude, openai, groq, ollama");
+            ExitCode::Configuration.exit();
+        })
+    });
+
+    // Project defaults from .autofix.toml, overridden by the environment and then by flags
+    let overrides = Config::from_env()
+        .map(|env| {
+            env.merge(Config {
+                provider: cli_provider,
+                model: args.model.clone(),
+                temperature: args.temperature,
+                max_iterations: args.max_iterations,
+                destination: args.destination.clone(),
+                scheme: args.scheme.clone(),
+                ignore_dir: args.ignore_dir.clone(),
+                ..Config::default()
+            })
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            ExitCode::Configuration.exit();
+        });
+    let project_settings =
+        Config::discover(&args.workspace.clone().unwrap_or_else(|| PathBuf::from(".")))
+            .unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                ExitCode::Configuration.exit();
+            });
+    if let Some(path) = &project_settings.path {
+        println!("📄 Using settings from {}", path.display());
+    }
+    let config = project_settings.merge(overrides.clone());
+    // A destination from the file would conflict with --os-version
+    if args.os_version.is_none() {
+        args.destination = config.destination.clone();
+    }
+    args.scheme = config.scheme.clone();
+    args.ignore_dir = config.ignore_dir.clone();
+
+    // --xcodebuild-arg wins over AUTOFIX_XCODEBUILD_ARGS (whitespace-separated)
+    let xcodebuild_args: Vec<String> = if args.xcodebuild_arg.is_empty() {
+        std::env::var("AUTOFIX_XCODEBUILD_ARGS")
+            .map(|value| value.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default()
+    } else {
+        args.xcodebuild_arg.clone()
     };
 
-    // Override provider type if specified via CLI
-    let provider_type = match ProviderType::from_str(&args.provider) {
-        Ok(provider) => provider,
+    // Verifying runs the test itself, so it needs neither a test result nor a provider
+    if let Some(Commands::Verify { test_id }) = &args.command {
+        if !args.ios {
+            eprintln!("Error: verify is only supported with --ios");
+            ExitCode::Configuration.exit();
+        }
+        let Some(workspace_path) = args.workspace.clone() else {
+            eprintln!("Error: --workspace (or AUTOFIX_WORKSPACE) is required for iOS");
+            ExitCode::Configuration.exit();
+        };
+        let test_runner = tools::TestRunnerTool::new()
+            .with_os_version(args.os_version.clone())
+            .with_destinations(args.destination.clone())
+            .with_extra_args(xcodebuild_args)
+            .with_isolation(args.isolate);
+        let test_runner = match args.test_timeout {
+            Some(secs) => test_runner.with_timeout(Duration::from_secs(secs)),
+            None => test_runner,
+        };
+        let test_runner = match args.simulator_lock_timeout {
+            Some(secs) => test_runner.with_lock_timeout(Duration::from_secs(secs)),
+            None => test_runner,
+        };
+        let cmd = VerifyCommand::new(workspace_path, test_id.clone())
+            .with_scheme(args.scheme.clone())
+            .with_test_runner(test_runner);
+        match cmd.execute_ios() {
+            Ok(true) => ExitCode::Success.exit(),
+            Ok(false) => ExitCode::NotFixed.exit(),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::from(&e).exit();
+            }
+        }
+    }
+
+    if args.ios && args.test_result.is_empty() {
+        eprintln!("Error: --test-result (or AUTOFIX_TEST_RESULT) is required for iOS");
+        ExitCode::Configuration.exit();
+    }
+
+    if let Some(Commands::Models { offline }) = &args.command {
+        ModelsCommand::new(config.provider, *offline)
+            .execute()
+            .await;
+        return;
+    }
+
+    if let Some(Commands::Clean) = &args.command {
+        let mut roots = vec![PathBuf::from(".")];
+        roots.extend(args.workspace.clone());
+        if let Err(e) = CleanCommand::new(&roots, args.yes).execute() {
+            eprintln!("Error: {}", e);
+            ExitCode::from(&e).exit();
+        }
+        return;
+    }
+
+    // Listing failures needs neither a provider nor a workspace
+    if let Some(Commands::ListFailures { format }) = &args.command {
+        if !args.ios {
+            eprintln!("Error: list-failures is only supported with --ios");
+            ExitCode::Configuration.exit();
+        }
+        let test_result_path = single_test_result(&args.test_result, "list-failures");
+        if let Err(e) = ListFailuresCommand::new(test_result_path, *format).execute_ios() {
+            eprintln!("Error: {}", e);
+            ExitCode::from(&e).exit();
+        }
+        return;
+    }
+
+    if args.ios && args.workspace.is_none() {
+        eprintln!("Error: --workspace (or AUTOFIX_WORKSPACE) is required for iOS");
+        ExitCode::Configuration.exit();
+    }
+
+    // Load provider configuration from environment
+    let provider_type = config.provider.unwrap_or_default();
+    let mut provider_config = match llm::ProviderConfig::from_env(provider_type) {
+        Ok(config) => config,
+        // A replayed run never reaches the provider, so it needs no API key
+        Err(_) if args.replay_cassette.is_some() => {
+            llm::ProviderConfig::default_for_provider(provider_type)
+        }
         Err(e) => {
-            eprintln!("Error: Invalid provider '{}': {}", args.provider, e);
-            eprintln!("Valid providers: claude, openai, ollama");
-            std::process::exit(1);
+            eprintln!("Error: Failed to load provider configuration: {}", e);
+            ExitCode::Configuration.exit();
         }
     };
-    provider_config.provider_type = provider_type;
 
-    // Override model if specified via CLI
-    if let Some(model) = &args.model {
+    // Override model if specified via CLI or the project config
+    if let Some(model) = &config.model {
         provider_config.model = model.clone();
     }
+    let model_ladder = match (&args.escalate[..], &args.deescalate[..]) {
+        ([], []) => None,
+        (models, []) => Some((LadderPolicy::Escalate, models)),
+        (_, models) => Some((LadderPolicy::Deescalate, models)),
+    }
+    .map(|(policy, models)| ModelLadder {
+        policy,
+        models: models.to_vec(),
+        switch_after: args
+            .switch_after
+            .map_or(DEFAULT_SWITCH_AFTER, |iterations| iterations as usize),
+    });
+    // The ladder starts with its first model
+    if let Some(ladder) = &model_ladder {
+        provider_config.model = ladder.models[0].clone();
+    }
+    // Catch e.g. `--provider ollama --model gpt-4o` before the first request fails
+    if !args.allow_model_mismatch {
+        let ladder_models = model_ladder.iter().flat_map(|ladder| &ladder.models);
+        for model in std::iter::once(&provider_config.model).chain(ladder_models) {
+            if let Err(e) = provider_config.provider_type.check_model(model) {
+                eprintln!("Error: {}", e);
+                ExitCode::Configuration.exit();
+            }
+        }
+    }
+    if let Some(thinking_budget) = args.thinking_budget {
+        provider_config.thinking_budget = Some(thinking_budget);
+    }
+    provider_config.ollama_auto_pull = args.ollama_auto_pull;
 
     // Display provider info in verbose mode
     if args.verbose {
@@ -102,87 +699,322 @@ async fn main() {
         println!("  Model: {}", provider_config.model);
         if args.model.is_some() {
             println!("  (model overridden via CLI)");
+        } else if config.model.is_some() {
+            println!(
+                "  (model overridden via AUTOFIX_MODEL or {})",
+                project_config::PROJECT_CONFIG_FILE
+            );
         }
         println!();
     }
 
+    // --editor wins over AUTOFIX_EDITOR (which may come from .env)
+    let editor = match (args.editor, std::env::var("AUTOFIX_EDITOR")) {
+        (Some(editor), _) => editor,
+        (None, Ok(value)) => Editor::from_str(&value, true).unwrap_or_else(|_| {
+            eprintln!("Error: Invalid AUTOFIX_EDITOR '{}'", value);
+            eprintln!("Valid editors: xcode, vscode, cursor, none");
+            ExitCode::Configuration.exit();
+        }),
+        (None, Err(_)) => Editor::default(),
+    };
+
+    let fix_target = if args.knightrider {
+        eprintln!("Warning: --knightrider is deprecated, use --fix-target app");
+        FixTarget::App
+    } else if args.fix_app_and_test {
+        FixTarget::Both
+    } else {
+        args.fix_target
+    };
+
+    // The REPL reads the user's messages from the terminal
+    let repl = if args.interactive_repl {
+        if !std::io::stdin().is_terminal() {
+            eprintln!("Error: --interactive-repl needs an interactive terminal");
+            ExitCode::Configuration.exit();
+        }
+        Some(ReplInput::stdin())
+    } else {
+        None
+    };
+
+    // Custom tools run arbitrary commands, so they are only loaded when explicitly allowed
+    let custom_tools = if args.allow_custom_tools {
+        let path = args.custom_tools.clone().unwrap_or_else(|| {
+            args.workspace
+                .clone()
+                .unwrap_or_default()
+                .join(".autofix/tools.json")
+        });
+        match tools::custom_tool::load(&path) {
+            Ok(custom_tools) => {
+                println!(
+                    "🧩 Loaded {} custom tool(s) from {}",
+                    custom_tools.len(),
+                    path.display()
+                );
+                custom_tools
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::Configuration.exit();
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    // Protected paths are part of the repo's config, so they apply without a flag. Each
+    // test loads those of its own workspace; this only reports a broken file up front
+    if let Some(workspace) = &args.workspace {
+        match pipeline::load_protected_paths(workspace) {
+            Ok(protected_paths) if !protected_paths.is_empty() => {
+                println!(
+                    "🛡️  Protecting {} path pattern(s) from {}",
+                    protected_paths.len(),
+                    pipeline::PROTECTED_PATHS_FILE
+                );
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::Configuration.exit();
+            }
+        }
+    }
+    let protected_paths = match config.protected_path_patterns() {
+        Ok(patterns) => {
+            if !patterns.is_empty() {
+                println!(
+                    "🛡️  Protecting {} path pattern(s) from {}",
+                    patterns.len(),
+                    project_config::PROJECT_CONFIG_FILE
+                );
+            }
+            patterns
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::Configuration.exit();
+        }
+    };
+
+    let pipeline_options = PipelineOptions {
+        transcript_dir: args
+            .transcript
+            .then(|| PathBuf::from(".autofix/transcripts")),
+        dump_prompt_dir: args.dump_prompt.clone(),
+        model_ladder,
+        disabled_tools: args.disabled_tools.clone(),
+        excluded_paths: args.exclude_path.clone(),
+        protected_paths,
+        max_iterations: config.max_iterations,
+        token_budget: args.token_budget,
+        temperature: config.temperature,
+        summarize_on_giveup: args.summarize_on_giveup,
+        repl,
+        compact_history_tokens: args.compact_history_tokens,
+        editor,
+        assume_yes: args.yes,
+        temp_retention: args.temp_retention.map(|count| count as usize),
+        revert_unfixed: args.revert_unfixed,
+        patch_only: args.patch_only.clone(),
+        test_file_index: args.test_file_index.clone(),
+        ignored_dirs: args.ignore_dir.clone(),
+        language: args.language,
+        os_version: args.os_version.clone(),
+        destinations: args.destination.clone(),
+        xcodebuild_args,
+        scheme: args.scheme.clone(),
+        skip_preflight: args.skip_preflight,
+        stream: args.stream,
+        max_image_dimension: args.max_image_dimension,
+        max_tool_result_bytes: args.max_tool_result_bytes.map(|bytes| bytes as usize),
+        custom_tools,
+        test_timeout: args.test_timeout.map(Duration::from_secs),
+        simulator_lock_timeout: args.simulator_lock_timeout.map(Duration::from_secs),
+        isolate: args.isolate,
+        allowed_tests: args.allow_testing.clone(),
+        ..PipelineOptions::default()
+    };
+
+    if let Some(warning) = llm::OllamaProvider::remote_endpoint_warning(&provider_config) {
+        eprintln!("⚠️  {}", warning);
+    }
+
+    // A rejected API key ends the run here, before parsing results and exporting attachments
+    if args.ios && !matches!(args.command, Some(Commands::Estimate { .. })) {
+        match llm::check_credentials(&provider_config).await {
+            Ok(()) => {}
+            Err(e @ llm::LLMError::ConfigurationError(_)) => {
+                eprintln!("Error: {}", e);
+                ExitCode::Configuration.exit();
+            }
+            // Anything else may be transient and surfaces again on the first request
+            Err(e) => eprintln!("⚠️  Could not check the API key: {}", e),
+        }
+    }
+
+    #[cfg(feature = "serve")]
+    if let Some(addr) = &args.serve {
+        let config = serve::ServeConfig {
+            test_result: args.test_result.first().cloned(),
+            workspace: args.workspace.clone(),
+            fix_target,
+            verbose: args.verbose,
+            provider_config: provider_config.clone(),
+            options: pipeline_options.clone(),
+            overrides,
+        };
+        if let Err(e) = serve::serve(addr, config).await {
+            eprintln!("Error: Server failed: {}", e);
+            ExitCode::Error.exit();
+        }
+        return;
+    }
+
+    // The first Ctrl-C stops the run cleanly, so the report is still written; the second quits
+    let cancellation = pipeline_options.cancellation.clone();
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if cancellation.is_cancelled() {
+                ExitCode::Error.exit();
+            }
+            println!("\n🛑 Stopping after the current step, press Ctrl-C again to quit");
+            cancellation.cancel();
+        }
+    });
+
     match args.command {
+        Some(
+            Commands::ListFailures { .. }
+            | Commands::Models { .. }
+            | Commands::Clean
+            | Commands::Verify { .. },
+        ) => {
+            unreachable!("handled before loading the provider")
+        }
+        // Handle "autofix estimate --test-id ..." subcommand
+        Some(Commands::Estimate { test_id }) => {
+            if !args.ios {
+                eprintln!("Error: estimate is only supported with --ios");
+                ExitCode::Configuration.exit();
+            }
+            let test_result_path = single_test_result(&args.test_result, "estimate");
+            let workspace_path = args.workspace.expect("--workspace is required for iOS");
+
+            let cmd = EstimateCommand::new(
+                test_result_path,
+                workspace_path,
+                test_id,
+                fix_target,
+                args.verbose,
+                provider_config.clone(),
+            )
+            .with_options(pipeline_options.clone());
+
+            if let Err(e) = cmd.execute_ios() {
+                eprintln!("Error: {}", e);
+                ExitCode::from(&e).exit();
+            }
+        }
         // Handle "autofix test --test-id ..." subcommand
         Some(Commands::Test { test_id }) => {
             if args.ios {
                 // iOS test details
-                let test_result_path = args.test_result.expect("--test-result is required for iOS");
+                let test_result_path = single_test_result(&args.test_result, "test");
                 let workspace_path = args.workspace.expect("--workspace is required for iOS");
 
                 let cmd = TestCommand::new(
                     test_result_path,
                     workspace_path,
                     test_id,
-                    args.knightrider,
+                    fix_target,
                     args.verbose,
                     provider_config.clone(),
-                );
+                )
+                .with_options(pipeline_options.clone())
+                .with_report_file(args.report_file.clone());
 
-                if let Err(e) = cmd.execute_ios().await {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
+                match cmd.execute_ios().await {
+                    Ok(outcome) => outcome
+                        .as_ref()
+                        .map_or(ExitCode::Success, ExitCode::from)
+                        .exit(),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        ExitCode::from(&e).exit();
+                    }
                 }
             } else if args.android {
                 // Android test details
                 let cmd = TestCommand::new(
-                    args.test_result.unwrap_or_default(),
+                    args.test_result.first().cloned().unwrap_or_default(),
                     args.workspace.unwrap_or_default(),
                     test_id,
-                    args.knightrider,
+                    fix_target,
                     args.verbose,
                     provider_config.clone(),
                 );
 
                 if let Err(e) = cmd.execute_android() {
                     eprintln!("Error: {}", e);
-                    std::process::exit(1);
+                    ExitCode::Error.exit();
                 }
             } else {
                 eprintln!("Error: Either --ios or --android must be specified");
-                std::process::exit(1);
+                ExitCode::Configuration.exit();
             }
         }
         // Handle "autofix --ios ..." (no subcommand - process all tests)
         None => {
             if args.ios {
                 // iOS autofix - process all failed tests
-                let test_result_path = args.test_result.expect("--test-result is required for iOS");
+                let mut test_result_paths = args.test_result.into_iter();
+                let test_result_path = test_result_paths
+                    .next()
+                    .expect("--test-result is required for iOS");
                 let workspace_path = args.workspace.expect("--workspace is required for iOS");
 
                 let cmd = AutofixCommand::new(
                     test_result_path,
                     workspace_path,
-                    args.knightrider,
+                    fix_target,
                     args.verbose,
                     provider_config.clone(),
-                );
+                )
+                .with_additional_test_results(test_result_paths.collect())
+                .with_options(pipeline_options.clone())
+                .with_baseline_report(args.baseline_report)
+                .with_exclude(args.exclude.clone())
+                .with_include_unexpected_passes(args.include_unexpected_passes)
+                .with_report_file(args.report_file.clone());
 
-                if let Err(e) = cmd.execute_ios().await {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
+                match cmd.execute_ios().await {
+                    Ok(report) => ExitCode::for_reports(&report.tests).exit(),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        ExitCode::from(&e).exit();
+                    }
                 }
             } else if args.android {
                 // Android autofix
                 let cmd = AutofixCommand::new(
-                    args.test_result.unwrap_or_default(),
+                    args.test_result.first().cloned().unwrap_or_default(),
                     args.workspace.unwrap_or_default(),
-                    args.knightrider,
+                    fix_target,
                     args.verbose,
                     provider_config.clone(),
                 );
 
                 if let Err(e) = cmd.execute_android() {
                     eprintln!("Error: {}", e);
-                    std::process::exit(1);
+                    ExitCode::Error.exit();
                 }
             } else {
                 eprintln!("Error: Either --ios or --android must be specified");
-                std::process::exit(1);
+                ExitCode::Configuration.exit();
             }
         }
     }
@@ -0,0 +1,146 @@
+use crate::llm::config::normalize_api_base;
+use crate::llm::{LLMError, ProviderConfig, ProviderFactory, ProviderType};
+use secrecy::SecretString;
+use std::env;
+
+/// Prints default and known models per provider, plus the live list where available
+pub struct ModelsCommand {
+    providers: Vec<ProviderType>,
+    offline: bool,
+}
+
+impl ModelsCommand {
+    /// List models for `provider`, or for all providers if `None`
+    pub fn new(provider: Option<ProviderType>, offline: bool) -> Self {
+        Self {
+            providers: provider.map_or_else(|| ProviderType::ALL.to_vec(), |p| vec![p]),
+            offline,
+        }
+    }
+
+    pub async fn execute(&self) {
+        // Load .env file if present so API keys are picked up for live listing
+        let _ = dotenvy::dotenv();
+
+        for provider_type in &self.providers {
+            print!("{}", Self::render_static(*provider_type));
+            if !self.offline {
+                self.print_live_models(*provider_type).await;
+            }
+            println!();
+        }
+    }
+
+    /// Default and well-known models of a provider, available offline
+    fn render_static(provider_type: ProviderType) -> String {
+        let defaults = ProviderConfig::default_for_provider(provider_type);
+        let known = provider_type.known_models();
+        let width = known
+            .iter()
+            .map(|known| known.name.len())
+            .max()
+            .unwrap_or_default();
+
+        let mut output = format!(
+            "{:?} (--provider {})\n  Default: {}\n  Known models:\n",
+            provider_type,
+            format!("{:?}", provider_type).to_lowercase(),
+            defaults.model
+        );
+        for known in known {
+            output.push_str(&format!("    {:width$}  {}\n", known.name, known.note));
+        }
+        output
+    }
+
+    /// Fetch and print the models the provider's endpoint serves
+    async fn print_live_models(&self, provider_type: ProviderType) {
+        let mut config = ProviderConfig::default_for_provider(provider_type);
+        // AUTOFIX_API_BASE belongs to the selected provider, e.g. a proxy in front of it
+        if self.providers.len() == 1
+            && let Ok(api_base) = env::var("AUTOFIX_API_BASE")
+        {
+            match Self::with_api_base(config, &api_base) {
+                Ok(with_api_base) => config = with_api_base,
+                Err(e) => {
+                    println!("  Live models: unavailable ({})", e);
+                    return;
+                }
+            }
+        }
+        if let Some(var) = provider_type.api_key_var() {
+            match env::var(var) {
+                Ok(api_key) => config.api_key = SecretString::new(api_key),
+                Err(_) => {
+                    println!("  Live models: set {} to fetch them", var);
+                    return;
+                }
+            }
+        }
+
+        let models = match ProviderFactory::create(config) {
+            Ok(provider) => provider.list_models().await,
+            Err(e) => Err(e),
+        };
+        match models {
+            Ok(models) if models.is_empty() => println!("  Live models: none"),
+            Ok(models) => {
+                println!("  Live models:");
+                for model in models {
+                    println!("    {}", model);
+                }
+            }
+            Err(e) => println!("  Live models: unavailable ({})", e),
+        }
+    }
+
+    /// `config` pointed at `api_base`, normalized like `AUTOFIX_API_BASE` for a run
+    fn with_api_base(
+        mut config: ProviderConfig,
+        api_base: &str,
+    ) -> Result<ProviderConfig, LLMError> {
+        config.api_base = normalize_api_base(config.provider_type, api_base)?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_static_lists_default_and_known_models() {
+        let output = ModelsCommand::render_static(ProviderType::Groq);
+
+        assert!(output.starts_with("Groq (--provider groq)"));
+        assert!(output.contains("Default: llama-3.3-70b-versatile"));
+        assert!(output.contains("llama-3.1-8b-instant"));
+    }
+
+    #[test]
+    fn test_api_base_override_is_normalized() {
+        let config = ModelsCommand::with_api_base(
+            ProviderConfig::default_for_provider(ProviderType::OpenAI),
+            "https://proxy.example.com/",
+        )
+        .unwrap();
+        assert_eq!(config.api_base, "https://proxy.example.com/v1");
+
+        assert!(
+            ModelsCommand::with_api_base(
+                ProviderConfig::default_for_provider(ProviderType::OpenAI),
+                "proxy.example.com",
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_all_providers_without_filter() {
+        assert_eq!(ModelsCommand::new(None, true).providers.len(), 4);
+        assert_eq!(
+            ModelsCommand::new(Some(ProviderType::Ollama), true).providers,
+            vec![ProviderType::Ollama]
+        );
+    }
+}
@@ -0,0 +1,437 @@
+use crate::tools::directory_inspector_tool::is_test_path;
+use crate::workspace_path;
+use crate::xc_workspace_file_locator::XCWorkspaceFileLocator;
+use crate::xctestresultdetailparser::XCTestResultDetail;
+use regex::Regex;
+use std::fs;
+use std::path::{Component, Path};
+use std::sync::LazyLock;
+
+/// Failure messages of a UI element the test could not find
+const MISSING_ELEMENT_MARKERS: &[&str] = &[
+    "no matches found",
+    "does not exist",
+    "failed to find",
+    "unable to find",
+    "not hittable",
+    "timed out waiting",
+];
+
+/// Most candidate lines put into the prompt
+const MAX_CANDIDATES: usize = 10;
+
+/// Source files larger than this are not scanned
+const MAX_SOURCE_FILE_BYTES: u64 = 512 * 1024;
+
+/// Code that declares a UI element or names one, in SwiftUI or UIKit from Swift or Objective-C
+const UI_ELEMENT_MARKERS: &[&str] = &[
+    "Button(",
+    "Text(",
+    "Label(",
+    "Toggle(",
+    "TextField(",
+    "SecureField(",
+    "NavigationLink(",
+    "Link(",
+    "Image(",
+    "navigationTitle(",
+    "accessibilityIdentifier",
+    "accessibilityLabel",
+    "setTitle(",
+    "setTitle:",
+    "setAccessibilityIdentifier:",
+    "setAccessibilityLabel:",
+    ".title =",
+    ".text =",
+    "placeholder",
+];
+
+/// Words naming the kind of element rather than the element itself, e.g. in `loginButton`
+const ELEMENT_TYPE_WORDS: &[&str] = &[
+    "button", "btn", "label", "text", "field", "view", "cell", "link", "toggle", "image", "switch",
+    "title",
+];
+
+/// A double-quoted string, as in `Failed to tap "Login" Button`
+static QUOTED_PATTERN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""([^"\n]+)""#).unwrap());
+
+/// An element query subscript, as in `app.buttons["loginButton"]` or Objective-C's `app.buttons[@"loginButton"]`
+static SUBSCRIPT_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\[\s*@?"([^"\n]+)"\s*\]"#).unwrap());
+
+/// Identifier lookups, as in `.matching(identifier: "login")` or `element(matching: .button, identifier: "login")`
+static IDENTIFIER_QUERY_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\bidentifier:\s*"([^"\n]+)""#).unwrap());
+
+/// Predicates on the label or identifier, as in `NSPredicate(format: "label == 'Log In'")`
+static PREDICATE_QUERY_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(label|identifier)\s*(?:==|=|beginswith|contains|like)(?:\[[a-z]+\])?\s*'([^'\n]+)'")
+        .unwrap()
+});
+
+/// An accessibility label or identifier set in app code, in SwiftUI or UIKit
+static ACCESSIBILITY_ATTRIBUTE_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"accessibility(Label|Identifier)\s*(?:\(\s*(?:Text\(\s*)?|=\s*)"([^"\n]+)""#)
+        .unwrap()
+});
+
+/// Which element attribute a test query matches
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QueryKind {
+    /// Only the accessibility identifier
+    Identifier,
+    /// Only the label
+    Label,
+    /// Identifier or label, like `app.buttons["login"]`
+    Either,
+}
+
+/// Whether a failure message says a UI element could not be found
+pub fn is_missing_element(message: &str) -> bool {
+    let message = message.to_lowercase();
+    MISSING_ELEMENT_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+/// An app source line that may be the element a failing query looks for
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementCandidate {
+    /// Path relative to the workspace
+    pub file: String,
+    pub line: usize,
+    pub content: String,
+    /// The identifier or label from the test that matched
+    pub query: String,
+}
+
+/// Identifiers and labels the failing test queried for
+///
+/// Taken from quoted strings in missing-element failure messages and from
+/// element subscripts like `app.buttons["login"]` on the failing lines.
+pub fn failing_queries(detail: &XCTestResultDetail, test_file_contents: &str) -> Vec<String> {
+    let test_lines: Vec<&str> = test_file_contents.lines().collect();
+    let mut queries: Vec<String> = Vec::new();
+
+    for failure in &detail.failures {
+        if is_missing_element(&failure.message) {
+            queries.extend(
+                QUOTED_PATTERN
+                    .captures_iter(&failure.message)
+                    .map(|captures| captures[1].to_string()),
+            );
+        }
+        let failing_line = failure
+            .line
+            .and_then(|line| test_lines.get((line as usize).checked_sub(1)?));
+        if let Some(failing_line) = failing_line {
+            queries.extend(
+                SUBSCRIPT_PATTERN
+                    .captures_iter(failing_line)
+                    .map(|captures| captures[1].to_string()),
+            );
+        }
+    }
+
+    let mut unique = Vec::new();
+    for query in queries {
+        if query.trim().len() >= 2 && !unique.contains(&query) {
+            unique.push(query);
+        }
+    }
+    unique
+}
+
+/// Search the app's source files with one of the `extensions` for UI elements matching the `queries`
+///
+/// The files are found like the test file, skipping the same and `ignored_dirs` directories.
+pub fn find_candidates(
+    workspace_path: &Path,
+    ignored_dirs: &[String],
+    queries: &[String],
+    extensions: &[&str],
+) -> Vec<ElementCandidate> {
+    if queries.is_empty() {
+        return Vec::new();
+    }
+    let variants: Vec<(&String, Vec<String>)> = queries
+        .iter()
+        .map(|query| (query, match_variants(query)))
+        .collect();
+
+    let paths = XCWorkspaceFileLocator::new(workspace_path)
+        .with_ignored_dirs(ignored_dirs.to_vec())
+        .source_files(extensions);
+
+    let mut candidates = Vec::new();
+    for path in paths {
+        let relative = path.strip_prefix(workspace_path).unwrap_or(&path);
+        let components: Vec<&str> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            })
+            .collect();
+        let is_too_large = fs::metadata(&path)
+            .map(|metadata| metadata.len() > MAX_SOURCE_FILE_BYTES)
+            .unwrap_or(true);
+        if is_test_path(&components) || is_too_large {
+            continue;
+        }
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+
+        for (index, line) in contents.lines().enumerate() {
+            if !UI_ELEMENT_MARKERS
+                .iter()
+                .any(|marker| line.contains(marker))
+            {
+                continue;
+            }
+            let lowercase_line = line.to_lowercase();
+            let matched = variants.iter().find(|(_, variants)| {
+                variants
+                    .iter()
+                    .any(|variant| lowercase_line.contains(variant.as_str()))
+            });
+            if let Some((query, _)) = matched {
+                candidates.push(ElementCandidate {
+                    file: workspace_path::relative(&path, workspace_path),
+                    line: index + 1,
+                    content: line.trim().to_string(),
+                    query: query.to_string(),
+                });
+                if candidates.len() == MAX_CANDIDATES {
+                    return candidates;
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// Lowercase spellings of a query to look for: itself, and its name words without the element type
+///
+/// `loginButton` also matches `"Login"`, and `sign_in_field` matches `"Sign In"`.
+fn match_variants(query: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for character in query.chars() {
+        if !character.is_alphanumeric() {
+            words.push(std::mem::take(&mut current));
+        } else if character.is_uppercase() && current.chars().last().is_some_and(char::is_lowercase)
+        {
+            words.push(std::mem::replace(&mut current, character.to_string()));
+        } else {
+            current.push(character);
+        }
+    }
+    words.push(current);
+
+    let name_words: Vec<String> = words
+        .into_iter()
+        .map(|word| word.to_lowercase())
+        .filter(|word| !word.is_empty() && !ELEMENT_TYPE_WORDS.contains(&word.as_str()))
+        .collect();
+
+    let mut variants = vec![query.to_lowercase()];
+    let name = name_words.join(" ");
+    if name.len() >= 3 && !variants.contains(&name) {
+        variants.push(name);
+    }
+    variants
+}
+
+/// Strings the test file looks elements up by, with the attribute each lookup matches
+fn test_queries(test_file_contents: &str) -> Vec<(String, QueryKind)> {
+    let identifiers = IDENTIFIER_QUERY_PATTERN
+        .captures_iter(test_file_contents)
+        .map(|captures| (captures[1].to_string(), QueryKind::Identifier));
+    let predicates = PREDICATE_QUERY_PATTERN
+        .captures_iter(test_file_contents)
+        .map(|captures| {
+            let kind = if captures[1].eq_ignore_ascii_case("label") {
+                QueryKind::Label
+            } else {
+                QueryKind::Identifier
+            };
+            (captures[2].to_string(), kind)
+        });
+    let subscripts = SUBSCRIPT_PATTERN
+        .captures_iter(test_file_contents)
+        .map(|captures| (captures[1].to_string(), QueryKind::Either));
+    identifiers.chain(predicates).chain(subscripts).collect()
+}
+
+/// Whether `value` reads like a test identifier (`loginButton`, `login_button`) rather than text for users
+fn looks_like_identifier(value: &str) -> bool {
+    !value.contains(char::is_whitespace)
+        && (value.contains(['_', '.', '-'])
+            || value
+                .chars()
+                .zip(value.chars().skip(1))
+                .any(|(a, b)| a.is_lowercase() && b.is_uppercase()))
+}
+
+/// Corrections for accessibility labels and identifiers an edit added that the test can't
+/// find the way it queries them
+///
+/// `.matching(identifier:)` only matches identifiers and a `label ==` predicate only labels,
+/// while subscripts like `app.buttons["..."]` match either; a label that reads like an
+/// identifier is flagged anyway, since VoiceOver reads labels to users.
+pub fn attribute_mismatches(
+    test_file_contents: &str,
+    old_content: &str,
+    new_content: &str,
+) -> Vec<String> {
+    let queries = test_queries(test_file_contents);
+    let queried_as = |value: &str, kind: QueryKind| {
+        queries
+            .iter()
+            .any(|(query, query_kind)| query == value && *query_kind == kind)
+    };
+    let old_lines: Vec<&str> = old_content.lines().map(str::trim).collect();
+
+    let mut mismatches = Vec::new();
+    for line in new_content
+        .lines()
+        .filter(|line| !old_lines.contains(&line.trim()))
+    {
+        for captures in ACCESSIBILITY_ATTRIBUTE_PATTERN.captures_iter(line) {
+            let value = &captures[2];
+            let either = queried_as(value, QueryKind::Either);
+            let mismatch = match &captures[1] {
+                "Label" if queried_as(value, QueryKind::Identifier) && !either => format!(
+                    "The edit sets \"{value}\" as an accessibilityLabel, but the test looks it up with `identifier:`, which only matches accessibility identifiers. Use `.accessibilityIdentifier(\"{value}\")` (UIKit: `accessibilityIdentifier = \"{value}\"`) instead; labels are read to VoiceOver users."
+                ),
+                "Label" if either && looks_like_identifier(value) => format!(
+                    "The edit sets \"{value}\" as an accessibilityLabel, which VoiceOver reads to users, but it reads like a test identifier. The test's `[\"{value}\"]` query matches identifiers too, so use `.accessibilityIdentifier(\"{value}\")` (UIKit: `accessibilityIdentifier = \"{value}\"`) instead."
+                ),
+                "Identifier" if queried_as(value, QueryKind::Label) && !either => format!(
+                    "The edit sets \"{value}\" as an accessibilityIdentifier, but the test matches \"{value}\" against the element's label, which identifiers don't change. Set the user-facing label instead, or query the identifier with `.matching(identifier: \"{value}\")`."
+                ),
+                _ => continue,
+            };
+            if !mismatches.contains(&mismatch) {
+                mismatches.push(mismatch);
+            }
+        }
+    }
+    mismatches
+}
+
+/// Prompt section listing the candidates, empty if there are none
+pub fn candidates_section(candidates: &[ElementCandidate]) -> String {
+    if candidates.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from(
+        "\n\n**Possible Elements Missing an Accessibility Identifier:**\n\
+        The test could not find a UI element. These app source lines mention what it queried for; \
+        check whether one of them is the element and needs an accessibility identifier (or a changed label) before exploring further:",
+    );
+    for candidate in candidates {
+        section.push_str(&format!(
+            "\n- {}:{} (for \"{}\"): `{}`",
+            candidate.file, candidate.line, candidate.query, candidate.content
+        ));
+    }
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xctestresultdetailparser::FailureDetail;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_label_identifier_mismatches_are_detected() {
+        let test_file = r#"
+func testLogin() {
+    app.descendants(matching: .any).matching(identifier: "loginButton").firstMatch.tap()
+    app.buttons["signUpButton"].tap()
+    app.buttons["Forgot Password"].tap()
+    let welcome = app.staticTexts.matching(NSPredicate(format: "label == 'Welcome back'"))
+    XCTAssertTrue(welcome.firstMatch.exists)
+}
+"#;
+        let mismatches = |new_content: &str| attribute_mismatches(test_file, "", new_content);
+
+        // A label where the test queries by identifier
+        let label_for_identifier =
+            mismatches("Button(\"Log In\") { login() }\n    .accessibilityLabel(\"loginButton\")");
+        assert_eq!(label_for_identifier.len(), 1);
+        assert!(label_for_identifier[0].contains("looks it up with `identifier:`"));
+
+        // An identifier where the test matches the label, in UIKit
+        let identifier_for_label =
+            mismatches("welcomeLabel.accessibilityIdentifier = \"Welcome back\"");
+        assert_eq!(identifier_for_label.len(), 1);
+        assert!(identifier_for_label[0].contains("against the element's label"));
+
+        // An identifier-like label for a subscript query
+        let identifier_like_label = mismatches(".accessibilityLabel(Text(\"signUpButton\"))");
+        assert_eq!(identifier_like_label.len(), 1);
+        assert!(identifier_like_label[0].contains("reads like a test identifier"));
+
+        // Matching attributes, user-facing labels and unchanged lines are fine
+        assert!(mismatches(".accessibilityIdentifier(\"loginButton\")").is_empty());
+        assert!(mismatches(".accessibilityLabel(\"Forgot Password\")").is_empty());
+        assert!(mismatches(".accessibilityIdentifier(\"signUpButton\")").is_empty());
+        let unchanged = ".accessibilityLabel(\"loginButton\")";
+        assert!(attribute_mismatches(test_file, unchanged, unchanged).is_empty());
+    }
+
+    #[test]
+    fn test_finds_app_element_for_failing_query() {
+        let workspace = std::env::temp_dir().join(format!("accessibility-{}", Uuid::new_v4()));
+        let files = [
+            (
+                "App/LoginView.swift",
+                "struct LoginView: View {\n    var body: some View {\n        Button(\"Login\") { login() }\n    }\n}\n",
+            ),
+            ("App/Session.swift", "// Login state\nlet login = true\n"),
+            (
+                "AppUITests/LoginTests.swift",
+                "func testLogin() {\n    app.buttons[\"loginButton\"].tap()\n}\n",
+            ),
+            ("Pods/Kit/Button.swift", "Button(\"Login\")\n"),
+            ("Generated/Button.swift", "Button(\"Login\")\n"),
+        ];
+        for (file, contents) in files {
+            let path = workspace.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let detail = XCTestResultDetail::failing(vec![FailureDetail {
+            message: "Failed to tap \"loginButton\" Button: No matches found".to_string(),
+            file: Some("LoginTests.swift".to_string()),
+            line: Some(2),
+        }]);
+
+        let test_file = fs::read_to_string(workspace.join(files[2].0)).unwrap();
+        let queries = failing_queries(&detail, &test_file);
+        let candidates =
+            find_candidates(&workspace, &["Generated".to_string()], &queries, &["swift"]);
+
+        assert_eq!(queries, vec!["loginButton"]);
+        // Neither the non-UI line, the test file nor the vendored or ignored copy is a candidate
+        assert_eq!(
+            candidates,
+            vec![ElementCandidate {
+                file: "App/LoginView.swift".to_string(),
+                line: 3,
+                content: "Button(\"Login\") { login() }".to_string(),
+                query: "loginButton".to_string(),
+            }]
+        );
+        assert!(candidates_section(&candidates).contains("App/LoginView.swift:3"));
+
+        // Clean up
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+}
@@ -1,22 +1,70 @@
+use super::accessibility;
+use super::editor::Editor;
+use super::exploration::{self, ExplorationTracker, ExplorationVerdict};
+use super::file_snapshots::FileSnapshots;
+use super::fix_target::FixTarget;
+use super::model_ladder::LadderPolicy;
+use super::oscillation::OscillationDetector;
+use super::prompt_dump;
 use super::prompts;
-use crate::llm::{LLMProvider, ProviderConfig, ProviderFactory};
+use super::protected_paths::PROTECTED_PATHS_FILE;
+use super::repl::{self, ReplInput, ReplReply};
+use super::retention;
+use super::test_file_excerpt;
+use super::tool_result_limit::{self, DEFAULT_MAX_TOOL_RESULT_BYTES};
+use super::transcript::{self, ConversationTurn};
+use super::virtual_files::{self, Checkpoint};
+use super::{PipelineEvent, PipelineOptions, PipelineOutcome};
+use crate::file_locator::{FileLocator, FileLocatorError, IndexFileLocator};
+use crate::git_worktree;
+use crate::junitparser;
+use crate::llm::provider_trait::collect_stream_into;
+use crate::llm::{
+    LLMError, LLMProvider, LLMRequest, LLMResponse, ProviderCapabilities, ProviderConfig,
+    ProviderFactory, StopReason, TokenUsage, ToolCall,
+};
 use crate::rate_limiter::RateLimiter;
+use crate::report::{FileEdit, Oscillation, Preflight, TestOutcome, TestReport};
+use crate::snapshot_image::{self, EncodedImage, SnapshotImageEncoder, SnapshotImageError};
+use crate::source_language::SourceLanguage;
+use crate::tools::directory_inspector_tool::is_test_path;
+use crate::tools::scheme_list::{self, SchemeError};
 use crate::tools::{
-    CodeEditorInput, CodeEditorTool, DirectoryInspectorInput, DirectoryInspectorTool,
-    TestRunnerInput, TestRunnerTool,
+    CodeEditorInput, CodeEditorTool, CustomTool, DirectoryInspectorInput, DirectoryInspectorTool,
+    DiskFiles, FileStore, FindRanking, TestRunnerInput, TestRunnerTool, input_schema,
 };
+use crate::workspace_path;
 use crate::xc_test_result_attachment_handler::{
     AttachmentHandlerError, XCTestResultAttachmentHandler,
 };
-use crate::xc_workspace_file_locator::{FileLocatorError, XCWorkspaceFileLocator};
-use crate::xctestresultdetailparser::XCTestResultDetail;
+use crate::xc_workspace_file_locator::XCWorkspaceFileLocator;
+use crate::xctestresultdetailparser::{self, FailureDetail, FailureKind, XCTestResultDetail};
 use anthropic_sdk::{ContentBlock, ContentBlockParam, Tool};
-use base64::Engine;
+use futures::StreamExt;
+use std::collections::HashMap;
 use std::fs;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
 use uuid::Uuid;
 
+/// Model turns per test before giving up, unless configured otherwise
+const DEFAULT_MAX_ITERATIONS: u32 = 20;
+
+/// Sampling temperature of the fix conversation, unless configured otherwise
+const DEFAULT_TEMPERATURE: f32 = 0.7;
+
+/// Tools whose calls don't change the workspace, so a repeat can reuse the result
+const READ_ONLY_TOOLS: &[&str] = &["directory_inspector"];
+
+/// Output tokens requested from models whose limits aren't known
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Told to models that can't read images in place of the snapshot
+const SNAPSHOT_OMITTED_NOTE: &str = "Note: a simulator snapshot was captured, but it was left out because this model cannot read images. Rely on the test code and failure details instead.";
+
 #[derive(Debug, thiserror::Error)]
 pub enum PipelineError {
     #[error("Failed to create temporary directory: {0}")]
@@ -30,17 +78,62 @@ pub enum PipelineError {
 
     #[error("Anthropic API error: {0}")]
     AnthropicApiError(String),
+
+    /// The provider can't be used as configured, e.g. a missing or rejected API key
+    #[error("Provider configuration error: {0}")]
+    ProviderConfiguration(LLMError),
+
+    #[error("Test infrastructure error: {0}")]
+    TestInfrastructureError(String),
+
+    #[error("Workspace does not build: {0}")]
+    BuildFailed(String),
+
+    #[error("{0}")]
+    Scheme(#[from] SchemeError),
+}
+
+/// What the first request of a run would send, computed without calling the provider
+#[derive(Debug, Clone)]
+pub struct PromptEstimate {
+    pub prompt: String,
+    /// Snapshot that would be attached, if any
+    pub snapshot_path: Option<PathBuf>,
+    /// Prompt and tool definitions, per the provider's estimator
+    pub prompt_tokens: u32,
+    /// Rough size of the attached snapshot
+    pub image_tokens: u32,
+}
+
+impl PromptEstimate {
+    pub fn input_tokens(&self) -> u32 {
+        self.prompt_tokens + self.image_tokens
+    }
 }
 
 pub struct AutofixPipeline {
     xcresult_path: PathBuf,
     workspace_path: PathBuf,
     temp_dir: PathBuf,
-    knightrider_mode: bool,
+    fix_target: FixTarget,
     verbose: bool,
     rate_limiter: Arc<RateLimiter>,
     provider: Box<dyn LLMProvider>,
     provider_config: ProviderConfig,
+    /// Finds the file of the failing test, `XCWorkspaceFileLocator` unless replaced
+    file_locator: Box<dyn FileLocator>,
+    options: PipelineOptions,
+    report: Mutex<TestReport>,
+    /// Whether editing a file with uncommitted changes was allowed, by file path
+    dirty_file_decisions: Mutex<HashMap<String, bool>>,
+    /// Originals of edited files in `temp_dir/snapshots`, taken with `revert_unfixed`
+    snapshots: FileSnapshots,
+    /// Scheme the preflight picked, `--scheme` or the closest match to the identifier's
+    scheme: OnceLock<String>,
+    /// Providers for the models after the first of `options.model_ladder`, which `provider` serves
+    ladder: OnceLock<Vec<Box<dyn LLMProvider>>>,
+    /// Position of the current model on the ladder
+    rung: AtomicUsize,
 }
 
 impl AutofixPipeline {
@@ -48,9 +141,32 @@ impl AutofixPipeline {
     pub fn new<P: AsRef<Path>>(
         xcresult_path: P,
         workspace_path: P,
-        knightrider_mode: bool,
+        fix_target: FixTarget,
+        verbose: bool,
+        provider_config: ProviderConfig,
+    ) -> Result<Self, PipelineError> {
+        // Create provider from configuration
+        let provider = ProviderFactory::create(provider_config.clone())
+            .map_err(PipelineError::ProviderConfiguration)?;
+
+        Self::with_provider(
+            xcresult_path,
+            workspace_path,
+            fix_target,
+            verbose,
+            provider_config,
+            provider,
+        )
+    }
+
+    /// Create a new AutofixPipeline that talks to an already constructed provider
+    pub fn with_provider<P: AsRef<Path>>(
+        xcresult_path: P,
+        workspace_path: P,
+        fix_target: FixTarget,
         verbose: bool,
         provider_config: ProviderConfig,
+        provider: Box<dyn LLMProvider>,
     ) -> Result<Self, PipelineError> {
         // Create .autofix/tmp directory in current directory
         let base_dir = PathBuf::from(".autofix/tmp");
@@ -68,11 +184,6 @@ impl AutofixPipeline {
             );
         }
 
-        // Create provider from configuration
-        let provider = ProviderFactory::create(provider_config.clone()).map_err(|e| {
-            PipelineError::AnthropicApiError(format!("Failed to create provider: {}", e))
-        })?;
-
         // Create rate limiter for the configured provider
         let rate_limiter = Arc::new(RateLimiter::from_env(
             provider_config.provider_type,
@@ -82,18 +193,476 @@ impl AutofixPipeline {
         Ok(Self {
             xcresult_path: xcresult_path.as_ref().to_path_buf(),
             workspace_path: workspace_path.as_ref().to_path_buf(),
+            file_locator: Box::new(XCWorkspaceFileLocator::new(workspace_path.as_ref())),
+            snapshots: FileSnapshots::new(temp_dir.join("snapshots")),
             temp_dir,
-            knightrider_mode,
+            fix_target,
             verbose,
             rate_limiter,
             provider,
             provider_config,
+            options: PipelineOptions::default(),
+            report: Mutex::new(TestReport::default()),
+            dirty_file_decisions: Mutex::new(HashMap::new()),
+            scheme: OnceLock::new(),
+            ladder: OnceLock::new(),
+            rung: AtomicUsize::new(0),
         })
     }
 
+    /// Apply optional run settings
+    pub fn with_options(mut self, options: PipelineOptions) -> Self {
+        if let Some(index_path) = &options.test_file_index {
+            let locator = IndexFileLocator::new(index_path, &self.workspace_path);
+            self = self.with_file_locator(Box::new(locator));
+        } else if !options.ignored_dirs.is_empty() || options.language.is_some() {
+            let locator = XCWorkspaceFileLocator::new(&self.workspace_path)
+                .with_ignored_dirs(options.ignored_dirs.clone())
+                .with_language(options.language);
+            self = self.with_file_locator(Box::new(locator));
+        }
+        self.options = options;
+        self
+    }
+
+    /// Find test files with `file_locator`, e.g. for another platform or project layout
+    pub fn with_file_locator(mut self, file_locator: Box<dyn FileLocator>) -> Self {
+        self.file_locator = file_locator;
+        self
+    }
+
+    /// Provider of the current model, which moves along `--escalate`/`--deescalate`'s ladder
+    fn provider(&self) -> &dyn LLMProvider {
+        match (self.rung.load(Ordering::Relaxed), self.ladder.get()) {
+            (rung @ 1.., Some(ladder)) => ladder[rung - 1].as_ref(),
+            _ => self.provider.as_ref(),
+        }
+    }
+
+    /// Name of the current model
+    fn model(&self) -> &str {
+        match &self.options.model_ladder {
+            Some(ladder) => &ladder.models[self.rung.load(Ordering::Relaxed)],
+            None => &self.provider_config.model,
+        }
+    }
+
+    /// Output tokens to request: what the current model can produce next to the thinking
+    /// budget, so long edits aren't cut off
+    fn max_tokens(&self) -> u32 {
+        match self
+            .provider_config
+            .provider_type
+            .model_limits(self.model())
+        {
+            Some(limits) => limits
+                .max_output_tokens
+                .saturating_sub(self.provider_config.thinking_budget.unwrap_or(0)),
+            None => DEFAULT_MAX_TOKENS,
+        }
+    }
+
+    /// Create providers for the models of the ladder, the first one being the configured
+    /// model, and check that each of them is ready
+    async fn prepare_ladder(&self) -> Result<(), PipelineError> {
+        let Some(ladder) = &self.options.model_ladder else {
+            return Ok(());
+        };
+        if self.ladder.get().is_some() {
+            return Ok(());
+        }
+        let providers = ladder.models[1..]
+            .iter()
+            .map(|model| {
+                ProviderFactory::create(ProviderConfig {
+                    model: model.clone(),
+                    ..self.provider_config.clone()
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(PipelineError::ProviderConfiguration)?;
+        // Better to fail now than when a stalled run switches to the model
+        for provider in &providers {
+            provider
+                .check_ready()
+                .await
+                .map_err(PipelineError::ProviderConfiguration)?;
+        }
+        let _ = self.ladder.set(providers);
+        Ok(())
+    }
+
+    /// Switch to the next model on the ladder because of `reason`, returning false when
+    /// there is none
+    fn advance_model(&self, reason: &str) -> bool {
+        let (Some(ladder), Some(providers)) = (&self.options.model_ladder, self.ladder.get())
+        else {
+            return false;
+        };
+        let rung = self.rung.load(Ordering::Relaxed);
+        if rung >= providers.len() {
+            return false;
+        }
+        let next = &ladder.models[rung + 1];
+        println!(
+            "\n🪜 {} with {} - {} to {}",
+            reason,
+            self.model(),
+            match ladder.policy {
+                LadderPolicy::Escalate => "escalating",
+                LadderPolicy::Deescalate => "downgrading",
+            },
+            next
+        );
+        self.rung.store(rung + 1, Ordering::Relaxed);
+        true
+    }
+
+    /// Structured report of the last `run()`
+    pub fn report(&self) -> TestReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    /// Update the report of the current run
+    fn record(&self, update: impl FnOnce(&mut TestReport)) {
+        update(&mut self.report.lock().unwrap());
+    }
+
+    /// Add a response's token usage to the report and the live counter
+    fn record_usage(&self, usage: &TokenUsage) {
+        self.record(|report| {
+            report.record_usage(usage);
+            if self.options.model_ladder.is_some() {
+                report.record_model_usage(self.model(), usage);
+            }
+        });
+        self.options.token_counter.record_usage(usage);
+    }
+
+    /// Record the usage of a response cut off while streaming: what the provider reported
+    /// until then, with at least the output tokens counted from the streamed text
+    fn record_partial_usage(&self, usage: &TokenUsage) {
+        let streamed = self.options.token_counter.streamed_tokens();
+        self.record_usage(&TokenUsage::new(
+            usage.input_tokens,
+            usage.output_tokens.max(streamed),
+        ));
+    }
+
+    /// Record the time elapsed since `started` under `name`
+    fn record_timing(&self, name: &str, started: Instant) {
+        let elapsed = started.elapsed();
+        self.record(|report| report.timings.record(name, elapsed));
+    }
+
+    /// Count a call of the tool `name` that started at `started` and returned `result`
+    fn record_tool_call(&self, name: &str, result: &serde_json::Value, started: Instant) {
+        let elapsed = started.elapsed();
+        let succeeded = !tool_call_failed(name, result);
+        self.record(|report| report.tool_metrics.record(name, succeeded, elapsed));
+    }
+
+    /// Whether the named tool was disabled for this run
+    fn is_tool_disabled(&self, name: &str) -> bool {
+        self.options.disabled_tools.iter().any(|tool| tool == name)
+    }
+
+    /// Whether `file_path` matches one of the excluded path patterns
+    fn is_path_excluded(&self, file_path: &str) -> bool {
+        self.matches_any(&self.options.excluded_paths, file_path)
+    }
+
+    /// Whether `file_path` matches one of the protected path patterns
+    fn is_path_protected(&self, file_path: &str) -> bool {
+        self.matches_any(&self.options.protected_paths, file_path)
+    }
+
+    /// Whether `file_path` matches one of `patterns`, which are relative to the workspace
+    fn matches_any(&self, patterns: &[glob::Pattern], file_path: &str) -> bool {
+        // `./Generated/API.swift` or `Sources/../Generated/API.swift` is still `Generated/**`
+        let relative = workspace_path::relative(Path::new(file_path), &self.workspace_path);
+        patterns
+            .iter()
+            .any(|pattern| pattern.matches_path(Path::new(&relative)))
+    }
+
+    /// Check whether the model may edit `file_path` given the user's uncommitted changes
+    ///
+    /// Each file is checked once per test, before the first edit touches it, against
+    /// the changes the workspace had before the run's first edit. In an interactive
+    /// terminal the user is asked, and cancelling the run keeps the changes; with
+    /// `--yes` or without a terminal the edit proceeds and the file is noted in the report.
+    async fn allow_dirty_edit(&self, file_path: &str) -> bool {
+        // A patch leaves the working tree alone
+        if self.options.patch_only.is_some() {
+            return true;
+        }
+        if let Some(allowed) = self.dirty_file_decisions.lock().unwrap().get(file_path) {
+            return *allowed;
+        }
+
+        let allowed = if self.had_uncommitted_changes(file_path) {
+            println!(
+                "   ⚠️  {} has uncommitted changes that this edit may overwrite",
+                file_path
+            );
+            let relative = workspace_path::relative(Path::new(file_path), &self.workspace_path);
+            self.record(|report| report.dirty_files.push(relative));
+
+            if self.options.assume_yes || !std::io::stdin().is_terminal() {
+                true
+            } else {
+                print!("   Edit it anyway? [y/N] ");
+                let _ = std::io::stdout().flush();
+                // Cancelled, or the end of input: keep the changes
+                tokio::select! {
+                    answer = repl::read_stdin_line() => {
+                        answer.is_some_and(|answer| matches!(answer.trim(), "y" | "Y" | "yes"))
+                    }
+                    _ = self.options.cancellation.cancelled() => false,
+                }
+            }
+        } else {
+            true
+        };
+
+        // Not held while the user answers, so other tool calls aren't blocked
+        self.dirty_file_decisions
+            .lock()
+            .unwrap()
+            .insert(file_path.to_string(), allowed);
+        allowed
+    }
+
+    /// Whether `file_path` had uncommitted changes before the run's first edit
+    fn had_uncommitted_changes(&self, file_path: &str) -> bool {
+        let dirty_files = self
+            .options
+            .dirty_files
+            .get_or_init(|| git_worktree::uncommitted_files(&self.workspace_path));
+        self.workspace_path
+            .join(file_path)
+            .canonicalize()
+            .is_ok_and(|path| dirty_files.contains(&path))
+    }
+
+    /// Snapshot `file_path` before its first edit when `revert_unfixed` is set
+    ///
+    /// Returns false if the copy failed, in which case the edit must not happen.
+    fn snapshot_before_edit(&self, file_path: &str) -> bool {
+        if !self.options.revert_unfixed || self.options.patch_only.is_some() {
+            return true;
+        }
+        match self.snapshots.save(&self.workspace_path, file_path) {
+            Ok(()) => true,
+            Err(e) => {
+                println!("   🚫 Not editing {} without a snapshot: {}", file_path, e);
+                false
+            }
+        }
+    }
+
+    /// Where code edits go: the working tree, or memory with `patch_only`
+    fn file_store(&self) -> &dyn FileStore {
+        if self.options.patch_only.is_some() {
+            self.options.virtual_files.as_ref()
+        } else {
+            &DiskFiles
+        }
+    }
+
+    /// Directory the directory inspector and test runner work in
+    ///
+    /// With `patch_only` this is a copy of the workspace in the run directory,
+    /// made on first use, with the edits so far written into it.
+    fn tool_workspace(&self) -> Result<PathBuf, PipelineError> {
+        if self.options.patch_only.is_none() {
+            return Ok(self.workspace_path.clone());
+        }
+        let copy = std::path::absolute(self.temp_dir.join("workspace"))?;
+        if !copy.exists()
+            && let Err(e) = virtual_files::copy_workspace(&self.workspace_path, &copy)
+        {
+            let _ = fs::remove_dir_all(&copy);
+            return Err(e.into());
+        }
+        self.options
+            .virtual_files
+            .write_into(&self.workspace_path, &copy)?;
+        Ok(copy)
+    }
+
+    /// Write the edits of a fixed test to the `patch_only` file, or drop those of an unfixed one
+    fn finish_patch(&self, patch_path: &Path, checkpoint: Checkpoint) {
+        if self.report().test_passed != Some(true) {
+            self.options.virtual_files.restore(checkpoint);
+            return;
+        }
+        match self
+            .options
+            .virtual_files
+            .write_patch(&self.workspace_path, patch_path)
+        {
+            Ok(files) => println!(
+                "📝 Wrote the fix for {} file(s) to {}, the working tree is unchanged",
+                files,
+                patch_path.display()
+            ),
+            Err(e) => println!("⚠️  Failed to write patch {}: {}", patch_path.display(), e),
+        }
+    }
+
+    /// Replace image blocks with a note if the model can't read images
+    fn drop_unsupported_images(
+        content: Vec<ContentBlockParam>,
+        capabilities: ProviderCapabilities,
+    ) -> Vec<ContentBlockParam> {
+        if capabilities.images {
+            return content;
+        }
+
+        let block_count = content.len();
+        let mut content: Vec<_> = content
+            .into_iter()
+            .filter(|block| !matches!(block, ContentBlockParam::Image { .. }))
+            .collect();
+        if content.len() < block_count {
+            println!("⚠️  Model does not support images - leaving out the simulator snapshot");
+            content.push(ContentBlockParam::text(SNAPSHOT_OMITTED_NOTE));
+        }
+        content
+    }
+
+    /// Send an event to the configured event sink, if any
+    fn emit(&self, event: PipelineEvent) {
+        if let Some(sink) = &self.options.event_sink {
+            // A closed receiver just means nobody is listening anymore
+            let _ = sink.send(event);
+        }
+    }
+
+    /// Test runner configured with the run's destination and xcodebuild arguments
+    fn test_runner(&self) -> TestRunnerTool {
+        let test_runner = TestRunnerTool::new()
+            .with_scheme(self.scheme.get().or(self.options.scheme.as_ref()).cloned())
+            .with_os_version(self.options.os_version.clone())
+            .with_destinations(self.options.destinations.clone())
+            .with_extra_args(self.options.xcodebuild_args.clone())
+            .with_isolation(self.options.isolate)
+            .with_allowed_tests(self.options.allowed_tests.clone());
+        let test_runner = match self.options.simulator_lock_timeout {
+            Some(lock_timeout) => test_runner.with_lock_timeout(lock_timeout),
+            None => test_runner,
+        };
+        match self.options.test_timeout {
+            Some(timeout) => test_runner.with_timeout(timeout),
+            None => test_runner,
+        }
+    }
+
+    /// Check that a scheme exists to run the test with, picking the closest one if the
+    /// identifier's scheme doesn't, so a wrong name fails here instead of in every test run
+    fn scheme_step(&self, test_identifier_url: &str) -> Result<(), PipelineError> {
+        let available = self.options.available_schemes.get_or_init(|| {
+            match scheme_list::list_schemes(&self.workspace_path) {
+                Ok(schemes) => Some(schemes),
+                Err(e) => {
+                    println!("⚠️  Scheme check skipped: {}\n", e);
+                    None
+                }
+            }
+        });
+        let Some(available) = available else {
+            return Ok(());
+        };
+
+        let scheme = scheme_list::resolve_scheme(
+            available,
+            self.options.scheme.as_deref(),
+            test_identifier_url,
+        )
+        .inspect_err(|e| println!("\n🚫 {}", e))?;
+        if self.verbose {
+            println!("  [DEBUG] Scheme: {}", scheme);
+        }
+        let _ = self.scheme.set(scheme);
+        Ok(())
+    }
+
+    /// Check that the workspace builds, so a broken build is reported instead of handed to the model
+    fn preflight_step(&self, test_identifier_url: &str) -> Result<(), PipelineError> {
+        println!("Step 0: Preflight build...");
+        self.emit(PipelineEvent::StepStarted {
+            step: 0,
+            name: "Preflight build".to_string(),
+        });
+
+        let test_runner = self.test_runner();
+        let scheme = test_runner
+            .scheme_for(test_identifier_url)
+            .unwrap_or_default();
+        // Held during the build, so other tests of the run wait for it instead of building too
+        let mut builds = self.options.preflight_builds.lock().unwrap();
+        let build = match builds.get(&scheme) {
+            Some(build) => {
+                if build.is_some() {
+                    println!("  Reusing the preflight build of scheme {}", scheme);
+                }
+                build.clone()
+            }
+            None => {
+                let build = test_runner
+                    .build_for_testing(test_identifier_url, &self.workspace_path)
+                    .inspect_err(|e| println!("⚠️  Preflight build skipped: {}\n", e))
+                    .ok();
+                builds.insert(scheme, build.clone());
+                build
+            }
+        };
+        drop(builds);
+        let Some(build) = build else {
+            return Ok(());
+        };
+        self.record(|report| {
+            report.preflight = Some(Preflight {
+                passed: build.success,
+                errors: build.errors.clone(),
+            })
+        });
+
+        if build.success {
+            println!("✓ Workspace builds\n");
+            return Ok(());
+        }
+        if let Some(infrastructure_error) = build.infrastructure_error {
+            println!("\n🚫 {}", infrastructure_error);
+            return Err(PipelineError::TestInfrastructureError(
+                infrastructure_error.to_string(),
+            ));
+        }
+
+        println!(
+            "❌ Workspace does not build (exit code: {})",
+            build.exit_code
+        );
+        for error in &build.errors {
+            println!("   • {}", error);
+        }
+        println!("   Fix the build first, or pass --skip-preflight to let the model try\n");
+        Err(PipelineError::BuildFailed(match build.errors.as_slice() {
+            [] => format!("xcodebuild exited with code {}", build.exit_code),
+            [error] => error.clone(),
+            [error, rest @ ..] => format!("{} (and {} more)", error, rest.len()),
+        }))
+    }
+
     /// Step 1: Fetch attachments from the XCResult bundle
     fn fetch_attachments_step(&self, test_identifier_url: &str) -> Result<(), PipelineError> {
         println!("Step 1: Fetching attachments...");
+        self.emit(PipelineEvent::StepStarted {
+            step: 1,
+            name: "Fetching attachments".to_string(),
+        });
 
         if self.verbose {
             println!("  [DEBUG] XCResult path: {}", self.xcresult_path.display());
@@ -101,6 +670,12 @@ impl AutofixPipeline {
             println!("  [DEBUG] Test ID: {}", test_identifier_url);
         }
 
+        if junitparser::is_junit(&self.xcresult_path) {
+            println!("⚠ JUnit results carry no attachments; continuing without a snapshot");
+            println!();
+            return Ok(());
+        }
+
         let attachment_handler = XCTestResultAttachmentHandler::new();
 
         match attachment_handler.fetch_attachments(
@@ -108,11 +683,27 @@ impl AutofixPipeline {
             &self.xcresult_path,
             &self.temp_dir,
         ) {
-            Ok(attachments_dir) => {
-                println!("✓ Attachments fetched to: {}", attachments_dir.display());
+            Ok(attachments) => {
+                println!(
+                    "✓ Attachments fetched to: {}",
+                    attachments.directory.display()
+                );
 
-                // List the attachments
-                if let Ok(entries) = fs::read_dir(&attachments_dir) {
+                // List the attachments, by the names the manifest gives them if possible
+                for attachment in &attachments.attachments {
+                    println!(
+                        "  - {}{}",
+                        attachment.name(),
+                        if attachment.is_failure_screenshot() {
+                            " (failure)"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+                if attachments.attachments.is_empty()
+                    && let Ok(entries) = fs::read_dir(&attachments.directory)
+                {
                     for entry in entries.flatten() {
                         if entry.path().is_file() {
                             println!("  - {}", entry.file_name().to_string_lossy());
@@ -132,6 +723,10 @@ impl AutofixPipeline {
     /// Step 2: Locate the test file in the workspace
     fn locate_test_file_step(&self, test_identifier_url: &str) -> Result<PathBuf, PipelineError> {
         println!("Step 2: Locating test file...");
+        self.emit(PipelineEvent::StepStarted {
+            step: 2,
+            name: "Locating test file".to_string(),
+        });
 
         if self.verbose {
             println!(
@@ -141,11 +736,12 @@ impl AutofixPipeline {
             println!("  [DEBUG] Test identifier URL: {}", test_identifier_url);
         }
 
-        let file_locator = XCWorkspaceFileLocator::new(&self.workspace_path);
-
-        match file_locator.locate_file(test_identifier_url) {
+        match self.file_locator.locate_file(test_identifier_url) {
             Ok(file_path) => {
-                println!("✓ Test file located at: {}", file_path.display());
+                println!(
+                    "✓ Test file located at: {}",
+                    workspace_path::display(&file_path, &self.workspace_path)
+                );
                 println!(
                     "  File URL: file://{}",
                     file_path
@@ -164,14 +760,15 @@ impl AutofixPipeline {
         }
     }
 
-    /// Helper function to find the latest simulator snapshot image
-    fn find_latest_snapshot(&self) -> Option<PathBuf> {
-        let attachments_dir = self.temp_dir.join("attachments");
+    /// Helper function to find the latest simulator snapshot image for a test
+    fn find_latest_snapshot(&self, test_id: &str) -> Option<PathBuf> {
+        let attachments_dir =
+            XCTestResultAttachmentHandler::attachments_dir_for(&self.temp_dir, test_id);
         if !attachments_dir.exists() {
             return None;
         }
 
-        // Look for image files (png, jpg, jpeg)
+        // Look for image files; the encoder converts anything providers don't accept
         let mut image_files: Vec<_> = fs::read_dir(&attachments_dir)
             .ok()?
             .filter_map(|entry| entry.ok())
@@ -181,7 +778,21 @@ impl AutofixPipeline {
                     && path
                         .extension()
                         .and_then(|ext| ext.to_str())
-                        .map(|ext| matches!(ext.to_lowercase().as_str(), "png" | "jpg" | "jpeg"))
+                        .map(|ext| {
+                            matches!(
+                                ext.to_lowercase().as_str(),
+                                "png"
+                                    | "jpg"
+                                    | "jpeg"
+                                    | "gif"
+                                    | "webp"
+                                    | "bmp"
+                                    | "heic"
+                                    | "heif"
+                                    | "tiff"
+                                    | "tif"
+                            )
+                        })
                         .unwrap_or(false)
             })
             .collect();
@@ -198,25 +809,65 @@ impl AutofixPipeline {
         image_files.first().map(|entry| entry.path())
     }
 
+    /// Text of the latest `.crash`/`.ips` crash log exported for a test, cut to a size
+    /// that leaves room for the rest of the prompt
+    fn find_crash_log(&self, test_id: &str) -> Option<String> {
+        const MAX_CRASH_LOG_BYTES: usize = 16 * 1024;
+
+        let attachments_dir =
+            XCTestResultAttachmentHandler::attachments_dir_for(&self.temp_dir, test_id);
+        let mut crash_logs: Vec<_> = fs::read_dir(&attachments_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry.path().is_file()
+                    && xctestresultdetailparser::is_crash_log(&entry.file_name().to_string_lossy())
+            })
+            .collect();
+        crash_logs.sort_by_key(|entry| {
+            entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .map(std::cmp::Reverse)
+        });
+
+        let crash_log = fs::read_to_string(crash_logs.first()?.path()).ok()?;
+        if crash_log.len() > MAX_CRASH_LOG_BYTES {
+            return Some(tool_result_limit::truncate(
+                &crash_log,
+                MAX_CRASH_LOG_BYTES,
+                None,
+            ));
+        }
+        Some(crash_log)
+    }
+
+    /// Whether the test crashed, judged by its failure messages and its crash log from
+    /// `find_crash_log`
+    fn failure_kind(detail: &XCTestResultDetail, crash_log: Option<&str>) -> FailureKind {
+        if crash_log.is_some() {
+            return FailureKind::Crash;
+        }
+        detail.failure_kind()
+    }
+
     /// Step 3: Perform autofix using Claude AI
     async fn autofix_step(
         &self,
         detail: &XCTestResultDetail,
         test_file_path: &Path,
-    ) -> Result<(), PipelineError> {
+    ) -> Result<PipelineOutcome, PipelineError> {
         println!("Step 3: Running autofix with LLM provider...");
+        self.emit(PipelineEvent::StepStarted {
+            step: 3,
+            name: "Running autofix".to_string(),
+        });
 
         if self.verbose {
-            println!(
-                "  [DEBUG] Mode: {}",
-                if self.knightrider_mode {
-                    "Knight Rider"
-                } else {
-                    "Standard"
-                }
-            );
-            println!("  [DEBUG] Provider: {:?}", self.provider.provider_type());
-            println!("  [DEBUG] Model: {}", self.provider_config.model);
+            println!("  [DEBUG] Fix target: {}", self.fix_target.name());
+            println!("  [DEBUG] Provider: {:?}", self.provider().provider_type());
+            println!("  [DEBUG] Model: {}", self.model());
             println!("  [DEBUG] Test file path: {}", test_file_path.display());
             println!("  [DEBUG] Test name: {}", detail.test_name);
         }
@@ -232,25 +883,55 @@ impl AutofixPipeline {
         }
 
         // Find the latest simulator snapshot
-        let snapshot_path = self.find_latest_snapshot();
+        let snapshot_path = self.find_latest_snapshot(&detail.test_identifier_url);
         let has_snapshot = snapshot_path.is_some();
 
+        let fix_target = self.fix_target.resolve(detail);
+        if self.fix_target == FixTarget::Auto {
+            println!(
+                "🧭 Fix target from the failure messages: {}",
+                fix_target.name()
+            );
+        }
+        self.record(|report| report.fix_target = Some(fix_target));
+        let crash_log = self.find_crash_log(&detail.test_identifier_url);
+        let failure_kind = Self::failure_kind(detail, crash_log.as_deref());
+        match failure_kind {
+            FailureKind::Crash => {
+                println!("💥 The test crashed; asking the model to find the crashing call site")
+            }
+            FailureKind::PerformanceRegression => {
+                println!(
+                    "🐢 The test exceeded its performance baseline; asking the model to find the slowdown"
+                );
+                for metric in &detail.performance_metrics {
+                    println!("   • {}", metric.summary());
+                }
+            }
+            FailureKind::Assertion => {}
+        }
+        self.record(|report| report.failure_kind = Some(failure_kind));
+
         // Generate the prompt based on mode
-        let prompt = if self.knightrider_mode {
-            prompts::generate_knightrider_prompt(
-                detail,
-                &test_file_contents,
-                &self.workspace_path,
-                has_snapshot,
-            )
-        } else {
-            prompts::generate_standard_prompt(
-                detail,
-                &test_file_contents,
-                &self.workspace_path,
-                has_snapshot,
-            )
-        };
+        let capabilities = self.provider().capabilities();
+        if !capabilities.tools {
+            println!(
+                "⚠️  {} does not support tools - falling back to a single analysis without edits",
+                self.model()
+            );
+        }
+        let language = self.source_language(test_file_path);
+        if self.verbose {
+            println!("  [DEBUG] Test language: {}", language.name());
+        }
+        let prompt = self.build_prompt(
+            detail,
+            fix_target,
+            &test_file_contents,
+            language,
+            has_snapshot,
+            crash_log.as_deref(),
+        );
 
         // Print the prompt
         println!("Sending prompt to Claude:");
@@ -265,10 +946,12 @@ impl AutofixPipeline {
         // Add the image if available
         if let Some(img_path) = snapshot_path {
             println!("Adding simulator snapshot: {}", img_path.display());
-            if let Ok(image_data) = fs::read(&img_path) {
-                // Convert image to base64
-                let base64_image = base64::engine::general_purpose::STANDARD.encode(&image_data);
-                content_blocks.push(ContentBlockParam::image_base64("image/jpeg", &base64_image));
+            match self.encode_snapshot(&img_path) {
+                Ok(image) => content_blocks.push(ContentBlockParam::image_base64(
+                    image.media_type,
+                    &image.data,
+                )),
+                Err(e) => println!("⚠️  Skipping snapshot: {}", e),
             }
         }
 
@@ -277,143 +960,364 @@ impl AutofixPipeline {
             .await
     }
 
-    /// Convert anthropic ContentBlock to provider-agnostic ToolCall
-    fn content_block_to_tool_call(block: &ContentBlock) -> Option<crate::llm::ToolCall> {
-        match block {
-            ContentBlock::ToolUse { id, name, input } => Some(crate::llm::ToolCall {
-                id: id.clone(),
-                name: name.clone(),
-                input: input.clone(),
-            }),
-            _ => None,
+    /// Encode a snapshot for the provider, downscaled to `--max-image-dimension`
+    fn encode_snapshot(&self, path: &Path) -> Result<EncodedImage, SnapshotImageError> {
+        let mut encoder = SnapshotImageEncoder::new();
+        if let Some(max_dimension) = self.options.max_image_dimension {
+            encoder = encoder.with_max_dimension(max_dimension);
+        }
+        let image = encoder.encode(path)?;
+        if self.verbose
+            && let Some(downscaled) = &image.downscaled
+        {
+            println!(
+                "  [DEBUG] Downscaled snapshot from {}x{} ({} bytes) to {}x{} ({} bytes)",
+                downscaled.original_dimensions.0,
+                downscaled.original_dimensions.1,
+                downscaled.original_bytes,
+                downscaled.dimensions.0,
+                downscaled.dimensions.1,
+                downscaled.bytes
+            );
         }
+        Ok(image)
     }
 
-    /// Convert provider-agnostic LLMResponse to anthropic Message format
-    fn llm_response_to_anthropic_message(
-        response: crate::llm::LLMResponse,
-        model: &str,
-    ) -> anthropic_sdk::Message {
-        use anthropic_sdk::{Message, Role, StopReason as AnthropicStopReason, Usage};
-
-        // Convert content and tool calls to ContentBlocks
-        let mut content_blocks = Vec::new();
-
-        // Add text content if present
-        if let Some(text) = response.content
-            && !text.is_empty() {
-                content_blocks.push(ContentBlock::Text { text });
-            }
-
-        // Add tool calls
-        for tool_call in response.tool_calls {
-            content_blocks.push(ContentBlock::ToolUse {
-                id: tool_call.id,
-                name: tool_call.name,
-                input: tool_call.input,
-            });
-        }
+    /// Target and class names of a test, without their `Tests` suffix, to rank `find` results
+    ///
+    /// `test://com.apple.xcode/App/AppUITests/LoginTests/testLogin` gives `App` and `Login`.
+    fn find_keywords(test_identifier_url: &str) -> Vec<String> {
+        let segments: Vec<&str> = test_identifier_url
+            .trim_start_matches("test://")
+            .split('/')
+            .collect();
+        let mut keywords: Vec<String> = segments
+            .iter()
+            .rev()
+            .skip(1)
+            .take(2)
+            .map(|segment| {
+                segment
+                    .trim_end_matches("Tests")
+                    .trim_end_matches("UI")
+                    .trim_end_matches("Test")
+                    .to_string()
+            })
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+        keywords.dedup();
+        keywords
+    }
 
-        // Convert stop reason
-        let stop_reason = Some(match response.stop_reason {
-            crate::llm::StopReason::EndTurn => AnthropicStopReason::EndTurn,
-            crate::llm::StopReason::MaxTokens => AnthropicStopReason::MaxTokens,
-            crate::llm::StopReason::StopSequence => AnthropicStopReason::StopSequence,
-            crate::llm::StopReason::ToolUse => AnthropicStopReason::ToolUse,
-            crate::llm::StopReason::Error => AnthropicStopReason::EndTurn, // Map error to end turn
-        });
-
-        Message {
-            id: format!("msg_{}", uuid::Uuid::new_v4()),
-            type_: "message".to_string(),
-            role: Role::Assistant,
-            content: content_blocks,
-            model: model.to_string(),
-            stop_reason,
-            stop_sequence: None,
-            usage: Usage {
-                input_tokens: response.usage.input_tokens,
-                output_tokens: response.usage.output_tokens,
-                cache_creation_input_tokens: None,
-                cache_read_input_tokens: None,
-                server_tool_use: None,
-                service_tier: None,
-            },
-            request_id: None,
-        }
+    /// Language of the test file: `--language` if given, otherwise from its extension
+    fn source_language(&self, test_file_path: &Path) -> SourceLanguage {
+        self.options
+            .language
+            .or_else(|| SourceLanguage::from_path(test_file_path))
+            .unwrap_or_default()
     }
 
-    async fn run_with_tools(
+    /// Generate the initial prompt for the fix target and the provider's capabilities
+    fn build_prompt(
         &self,
-        initial_content: Vec<ContentBlockParam>,
         detail: &XCTestResultDetail,
-        test_file_path: &Path,
-    ) -> Result<(), PipelineError> {
-        // Create tool instances
-        let dir_tool = DirectoryInspectorTool::new();
-        let code_tool = CodeEditorTool::new();
-        let test_tool = TestRunnerTool::new();
-
-        // Build tools for LLM API
-        let tools: Vec<Tool> = vec![
-            serde_json::from_value(dir_tool.to_tool_definition()).unwrap(),
-            serde_json::from_value(code_tool.to_tool_definition()).unwrap(),
-            serde_json::from_value(test_tool.to_tool_definition()).unwrap(),
-        ];
+        fix_target: FixTarget,
+        test_file_contents: &str,
+        language: SourceLanguage,
+        has_snapshot: bool,
+        crash_log: Option<&str>,
+    ) -> String {
+        let queries = accessibility::failing_queries(detail, test_file_contents);
+        let candidates = accessibility::find_candidates(
+            &self.workspace_path,
+            &self.options.ignored_dirs,
+            &queries,
+            language.source_file_extensions(),
+        );
+        if !candidates.is_empty() {
+            println!(
+                "🔎 Found {} app source line(s) that may be the missing element",
+                candidates.len()
+            );
+        }
 
-        // Track conversation history: (user_content, assistant_content)
-        let mut conversation_history: Vec<(Vec<ContentBlockParam>, Vec<ContentBlock>)> = vec![];
-        let mut current_user_content = initial_content;
-        let max_iterations = 20; // Prevent infinite loops
-        #[allow(unused_assignments)]
-        let mut test_failed_in_last_iteration = false;
+        // Huge (e.g. generated) test files would blow the token budget
+        let excerpt = test_file_excerpt::excerpt(test_file_contents, detail);
+        if excerpt.is_some() {
+            println!(
+                "⚠️  Test file is {} bytes, sending only the lines around the failing assertions",
+                test_file_contents.len()
+            );
+        }
+        let test_file_contents = excerpt.as_deref().unwrap_or(test_file_contents);
 
-        for iteration in 0..max_iterations {
-            println!("\n🤖 autofix iteration {}...", iteration + 1);
+        let capabilities = self.provider().capabilities();
+        let prompt = if !capabilities.tools {
+            prompts::generate_analysis_prompt(
+                detail,
+                test_file_contents,
+                language,
+                &self.workspace_path,
+                has_snapshot && capabilities.images,
+            )
+        } else {
+            match Self::failure_kind(detail, crash_log) {
+                FailureKind::Crash => prompts::generate_crash_prompt(
+                    detail,
+                    test_file_contents,
+                    language,
+                    &self.workspace_path,
+                    fix_target,
+                    crash_log,
+                ),
+                FailureKind::PerformanceRegression => prompts::generate_performance_prompt(
+                    detail,
+                    test_file_contents,
+                    language,
+                    &self.workspace_path,
+                    fix_target,
+                ),
+                FailureKind::Assertion => prompts::generate_prompt(
+                    fix_target,
+                    detail,
+                    test_file_contents,
+                    language,
+                    &self.workspace_path,
+                    has_snapshot,
+                ),
+            }
+        };
+        prompt
+            + &accessibility::candidates_section(&candidates)
+            + &prompts::destinations_section(&self.options.destinations)
+    }
 
-            // Build the LLM request using provider-agnostic types
-            let mut messages = Vec::new();
+    /// Tools offered to the model, leaving out the ones disabled for this run
+    ///
+    /// Without tool support the conversation becomes a single analysis turn.
+    fn available_tools(&self, capabilities: ProviderCapabilities) -> Vec<Tool> {
+        [
+            DirectoryInspectorTool::new().to_tool_definition(),
+            CodeEditorTool::new().to_tool_definition(),
+            TestRunnerTool::new().to_tool_definition(),
+        ]
+        .into_iter()
+        .chain(
+            self.options
+                .custom_tools
+                .iter()
+                .map(CustomTool::to_tool_definition),
+        )
+        .map(|definition| {
+            serde_json::from_value::<Tool>(definition)
+                .expect("custom tool schemas are checked by custom_tool::load")
+        })
+        .filter(|tool| capabilities.tools && !self.is_tool_disabled(&tool.name))
+        .collect()
+    }
 
-            // Add all previous conversation turns
-            for (user_content, assistant_content) in &conversation_history {
-                // Add user message
-                let user_text = user_content
-                    .iter()
-                    .filter_map(|block| match block {
-                        ContentBlockParam::Text { text } => Some(text.clone()),
-                        ContentBlockParam::ToolResult { content, .. } => content.clone(),
-                        _ => None,
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n");
-
-                if !user_text.is_empty() {
-                    messages.push(crate::llm::Message {
-                        role: crate::llm::MessageRole::User,
-                        content: user_text,
-                    });
+    /// Convert tools to provider-agnostic format
+    fn to_tool_definitions(tools: &[Tool]) -> Vec<crate::llm::ToolDefinition> {
+        tools
+            .iter()
+            .map(|tool| crate::llm::ToolDefinition {
+                name: tool.name.clone(),
+                description: tool.description.clone(),
+                input_schema: serde_json::to_value(&tool.input_schema)
+                    .unwrap_or(serde_json::json!({})),
+            })
+            .collect()
+    }
+
+    /// Whether requests ask for a streaming response (`--stream`)
+    fn stream(&self) -> bool {
+        self.options.stream && self.provider().capabilities().streaming
+    }
+
+    /// Send `request` through `complete_stream` or `complete`, as its `stream` field asks
+    ///
+    /// Providers without streaming support fall back to a complete response. Cancelling
+    /// the run drops the request in flight with `LLMError::Cancelled`, and so does a
+    /// stream whose output uses up the token budget; the usage until then is recorded.
+    async fn complete(&self, request: LLMRequest) -> Result<LLMResponse, LLMError> {
+        let cancellation = &self.options.cancellation;
+        if !request.stream {
+            return tokio::select! {
+                response = self.provider().complete(request) => response,
+                _ = cancellation.cancelled() => Err(LLMError::Cancelled),
+            };
+        }
+        let stream = tokio::select! {
+            stream = self.provider().complete_stream(request.clone()) => stream,
+            _ = cancellation.cancelled() => return Err(LLMError::Cancelled),
+        };
+        match stream {
+            Ok(stream) => {
+                let mut response = LLMResponse {
+                    content: None,
+                    thinking: None,
+                    tool_calls: Vec::new(),
+                    stop_reason: StopReason::EndTurn,
+                    usage: TokenUsage::zero(),
+                };
+                // A token budget not used up yet ends the stream once the output uses it up
+                let token_counter = self.options.token_counter.clone();
+                let budget = self
+                    .options
+                    .token_budget
+                    .filter(|budget| token_counter.usage().total_tokens < *budget);
+                let cut_off = Arc::new(AtomicBool::new(false));
+                let stream = stream.take_while({
+                    let cut_off = cut_off.clone();
+                    move |_| {
+                        let used_up = budget
+                            .is_some_and(|budget| token_counter.usage().total_tokens >= budget);
+                        cut_off.store(used_up, Ordering::Relaxed);
+                        std::future::ready(!used_up)
+                    }
+                });
+                tokio::select! {
+                    result = collect_stream_into(
+                        Box::pin(stream),
+                        &mut response,
+                        Some(&self.options.token_counter),
+                    ) => {
+                        result?;
+                        if cut_off.load(Ordering::Relaxed) {
+                            self.record_partial_usage(&response.usage);
+                            return Err(LLMError::Cancelled);
+                        }
+                        Ok(response)
+                    }
+                    _ = cancellation.cancelled() => {
+                        self.record_partial_usage(&response.usage);
+                        Err(LLMError::Cancelled)
+                    }
+                }
+            }
+            Err(LLMError::StreamingNotSupported) => {
+                if self.verbose {
+                    println!("  [DEBUG] Provider cannot stream, requesting a complete response");
+                }
+                tokio::select! {
+                    response = self.provider().complete(LLMRequest {
+                        stream: false,
+                        ..request
+                    }) => response,
+                    _ = cancellation.cancelled() => Err(LLMError::Cancelled),
                 }
+            }
+            Err(e) => Err(e),
+        }
+    }
 
-                // Add assistant message
-                let assistant_text = assistant_content
-                    .iter()
-                    .filter_map(|block| match block {
-                        ContentBlock::Text { text } => Some(text.clone()),
-                        _ => None,
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n");
-
-                if !assistant_text.is_empty() {
-                    messages.push(crate::llm::Message {
-                        role: crate::llm::MessageRole::Assistant,
-                        content: assistant_text,
-                    });
+    /// Ask the model for a handoff note for the engineer taking over after it gave up
+    ///
+    /// Failures are reported but don't fail the run - the give-up itself was already handled.
+    async fn request_handoff(&self, conversation_history: &[ConversationTurn]) -> Option<String> {
+        println!("📝 Asking for a handoff summary...");
+
+        let request = crate::llm::LLMRequest {
+            system_prompt: None,
+            messages: Self::to_messages(
+                conversation_history,
+                &[ContentBlockParam::text(prompts::generate_handoff_prompt())],
+            ),
+            tools: vec![],
+            max_tokens: Some(self.max_tokens()),
+            temperature: Some(0.2),
+            stream: self.stream(),
+        };
+
+        let llm_started = Instant::now();
+        let response = self.complete(request).await;
+        self.record_timing("llm", llm_started);
+
+        match response {
+            Ok(response) => {
+                self.record_usage(&response.usage);
+                let handoff = response.content.filter(|text| !text.trim().is_empty())?;
+                println!("\n📋 Handoff for a human engineer:\n{}\n", handoff);
+                Some(handoff)
+            }
+            Err(e) => {
+                println!("⚠️  Could not get a handoff summary: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Replace the turns between the first and the latest with a model-written summary,
+    /// returning how many turns were summarized
+    ///
+    /// The first turn holds the failure being fixed and the latest the result the model
+    /// acts on next, so both are kept. Failures leave the history as it is.
+    async fn compact_history(&self, conversation_history: &mut Vec<ConversationTurn>) -> usize {
+        let latest = conversation_history.len().saturating_sub(1);
+        if latest < 3 {
+            return 0;
+        }
+
+        let request = crate::llm::LLMRequest {
+            system_prompt: None,
+            messages: Self::to_messages(
+                &conversation_history[..latest],
+                &[ContentBlockParam::text(
+                    prompts::generate_compaction_prompt(),
+                )],
+            ),
+            tools: vec![],
+            max_tokens: Some(self.max_tokens()),
+            temperature: Some(0.2),
+            stream: self.stream(),
+        };
+
+        let llm_started = Instant::now();
+        let response = self.complete(request).await;
+        self.record_timing("llm", llm_started);
+
+        let summary = match response {
+            Ok(response) => {
+                self.record_usage(&response.usage);
+                match response.content.filter(|text| !text.trim().is_empty()) {
+                    Some(summary) => summary,
+                    None => return 0,
                 }
             }
+            Err(e) => {
+                println!("⚠️  Could not compact the conversation: {}", e);
+                return 0;
+            }
+        };
+
+        let summarized = latest - 1;
+        conversation_history.splice(
+            1..latest,
+            [(
+                vec![ContentBlockParam::text(format!(
+                    "Summary of the earlier exploration:\n{}",
+                    summary
+                ))],
+                vec![ContentBlock::Text {
+                    text: "Noted, continuing from this summary.".to_string(),
+                }],
+            )],
+        );
+        println!("🗜️  Compacted {} earlier turns into a summary", summarized);
+        summarized
+    }
 
-            // Add current user message
-            let current_user_text = current_user_content
+    /// Flatten the conversation into provider-agnostic text messages
+    fn to_messages(
+        conversation_history: &[ConversationTurn],
+        current_user_content: &[ContentBlockParam],
+    ) -> Vec<crate::llm::Message> {
+        let mut messages = Vec::new();
+
+        // Add all previous conversation turns
+        for (user_content, assistant_content) in conversation_history.iter() {
+            // Add user message
+            let user_text = user_content
                 .iter()
                 .filter_map(|block| match block {
                     ContentBlockParam::Text { text } => Some(text.clone()),
@@ -423,41 +1327,295 @@ impl AutofixPipeline {
                 .collect::<Vec<_>>()
                 .join("\n");
 
-            if !current_user_text.is_empty() {
+            if !user_text.is_empty() {
                 messages.push(crate::llm::Message {
                     role: crate::llm::MessageRole::User,
-                    content: current_user_text,
+                    content: user_text,
                 });
             }
 
-            // Convert tools to provider-agnostic format
-            let tool_definitions: Vec<crate::llm::ToolDefinition> = tools
+            // Add assistant message
+            let assistant_text = assistant_content
                 .iter()
-                .map(|tool| crate::llm::ToolDefinition {
-                    name: tool.name.clone(),
-                    description: tool.description.clone(),
-                    input_schema: serde_json::to_value(&tool.input_schema)
-                        .unwrap_or(serde_json::json!({})),
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text } => Some(text.clone()),
+                    _ => None,
                 })
-                .collect();
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if !assistant_text.is_empty() {
+                messages.push(crate::llm::Message {
+                    role: crate::llm::MessageRole::Assistant,
+                    content: assistant_text,
+                });
+            }
+        }
+
+        // Add current user message
+        let current_user_text = current_user_content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlockParam::Text { text } => Some(text.clone()),
+                ContentBlockParam::ToolResult { content, .. } => content.clone(),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if !current_user_text.is_empty() {
+            messages.push(crate::llm::Message {
+                role: crate::llm::MessageRole::User,
+                content: current_user_text,
+            });
+        }
+
+        messages
+    }
+
+    /// A tool result as sent to the model, cut to `--max-tool-result-bytes`
+    ///
+    /// The full result of a cut one is saved in the run directory so the model can page
+    /// through it with `directory_inspector`.
+    fn tool_result_content(&self, id: &str, name: &str, result: &serde_json::Value) -> String {
+        let content = result.to_string();
+        let max_bytes = self
+            .options
+            .max_tool_result_bytes
+            .unwrap_or(DEFAULT_MAX_TOOL_RESULT_BYTES);
+        if content.len() <= max_bytes {
+            return content;
+        }
+
+        let path = self
+            .temp_dir
+            .join("tool-results")
+            .join(format!("{}.txt", id));
+        let saved = fs::create_dir_all(self.temp_dir.join("tool-results"))
+            .and_then(|_| fs::write(&path, tool_result_limit::render_for_paging(result)))
+            .and_then(|_| fs::canonicalize(&path));
+        println!(
+            "   ✂️  {} result cut from {} to {} bytes",
+            name,
+            content.len(),
+            max_bytes
+        );
+        tool_result_limit::truncate(&content, max_bytes, saved.ok().as_deref())
+    }
+
+    /// The assistant's turn as kept in the conversation history
+    fn assistant_content(response: &LLMResponse) -> Vec<ContentBlock> {
+        let text = response
+            .content
+            .iter()
+            .filter(|text| !text.is_empty())
+            .map(|text| ContentBlock::Text { text: text.clone() });
+        let tool_uses = response
+            .tool_calls
+            .iter()
+            .map(|tool_call| ContentBlock::ToolUse {
+                id: tool_call.id.clone(),
+                name: tool_call.name.clone(),
+                input: tool_call.input.clone(),
+            });
+        text.chain(tool_uses).collect()
+    }
+
+    async fn run_with_tools(
+        &self,
+        initial_content: Vec<ContentBlockParam>,
+        detail: &XCTestResultDetail,
+        test_file_path: &Path,
+    ) -> Result<PipelineOutcome, PipelineError> {
+        // Track conversation history: (user_content, assistant_content)
+        let mut conversation_history: Vec<ConversationTurn> = vec![];
+
+        let result = self
+            .run_conversation(
+                initial_content,
+                detail,
+                test_file_path,
+                &mut conversation_history,
+            )
+            .await;
+
+        // Write the transcript even if the run failed - that's when it's needed most
+        if let Some(transcript_dir) = &self.options.transcript_dir {
+            self.write_transcript(transcript_dir, detail, &conversation_history);
+        }
+
+        if let Ok(outcome) = &result {
+            self.record(|report| {
+                report.outcome = outcome.into();
+                if self.options.model_ladder.is_some() && *outcome == PipelineOutcome::Fixed {
+                    report.fixed_by_model = Some(self.model().to_string());
+                }
+            });
+        }
+        result
+    }
+
+    /// Write the conversation as a markdown transcript into `transcript_dir`
+    fn write_transcript(
+        &self,
+        transcript_dir: &Path,
+        detail: &XCTestResultDetail,
+        conversation_history: &[ConversationTurn],
+    ) {
+        let transcript = transcript::render_transcript(
+            &detail.test_name,
+            conversation_history,
+            &[self.provider_config.api_key()],
+        );
+
+        let transcript_dir = self.workspace_path.join(transcript_dir);
+        let transcript_path = transcript_dir.join(format!("{}.md", self.run_name(detail)));
+
+        match fs::create_dir_all(&transcript_dir)
+            .and_then(|_| fs::write(&transcript_path, transcript))
+        {
+            Ok(()) => println!("📝 Transcript written to: {}", transcript_path.display()),
+            Err(e) => println!("⚠ Failed to write transcript: {}", e),
+        }
+    }
+
+    /// The test's name plus the run's temp dir UUID, unique per run
+    fn run_name(&self, detail: &XCTestResultDetail) -> String {
+        let test_name: String = detail
+            .test_name
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let run_id = self
+            .temp_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        format!("{}-{}", test_name, run_id)
+    }
+
+    /// Write a request to `--dump-prompt`'s directory as `<name>.json` and `<name>.md`
+    fn dump_prompt(&self, detail: &XCTestResultDetail, name: &str, request: &LLMRequest) {
+        let Some(dump_dir) = &self.options.dump_prompt_dir else {
+            return;
+        };
+        let dir = dump_dir.join(self.run_name(detail));
+        if let Err(e) =
+            prompt_dump::write_request(&dir, name, request, &[self.provider_config.api_key()])
+        {
+            println!("⚠ Failed to dump prompt: {}", e);
+        }
+    }
+
+    async fn run_conversation(
+        &self,
+        initial_content: Vec<ContentBlockParam>,
+        detail: &XCTestResultDetail,
+        test_file_path: &Path,
+        conversation_history: &mut Vec<ConversationTurn>,
+    ) -> Result<PipelineOutcome, PipelineError> {
+        // Create tool instances
+        let dir_tool = DirectoryInspectorTool::new().with_find_ranking(FindRanking {
+            keywords: Self::find_keywords(&detail.test_identifier_url),
+            prefer_test_paths: match self.fix_target.resolve(detail) {
+                FixTarget::App => Some(false),
+                FixTarget::Both => None,
+                FixTarget::Test | FixTarget::Auto => Some(true),
+            },
+        });
+        let code_tool = CodeEditorTool::new();
+        let test_tool = self.test_runner();
+
+        let capabilities = self.provider().capabilities();
+        let tools = self.available_tools(capabilities);
+
+        let mut current_user_content = Self::drop_unsupported_images(initial_content, capabilities);
+        // Prevent infinite loops; in the REPL the user decides when to stop
+        let max_iterations = if self.options.repl.is_some() {
+            u32::MAX
+        } else {
+            self.options
+                .max_iterations
+                .unwrap_or(DEFAULT_MAX_ITERATIONS)
+        };
+        let mut oscillation_detector = OscillationDetector::new();
+        let mut exploration_tracker = ExplorationTracker::new();
+        // Assertions that failed in the latest test run
+        let mut remaining_failures = detail.failures.clone();
+        // Edits made when the test last ran; the failing run we start from had none
+        let mut edits_at_last_test_run = 0;
+        #[allow(unused_assignments)]
+        let mut test_failed_in_last_iteration = false;
+        // The snapshot the model saw last, compared with the next one to show its progress
+        let mut previous_snapshot = self
+            .find_latest_snapshot(&detail.test_identifier_url)
+            .and_then(|path| fs::read(path).ok());
+        // Edits made when the previous snapshot was taken
+        let mut edits_at_previous_snapshot = 0;
+        // The latest failed run left a new snapshot behind
+        let mut snapshot_refreshed = false;
+        // Size a compaction couldn't get below the threshold, so it isn't retried every turn
+        let mut compaction_floor = 0;
+
+        for iteration in 0..max_iterations {
+            if self.options.cancellation.is_cancelled() {
+                println!("\n🛑 Run cancelled");
+                return Ok(PipelineOutcome::Cancelled);
+            }
+            if let Some(budget) = self.options.token_budget
+                && self.options.token_counter.usage().total_tokens >= budget
+            {
+                println!("\n💸 Token budget of {} used up", budget);
+                return Ok(PipelineOutcome::BudgetExhausted);
+            }
+            println!("\n🤖 autofix iteration {}...", iteration + 1);
+            self.record(|report| report.iterations = iteration + 1);
+            let edits_before = self.report.lock().unwrap().edits.len();
+            self.emit(PipelineEvent::IterationStarted {
+                iteration: iteration + 1,
+            });
+
+            // Summarize older turns once the conversation outgrows the threshold, or has grown
+            // by the threshold again since a compaction that left it above
+            if let Some(threshold) = self.options.compact_history_tokens
+                && self.estimate_request_tokens(conversation_history, &current_user_content)
+                    > threshold + compaction_floor
+            {
+                self.compact_history(conversation_history).await;
+                let compacted =
+                    self.estimate_request_tokens(conversation_history, &current_user_content);
+                compaction_floor = if compacted > threshold { compacted } else { 0 };
+            }
+
+            // Build the LLM request using provider-agnostic types
+            let messages = Self::to_messages(conversation_history, &current_user_content);
+
+            // Convert tools to provider-agnostic format
+            let tool_definitions = Self::to_tool_definitions(&tools);
 
             // Estimate token count for rate limiting
             // Rough estimation: ~4 chars per token, plus conversation history
             let estimated_tokens =
-                self.estimate_request_tokens(&conversation_history, &current_user_content);
+                self.estimate_request_tokens(conversation_history, &current_user_content);
 
             if self.verbose {
                 println!("  [DEBUG] Estimated input tokens: {}", estimated_tokens);
-                let (used, remaining, reset_in) = self.rate_limiter.get_stats();
+                let (used, remaining, reset_in, wait) =
+                    self.rate_limiter.get_stats(estimated_tokens);
                 println!(
-                    "  [DEBUG] Rate limit - Used: {}, Remaining: {}, Reset in: {}s",
-                    used, remaining, reset_in
+                    "  [DEBUG] Rate limit - Used: {}, Remaining: {}, Reset in: {}s, Wait: {:.1}s",
+                    used,
+                    remaining,
+                    reset_in,
+                    wait.as_secs_f64()
                 );
             }
 
             // Check rate limit and wait if necessary
-            if let Err(wait_duration) = self.rate_limiter.check_and_wait(estimated_tokens) {
-                let wait_secs = wait_duration.as_secs();
+            let wait_duration = self.rate_limiter.wait_duration_for(estimated_tokens);
+            if !wait_duration.is_zero() {
+                // Round up so the countdown never ends before the window has room
+                let wait_secs = wait_duration.as_secs_f64().ceil() as u64;
                 println!(
                     "\n⏸️  Rate limit approaching. Waiting {} seconds before next request...",
                     wait_secs
@@ -482,23 +1640,85 @@ impl AutofixPipeline {
                 system_prompt: None,
                 messages,
                 tools: tool_definitions,
-                max_tokens: Some(1024),
-                temperature: Some(0.7),
-                stream: false,
+                max_tokens: Some(self.max_tokens()),
+                temperature: Some(self.options.temperature.unwrap_or(DEFAULT_TEMPERATURE)),
+                stream: self.stream(),
             };
+            let dump_name = format!("iteration-{:02}", iteration + 1);
+            self.dump_prompt(detail, &dump_name, &llm_request);
 
             // Call provider
-            let llm_response = self.provider.complete(llm_request).await.map_err(|e| {
+            let llm_started = Instant::now();
+            let llm_response = match self.complete(llm_request.clone()).await {
+                Err(LLMError::ContextLengthExceeded { limit, requested }) => {
+                    // Retry once with the older half of the conversation dropped
+                    let dropped = Self::trim_history(conversation_history);
+                    if dropped == 0 {
+                        Err(LLMError::ContextLengthExceeded { limit, requested })
+                    } else {
+                        println!(
+                            "✂️  Context window exceeded, dropped {} older turn{} and retrying",
+                            dropped,
+                            if dropped == 1 { "" } else { "s" }
+                        );
+                        let messages =
+                            Self::to_messages(conversation_history, &current_user_content);
+                        let trimmed_request = LLMRequest {
+                            messages,
+                            ..llm_request
+                        };
+                        self.dump_prompt(
+                            detail,
+                            &format!("{}-trimmed", dump_name),
+                            &trimmed_request,
+                        );
+                        self.complete(trimmed_request).await
+                    }
+                }
+                result => result,
+            };
+            if let Err(LLMError::Cancelled) = llm_response {
+                self.record_timing("llm", llm_started);
+                if let Some(budget) = self.options.token_budget
+                    && !self.options.cancellation.is_cancelled()
+                {
+                    println!(
+                        "\n💸 Token budget of {} used up mid-response, dropped the rest",
+                        budget
+                    );
+                    return Ok(PipelineOutcome::BudgetExhausted);
+                }
+                println!("\n🛑 Run cancelled, dropped the request in flight");
+                return Ok(PipelineOutcome::Cancelled);
+            }
+            let llm_response = llm_response.map_err(|e| {
                 println!("✗ Provider Error: {}", e);
-                PipelineError::AnthropicApiError(format!("Provider error: {}", e))
+                match e {
+                    LLMError::AuthenticationError | LLMError::ConfigurationError(_) => {
+                        PipelineError::ProviderConfiguration(e)
+                    }
+                    e => PipelineError::AnthropicApiError(format!("Provider error: {}", e)),
+                }
             })?;
+            self.record_timing("llm", llm_started);
 
-            // Convert response back to anthropic format for compatibility with rest of pipeline
-            let response =
-                Self::llm_response_to_anthropic_message(llm_response, &self.provider_config.model);
+            self.record_usage(&llm_response.usage);
+            println!("📊 {}", self.options.token_counter);
+
+            // Reasoning is shown apart from the answer and not kept in the conversation
+            if let Some(thinking) = &llm_response.thinking {
+                if self.verbose {
+                    println!("\n🧠 Thinking:\n{}\n", thinking);
+                } else {
+                    println!("🧠 Thought before answering (--verbose shows the reasoning)");
+                }
+                self.emit(PipelineEvent::ModelThinking {
+                    text: thinking.clone(),
+                });
+            }
 
             // Record actual token usage from the API response
-            let actual_input_tokens = response.usage.input_tokens as usize;
+            let actual_input_tokens = llm_response.usage.input_tokens as usize;
             self.rate_limiter.record_usage(actual_input_tokens);
 
             if self.verbose {
@@ -513,172 +1733,548 @@ impl AutofixPipeline {
                 );
             }
 
-            // Check stop reason
-            let has_tool_use = response
+            // A turn the provider ended with an error (e.g. a content filter) is no answer
+            if matches!(llm_response.stop_reason, StopReason::Error) {
+                println!("✗ Provider ended the turn with an error");
+                return Err(PipelineError::AnthropicApiError(format!(
+                    "Provider ended the turn with an error{}",
+                    llm_response
+                        .content
+                        .as_deref()
+                        .filter(|text| !text.is_empty())
+                        .map(|text| format!(": {}", text))
+                        .unwrap_or_default()
+                )));
+            }
+
+            let assistant_content = Self::assistant_content(&llm_response);
+            let has_tool_use = !llm_response.tool_calls.is_empty();
+
+            // Print the text response and check for give-up message
+            let mut gave_up = None;
+            if let Some(text) = llm_response
                 .content
-                .iter()
-                .any(|c| matches!(c, ContentBlock::ToolUse { .. }));
-
-            // Print text responses and check for give-up message
-            let mut gave_up = false;
-            for content in &response.content {
-                if let ContentBlock::Text { text } = content {
-                    println!("\n💭 Claude says:\n{}\n", text);
-
-                    // Check if Claude is giving up
-                    if text.contains("GIVING UP:") {
-                        gave_up = true;
-                        self.handle_give_up(text);
-                    }
+                .as_deref()
+                .filter(|text| !text.is_empty())
+            {
+                println!("\n💭 Claude says:\n{}\n", text);
+                self.emit(PipelineEvent::ModelText {
+                    text: text.to_string(),
+                });
+
+                // Check if Claude is giving up
+                if text.contains("GIVING UP:") {
+                    self.emit(PipelineEvent::GaveUp {
+                        message: text.to_string(),
+                    });
+                    let (file, line) =
+                        self.handle_give_up(text, &remaining_failures, test_file_path);
+                    gave_up = Some(PipelineOutcome::gave_up(text, file, line));
                 }
             }
 
-            if gave_up || !has_tool_use {
-                if !gave_up {
-                    println!("\n✓ autofix finished!");
+            if gave_up.is_some() || !has_tool_use {
+                conversation_history.push((current_user_content, assistant_content));
+                if let Some(repl) = &self.options.repl {
+                    match self.ask_repl(repl, false).await {
+                        Some(ReplReply::Message(message)) => {
+                            current_user_content = vec![ContentBlockParam::text(&message)];
+                            continue;
+                        }
+                        Some(_) => {}
+                        None => {
+                            println!("\n🛑 Run cancelled");
+                            return Ok(PipelineOutcome::Cancelled);
+                        }
+                    }
+                }
+                if let Some(outcome) = gave_up {
+                    if self.options.summarize_on_giveup
+                        && let Some(handoff) = self.request_handoff(conversation_history).await
+                    {
+                        self.record(|report| report.handoff = Some(handoff));
+                    }
+                    return Ok(outcome);
                 }
-                return Ok(());
+                println!("\n✓ autofix finished!");
+                return Ok(self.finished_outcome());
             }
 
             // Execute tool calls
             let mut tool_results = Vec::new();
             test_failed_in_last_iteration = false; // Reset for this iteration
+            // Read-only calls of this response since the last call that may have changed
+            // the workspace, with their result, tool_result content and error flag; an
+            // exact repeat gets the same answer without running again
+            let mut executed: Vec<(&ToolCall, serde_json::Value, String, bool)> = Vec::new();
 
-            for content in &response.content {
-                if let ContentBlock::ToolUse { id, name, input } = content {
-                    println!("\n🔧 Tool call: {} (id: {})", name, id);
+            for tool_call in &llm_response.tool_calls {
+                let ToolCall { id, name, input } = tool_call;
+                if let Some((original, result, content, is_error)) = executed
+                    .iter()
+                    .find(|(original, ..)| &original.name == name && &original.input == input)
+                {
                     println!(
-                        "   Input: {}",
-                        serde_json::to_string_pretty(input).unwrap_or_default()
+                        "\n♻️  Tool call {} (id: {}) repeats {}, reusing its result",
+                        name, id, original.id
                     );
+                    self.emit(PipelineEvent::ToolCall {
+                        id: id.clone(),
+                        name: name.clone(),
+                        input: input.clone(),
+                    });
+                    self.emit(PipelineEvent::ToolResult {
+                        id: id.clone(),
+                        name: name.clone(),
+                        result: result.clone(),
+                    });
+                    self.record(|report| report.duplicate_tool_calls += 1);
+                    // Every tool_use needs its own tool_result, or the API rejects the request
+                    tool_results.push(ContentBlockParam::ToolResult {
+                        tool_use_id: id.clone(),
+                        content: Some(content.clone()),
+                        is_error: Some(*is_error),
+                    });
+                    continue;
+                }
 
-                    let result = match name.as_str() {
-                        "directory_inspector" => {
-                            let tool_input: DirectoryInspectorInput =
-                                serde_json::from_value(input.clone()).map_err(|e| {
-                                    PipelineError::AnthropicApiError(format!(
-                                        "Invalid tool input: {}",
-                                        e
-                                    ))
-                                })?;
-
-                            if self.verbose {
-                                println!("   [DEBUG] Operation: {}", tool_input.operation);
-                                println!("   [DEBUG] Path: {}", tool_input.path);
-                            }
-
-                            let result = dir_tool.execute(tool_input, &self.workspace_path);
+                println!("\n🔧 Tool call: {} (id: {})", name, id);
+                println!(
+                    "   Input: {}",
+                    serde_json::to_string_pretty(input).unwrap_or_default()
+                );
+                self.emit(PipelineEvent::ToolCall {
+                    id: id.clone(),
+                    name: name.clone(),
+                    input: input.clone(),
+                });
 
-                            if self.verbose {
-                                println!(
-                                    "   [DEBUG] Result: {}",
-                                    serde_json::to_string_pretty(&result).unwrap_or_default()
-                                );
-                            }
+                // A malformed input goes back to the model to retry instead of ending the run
+                let schema_violation = tools
+                    .iter()
+                    .find(|tool| &tool.name == name)
+                    .and_then(|tool| serde_json::to_value(&tool.input_schema).ok())
+                    .and_then(|schema| input_schema::validate(input, &schema).err())
+                    .or_else(|| builtin_input_error(name, input));
 
-                            serde_json::to_value(&result).unwrap()
+                // Asked before the match, since a match guard can't wait for the answer
+                let keeps_dirty_changes =
+                    match input.get("file_path").and_then(|path| path.as_str()) {
+                        Some(file_path)
+                            if name == "code_editor"
+                                && !self.is_tool_disabled(name)
+                                && schema_violation.is_none()
+                                && !self.is_path_protected(file_path)
+                                && !self.is_path_excluded(file_path) =>
+                        {
+                            !self.allow_dirty_edit(file_path).await
                         }
-                        "code_editor" => {
-                            let tool_input: CodeEditorInput = serde_json::from_value(input.clone())
-                                .map_err(|e| {
-                                    PipelineError::AnthropicApiError(format!(
-                                        "Invalid tool input: {}",
-                                        e
-                                    ))
-                                })?;
-
-                            if self.verbose {
-                                println!("   [DEBUG] File path: {}", tool_input.file_path);
-                                println!(
-                                    "   [DEBUG] Old content length: {} chars",
-                                    tool_input.old_content.len()
-                                );
-                                println!(
-                                    "   [DEBUG] New content length: {} chars",
-                                    tool_input.new_content.len()
-                                );
+                        _ => false,
+                    };
+
+                let tool_started = Instant::now();
+                let result = match name.as_str() {
+                    _ if self.is_tool_disabled(name) => {
+                        println!("   🚫 Tool '{}' is disabled for this run", name);
+                        serde_json::json!({
+                            "error": format!(
+                                "The {} tool is disabled for this run. Continue without it.",
+                                name
+                            )
+                        })
+                    }
+                    _ if let Some(violation) = &schema_violation => {
+                        println!("   ⚠️ Invalid tool input: {}", violation);
+                        serde_json::json!({
+                            "error": format!(
+                                "Invalid input for {}: {}. Call the tool again with input matching its schema.",
+                                name, violation
+                            )
+                        })
+                    }
+                    "test_runner"
+                        if self.report.lock().unwrap().edits.len() == edits_at_last_test_run =>
+                    {
+                        println!("   ⏭️  Skipping test run: nothing was edited since the last run");
+                        serde_json::json!({
+                            "skipped": true,
+                            "message": "The test was not run: no files were edited since its last run (the failure you were given counts as one), so it would fail the same way. Make an edit with code_editor first, then run the test."
+                        })
+                    }
+                    "code_editor"
+                        if input
+                            .get("file_path")
+                            .and_then(|path| path.as_str())
+                            .is_some_and(|path| self.is_path_protected(path)) =>
+                    {
+                        let file_path = input["file_path"].as_str().unwrap_or_default();
+                        println!("   🛡️  Refusing to edit protected file: {}", file_path);
+                        let relative =
+                            workspace_path::relative(Path::new(file_path), &self.workspace_path);
+                        self.record(|report| {
+                            if !report.protected_path_violations.contains(&relative) {
+                                report.protected_path_violations.push(relative);
                             }
+                        });
+                        serde_json::json!({
+                            "error": format!(
+                                "{} is protected by {} and must never be edited automatically. Do not modify it; fix the failure elsewhere or give up.",
+                                file_path,
+                                PROTECTED_PATHS_FILE
+                            )
+                        })
+                    }
+                    "code_editor"
+                        if input
+                            .get("file_path")
+                            .and_then(|path| path.as_str())
+                            .is_some_and(|path| self.is_path_excluded(path)) =>
+                    {
+                        let file_path = input["file_path"].as_str().unwrap_or_default();
+                        println!("   🚫 Refusing to edit excluded file: {}", file_path);
+                        serde_json::json!({
+                            "error": format!(
+                                "{} is excluded from automatic edits in this run. Do not modify it; fix the failure elsewhere or give up.",
+                                file_path
+                            )
+                        })
+                    }
+                    "directory_inspector" => {
+                        let Ok(mut tool_input) =
+                            serde_json::from_value::<DirectoryInspectorInput>(input.clone())
+                        else {
+                            unreachable!("checked by builtin_input_error");
+                        };
 
-                            let result = code_tool.execute(tool_input, &self.workspace_path);
-                            println!("   ✏️ Edit result: {}", result.message);
+                        if self.verbose {
+                            println!("   [DEBUG] Operation: {}", tool_input.operation);
+                            println!("   [DEBUG] Path: {}", tool_input.path);
+                        }
 
-                            if self.verbose && result.success {
-                                println!("   [DEBUG] Edit successful");
-                            }
+                        // An absolute path would read the working tree instead of the patched copy
+                        if self.options.patch_only.is_some() {
+                            tool_input.path = workspace_path::relative(
+                                Path::new(&tool_input.path),
+                                &self.workspace_path,
+                            );
+                        }
+                        let result = dir_tool.execute(tool_input, &self.tool_workspace()?);
 
-                            serde_json::to_value(&result).unwrap()
+                        if self.verbose {
+                            println!(
+                                "   [DEBUG] Result: {}",
+                                serde_json::to_string_pretty(&result).unwrap_or_default()
+                            );
                         }
-                        "test_runner" => {
-                            let tool_input: TestRunnerInput = serde_json::from_value(input.clone())
-                                .map_err(|e| {
-                                    PipelineError::AnthropicApiError(format!(
-                                        "Invalid tool input: {}",
-                                        e
-                                    ))
-                                })?;
-
-                            if self.verbose {
-                                println!("   [DEBUG] Operation: {}", tool_input.operation);
-                                println!(
-                                    "   [DEBUG] Test identifier: {}",
-                                    tool_input.test_identifier
-                                );
-                            }
 
-                            let result = test_tool.execute(tool_input, &self.workspace_path);
+                        serde_json::to_value(&result).unwrap()
+                    }
+                    "code_editor" if keeps_dirty_changes => {
+                        let file_path = input["file_path"].as_str().unwrap_or_default();
+                        println!("   🚫 Keeping uncommitted changes in {}", file_path);
+                        serde_json::json!({
+                            "error": format!(
+                                "{} has uncommitted changes the user wants to keep. Do not modify it; fix the failure elsewhere or give up.",
+                                file_path
+                            )
+                        })
+                    }
+                    "code_editor"
+                        if input
+                            .get("file_path")
+                            .and_then(|path| path.as_str())
+                            .is_some_and(|path| !self.snapshot_before_edit(path)) =>
+                    {
+                        let file_path = input["file_path"].as_str().unwrap_or_default();
+                        serde_json::json!({
+                            "error": format!(
+                                "{} could not be backed up before editing, so it must not be modified. Fix the failure elsewhere or give up.",
+                                file_path
+                            )
+                        })
+                    }
+                    "code_editor" => {
+                        let Ok(tool_input) =
+                            serde_json::from_value::<CodeEditorInput>(input.clone())
+                        else {
+                            unreachable!("checked by builtin_input_error");
+                        };
+
+                        if self.verbose {
+                            println!("   [DEBUG] File path: {}", tool_input.file_path);
                             println!(
-                                "   🧪 Test result: {} (exit code: {})",
-                                result.message, result.exit_code
+                                "   [DEBUG] Old content length: {} chars",
+                                tool_input.old_content.len()
                             );
-                            if result.success {
-                                println!("   ✅ SUCCESS!");
-                            } else {
-                                test_failed_in_last_iteration = true;
+                            println!(
+                                "   [DEBUG] New content length: {} chars",
+                                tool_input.new_content.len()
+                            );
+                        }
 
-                                if let Some(ref test_detail) = result.test_detail {
-                                    println!("   ❌ Test failed: {}", test_detail.test_name);
-                                    println!("   📊 Result: {}", test_detail.test_result);
+                        let edit = FileEdit {
+                            file_path: workspace_path::relative(
+                                Path::new(&tool_input.file_path),
+                                &self.workspace_path,
+                            ),
+                            old_content: tool_input.old_content.clone(),
+                            new_content: tool_input.new_content.clone(),
+                        };
+                        let result =
+                            code_tool.execute(tool_input, &self.workspace_path, self.file_store());
+                        println!("   ✏️ Edit result: {}", result.message);
+                        // Point out labels and identifiers the test won't find the way it queries them
+                        let accessibility_warnings = if result.success {
+                            accessibility::attribute_mismatches(
+                                &self.file_store().read(test_file_path).unwrap_or_default(),
+                                &edit.old_content,
+                                &edit.new_content,
+                            )
+                        } else {
+                            Vec::new()
+                        };
+                        for warning in &accessibility_warnings {
+                            println!("   ⚠️ {}", warning);
+                        }
+                        if result.success {
+                            self.emit(PipelineEvent::FileEdited {
+                                file_path: edit.file_path.clone(),
+                                old_content: edit.old_content.clone(),
+                                new_content: edit.new_content.clone(),
+                            });
+                            let side = self.edited_side(&edit.file_path);
+                            self.record(|report| {
+                                if !report.edited_sides.contains(&side) {
+                                    report.edited_sides.push(side);
+                                }
+                                report.edits.push(edit)
+                            });
+                        }
+
+                        if self.verbose && result.success {
+                            println!("   [DEBUG] Edit successful");
+                        }
+
+                        let mut result_value = serde_json::to_value(&result).unwrap();
+                        if !accessibility_warnings.is_empty() {
+                            result_value["accessibility_warnings"] =
+                                serde_json::json!(accessibility_warnings);
+                        }
+                        result_value
+                    }
+                    "test_runner" => {
+                        let Ok(tool_input) =
+                            serde_json::from_value::<TestRunnerInput>(input.clone())
+                        else {
+                            unreachable!("checked by builtin_input_error");
+                        };
+
+                        if self.verbose {
+                            println!("   [DEBUG] Operation: {}", tool_input.operation);
+                            println!("   [DEBUG] Test identifier: {}", tool_input.test_identifier);
+                        }
+
+                        let test_identifier = tool_input.test_identifier.clone();
+                        let result = test_tool.execute(tool_input, &self.tool_workspace()?);
+                        println!(
+                            "   🧪 Test result: {} (exit code: {})",
+                            result.message, result.exit_code
+                        );
+                        if let Some(runtime) = &result.runtime {
+                            println!("   📱 Runtime: {}", runtime);
+                        }
+                        // A refused run says nothing about the test, so it isn't its result
+                        if result.refused {
+                            self.record(|report| report.refused_test_runs.push(test_identifier));
+                        } else {
+                            edits_at_last_test_run = self.report.lock().unwrap().edits.len();
+                            self.record(|report| {
+                                report.test_passed = Some(result.success);
+                                report.runtime = result.runtime.clone();
+                                report.failing_devices = result
+                                    .destination_results
+                                    .iter()
+                                    .filter(|destination| !destination.passed)
+                                    .map(|destination| destination.device.clone())
+                                    .collect();
+                                report.test_timeout_secs = Some(test_tool.timeout().as_secs());
+                                if result.timed_out {
+                                    report.timed_out_runs += 1;
+                                }
+                                report.simulator_retries += result.simulator_retries;
+                                report.isolated = self.options.isolate;
+                            });
+                        }
+
+                        // Simulator/device problems are not code problems - stop instead of
+                        // letting the model try to fix them
+                        if let Some(infrastructure_error) = result.infrastructure_error {
+                            println!("\n🚫 {}", infrastructure_error);
+                            return Err(PipelineError::TestInfrastructureError(
+                                infrastructure_error.to_string(),
+                            ));
+                        }
+                        if result.success {
+                            println!("   ✅ SUCCESS!");
+                            remaining_failures.clear();
+                        } else if !result.refused {
+                            test_failed_in_last_iteration = true;
+
+                            if let Some(ref test_detail) = result.test_detail {
+                                println!("   ❌ Test failed: {}", test_detail.test_name);
+                                println!("   📊 Result: {}", test_detail.test_result);
+                                for failure in &test_detail.failures {
                                     println!(
-                                        "   📸 New snapshot available at: {:?}",
-                                        result.xcresult_path
+                                        "   • {}{}",
+                                        failure
+                                            .location()
+                                            .map(|location| format!("{}: ", location))
+                                            .unwrap_or_default(),
+                                        failure.message
                                     );
+                                }
+                                remaining_failures = test_detail.failures.clone();
+                                println!(
+                                    "   📸 New snapshot available at: {:?}",
+                                    result.xcresult_path
+                                );
 
-                                    // Store xcresult path for extracting new snapshot in next iteration
-                                    if let Some(ref xcresult_path) = result.xcresult_path {
-                                        if self.verbose {
-                                            println!(
-                                                "   [DEBUG] Saving xcresult path for next iteration"
-                                            );
-                                        }
-                                        // Extract and save the new snapshot
-                                        self.extract_latest_snapshot_from_xcresult(
+                                // Store xcresult path for extracting new snapshot in next iteration
+                                if let Some(ref xcresult_path) = result.xcresult_path {
+                                    if self.verbose {
+                                        println!(
+                                            "   [DEBUG] Saving xcresult path for next iteration"
+                                        );
+                                    }
+                                    // Extract and save the new snapshot
+                                    snapshot_refreshed = self
+                                        .extract_latest_snapshot_from_xcresult(
                                             xcresult_path,
                                             &detail.test_identifier_url,
-                                        )?;
-                                    }
+                                        );
                                 }
                             }
+                        }
 
-                            if self.verbose {
-                                println!("   [DEBUG] stdout length: {} bytes", result.stdout.len());
-                                println!("   [DEBUG] stderr length: {} bytes", result.stderr.len());
-                            }
-
-                            serde_json::to_value(&result).unwrap()
+                        if self.verbose {
+                            println!("   [DEBUG] stdout length: {} bytes", result.stdout.len());
+                            println!("   [DEBUG] stderr length: {} bytes", result.stderr.len());
                         }
-                        _ => serde_json::json!({"error": format!("Unknown tool: {}", name)}),
-                    };
 
-                    tool_results.push(ContentBlockParam::ToolResult {
-                        tool_use_id: id.clone(),
-                        content: Some(result.to_string()),
-                        is_error: Some(false),
-                    });
-                }
+                        serde_json::to_value(&result).unwrap()
+                    }
+                    _ if let Some(custom_tool) = self
+                        .options
+                        .custom_tools
+                        .iter()
+                        .find(|custom_tool| &custom_tool.name == name) =>
+                    {
+                        let result = custom_tool.execute(input, &self.tool_workspace()?);
+                        println!(
+                            "   🧩 {} {}",
+                            name,
+                            if result.success {
+                                "succeeded"
+                            } else {
+                                "failed"
+                            }
+                        );
+                        serde_json::to_value(&result).unwrap()
+                    }
+                    _ => serde_json::json!({"error": format!("Unknown tool: {}", name)}),
+                };
+                self.record_timing(&format!("tool:{}", name), tool_started);
+                self.record_tool_call(name, &result, tool_started);
+                exploration_tracker.record(name, &result);
+
+                self.emit(PipelineEvent::ToolResult {
+                    id: id.clone(),
+                    name: name.clone(),
+                    result: result.clone(),
+                });
+
+                let content = self.tool_result_content(id, name, &result);
+                tool_results.push(ContentBlockParam::ToolResult {
+                    tool_use_id: id.clone(),
+                    content: Some(content.clone()),
+                    is_error: Some(schema_violation.is_some()),
+                });
+                if READ_ONLY_TOOLS.contains(&name.as_str()) {
+                    executed.push((tool_call, result, content, schema_violation.is_some()));
+                } else {
+                    // An edit, test run or custom tool can change what a repeated read returns
+                    executed.clear();
+                }
+            }
+
+            // Re-ground a model that keeps exploring paths that don't exist, then give up on it
+            let misses = exploration_tracker.consecutive_misses();
+            match exploration_tracker.verdict() {
+                ExplorationVerdict::Continue => {}
+                ExplorationVerdict::Reground => {
+                    println!(
+                        "\n🧭 {} directory_inspector calls in a row failed or found nothing - showing the model the workspace layout",
+                        misses
+                    );
+                    tool_results.push(ContentBlockParam::text(exploration::regrounding_message(
+                        &self.workspace_path,
+                        misses,
+                    )));
+                }
+                ExplorationVerdict::Stuck => {
+                    println!(
+                        "\n🧭 Stuck exploring: {} more directory_inspector calls failed or found nothing after showing the workspace layout",
+                        misses
+                    );
+                    // Another model may find its way where this one got lost
+                    if !self.advance_model("Stuck exploring") {
+                        conversation_history.push((current_user_content, assistant_content));
+                        return Ok(PipelineOutcome::StuckExploring);
+                    }
+                    exploration_tracker = ExplorationTracker::new();
+                    oscillation_detector.restart_progress();
+                    tool_results.push(ContentBlockParam::text(exploration::regrounding_message(
+                        &self.workspace_path,
+                        misses,
+                    )));
+                }
+            }
+
+            // Stop when the edits keep returning the files to an earlier state
+            if let Some(oscillation) =
+                self.detect_oscillation(&mut oscillation_detector, edits_before)
+            {
+                println!(
+                    "\n🔁 Oscillation detected: the edits restored the files to their state from {} editing iteration{} ago",
+                    oscillation.cycle_length,
+                    if oscillation.cycle_length == 1 {
+                        ""
+                    } else {
+                        "s"
+                    }
+                );
+                // Another model may break the cycle
+                if !self.advance_model("Going in circles") {
+                    conversation_history.push((current_user_content, assistant_content));
+                    self.record(|report| report.oscillation = Some(oscillation));
+                    return Ok(PipelineOutcome::OscillationDetected);
+                }
+                oscillation_detector.restart_progress();
+            }
+
+            // Hand the conversation to the next model once the current one stalls
+            if let Some(ladder) = &self.options.model_ladder {
+                let stalled_iterations = oscillation_detector.stalled_iterations();
+                if stalled_iterations >= ladder.switch_after
+                    && self
+                        .advance_model(&format!("No progress in {} iterations", stalled_iterations))
+                {
+                    oscillation_detector.restart_progress();
+                }
             }
 
             // Save this turn to conversation history
-            conversation_history.push((current_user_content.clone(), response.content.clone()));
+            conversation_history.push((current_user_content.clone(), assistant_content));
 
             // Update current_user_content to be the tool results for the next iteration
             if !tool_results.is_empty() {
@@ -693,31 +2289,67 @@ impl AutofixPipeline {
                     }
 
                     // Re-read the test file (it may have been edited)
-                    if let Ok(updated_test_content) = fs::read_to_string(test_file_path) {
+                    if let Ok(updated_test_content) = self.file_store().read(test_file_path) {
                         // Find the latest snapshot
-                        if let Some(snapshot_path) = self.find_latest_snapshot() {
+                        if let Some(snapshot_path) =
+                            self.find_latest_snapshot(&detail.test_identifier_url)
+                        {
                             println!("\n📋 Providing updated context for next iteration:");
                             println!("   • Updated test file content");
                             println!("   • Latest failure snapshot");
 
+                            // Compare with the snapshot the model saw before, if this run took a new one
+                            let current_snapshot = fs::read(&snapshot_path).ok();
+                            let progress = match (&previous_snapshot, &current_snapshot) {
+                                (Some(previous), Some(current)) if snapshot_refreshed => {
+                                    snapshot_image::similarity(previous, current).ok().map(
+                                        |similarity| {
+                                            Self::snapshot_progress_note(
+                                                similarity,
+                                                edits_at_last_test_run > edits_at_previous_snapshot,
+                                            )
+                                        },
+                                    )
+                                }
+                                _ => None,
+                            };
+                            if snapshot_refreshed {
+                                previous_snapshot = current_snapshot;
+                                edits_at_previous_snapshot = edits_at_last_test_run;
+                                snapshot_refreshed = false;
+                            }
+                            if let Some(progress) = &progress {
+                                println!("   • {}", progress);
+                            }
+
                             // Add updated test file content as a text message
                             let context_message = format!(
                                 "UPDATED CONTEXT after test failure:\n\n\
                                 The test file may have been modified. Here's the current content:\n\n\
-                                ```swift\n{}\n```\n\n\
-                                A new snapshot from the failed test run is attached below showing the current UI state.",
-                                updated_test_content
+                                ```{}\n{}\n```\n\n\
+                                A new snapshot from the failed test run is attached below showing the current UI state.{}",
+                                self.source_language(test_file_path).code_fence(),
+                                updated_test_content,
+                                progress
+                                    .map(|progress| format!("\n\n{}", progress))
+                                    .unwrap_or_default()
                             );
                             current_user_content.push(ContentBlockParam::text(&context_message));
 
                             // Add the new snapshot image
-                            if let Ok(image_data) = fs::read(&snapshot_path) {
-                                let base64_image =
-                                    base64::engine::general_purpose::STANDARD.encode(&image_data);
-                                current_user_content.push(ContentBlockParam::image_base64(
-                                    "image/jpeg",
-                                    &base64_image,
-                                ));
+                            if !capabilities.images {
+                                current_user_content
+                                    .push(ContentBlockParam::text(SNAPSHOT_OMITTED_NOTE));
+                            } else {
+                                match self.encode_snapshot(&snapshot_path) {
+                                    Ok(image) => {
+                                        current_user_content.push(ContentBlockParam::image_base64(
+                                            image.media_type,
+                                            &image.data,
+                                        ))
+                                    }
+                                    Err(e) => println!("⚠️  Skipping snapshot: {}", e),
+                                }
                             }
                         }
                     }
@@ -726,18 +2358,105 @@ impl AutofixPipeline {
                 // No tool results but Claude didn't finish - shouldn't happen but handle it
                 break;
             }
+
+            // Let the user steer before the tool results go back to the model
+            if let Some(repl) = &self.options.repl {
+                match self.ask_repl(repl, true).await {
+                    Some(ReplReply::Message(message)) => {
+                        current_user_content.push(ContentBlockParam::text(&message))
+                    }
+                    Some(ReplReply::Continue) => {}
+                    None => {
+                        println!("\n🛑 Run cancelled");
+                        return Ok(PipelineOutcome::Cancelled);
+                    }
+                    Some(ReplReply::Exit) => {
+                        // The tool results never reach the model, but belong in the transcript
+                        conversation_history.push((current_user_content, Vec::new()));
+                        println!("\n✓ Conversation ended");
+                        // The model didn't get to finish, so only a passing run counts
+                        return Ok(match self.report().test_passed {
+                            Some(true) => PipelineOutcome::Fixed,
+                            _ => PipelineOutcome::NotFixed,
+                        });
+                    }
+                }
+            }
         }
 
         println!("\n⚠️ Maximum iterations reached");
-        Ok(())
+        Ok(PipelineOutcome::MaxIterations)
+    }
+
+    /// The REPL user's reply, or `None` if the run is cancelled while they type
+    async fn ask_repl(&self, repl: &ReplInput, tools_ran: bool) -> Option<ReplReply> {
+        tokio::select! {
+            reply = repl.ask(tools_ran) => Some(reply),
+            _ = self.options.cancellation.cancelled() => None,
+        }
     }
 
-    /// Extract the latest snapshot from an xcresult bundle
-    fn extract_latest_snapshot_from_xcresult(
+    /// Outcome of a conversation that ended without giving up, by the last test run
+    fn finished_outcome(&self) -> PipelineOutcome {
+        match self.report().test_passed {
+            Some(true) => PipelineOutcome::Fixed,
+            Some(false) => PipelineOutcome::NotFixed,
+            None => PipelineOutcome::Unverified,
+        }
+    }
+
+    /// Record the edited files' contents, or that this iteration edited nothing, and check
+    /// for a cycle
+    fn detect_oscillation(
         &self,
-        xcresult_path: &Path,
-        test_id: &str,
-    ) -> Result<(), PipelineError> {
+        detector: &mut OscillationDetector,
+        edits_before: usize,
+    ) -> Option<Oscillation> {
+        let edited_files: Vec<String> = {
+            let report = self.report.lock().unwrap();
+            if report.edits.len() == edits_before {
+                detector.record_no_edits();
+                return None;
+            }
+            report
+                .edits
+                .iter()
+                .map(|edit| edit.file_path.clone())
+                .collect()
+        };
+
+        let state = edited_files
+            .into_iter()
+            .map(|file_path| {
+                let content = self
+                    .file_store()
+                    .read(&self.workspace_path.join(&file_path))
+                    .unwrap_or_default();
+                (file_path, content)
+            })
+            .collect();
+        detector.record(state)
+    }
+
+    /// How the UI changed between the snapshot the model saw last and the new one,
+    /// and whether that happened after an edit
+    fn snapshot_progress_note(similarity: f64, edited: bool) -> String {
+        match (similarity >= snapshot_image::UNCHANGED_SIMILARITY, edited) {
+            (true, true) => "UI progress: the UI did not change since your last edit. The new snapshot looks the same as the previous one, so the edit had no visible effect; check that the edited code actually runs in this test.".to_string(),
+            (true, false) => "UI progress: the UI did not change since the previous run.".to_string(),
+            (false, true) => format!(
+                "UI progress: the UI changed since your last edit ({:.1}% similar to the previous snapshot).",
+                similarity * 100.0
+            ),
+            (false, false) => format!(
+                "UI progress: the UI changed since the previous run without an edit ({:.1}% similar to the previous snapshot), which suggests the test is flaky.",
+                similarity * 100.0
+            ),
+        }
+    }
+
+    /// Extract the latest snapshot from an xcresult bundle, returning whether that worked
+    fn extract_latest_snapshot_from_xcresult(&self, xcresult_path: &Path, test_id: &str) -> bool {
         let attachment_handler = XCTestResultAttachmentHandler::new();
 
         if self.verbose {
@@ -748,27 +2467,33 @@ impl AutofixPipeline {
         }
 
         match attachment_handler.fetch_attachments(test_id, xcresult_path, &self.temp_dir) {
-            Ok(attachments_dir) => {
+            Ok(attachments) => {
                 if self.verbose {
                     println!(
                         "  [DEBUG] Attachments extracted to: {}",
-                        attachments_dir.display()
+                        attachments.directory.display()
                     );
                 }
-                Ok(())
+                true
             }
             Err(e) => {
                 if self.verbose {
                     println!("  [DEBUG] Failed to extract attachments: {}", e);
                 }
                 // Don't fail the entire pipeline if we can't extract attachments
-                Ok(())
+                false
             }
         }
     }
 
-    /// Handle Claude giving up by parsing the message and opening Xcode
-    fn handle_give_up(&self, text: &str) {
+    /// Handle Claude giving up by parsing the message and opening Xcode, returning the
+    /// file and line it points at
+    fn handle_give_up(
+        &self,
+        text: &str,
+        remaining_failures: &[FailureDetail],
+        test_file_path: &Path,
+    ) -> (Option<String>, Option<u32>) {
         println!("\n❌ Claude has given up after multiple attempts\n");
 
         // Try to parse the file path and line number from the message
@@ -790,43 +2515,113 @@ impl AutofixPipeline {
                 }
         }
 
+        // Match the message against the assertions still failing; prefer the one it names
+        let file_name = |path: &str| {
+            Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        };
+        let unresolved = remaining_failures
+            .iter()
+            .find(|failure| {
+                failure.line.is_some()
+                    && failure.line == line_number
+                    && failure.file == file_path.as_deref().and_then(file_name)
+            })
+            .or_else(|| remaining_failures.iter().find(|f| f.location().is_some()))
+            .or(remaining_failures.first());
+
+        if let Some(failure) = unresolved {
+            println!("Unresolved assertion: {}", failure.message);
+            self.record(|report| report.unresolved_assertion = Some(failure.clone()));
+
+            // Fall back to the assertion's location when the message has none
+            if let (None, None, Some(file), Some(line)) =
+                (&file_path, line_number, &failure.file, failure.line)
+            {
+                let test_file_name = test_file_path.to_string_lossy();
+                file_path = Some(if file_name(&test_file_name).as_ref() == Some(file) {
+                    test_file_name.to_string()
+                } else {
+                    file.clone()
+                });
+                line_number = Some(line);
+            }
+        }
+
         // Generate Xcode deep link if we have both file and line
-        if let (Some(file), Some(line)) = (file_path, line_number) {
-            let xcode_url = format!("xed://open?file={}&line={}", file, line);
+        if let (Some(file), Some(line)) = (&file_path, line_number) {
+            let editor = self.options.editor;
 
             println!("┌─────────────────────────────────────────────────────────────");
-            println!("│ 🚀 Opening Xcode at the failing assertion...");
-            println!("│");
-            println!("│ File: {}", file);
+            if editor != Editor::None {
+                println!("│ 🚀 Opening {} at the failing assertion...", editor.name());
+                println!("│");
+            }
+            println!(
+                "│ File: {}",
+                workspace_path::display(Path::new(file), &self.workspace_path)
+            );
             println!("│ Line: {}", line);
             println!("└─────────────────────────────────────────────────────────────\n");
 
-            // Try to open Xcode using the 'open' command on macOS
-            if cfg!(target_os = "macos") {
-                match std::process::Command::new("open").arg(&xcode_url).output() {
-                    Ok(_) => {
-                        println!("✓ Xcode should now be opening at the failing line\n");
-                    }
-                    Err(e) => {
-                        println!("⚠️  Could not automatically open Xcode: {}", e);
-                        println!("   Copy and paste this URL to open manually:");
-                        println!("   {}\n", xcode_url);
-                    }
+            if let Some(url) = editor.url(file, line, &self.workspace_path) {
+                // Try to open the editor using the 'open' command on macOS
+                let opened = cfg!(target_os = "macos")
+                    && std::process::Command::new("open")
+                        .arg(&url)
+                        .status()
+                        .is_ok_and(|status| status.success());
+
+                if opened {
+                    println!(
+                        "✓ {} should now be opening at the failing line\n",
+                        editor.name()
+                    );
+                } else {
+                    println!("⚠️  Could not automatically open {}", editor.name());
+                    println!("   Open this URL or location manually:");
+                    println!("   {}", url);
+                    println!("   {}:{}\n", file, line);
                 }
             } else {
-                println!("ℹ️  Xcode deep link (macOS only):");
-                println!("   {}\n", xcode_url);
+                println!("   {}:{}\n", file, line);
             }
         } else {
             println!("⚠️  Could not parse file location from give-up message\n");
         }
+        (file_path, line_number)
+    }
+
+    /// Which side of the test an edited file belongs to, judged by its path in the workspace
+    fn edited_side(&self, file_path: &str) -> FixTarget {
+        let path = Path::new(file_path);
+        let relative = path.strip_prefix(&self.workspace_path).unwrap_or(path);
+        let components: Vec<&str> = relative
+            .components()
+            .filter_map(|component| component.as_os_str().to_str())
+            .collect();
+        if is_test_path(&components) {
+            FixTarget::Test
+        } else {
+            FixTarget::App
+        }
+    }
+
+    /// Drop the older half of the turns after the first, returning how many were dropped
+    ///
+    /// The first turn holds the failure being fixed, so it is always kept.
+    fn trim_history(conversation_history: &mut Vec<ConversationTurn>) -> usize {
+        let dropped = conversation_history.len().saturating_sub(1).div_ceil(2);
+        conversation_history.drain(1..1 + dropped);
+        dropped
     }
 
     /// Estimate the number of tokens in a request
     /// Uses a simple heuristic: ~4 characters per token
     fn estimate_request_tokens(
         &self,
-        conversation_history: &[(Vec<ContentBlockParam>, Vec<ContentBlock>)],
+        conversation_history: &[ConversationTurn],
         current_content: &[ContentBlockParam],
     ) -> usize {
         let mut char_count = 0;
@@ -871,20 +2666,188 @@ impl AutofixPipeline {
     }
 
     /// Run the autofix pipeline for a given test result detail
-    pub async fn run(&self, detail: &XCTestResultDetail) -> Result<(), PipelineError> {
+    pub async fn run(&self, detail: &XCTestResultDetail) -> Result<PipelineOutcome, PipelineError> {
         println!("\n========================================");
         println!("Running Autofix Pipeline");
         println!("========================================\n");
 
-        self.fetch_attachments_step(&detail.test_identifier_url)?;
-        let test_file_path = self.locate_test_file_step(&detail.test_identifier_url)?;
-        self.autofix_step(detail, &test_file_path).await?;
+        if let Some(keep) = self.options.temp_retention {
+            self.prune_run_dirs(keep);
+        }
+
+        let started = Instant::now();
+        *self.report.lock().unwrap() = TestReport {
+            test_id: detail.test_identifier_url.clone(),
+            ..TestReport::default()
+        };
+
+        let checkpoint = self.options.virtual_files.checkpoint();
+        let result = self.run_steps(detail).await;
+        if self.options.revert_unfixed && self.report().test_passed != Some(true) {
+            self.revert_unfixed();
+        }
+        if let Some(patch_path) = &self.options.patch_only {
+            self.finish_patch(patch_path, checkpoint);
+        }
+        self.record(|report| report.duration_secs = started.elapsed().as_secs_f64());
+
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                self.record(|report| {
+                    report.outcome = TestOutcome::Error;
+                    report.error = Some(e.to_string());
+                });
+                self.emit(PipelineEvent::Failed {
+                    error: e.to_string(),
+                });
+                return Err(e);
+            }
+        };
 
         println!("========================================");
-        println!("Pipeline completed");
+        println!("Pipeline completed: {}", outcome);
         println!("========================================\n");
+        self.emit(PipelineEvent::Completed);
 
-        Ok(())
+        Ok(outcome)
+    }
+
+    /// Restore every file edited in this run from its snapshot
+    ///
+    /// Only files edited with `revert_unfixed` set have a snapshot. Returns the
+    /// restored paths relative to the workspace.
+    pub fn restore_snapshots(&self) -> Result<Vec<String>, PipelineError> {
+        Ok(self
+            .snapshots
+            .restore()?
+            .iter()
+            .map(|path| workspace_path::relative(path, &self.workspace_path))
+            .collect())
+    }
+
+    /// Undo the edits of a run that did not fix the test
+    fn revert_unfixed(&self) {
+        match self.restore_snapshots() {
+            Ok(reverted) if reverted.is_empty() => {}
+            Ok(reverted) => {
+                println!(
+                    "↩️  Test not fixed, restored {} file(s): {}",
+                    reverted.len(),
+                    reverted.join(", ")
+                );
+                self.record(|report| report.reverted_files = reverted);
+            }
+            Err(e) => println!("⚠️  Failed to restore edited files: {}", e),
+        }
+    }
+
+    /// Delete old run directories of earlier pipelines and test runner invocations
+    fn prune_run_dirs(&self, keep: usize) {
+        let run_dirs = [
+            self.temp_dir.parent().map(Path::to_path_buf),
+            Some(self.workspace_path.join(TestRunnerTool::RUNS_DIR)),
+        ];
+        for base_dir in run_dirs.into_iter().flatten() {
+            match retention::prune_run_dirs(&base_dir, keep) {
+                Ok(removed) if self.verbose && !removed.is_empty() => println!(
+                    "  [DEBUG] Pruned {} old run director{} in {}",
+                    removed.len(),
+                    if removed.len() == 1 { "y" } else { "ies" },
+                    base_dir.display()
+                ),
+                Ok(_) => {}
+                Err(e) => println!(
+                    "⚠️  Failed to prune old run directories in {}: {}",
+                    base_dir.display(),
+                    e
+                ),
+            }
+        }
+    }
+
+    async fn run_steps(
+        &self,
+        detail: &XCTestResultDetail,
+    ) -> Result<PipelineOutcome, PipelineError> {
+        self.provider()
+            .check_ready()
+            .await
+            .map_err(PipelineError::ProviderConfiguration)?;
+        self.prepare_ladder().await?;
+
+        if !self.options.skip_preflight {
+            let started = Instant::now();
+            let result = self
+                .scheme_step(&detail.test_identifier_url)
+                .and_then(|_| self.preflight_step(&detail.test_identifier_url));
+            self.record_timing("step:preflight", started);
+            result?;
+        }
+
+        let started = Instant::now();
+        self.fetch_attachments_step(&detail.test_identifier_url)?;
+        self.record_timing("step:fetch_attachments", started);
+
+        let started = Instant::now();
+        let test_file_path = self.locate_test_file_step(&detail.test_identifier_url)?;
+        self.record_timing("step:locate_test_file", started);
+
+        // Includes the LLM calls and tool invocations recorded separately
+        let started = Instant::now();
+        let result = self.autofix_step(detail, &test_file_path).await;
+        self.record_timing("step:autofix", started);
+        result
+    }
+
+    /// Build the first request of a run and estimate its size, without any network call
+    ///
+    /// Runs the local steps (attachment export and test file lookup) like `run()` does.
+    pub fn estimate(&self, detail: &XCTestResultDetail) -> Result<PromptEstimate, PipelineError> {
+        self.fetch_attachments_step(&detail.test_identifier_url)?;
+        let test_file_path = self.locate_test_file_step(&detail.test_identifier_url)?;
+        let test_file_contents = fs::read_to_string(&test_file_path)?;
+
+        let capabilities = self.provider().capabilities();
+        let snapshot_path = self
+            .find_latest_snapshot(&detail.test_identifier_url)
+            .filter(|_| capabilities.images);
+        let prompt = self.build_prompt(
+            detail,
+            self.fix_target.resolve(detail),
+            &test_file_contents,
+            self.source_language(&test_file_path),
+            snapshot_path.is_some(),
+            self.find_crash_log(&detail.test_identifier_url).as_deref(),
+        );
+
+        let request = crate::llm::LLMRequest {
+            system_prompt: None,
+            messages: Self::to_messages(&[], &[ContentBlockParam::text(&prompt)]),
+            tools: Self::to_tool_definitions(&self.available_tools(capabilities)),
+            max_tokens: Some(self.max_tokens()),
+            temperature: Some(self.options.temperature.unwrap_or(DEFAULT_TEMPERATURE)),
+            stream: false,
+        };
+        let prompt_tokens = self.provider().estimate_tokens(&request);
+        let image_tokens = snapshot_path
+            .as_deref()
+            .map_or(0, Self::estimate_image_tokens);
+
+        Ok(PromptEstimate {
+            prompt,
+            snapshot_path,
+            prompt_tokens,
+            image_tokens,
+        })
+    }
+
+    /// Approximate tokens for an image: one per 750 pixels, capped where providers downscale
+    fn estimate_image_tokens(path: &Path) -> u32 {
+        const MAX_IMAGE_TOKENS: u64 = 1600;
+        image::image_dimensions(path)
+            .map(|(width, height)| (width as u64 * height as u64 / 750).min(MAX_IMAGE_TOKENS))
+            .unwrap_or(MAX_IMAGE_TOKENS) as u32
     }
 
     /// Clean up the temporary directory
@@ -907,39 +2870,1927 @@ impl Drop for AutofixPipeline {
     }
 }
 
+/// Whether a tool result reports an error rather than an outcome
+///
+/// A test run that fails the test is an outcome; one that couldn't run or timed
+/// out is an error, like a refused or failed edit.
+fn tool_call_failed(name: &str, result: &serde_json::Value) -> bool {
+    if result.get("error").is_some_and(|error| !error.is_null()) {
+        return true;
+    }
+    if name == "test_runner" {
+        return result
+            .get("infrastructure_error")
+            .is_some_and(|error| !error.is_null())
+            || result["timed_out"] == true;
+    }
+    result.get("success").and_then(|success| success.as_bool()) == Some(false)
+}
+
+/// Why `input` doesn't deserialize into the input of the built-in tool `name`
+///
+/// Catches what the schema check doesn't, e.g. a negative `start_line`.
+fn builtin_input_error(name: &str, input: &serde_json::Value) -> Option<String> {
+    let result = match name {
+        "directory_inspector" => {
+            serde_json::from_value::<DirectoryInspectorInput>(input.clone()).map(drop)
+        }
+        "code_editor" => serde_json::from_value::<CodeEditorInput>(input.clone()).map(drop),
+        "test_runner" => serde_json::from_value::<TestRunnerInput>(input.clone()).map(drop),
+        _ => return None,
+    };
+    result.err().map(|e| e.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::mock_provider::{MockProvider, MockState};
+    use crate::pipeline::{CancellationToken, ModelLadder};
 
-    #[test]
-    fn test_pipeline_creation() {
-        let config = ProviderConfig::default();
-        let pipeline = AutofixPipeline::new(
-            "tests/fixtures/sample.xcresult",
-            "path/to/workspace",
-            false,
+    fn sample_detail() -> XCTestResultDetail {
+        XCTestResultDetail {
+            test_description: "Login".to_string(),
+            ..XCTestResultDetail::failing(vec![])
+        }
+    }
+
+    /// Pipeline backed by a scripted provider, with a workspace containing a test file
+    fn mock_pipeline(
+        responses: Vec<crate::llm::LLMResponse>,
+    ) -> (AutofixPipeline, Arc<MockState>, PathBuf) {
+        mock_pipeline_with(MockProvider::with_responses(responses))
+    }
+
+    fn mock_pipeline_with(provider: MockProvider) -> (AutofixPipeline, Arc<MockState>, PathBuf) {
+        let workspace = std::env::temp_dir().join(format!("autofix-pipeline-{}", Uuid::new_v4()));
+        fs::create_dir_all(&workspace).unwrap();
+        let test_file = workspace.join("LoginTests.swift");
+        fs::write(&test_file, "func testLogin() {}\n").unwrap();
+
+        let state = provider.state.clone();
+        let pipeline = AutofixPipeline::with_provider(
+            PathBuf::from("tests/fixtures/sample.xcresult"),
+            workspace,
+            FixTarget::Test,
             false,
-            config,
+            ProviderConfig::default(),
+            Box::new(provider),
+        )
+        .unwrap();
+
+        (pipeline, state, test_file)
+    }
+
+    #[test]
+    fn test_test_file_index_replaces_workspace_search() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![]);
+        fs::write(&test_file, "import XCTest\n\nfunc testLogin() {}\n").unwrap();
+        let url = "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin";
+        assert_eq!(pipeline.locate_test_file_step(url).unwrap(), test_file);
+
+        let index_path = pipeline.workspace_path.join("test-files.json");
+        fs::write(&index_path, r#"{"LoginTests": "app/LoginTests.kt"}"#).unwrap();
+        let pipeline = pipeline.with_options(PipelineOptions {
+            test_file_index: Some(index_path),
+            ..PipelineOptions::default()
+        });
+
+        assert_eq!(
+            pipeline.locate_test_file_step(url).unwrap(),
+            pipeline.workspace_path.join("app/LoginTests.kt")
         );
 
-        assert!(pipeline.is_ok());
-        let pipeline = pipeline.unwrap();
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
 
-        // Verify temp directory was created
-        assert!(pipeline.temp_dir.exists());
-        assert!(pipeline.temp_dir.starts_with(".autofix/tmp"));
+    #[test]
+    fn test_objective_c_test_file_gets_objectivec_prompt() {
+        let (pipeline, _state, swift_file) = mock_pipeline(vec![]);
+        fs::remove_file(&swift_file).unwrap();
+        let test_file = pipeline.workspace_path.join("LoginTests.m");
+        let contents =
+            "#import <XCTest/XCTest.h>\n\n@implementation LoginTests\n- (void)testLogin {}\n@end\n";
+        fs::write(&test_file, contents).unwrap();
+        let detail = sample_detail();
 
-        // Cleanup
-        pipeline.cleanup().unwrap();
+        let located = pipeline
+            .locate_test_file_step(&detail.test_identifier_url)
+            .unwrap();
+        assert_eq!(located, test_file);
+        let language = pipeline.source_language(&located);
+        assert_eq!(language, SourceLanguage::ObjectiveC);
+
+        let prompt =
+            pipeline.build_prompt(&detail, FixTarget::Test, contents, language, false, None);
+        assert!(prompt.contains("```objectivec\n#import <XCTest/XCTest.h>"));
+        assert!(prompt.contains("setAccessibilityIdentifier:"));
+        assert!(!prompt.contains("```swift"));
+
+        // `--language swift` overrides the extension and only searches Swift files
+        let pipeline = pipeline.with_options(PipelineOptions {
+            language: Some(SourceLanguage::Swift),
+            ..PipelineOptions::default()
+        });
+        assert_eq!(pipeline.source_language(&located), SourceLanguage::Swift);
+        assert!(
+            pipeline
+                .locate_test_file_step(&detail.test_identifier_url)
+                .is_err()
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disabled_tool_is_hidden_and_refused() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "test_runner",
+                serde_json::json!({"operation": "test", "test_identifier": "LoginTests/testLogin"}),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            disabled_tools: vec!["test_runner".to_string()],
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        let requests = state.requests.lock().unwrap();
+        let tool_names: Vec<_> = requests[0].tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(tool_names, vec!["directory_inspector", "code_editor"]);
+
+        // The call is answered with a refusal instead of running xcodebuild
+        let tool_result = &requests[1].messages.last().unwrap().content;
+        assert!(tool_result.contains("The test_runner tool is disabled for this run"));
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_edited_file_paths_are_workspace_relative() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![]);
+        // The model names the file by its absolute path
+        state.responses.lock().unwrap().extend([
+            Ok(MockProvider::tool_call(
+                "toolu_1",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": test_file.display().to_string(),
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTFail() }"
+                }),
+            )),
+            Ok(MockProvider::text("Done")),
+        ]);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        let report = pipeline.report();
+        assert_eq!(report.edits.len(), 1);
+        assert_eq!(report.edits[0].file_path, "LoginTests.swift");
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_are_counted_per_tool() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": "func testLogout() {}",
+                    "new_content": "func testLogout() { XCTFail() }"
+                }),
+            ),
+            MockProvider::tool_call(
+                "toolu_2",
+                "directory_inspector",
+                serde_json::json!({"operation": "read", "path": "LoginTests.swift"}),
+            ),
+            MockProvider::tool_call(
+                "toolu_3",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTAssertTrue(true) }"
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        let metrics = serde_json::to_value(&pipeline.report().tool_metrics).unwrap();
+        assert_eq!(metrics["code_editor"]["calls"], 2);
+        assert_eq!(metrics["code_editor"]["succeeded"], 1);
+        assert_eq!(metrics["code_editor"]["errored"], 1);
+        assert_eq!(metrics["directory_inspector"]["calls"], 1);
+        assert_eq!(metrics["directory_inspector"]["errored"], 0);
+        assert!(metrics.get("test_runner").is_none());
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_tool_calls_run_once() {
+        let read = serde_json::json!({"operation": "read", "path": "LoginTests.swift"});
+        let mut turn = MockProvider::tool_call("toolu_1", "directory_inspector", read.clone());
+        turn.tool_calls.push(ToolCall {
+            id: "toolu_2".to_string(),
+            name: "directory_inspector".to_string(),
+            input: read,
+        });
+        let (pipeline, _state, test_file) = mock_pipeline(vec![turn, MockProvider::text("Done")]);
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let pipeline = pipeline.with_options(PipelineOptions {
+            event_sink: Some(sender),
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // Read once, but each tool_use still gets its own, identical tool_result
+        let metrics = serde_json::to_value(&pipeline.report().tool_metrics).unwrap();
+        assert_eq!(metrics["directory_inspector"]["calls"], 1);
+        assert_eq!(pipeline.report().duplicate_tool_calls, 1);
+        let mut results = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let PipelineEvent::ToolResult { id, result, .. } = event {
+                results.push((id, result));
+            }
+        }
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "toolu_1");
+        assert_eq!(results[1].0, "toolu_2");
+        assert_eq!(results[0].1, results[1].1);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
     }
 
     #[test]
-    fn test_pipeline_temp_dir_has_uuid() {
-        let config = ProviderConfig::default();
-        let pipeline = AutofixPipeline::new(
-            "tests/fixtures/sample.xcresult",
-            "path/to/workspace",
-            false,
+    fn test_protected_paths_match_normalized_paths() {
+        let (pipeline, _state, _test_file) = mock_pipeline(vec![]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            protected_paths: vec![glob::Pattern::new("Generated/**").unwrap()],
+            ..PipelineOptions::default()
+        });
+
+        for path in [
+            "Generated/API.swift",
+            "./Generated/API.swift",
+            "Sources/../Generated/API.swift",
+        ] {
+            assert!(
+                pipeline.is_path_protected(path),
+                "{} is not protected",
+                path
+            );
+        }
+        let absolute = pipeline
+            .workspace_path
+            .join("Sources/../Generated/API.swift");
+        assert!(pipeline.is_path_protected(absolute.to_str().unwrap()));
+        assert!(!pipeline.is_path_protected("Sources/API.swift"));
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_after_an_edit_runs_again() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![]);
+        let read = serde_json::json!({"operation": "read", "path": "LoginTests.swift"});
+        let mut turn = MockProvider::tool_call("toolu_1", "directory_inspector", read.clone());
+        turn.tool_calls.push(ToolCall {
+            id: "toolu_2".to_string(),
+            name: "code_editor".to_string(),
+            input: serde_json::json!({
+                "file_path": test_file.display().to_string(),
+                "old_content": "func testLogin() {}",
+                "new_content": "func testLogin() { XCTFail() }"
+            }),
+        });
+        turn.tool_calls.push(ToolCall {
+            id: "toolu_3".to_string(),
+            name: "directory_inspector".to_string(),
+            input: read,
+        });
+        state
+            .responses
+            .lock()
+            .unwrap()
+            .extend([Ok(turn), Ok(MockProvider::text("Done"))]);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The second read sees the edit instead of reusing the first read's result
+        let metrics = serde_json::to_value(&pipeline.report().tool_metrics).unwrap();
+        assert_eq!(metrics["directory_inspector"]["calls"], 2);
+        assert_eq!(pipeline.report().duplicate_tool_calls, 0);
+        let requests = state.requests.lock().unwrap();
+        let tool_results = &requests[1].messages.last().unwrap().content;
+        assert!(tool_results.contains("XCTFail()"), "{}", tool_results);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_protected_path_edit_is_refused_even_with_yes() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTFail() }"
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            protected_paths: vec![glob::Pattern::new("Login*.swift").unwrap()],
+            assume_yes: true,
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "func testLogin() {}\n"
+        );
+        let requests = state.requests.lock().unwrap();
+        let tool_result = &requests[1].messages.last().unwrap().content;
+        assert!(tool_result.contains("LoginTests.swift is protected by .autofix/protected.toml"));
+        let report = pipeline.report();
+        assert!(report.edits.is_empty());
+        assert_eq!(report.protected_path_violations, vec!["LoginTests.swift"]);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_excluded_path_edit_is_refused() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTFail() }"
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            excluded_paths: vec![glob::Pattern::new("*Tests.swift").unwrap()],
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The file is untouched and the model is told why
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "func testLogin() {}\n"
+        );
+        let requests = state.requests.lock().unwrap();
+        let tool_result = &requests[1].messages.last().unwrap().content;
+        assert!(tool_result.contains("LoginTests.swift is excluded from automatic edits"));
+        assert!(pipeline.report().edits.is_empty());
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_tokens_follows_the_model_output_limit() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![MockProvider::text("Done")]);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // claude-sonnet-4 produces up to 64k output tokens
+        assert_eq!(state.requests.lock().unwrap()[0].max_tokens, Some(64_000));
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_used_up_token_budget_stops_the_run() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "directory_inspector",
+                serde_json::json!({ "operation": "read", "path": "LoginTests.swift" }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            token_budget: Some(100),
+            ..PipelineOptions::default()
+        });
+
+        let outcome = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The first response used 110 tokens, so no second request is sent
+        assert_eq!(outcome, PipelineOutcome::BudgetExhausted);
+        assert_eq!(state.requests.lock().unwrap().len(), 1);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_refused_test_run_is_not_a_test_result() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "test_runner",
+                serde_json::json!({
+                    "operation": "test",
+                    "test_identifier": "test://com.apple.xcode/App/AppE2ETests/CheckoutTests/testPurchase"
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            allowed_tests: vec!["AppUITests/LoginTests".to_string()],
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        let report = pipeline.report();
+        assert_eq!(report.test_passed, None);
+        assert_eq!(
+            report.refused_test_runs,
+            vec!["test://com.apple.xcode/App/AppE2ETests/CheckoutTests/testPurchase"]
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_revert_unfixed_restores_edited_and_created_files() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTFail() }"
+                }),
+            ),
+            MockProvider::tool_call(
+                "toolu_2",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "Helpers.swift",
+                    "operation": "create",
+                    "old_content": "",
+                    "new_content": "func helper() {}"
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            revert_unfixed: true,
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+        assert_eq!(pipeline.report().edits.len(), 2);
+        assert!(
+            pipeline
+                .temp_dir
+                .join("snapshots/LoginTests.swift")
+                .exists()
+        );
+
+        let restored = pipeline.restore_snapshots().unwrap();
+
+        assert_eq!(restored, vec!["Helpers.swift", "LoginTests.swift"]);
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "func testLogin() {}\n"
+        );
+        assert!(!pipeline.workspace_path.join("Helpers.swift").exists());
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_patch_only_writes_patch_and_leaves_files_unchanged() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTAssertTrue(true) }"
+                }),
+            ),
+            MockProvider::tool_call(
+                "toolu_2",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "Helpers.swift",
+                    "operation": "create",
+                    "old_content": "",
+                    "new_content": "func helper() {}\n"
+                }),
+            ),
+            // Sees the edits and writes into the copy, not the working tree
+            MockProvider::tool_call("toolu_3", "generate", serde_json::json!({})),
+            MockProvider::text("Done"),
+        ]);
+        let patch_path = pipeline.workspace_path.join("fix.patch");
+        let pipeline = pipeline.with_options(PipelineOptions {
+            patch_only: Some(patch_path.clone()),
+            custom_tools: vec![CustomTool {
+                name: "generate".to_string(),
+                description: "Generate code".to_string(),
+                input_schema: serde_json::json!({ "type": "object", "properties": {} }),
+                command: "cat LoginTests.swift > Generated.swift".to_string(),
+            }],
+            ..PipelineOptions::default()
+        });
+
+        let checkpoint = pipeline.options.virtual_files.checkpoint();
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+        pipeline.record(|report| report.test_passed = Some(true));
+        pipeline.finish_patch(&patch_path, checkpoint);
+
+        assert_eq!(pipeline.report().edits.len(), 2);
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "func testLogin() {}\n"
+        );
+        assert!(!pipeline.workspace_path.join("Helpers.swift").exists());
+        assert!(!pipeline.workspace_path.join("Generated.swift").exists());
+
+        let patched = pipeline.tool_workspace().unwrap();
+        assert_eq!(
+            fs::read_to_string(patched.join("LoginTests.swift")).unwrap(),
+            "func testLogin() { XCTAssertTrue(true) }\n"
+        );
+        if cfg!(unix) {
+            assert_eq!(
+                fs::read_to_string(patched.join("Generated.swift")).unwrap(),
+                "func testLogin() { XCTAssertTrue(true) }\n"
+            );
+        }
+        if let Ok(status) = std::process::Command::new("git")
+            .arg("apply")
+            .arg(&patch_path)
+            .current_dir(&pipeline.workspace_path)
+            .status()
+        {
+            assert!(status.success());
+            assert_eq!(
+                fs::read_to_string(&test_file).unwrap(),
+                "func testLogin() { XCTAssertTrue(true) }\n"
+            );
+            assert_eq!(
+                fs::read_to_string(pipeline.workspace_path.join("Helpers.swift")).unwrap(),
+                "func helper() {}\n"
+            );
+        }
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+        let _ = fs::remove_dir_all(&pipeline.temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_stops_the_loop_between_iterations() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "directory_inspector",
+                serde_json::json!({"operation": "read", "path": "LoginTests.swift"}),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        // Cancel while the tool results wait to go back to the model, as Ctrl-C would
+        let cancellation = CancellationToken::default();
+        let repl_cancellation = cancellation.clone();
+        let pipeline = pipeline.with_options(PipelineOptions {
+            repl: Some(ReplInput::new(move || {
+                repl_cancellation.cancel();
+                Some("\n".to_string())
+            })),
+            cancellation,
+            ..PipelineOptions::default()
+        });
+
+        let outcome = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(outcome, PipelineOutcome::Cancelled);
+        assert_eq!(state.requests.lock().unwrap().len(), 1);
+        assert_eq!(pipeline.report().iterations, 1);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+        let _ = fs::remove_dir_all(&pipeline.temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_transcript_is_written_into_the_workspace() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![MockProvider::text("Done")]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            transcript_dir: Some(PathBuf::from(".autofix/transcripts")),
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        let transcripts = pipeline.workspace_path.join(".autofix/transcripts");
+        assert_eq!(fs::read_dir(&transcripts).unwrap().count(), 1);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+        let _ = fs::remove_dir_all(&pipeline.temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_repl_sends_user_messages_between_turns() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::text("Should I change the test or the app?"),
+            MockProvider::tool_call(
+                "toolu_1",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTAssert(true) }"
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        let mut lines = vec!["Only touch the test\n", "\n", "exit\n"].into_iter();
+        let pipeline = pipeline.with_options(PipelineOptions {
+            repl: Some(ReplInput::new(move || lines.next().map(str::to_string))),
+            ..PipelineOptions::default()
+        });
+
+        let outcome = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(outcome, PipelineOutcome::Unverified);
+        let requests = state.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(
+            requests[1].messages.last().unwrap().content,
+            "Only touch the test"
+        );
+        assert!(
+            !requests[2]
+                .messages
+                .last()
+                .unwrap()
+                .content
+                .contains("Only touch")
+        );
+        assert_eq!(pipeline.report().edits.len(), 1);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_repl_exit_keeps_the_last_turn_and_is_not_fixed() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![MockProvider::tool_call(
+            "toolu_1",
+            "directory_inspector",
+            serde_json::json!({"operation": "read", "path": "LoginTests.swift"}),
+        )]);
+        let mut lines = vec!["exit\n"].into_iter();
+        let pipeline = pipeline.with_options(PipelineOptions {
+            repl: Some(ReplInput::new(move || lines.next().map(str::to_string))),
+            transcript_dir: Some(PathBuf::from(".autofix/transcripts")),
+            ..PipelineOptions::default()
+        });
+
+        let outcome = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(outcome, PipelineOutcome::NotFixed);
+        let transcripts = pipeline.workspace_path.join(".autofix/transcripts");
+        let transcript = fs::read_dir(&transcripts)
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .next()
+            .unwrap();
+        assert!(transcript.contains("## Turn 2"));
+        assert!(transcript.contains("Tool result (`toolu_1`)"));
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hallucinated_paths_reground_then_stop() {
+        let bad_read = |id: &str| {
+            MockProvider::tool_call(
+                id,
+                "directory_inspector",
+                serde_json::json!({
+                    "operation": "read",
+                    "path": format!("Sources/Hallucinated/{}.swift", id)
+                }),
+            )
+        };
+        let mut responses: Vec<_> = (1..=6)
+            .map(|index| bad_read(&format!("toolu_{}", index)))
+            .collect();
+        responses.push(MockProvider::text("Done"));
+        let (pipeline, state, test_file) = mock_pipeline(responses);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // After three misses the model is shown the workspace, after three more the run stops
+        let requests = state.requests.lock().unwrap();
+        assert_eq!(requests.len(), 6);
+        let regrounded = &requests[3].messages.last().unwrap().content;
+        assert!(regrounded.contains("Your last 3 directory_inspector calls failed"));
+        assert!(regrounded.contains("- LoginTests.swift"));
+        assert!(
+            !requests[2]
+                .messages
+                .last()
+                .unwrap()
+                .content
+                .contains("NOTE:")
+        );
+        assert_eq!(pipeline.report().outcome, TestOutcome::StuckExploring);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_streaming_request_routes_to_complete_stream() {
+        let mut edit = MockProvider::tool_call(
+            "toolu_1",
+            "code_editor",
+            serde_json::json!({
+                "file_path": "LoginTests.swift",
+                "old_content": "func testLogin() {}",
+                "new_content": "func testLogin() { XCTAssert(true) }"
+            }),
+        );
+        edit.content = Some("Adding the assertion".to_string());
+        let (pipeline, state, test_file) = mock_pipeline(vec![edit, MockProvider::text("Done")]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            stream: true,
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // Both requests were streamed and the deltas merged back into tool calls and usage
+        assert_eq!(*state.stream_calls.lock().unwrap(), 2);
+        assert!(state.requests.lock().unwrap().iter().all(|r| r.stream));
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "func testLogin() { XCTAssert(true) }\n"
+        );
+        assert_eq!(pipeline.report().token_usage.total_tokens, 220);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_token_budget_cuts_off_a_streaming_response() {
+        // 400 characters stream in as about 100 output tokens
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::text(&"a".repeat(400)),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            stream: true,
+            token_budget: Some(50),
+            ..PipelineOptions::default()
+        });
+
+        let outcome = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The rest of the response is dropped and its streamed output counted
+        assert_eq!(outcome, PipelineOutcome::BudgetExhausted);
+        assert_eq!(state.requests.lock().unwrap().len(), 1);
+        assert_eq!(pipeline.report().token_usage.output_tokens, 100);
+        assert!(!pipeline.options.cancellation.is_cancelled());
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancel_mid_stream_records_partial_usage() {
+        let (pipeline, _state, test_file) = mock_pipeline_with(
+            MockProvider::with_responses(vec![MockProvider::text(&"a".repeat(400))])
+                .with_stalled_streams(),
+        );
+        let pipeline = pipeline.with_options(PipelineOptions {
+            stream: true,
+            ..PipelineOptions::default()
+        });
+        let cancellation = pipeline.options.cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            cancellation.cancel();
+        });
+
+        let outcome = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(outcome, PipelineOutcome::Cancelled);
+        assert_eq!(pipeline.report().token_usage.output_tokens, 100);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_context_length_exceeded_trims_history_and_retries() {
+        let read = |id: &str| {
+            MockProvider::tool_call(
+                id,
+                "directory_inspector",
+                serde_json::json!({ "operation": "read", "path": "LoginTests.swift" }),
+            )
+        };
+        let (pipeline, state, test_file) = mock_pipeline_with(MockProvider::with_results(vec![
+            Ok(read("toolu_1")),
+            Ok(read("toolu_2")),
+            Ok(read("toolu_3")),
+            Err(LLMError::ContextLengthExceeded {
+                limit: Some(200000),
+                requested: Some(210000),
+            }),
+            Ok(MockProvider::text("Done")),
+        ]));
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The retry drops older turns but keeps the original prompt and the latest tool result
+        let requests = state.requests.lock().unwrap();
+        assert_eq!(requests.len(), 5);
+        let (rejected, retried) = (&requests[3].messages, &requests[4].messages);
+        assert!(retried.len() < rejected.len());
+        assert_eq!(retried[0].content, "Fix the test");
+        assert_eq!(
+            retried.last().unwrap().content,
+            rejected.last().unwrap().content
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_error_stop_reason_fails_the_run() {
+        let mut filtered = MockProvider::text("I can't help with that.");
+        filtered.stop_reason = StopReason::Error;
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "directory_inspector",
+                serde_json::json!({ "operation": "read", "path": "LoginTests.swift" }),
+            ),
+            filtered,
+            MockProvider::text("Done"),
+        ]);
+
+        let result = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await;
+
+        // The error turn is neither taken as the model finishing nor answered
+        match result {
+            Err(PipelineError::AnthropicApiError(message)) => assert_eq!(
+                message,
+                "Provider ended the turn with an error: I can't help with that."
+            ),
+            other => panic!("expected a provider error, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(state.requests.lock().unwrap().len(), 2);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_compaction_replaces_older_turns_with_summary() {
+        let (pipeline, state, _) = mock_pipeline(vec![MockProvider::text(
+            "- Already learned: the button lives in LoginView.swift",
+        )]);
+        let turn = |n: usize| -> ConversationTurn {
+            (
+                vec![ContentBlockParam::text(format!("Tool result {}", n))],
+                vec![ContentBlock::Text {
+                    text: format!("Reading file {}", n),
+                }],
+            )
+        };
+        let mut history: Vec<ConversationTurn> = (0..6).map(turn).collect();
+        let before = AutofixPipeline::to_messages(&history, &[]).len();
+
+        assert_eq!(pipeline.compact_history(&mut history).await, 4);
+
+        let messages = AutofixPipeline::to_messages(&history, &[]);
+        assert!(messages.len() < before);
+        assert_eq!(messages[0].content, "Tool result 0");
+        assert!(
+            messages[2]
+                .content
+                .contains("Already learned: the button lives in LoginView.swift")
+        );
+        assert_eq!(messages.last().unwrap().content, "Reading file 5");
+        // The summary request sees the summarized turns and offers no tools
+        let requests = state.requests.lock().unwrap();
+        assert!(requests[0].tools.is_empty());
+        assert!(
+            requests[0]
+                .messages
+                .iter()
+                .any(|message| message.content.contains("Reading file 4"))
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_compaction_is_not_retried_while_history_stays_above_threshold() {
+        let read = |id: &str| {
+            MockProvider::tool_call(
+                id,
+                "directory_inspector",
+                serde_json::json!({ "operation": "read", "path": "LoginTests.swift" }),
+            )
+        };
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            read("toolu_1"),
+            read("toolu_2"),
+            read("toolu_3"),
+            read("toolu_4"),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            compact_history_tokens: Some(500),
+            ..PipelineOptions::default()
+        });
+
+        // The original prompt alone is above the threshold and is never summarized
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test. ".repeat(300))],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The few small reads since don't add up to another threshold's worth of turns
+        let requests = state.requests.lock().unwrap();
+        assert_eq!(requests.len(), 5);
+        assert!(requests.iter().all(|request| !request.tools.is_empty()));
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[test]
+    fn test_nearly_identical_snapshot_after_edit_has_no_visible_effect() {
+        assert!(AutofixPipeline::snapshot_progress_note(0.995, true).contains("no visible effect"));
+        assert!(
+            AutofixPipeline::snapshot_progress_note(0.9, true)
+                .contains("the UI changed since your last edit (90.0% similar")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_report_lists_edited_sides() {
+        let edit = |id: &str, file_path: &str, old: &str, new: &str| {
+            MockProvider::tool_call(
+                id,
+                "code_editor",
+                serde_json::json!({
+                    "file_path": file_path,
+                    "old_content": old,
+                    "new_content": new
+                }),
+            )
+        };
+        let (pipeline, _state, test_file) = mock_pipeline(vec![
+            edit(
+                "toolu_1",
+                "Sources/LoginView.swift",
+                "Button(\"Login\")",
+                "Button(\"Login\").accessibilityIdentifier(\"login\")",
+            ),
+            edit(
+                "toolu_2",
+                "LoginTests.swift",
+                "func testLogin() {}",
+                "func testLogin() { XCTAssert(true) }",
+            ),
+            MockProvider::text("Done"),
+        ]);
+        fs::create_dir_all(pipeline.workspace_path.join("Sources")).unwrap();
+        fs::write(
+            pipeline.workspace_path.join("Sources/LoginView.swift"),
+            "Button(\"Login\")\n",
+        )
+        .unwrap();
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            pipeline.report().edited_sides,
+            vec![FixTarget::App, FixTarget::Test]
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[test]
+    fn test_combined_prompt_keeps_assertion_rules() {
+        let prompt = prompts::generate_prompt(
+            FixTarget::Both,
+            &sample_detail(),
+            "",
+            SourceLanguage::Swift,
+            Path::new("/tmp"),
+            false,
+        );
+        assert!(prompt.contains("Prefer minimal app changes"));
+        assert!(prompt.contains("NEVER delete or comment out test assertions"));
+        assert!(prompt.contains("GIVING UP:"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_custom_tool_is_offered_and_dispatched() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call("toolu_1", "echo", serde_json::json!({ "message": "hi" })),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            custom_tools: vec![CustomTool {
+                name: "echo".to_string(),
+                description: "Echo a message".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": { "message": { "type": "string" } },
+                    "required": ["message"]
+                }),
+                command: "echo {message}".to_string(),
+            }],
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        let requests = state.requests.lock().unwrap();
+        assert!(requests[0].tools.iter().any(|tool| tool.name == "echo"));
+        let tool_result: serde_json::Value =
+            serde_json::from_str(&requests[1].messages.last().unwrap().content).unwrap();
+        assert_eq!(tool_result["success"], true);
+        assert_eq!(tool_result["stdout"], "hi\n");
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_test_run_without_edits_is_skipped() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "directory_inspector",
+                serde_json::json!({"operation": "list", "path": "."}),
+            ),
+            MockProvider::tool_call(
+                "toolu_2",
+                "test_runner",
+                serde_json::json!({
+                    "operation": "test",
+                    "test_identifier": "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin"
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // Only reads happened, so xcodebuild was never spawned
+        let requests = state.requests.lock().unwrap();
+        let note = &requests[2].messages.last().unwrap().content;
+        assert!(note.contains("no files were edited since its last run"));
+        assert_eq!(pipeline.report().test_passed, None);
+        assert!(
+            !pipeline
+                .workspace_path
+                .join(TestRunnerTool::RUNS_DIR)
+                .exists()
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_malformed_tool_input_is_returned_to_model() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "code_editor",
+                serde_json::json!({
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTAssert(true) }"
+                }),
+            ),
+            MockProvider::tool_call(
+                "toolu_2",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTAssert(true) }"
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The schema violation is reported to the model, which retries with a valid call
+        let requests = state.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        let violation = &requests[1].messages.last().unwrap().content;
+        assert!(violation.contains("missing required field \\\"file_path\\\""));
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "func testLogin() { XCTAssert(true) }\n"
+        );
+        assert_eq!(pipeline.report().outcome, TestOutcome::Unverified);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_undeserializable_tool_input_is_returned_to_model() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "directory_inspector",
+                serde_json::json!({
+                    "operation": "read",
+                    "path": "LoginTests.swift",
+                    "start_line": -1
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // Matches the schema, but not the tool's input type
+        let requests = state.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let tool_result = &requests[1].messages.last().unwrap().content;
+        assert!(tool_result.contains("Invalid input for directory_inspector"));
+        assert_eq!(pipeline.report().outcome, TestOutcome::Unverified);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_oscillating_edits_stop_the_run() {
+        let edit = |id: &str, old_content: &str, new_content: &str| {
+            MockProvider::tool_call(
+                id,
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": old_content,
+                    "new_content": new_content
+                }),
+            )
+        };
+        let (a, b) = ("func testLogin() {}", "func testLogin() { sleep(1) }");
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            edit("toolu_1", a, b),
+            edit("toolu_2", b, a),
+            edit("toolu_3", a, b),
+            MockProvider::text("Done"),
+        ]);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The third edit restores the state after the first, so the run stops there
+        assert_eq!(state.requests.lock().unwrap().len(), 3);
+        let report = pipeline.report();
+        assert_eq!(report.outcome, TestOutcome::Oscillation);
+        let oscillation = report.oscillation.unwrap();
+        assert_eq!(oscillation.cycle_length, 2);
+        assert_eq!(
+            oscillation.states[0]["LoginTests.swift"],
+            format!("{}\n", b)
+        );
+        assert_eq!(
+            oscillation.states[1]["LoginTests.swift"],
+            format!("{}\n", a)
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dirty_file_is_edited_and_reported_with_yes() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": "func testLogin() {}",
+                    "new_content": "func testLogin() { XCTFail() }"
+                }),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            assume_yes: true,
+            ..PipelineOptions::default()
+        });
+
+        // Commit the test file, then leave an uncommitted change in it
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(&pipeline.workspace_path)
+                .args([
+                    "-c",
+                    "user.name=autofix",
+                    "-c",
+                    "user.email=autofix@example.com",
+                ])
+                .args(args)
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+        if !(git(&["init", "-q"]) && git(&["add", "."]) && git(&["commit", "-q", "-m", "init"])) {
+            fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+            return;
+        }
+        fs::write(&test_file, "// work in progress\nfunc testLogin() {}\n").unwrap();
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        let report = pipeline.report();
+        assert_eq!(report.dirty_files, vec!["LoginTests.swift"]);
+        assert_eq!(report.edits.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "// work in progress\nfunc testLogin() { XCTFail() }\n"
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_edits_of_earlier_tests_are_not_dirty() {
+        let edit = |old: &str, new: &str| {
+            vec![
+                MockProvider::tool_call(
+                    "toolu_1",
+                    "code_editor",
+                    serde_json::json!({
+                        "file_path": "LoginTests.swift",
+                        "old_content": old,
+                        "new_content": new
+                    }),
+                ),
+                MockProvider::text("Done"),
+            ]
+        };
+        let (first, _state, test_file) =
+            mock_pipeline(edit("func testLogin() {}", "func testLogin() { a() }"));
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(&first.workspace_path)
+                .args([
+                    "-c",
+                    "user.name=autofix",
+                    "-c",
+                    "user.email=autofix@example.com",
+                ])
+                .args(args)
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+        if !(git(&["init", "-q"]) && git(&["add", "."]) && git(&["commit", "-q", "-m", "init"])) {
+            fs::remove_dir_all(&first.workspace_path).unwrap();
+            return;
+        }
+        // Two tests of one run share the options
+        let options = PipelineOptions::default();
+        let first = first.with_options(options.clone());
+        let second = AutofixPipeline::with_provider(
+            PathBuf::from("tests/fixtures/sample.xcresult"),
+            first.workspace_path.clone(),
+            FixTarget::Test,
+            false,
+            ProviderConfig::default(),
+            Box::new(MockProvider::with_responses(edit(
+                "func testLogin() { a() }",
+                "func testLogin() { b() }",
+            ))),
+        )
+        .unwrap()
+        .with_options(options);
+
+        for pipeline in [&first, &second] {
+            pipeline
+                .run_with_tools(
+                    vec![ContentBlockParam::text("Fix the test")],
+                    &sample_detail(),
+                    &test_file,
+                )
+                .await
+                .unwrap();
+            assert!(pipeline.report().dirty_files.is_empty());
+            assert_eq!(pipeline.report().edits.len(), 1);
+        }
+
+        fs::remove_dir_all(&first.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_no_tool_support_falls_back_to_single_shot() {
+        let provider = MockProvider::with_responses(vec![MockProvider::text(
+            "Root cause: the button label changed",
+        )])
+        .with_capabilities(ProviderCapabilities {
+            tools: false,
+            ..ProviderCapabilities::default()
+        });
+        let (pipeline, state, test_file) = mock_pipeline_with(provider);
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Analyze the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // One request, no tools offered, finished without running the test
+        let requests = state.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].tools.is_empty());
+        let report = pipeline.report();
+        assert_eq!(report.outcome, TestOutcome::Unverified);
+        assert_eq!(report.iterations, 1);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_no_image_support_drops_snapshot_with_note() {
+        let provider = MockProvider::with_responses(vec![MockProvider::text("Done")])
+            .with_capabilities(ProviderCapabilities {
+                images: false,
+                ..ProviderCapabilities::default()
+            });
+        let (pipeline, state, test_file) = mock_pipeline_with(provider);
+
+        pipeline
+            .run_with_tools(
+                vec![
+                    ContentBlockParam::text("Fix the test"),
+                    ContentBlockParam::image_base64("image/png", "iVBORw0KGgo="),
+                ],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        let requests = state.requests.lock().unwrap();
+        assert!(
+            requests[0].messages[0]
+                .content
+                .contains(SNAPSHOT_OMITTED_NOTE)
+        );
+        // Tools are still offered
+        assert_eq!(requests[0].tools.len(), 3);
+
+        let dropped = AutofixPipeline::drop_unsupported_images(
+            vec![ContentBlockParam::image_base64("image/png", "iVBORw0KGgo=")],
+            ProviderCapabilities {
+                images: false,
+                ..ProviderCapabilities::default()
+            },
+        );
+        assert!(
+            !dropped
+                .iter()
+                .any(|block| matches!(block, ContentBlockParam::Image { .. }))
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_give_up_names_unresolved_assertion() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![MockProvider::text(
+            "GIVING UP: Unable to fix assertion failure after 2 attempts\nFile: /tmp/LoginTests.swift\nLine: 41",
+        )]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            editor: Editor::None,
+            ..PipelineOptions::default()
+        });
+        let failure = |message: &str, line: u32| FailureDetail {
+            message: message.to_string(),
+            file: Some("LoginTests.swift".to_string()),
+            line: Some(line),
+        };
+        let detail = XCTestResultDetail {
+            failures: vec![
+                failure("XCTAssertEqual failed", 34),
+                failure("Welcome label does not exist", 41),
+            ],
+            ..sample_detail()
+        };
+        let prompt = prompts::generate_standard_prompt(
+            &detail,
+            "",
+            SourceLanguage::Swift,
+            Path::new("/tmp"),
+            false,
+        );
+
+        pipeline
+            .run_with_tools(vec![ContentBlockParam::text(&prompt)], &detail, &test_file)
+            .await
+            .unwrap();
+
+        // Both assertions are in the prompt, the give-up names the one at line 41
+        let requests = state.requests.lock().unwrap();
+        assert!(requests[0].messages[0].content.contains(
+            "1. LoginTests.swift:34: XCTAssertEqual failed\n2. LoginTests.swift:41: Welcome label does not exist"
+        ));
+        assert_eq!(
+            pipeline.report().unresolved_assertion,
+            Some(failure("Welcome label does not exist", 41))
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_give_up_produces_gave_up_outcome() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![MockProvider::text(
+            "GIVING UP: The welcome label is never shown after login\nFile: /tmp/LoginTests.swift\nLine: 41",
+        )]);
+
+        let outcome = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            outcome,
+            PipelineOutcome::GaveUp {
+                file: Some("/tmp/LoginTests.swift".to_string()),
+                line: Some(41),
+                reason: "The welcome label is never shown after login".to_string(),
+            }
+        );
+        assert_eq!(pipeline.report().outcome, TestOutcome::GaveUp);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_model_ladder_advances_after_stalls() {
+        let read = |id: &str| {
+            MockProvider::tool_call(
+                id,
+                "directory_inspector",
+                serde_json::json!({"operation": "read", "path": "LoginTests.swift"}),
+            )
+        };
+        let (pipeline, cheap_state, test_file) =
+            mock_pipeline(vec![read("toolu_1"), read("toolu_2")]);
+        let strong = MockProvider::with_responses(vec![MockProvider::text("Done")]);
+        let strong_state = strong.state.clone();
+        let pipeline = pipeline.with_options(PipelineOptions {
+            model_ladder: Some(ModelLadder {
+                policy: LadderPolicy::Escalate,
+                models: vec!["cheap".to_string(), "strong".to_string()],
+                switch_after: 2,
+            }),
+            ..PipelineOptions::default()
+        });
+        let _ = pipeline.ladder.set(vec![Box::new(strong)]);
+        // An earlier test run passed, so finishing counts as a fix
+        pipeline.record(|report| report.test_passed = Some(true));
+
+        let outcome = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(outcome, PipelineOutcome::Fixed);
+        assert_eq!(cheap_state.requests.lock().unwrap().len(), 2);
+        // The stronger model picks up the whole conversation
+        let strong_requests = strong_state.requests.lock().unwrap();
+        assert_eq!(strong_requests.len(), 1);
+        assert!(strong_requests[0].messages.len() > 1);
+        let report = pipeline.report();
+        assert_eq!(report.fixed_by_model.as_deref(), Some("strong"));
+        assert_eq!(
+            report.model_usage.keys().collect::<Vec<_>>(),
+            vec!["cheap", "strong"]
+        );
+        assert_eq!(report.model_usage["cheap"].input_tokens, 200);
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_model_ladder_advances_instead_of_giving_up_on_oscillation() {
+        let edit = |id: &str, old_content: &str, new_content: &str| {
+            MockProvider::tool_call(
+                id,
+                "code_editor",
+                serde_json::json!({
+                    "file_path": "LoginTests.swift",
+                    "old_content": old_content,
+                    "new_content": new_content
+                }),
+            )
+        };
+        let (a, b) = ("func testLogin() {}", "func testLogin() { sleep(1) }");
+        let (pipeline, cheap_state, test_file) = mock_pipeline(vec![
+            edit("toolu_1", a, b),
+            edit("toolu_2", b, a),
+            edit("toolu_3", a, b),
+        ]);
+        let strong = MockProvider::with_responses(vec![MockProvider::text("Done")]);
+        let strong_state = strong.state.clone();
+        let pipeline = pipeline.with_options(PipelineOptions {
+            model_ladder: Some(ModelLadder {
+                policy: LadderPolicy::Escalate,
+                models: vec!["cheap".to_string(), "strong".to_string()],
+                switch_after: 10,
+            }),
+            ..PipelineOptions::default()
+        });
+        let _ = pipeline.ladder.set(vec![Box::new(strong)]);
+        pipeline.record(|report| report.test_passed = Some(true));
+
+        let outcome = pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The cycle hands the run to the stronger model rather than ending it
+        assert_eq!(outcome, PipelineOutcome::Fixed);
+        assert_eq!(cheap_state.requests.lock().unwrap().len(), 3);
+        assert_eq!(strong_state.requests.lock().unwrap().len(), 1);
+        let report = pipeline.report();
+        assert_eq!(report.oscillation, None);
+        assert_eq!(report.fixed_by_model.as_deref(), Some("strong"));
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dump_prompt_writes_each_iteration() {
+        let (pipeline, _state, test_file) = mock_pipeline(vec![
+            MockProvider::tool_call(
+                "toolu_1",
+                "directory_inspector",
+                serde_json::json!({"operation": "read", "path": "LoginTests.swift"}),
+            ),
+            MockProvider::text("Done"),
+        ]);
+        let dump_dir = pipeline.workspace_path.join("prompts");
+        let pipeline = pipeline.with_options(PipelineOptions {
+            dump_prompt_dir: Some(dump_dir.clone()),
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        let run_dir = dump_dir.join(pipeline.run_name(&sample_detail()));
+        let mut files: Vec<String> = fs::read_dir(&run_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "iteration-01.json",
+                "iteration-01.md",
+                "iteration-02.json",
+                "iteration-02.md"
+            ]
+        );
+        // The second request carries the first turn's tool result
+        let second = fs::read_to_string(run_dir.join("iteration-02.json")).unwrap();
+        let second: LLMRequest = serde_json::from_str(&second).unwrap();
+        assert!(second.messages.len() > 1);
+        assert!(second.tools.iter().any(|tool| tool.name == "code_editor"));
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handoff_requested_on_give_up() {
+        let (pipeline, state, test_file) = mock_pipeline(vec![
+            MockProvider::text("GIVING UP: Unable to fix assertion failure after 2 attempts"),
+            MockProvider::text("Root cause hypothesis: the login button was renamed"),
+        ]);
+        let pipeline = pipeline.with_options(PipelineOptions {
+            summarize_on_giveup: true,
+            ..PipelineOptions::default()
+        });
+
+        pipeline
+            .run_with_tools(
+                vec![ContentBlockParam::text("Fix the test")],
+                &sample_detail(),
+                &test_file,
+            )
+            .await
+            .unwrap();
+
+        // The handoff request sees the conversation and offers no tools
+        let requests = state.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].tools.is_empty());
+        assert!(requests[1].messages[1].content.contains("GIVING UP"));
+
+        let report = pipeline.report();
+        assert_eq!(report.outcome, TestOutcome::GaveUp);
+        assert_eq!(
+            report.handoff.as_deref(),
+            Some("Root cause hypothesis: the login button was renamed")
+        );
+
+        fs::remove_dir_all(&pipeline.workspace_path).unwrap();
+    }
+
+    #[test]
+    fn test_pipeline_creation() {
+        let config = ProviderConfig::default();
+        let pipeline = AutofixPipeline::new(
+            "tests/fixtures/sample.xcresult",
+            "path/to/workspace",
+            FixTarget::Test,
+            false,
+            config,
+        );
+
+        assert!(pipeline.is_ok());
+        let pipeline = pipeline.unwrap();
+
+        // Verify temp directory was created
+        assert!(pipeline.temp_dir.exists());
+        assert!(pipeline.temp_dir.starts_with(".autofix/tmp"));
+
+        // Cleanup
+        pipeline.cleanup().unwrap();
+    }
+
+    #[test]
+    fn test_pipeline_temp_dir_has_uuid() {
+        let config = ProviderConfig::default();
+        let pipeline = AutofixPipeline::new(
+            "tests/fixtures/sample.xcresult",
+            "path/to/workspace",
+            FixTarget::Test,
             false,
             config,
         )
@@ -0,0 +1,89 @@
+use std::path::Path;
+
+/// Editor opened at the failing assertion when the model gives up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Editor {
+    #[default]
+    Xcode,
+    Vscode,
+    Cursor,
+    /// Only print the location
+    None,
+}
+
+impl Editor {
+    /// Human-readable name for console output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Editor::Xcode => "Xcode",
+            Editor::Vscode => "VS Code",
+            Editor::Cursor => "Cursor",
+            Editor::None => "no editor",
+        }
+    }
+
+    /// URL that opens `file` at `line`, or `None` if nothing should be opened
+    ///
+    /// A relative `file` is resolved against `workspace`.
+    pub fn url(&self, file: &str, line: u32, workspace: &Path) -> Option<String> {
+        let file = Self::absolute(file, workspace);
+        match self {
+            Editor::Xcode => Some(format!("xed://open?file={}&line={}", file, line)),
+            Editor::Vscode => Some(format!("vscode://file{}:{}", file, line)),
+            Editor::Cursor => Some(format!("cursor://file{}:{}", file, line)),
+            Editor::None => None,
+        }
+    }
+
+    /// Editors can't resolve relative paths, and VS Code style URLs need the
+    /// leading slash an absolute path starts with
+    fn absolute(file: &str, workspace: &Path) -> String {
+        let path = workspace.join(file);
+        std::path::absolute(&path)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_per_editor() {
+        let file = "/Users/dev/App/AppUITests/LoginTests.swift";
+        let workspace = Path::new("/Users/dev/App");
+
+        assert_eq!(
+            Editor::Xcode.url(file, 42, workspace).as_deref(),
+            Some("xed://open?file=/Users/dev/App/AppUITests/LoginTests.swift&line=42")
+        );
+        assert_eq!(
+            Editor::Vscode.url(file, 42, workspace).as_deref(),
+            Some("vscode://file/Users/dev/App/AppUITests/LoginTests.swift:42")
+        );
+        assert_eq!(
+            Editor::Cursor.url(file, 42, workspace).as_deref(),
+            Some("cursor://file/Users/dev/App/AppUITests/LoginTests.swift:42")
+        );
+        assert_eq!(Editor::None.url(file, 42, workspace), None);
+    }
+
+    #[test]
+    fn test_relative_path_is_resolved_against_workspace() {
+        let workspace = Path::new("/Users/dev/App");
+        assert_eq!(
+            Editor::Vscode
+                .url("AppUITests/LoginTests.swift", 7, workspace)
+                .as_deref(),
+            Some("vscode://file/Users/dev/App/AppUITests/LoginTests.swift:7")
+        );
+        assert_eq!(
+            Editor::Xcode
+                .url("AppUITests/LoginTests.swift", 7, workspace)
+                .as_deref(),
+            Some("xed://open?file=/Users/dev/App/AppUITests/LoginTests.swift&line=7")
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Channel end that receives pipeline events as a run proceeds
+pub type EventSink = UnboundedSender<PipelineEvent>;
+
+/// A structured event emitted while the autofix pipeline runs
+///
+/// Events mirror the console output and are serialized as JSON so that
+/// external consumers (e.g. an IDE) can follow a run live.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PipelineEvent {
+    /// A pipeline step started
+    StepStarted { step: u32, name: String },
+
+    /// A new iteration of the agent loop started
+    IterationStarted { iteration: u32 },
+
+    /// The model produced text
+    ModelText { text: String },
+
+    /// A thinking model reasoned before answering
+    ModelThinking { text: String },
+
+    /// The model requested a tool call
+    ToolCall {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+
+    /// A tool call finished
+    ToolResult {
+        id: String,
+        name: String,
+        result: serde_json::Value,
+    },
+
+    /// A file in the workspace was edited
+    FileEdited {
+        file_path: String,
+        old_content: String,
+        new_content: String,
+    },
+
+    /// The model gave up on fixing the test
+    GaveUp { message: String },
+
+    /// The pipeline completed
+    Completed,
+
+    /// The pipeline failed with an error
+    Failed { error: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serializes_with_type_tag() {
+        let event = PipelineEvent::StepStarted {
+            step: 1,
+            name: "Fetching attachments".to_string(),
+        };
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "step_started");
+        assert_eq!(json["step"], 1);
+        assert_eq!(json["name"], "Fetching attachments");
+    }
+
+    #[test]
+    fn test_unit_event_serialization() {
+        let json = serde_json::to_string(&PipelineEvent::Completed).unwrap();
+        assert_eq!(json, r#"{"type":"completed"}"#);
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::Path;
+
+/// Consecutive failed or empty `directory_inspector` results before the pipeline steps in
+const MISS_THRESHOLD: usize = 3;
+
+/// Re-grounding messages sent before a run counts as stuck
+const MAX_REGROUNDINGS: usize = 1;
+
+/// What to do after the tool results of an iteration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplorationVerdict {
+    Continue,
+    /// Show the model the real workspace layout
+    Reground,
+    /// The model kept missing even after being re-grounded
+    Stuck,
+}
+
+/// Detects a model looking for paths that don't exist
+///
+/// Models sometimes hallucinate a project layout and keep listing, reading or
+/// searching paths that aren't there. Every failed or empty `directory_inspector`
+/// result counts as a miss; any other tool result resets the count.
+#[derive(Debug, Default)]
+pub struct ExplorationTracker {
+    consecutive_misses: usize,
+    regroundings: usize,
+}
+
+impl ExplorationTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one tool result
+    pub fn record(&mut self, tool_name: &str, result: &serde_json::Value) {
+        if tool_name == "directory_inspector" && Self::is_miss(result) {
+            self.consecutive_misses += 1;
+        } else {
+            self.consecutive_misses = 0;
+        }
+    }
+
+    /// Decide after an iteration's tool results were recorded
+    pub fn verdict(&mut self) -> ExplorationVerdict {
+        if self.consecutive_misses < MISS_THRESHOLD {
+            return ExplorationVerdict::Continue;
+        }
+        if self.regroundings >= MAX_REGROUNDINGS {
+            return ExplorationVerdict::Stuck;
+        }
+
+        self.regroundings += 1;
+        self.consecutive_misses = 0;
+        ExplorationVerdict::Reground
+    }
+
+    pub fn consecutive_misses(&self) -> usize {
+        self.consecutive_misses
+    }
+
+    /// A failed operation, or one that found nothing
+    fn is_miss(result: &serde_json::Value) -> bool {
+        if result.get("success").and_then(|success| success.as_bool()) != Some(true) {
+            return true;
+        }
+        // `find` wraps its matches in an object with the total count
+        result
+            .get("data")
+            .map(|data| data.get("files").unwrap_or(data))
+            .and_then(|entries| entries.as_array())
+            .is_some_and(|entries| entries.is_empty())
+    }
+}
+
+/// Message listing the top-level workspace entries, sent to re-ground a lost model
+pub fn regrounding_message(workspace_path: &Path, misses: usize) -> String {
+    let mut entries: Vec<String> = fs::read_dir(workspace_path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_str()?.to_string();
+                    if name.starts_with('.') {
+                        return None;
+                    }
+                    let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+                    Some(if is_dir { format!("{}/", name) } else { name })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    entries.sort();
+
+    format!(
+        "NOTE: Your last {} directory_inspector calls failed or found nothing, so the paths you are using probably don't exist. \
+        These are the actual top-level contents of the workspace {}:\n\n{}\n\n\
+        Start from these entries (use the \"find\" operation to locate files by name) instead of guessing paths.",
+        misses,
+        workspace_path.display(),
+        entries
+            .iter()
+            .map(|entry| format!("- {}", entry))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reground_then_stuck() {
+        let miss = json!({"success": false, "data": null, "error": "No such file or directory"});
+        let empty = json!({"success": true, "data": [], "error": null});
+        let hit = json!({"success": true, "data": [{"name": "App"}], "error": null});
+        let no_files = json!({"success": true, "data": {"total_matches": 0, "files": []}});
+        let mut tracker = ExplorationTracker::new();
+
+        tracker.record("directory_inspector", &miss);
+        tracker.record("directory_inspector", &empty);
+        assert_eq!(tracker.verdict(), ExplorationVerdict::Continue);
+        // A successful result starts the count over
+        tracker.record("directory_inspector", &hit);
+        tracker.record("directory_inspector", &miss);
+        tracker.record("directory_inspector", &miss);
+        assert_eq!(tracker.verdict(), ExplorationVerdict::Continue);
+
+        tracker.record("directory_inspector", &no_files);
+        assert_eq!(tracker.verdict(), ExplorationVerdict::Reground);
+        for _ in 0..MISS_THRESHOLD {
+            tracker.record("directory_inspector", &miss);
+        }
+        assert_eq!(tracker.verdict(), ExplorationVerdict::Stuck);
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// Copies of workspace files taken before their first edit, to revert a run without git
+///
+/// Files are mirrored under `dir` by their path relative to the workspace. A file
+/// the run creates is remembered as missing, so restoring deletes it again.
+pub struct FileSnapshots {
+    dir: PathBuf,
+    /// Original file by its full path, `None` if it did not exist
+    originals: Mutex<BTreeMap<PathBuf, Option<PathBuf>>>,
+}
+
+impl FileSnapshots {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            originals: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Copy `file_path` unless it was already saved; later edits keep the first copy
+    pub fn save(&self, workspace_path: &Path, file_path: &str) -> io::Result<()> {
+        let full_path = workspace_path.join(file_path);
+        let mut originals = self.originals.lock().unwrap();
+        if originals.contains_key(&full_path) {
+            return Ok(());
+        }
+
+        let copy = if full_path.exists() {
+            let copy = self
+                .dir
+                .join(Self::mirrored_path(workspace_path, &full_path));
+            if let Some(parent) = copy.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&full_path, &copy)?;
+            Some(copy)
+        } else {
+            None
+        };
+        originals.insert(full_path, copy);
+        Ok(())
+    }
+
+    /// Put every saved file back and delete files that did not exist before
+    ///
+    /// Returns the restored paths. The snapshots are kept, so a second call is harmless.
+    pub fn restore(&self) -> io::Result<Vec<PathBuf>> {
+        let originals = self.originals.lock().unwrap();
+        let mut restored = Vec::new();
+        for (full_path, copy) in originals.iter() {
+            match copy {
+                Some(copy) => {
+                    fs::copy(copy, full_path)?;
+                }
+                None => {
+                    if let Err(e) = fs::remove_file(full_path)
+                        && e.kind() != io::ErrorKind::NotFound
+                    {
+                        return Err(e);
+                    }
+                }
+            }
+            restored.push(full_path.clone());
+        }
+        Ok(restored)
+    }
+
+    /// Location of a file's copy inside the snapshot directory
+    fn mirrored_path(workspace_path: &Path, full_path: &Path) -> PathBuf {
+        // Paths outside the workspace keep their absolute layout below the snapshot dir
+        let relative = full_path.strip_prefix(workspace_path).unwrap_or(full_path);
+        relative
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_snapshot_edit_restore() {
+        let root = std::env::temp_dir().join(format!("file-snapshots-{}", Uuid::new_v4()));
+        let workspace = root.join("workspace");
+        fs::create_dir_all(workspace.join("Tests")).unwrap();
+        fs::write(workspace.join("Tests/LoginTests.swift"), "original").unwrap();
+        let snapshots = FileSnapshots::new(root.join("snapshots"));
+
+        snapshots
+            .save(&workspace, "Tests/LoginTests.swift")
+            .unwrap();
+        fs::write(workspace.join("Tests/LoginTests.swift"), "first edit").unwrap();
+        // A second save must not overwrite the original copy
+        snapshots
+            .save(&workspace, "Tests/LoginTests.swift")
+            .unwrap();
+        fs::write(workspace.join("Tests/LoginTests.swift"), "second edit").unwrap();
+        snapshots.save(&workspace, "Tests/Helper.swift").unwrap();
+        fs::write(workspace.join("Tests/Helper.swift"), "created").unwrap();
+
+        let restored = snapshots.restore().unwrap();
+
+        assert_eq!(restored.len(), 2);
+        assert_eq!(
+            fs::read_to_string(workspace.join("Tests/LoginTests.swift")).unwrap(),
+            "original"
+        );
+        assert!(!workspace.join("Tests/Helper.swift").exists());
+        assert!(root.join("snapshots/Tests/LoginTests.swift").exists());
+
+        // Clean up
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
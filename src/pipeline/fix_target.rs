@@ -0,0 +1,119 @@
+use super::accessibility;
+use crate::xctestresultdetailparser::{FailureKind, XCTestResultDetail};
+use serde::{Deserialize, Serialize};
+
+/// Failure messages pointing at the test code itself
+const TEST_FAILURE_MARKERS: &[&str] = &[
+    "multiple matching elements",
+    "invalid query",
+    "xctunwrap failed",
+    "unexpectedly found nil",
+    "index out of range",
+    "fatal error",
+    "test crashed",
+];
+
+/// Which side of a failing UI test the model is asked to change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum FixTarget {
+    /// The test is correct; fix the application code
+    App,
+    /// The app is correct; fix the test code
+    #[default]
+    Test,
+    /// Decide per test from its failure messages
+    Auto,
+    /// Fix both, with minimal app changes and the test's assertions kept
+    Both,
+}
+
+impl FixTarget {
+    /// Human-readable name for console output
+    pub fn name(&self) -> &'static str {
+        match self {
+            FixTarget::App => "fix app",
+            FixTarget::Test => "fix test",
+            FixTarget::Auto => "auto",
+            FixTarget::Both => "fix app and test",
+        }
+    }
+
+    /// Replace `Auto` by the target the failure of `detail` suggests
+    pub fn resolve(self, detail: &XCTestResultDetail) -> FixTarget {
+        match self {
+            FixTarget::Auto => Self::classify(detail),
+            target => target,
+        }
+    }
+
+    /// Classify a failure by counting app and test markers in its assertion messages
+    ///
+    /// A UI element that can't be found points at the app, and so does a performance
+    /// regression. Ties and unknown messages fall back to fixing the test, the
+    /// long-standing default.
+    fn classify(detail: &XCTestResultDetail) -> FixTarget {
+        if detail.failure_kind() == FailureKind::PerformanceRegression {
+            return FixTarget::App;
+        }
+        let count = |is_match: &dyn Fn(&str) -> bool| {
+            detail
+                .failures
+                .iter()
+                .filter(|failure| is_match(&failure.message))
+                .count()
+        };
+        let points_at_test = |message: &str| {
+            let message = message.to_lowercase();
+            TEST_FAILURE_MARKERS
+                .iter()
+                .any(|marker| message.contains(marker))
+        };
+
+        if count(&accessibility::is_missing_element) > count(&points_at_test) {
+            FixTarget::App
+        } else {
+            FixTarget::Test
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xctestresultdetailparser::FailureDetail;
+
+    fn detail_with_failures(messages: &[&str]) -> XCTestResultDetail {
+        XCTestResultDetail::failing(
+            messages
+                .iter()
+                .map(|message| FailureDetail {
+                    message: message.to_string(),
+                    file: None,
+                    line: None,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_auto_resolves_from_failure_messages() {
+        let missing_element = detail_with_failures(&[
+            "Failed to tap \"Login\" Button: No matches found for Elements matching predicate",
+        ]);
+        let crashed_test = detail_with_failures(&["Fatal error: Index out of range"]);
+        let unknown = detail_with_failures(&[]);
+        let slower = detail_with_failures(&[
+            "Clock Monotonic Time average is 24% worse than baseline (max allowed regression: 10%)",
+        ]);
+
+        assert_eq!(FixTarget::Auto.resolve(&missing_element), FixTarget::App);
+        assert_eq!(FixTarget::Auto.resolve(&crashed_test), FixTarget::Test);
+        assert_eq!(FixTarget::Auto.resolve(&unknown), FixTarget::Test);
+        assert_eq!(FixTarget::Auto.resolve(&slower), FixTarget::App);
+        // Explicit targets are kept regardless of the failure
+        assert_eq!(FixTarget::Test.resolve(&missing_element), FixTarget::Test);
+        assert_eq!(FixTarget::App.resolve(&crashed_test), FixTarget::App);
+        assert_eq!(FixTarget::Both.resolve(&missing_element), FixTarget::Both);
+    }
+}
@@ -1,4 +1,33 @@
+mod accessibility;
 mod autofix_pipeline;
+mod cancellation;
+mod editor;
+mod events;
+mod exploration;
+mod file_snapshots;
+mod fix_target;
+mod model_ladder;
+mod options;
+mod oscillation;
+mod outcome;
+mod prompt_dump;
 mod prompts;
+mod protected_paths;
+mod repl;
+mod retention;
+mod test_file_excerpt;
+mod tool_result_limit;
+mod transcript;
+mod virtual_files;
 
-pub use autofix_pipeline::{AutofixPipeline, PipelineError};
+pub use autofix_pipeline::{AutofixPipeline, PipelineError, PromptEstimate};
+pub use cancellation::CancellationToken;
+pub use editor::Editor;
+pub use events::{EventSink, PipelineEvent};
+pub use fix_target::FixTarget;
+pub use model_ladder::{DEFAULT_SWITCH_AFTER, LadderPolicy, ModelLadder};
+pub use options::PipelineOptions;
+pub use outcome::PipelineOutcome;
+pub use protected_paths::{PROTECTED_PATHS_FILE, ProtectedPathsError, load_protected_paths};
+pub use repl::ReplInput;
+pub use virtual_files::VirtualFiles;
@@ -0,0 +1,25 @@
+use serde::Serialize;
+
+/// Iterations without progress before switching to the next model unless
+/// `--switch-after` says otherwise
+pub const DEFAULT_SWITCH_AFTER: usize = 6;
+
+/// Which way the models of a ladder are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LadderPolicy {
+    /// Cheap models first, moving to stronger ones when they stall (`--escalate`)
+    Escalate,
+    /// Strong models first, moving to cheaper ones when they stall (`--deescalate`)
+    Deescalate,
+}
+
+/// Models of one provider to move through when the current one stops making progress
+#[derive(Debug, Clone)]
+pub struct ModelLadder {
+    pub policy: LadderPolicy,
+    /// Models in the order they are tried; the run starts with the first
+    pub models: Vec<String>,
+    /// Iterations without progress before switching to the next model
+    pub switch_after: usize,
+}
@@ -0,0 +1,115 @@
+use super::{CancellationToken, Editor, EventSink, ModelLadder, ReplInput, VirtualFiles};
+use crate::llm::TokenCounter;
+use crate::source_language::SourceLanguage;
+use crate::tools::CustomTool;
+use crate::tools::test_runner_tool::BuildResult;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Optional settings for an autofix pipeline run
+///
+/// Everything here has a sensible default so that callers only need to set
+/// what they care about.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineOptions {
+    /// Receives `PipelineEvent`s as the run proceeds
+    pub event_sink: Option<EventSink>,
+    /// Directory to write a markdown transcript of the conversation to, relative
+    /// to the workspace unless absolute
+    pub transcript_dir: Option<PathBuf>,
+    /// Directory to write each iteration's request to, as assembled before provider translation
+    pub dump_prompt_dir: Option<PathBuf>,
+    /// Models to switch between when the current one stalls (`--escalate`/`--deescalate`)
+    pub model_ladder: Option<ModelLadder>,
+    /// Names of tools the model must not use in this run
+    pub disabled_tools: Vec<String>,
+    /// Files the code editor must not write to, relative to the workspace
+    pub excluded_paths: Vec<glob::Pattern>,
+    /// Files the code editor must never write to, even with `--yes`, from the
+    /// workspace's `.autofix/protected.toml` and the project's `.autofix.toml`
+    pub protected_paths: Vec<glob::Pattern>,
+    /// Wait for the user's message after every model turn (`--interactive-repl`)
+    pub repl: Option<ReplInput>,
+    /// Ask the model for a handoff note for a human engineer when it gives up
+    pub summarize_on_giveup: bool,
+    /// Estimated request size, in tokens, above which the turns between the first and the
+    /// latest are replaced by a model-written summary; `None` never compacts
+    pub compact_history_tokens: Option<usize>,
+    /// Most model turns per test before giving up; `None` allows `DEFAULT_MAX_ITERATIONS`
+    pub max_iterations: Option<u32>,
+    /// Tokens the whole run may use, counted by `token_counter`; once used up, the current
+    /// test stops and later ones stop before their first request. `None` is unlimited
+    pub token_budget: Option<u32>,
+    /// Sampling temperature of the fix conversation; `None` uses `DEFAULT_TEMPERATURE`
+    pub temperature: Option<f32>,
+    /// Editor opened at the failing assertion when the model gives up
+    pub editor: Editor,
+    /// Edit files with uncommitted changes without asking (`--yes`), noting them in the report
+    pub assume_yes: bool,
+    /// Files that had uncommitted changes before the first edit; clones of these options
+    /// share it, so edits made for earlier tests of a run don't count as the user's
+    pub dirty_files: Arc<OnceLock<HashSet<PathBuf>>>,
+    /// Number of run directories to keep in `.autofix/tmp` and the test runner's
+    /// build directory; older ones are deleted at startup. `None` keeps all.
+    pub temp_retention: Option<usize>,
+    /// Copy files into the run directory before their first edit and restore them
+    /// when the test is not fixed, without relying on git
+    pub revert_unfixed: bool,
+    /// Write a successful fix to this file as a patch instead of editing the working tree
+    /// (`--patch-only`); tests run in a copy of the workspace with the edits applied
+    pub patch_only: Option<PathBuf>,
+    /// Edits made with `patch_only`; clones of these options share them, so the patch
+    /// covers the fixes of all tests of a run
+    pub virtual_files: Arc<VirtualFiles>,
+    /// Directory names the test file search skips, on top of build output and vendored code
+    pub ignored_dirs: Vec<String>,
+    /// JSON index mapping test classes to their files, used instead of searching
+    /// the workspace for `<Class>.swift`
+    pub test_file_index: Option<PathBuf>,
+    /// Language of the test files (`--language`); detected from the located file's
+    /// extension when `None`
+    pub language: Option<SourceLanguage>,
+    /// Simulator OS version the test runner targets, e.g. `18.5`
+    pub os_version: Option<String>,
+    /// xcodebuild `-destination` specifiers the test runner runs on (`--destination`);
+    /// the default simulator when empty
+    pub destinations: Vec<String>,
+    /// Extra arguments appended to the test runner's xcodebuild invocation
+    pub xcodebuild_args: Vec<String>,
+    /// Scheme to run tests with (`--scheme`); checked against the schemes xcodebuild lists
+    pub scheme: Option<String>,
+    /// Schemes `xcodebuild -list` reported, `None` if listing failed; clones of these
+    /// options share it, so the list is read once per run
+    pub available_schemes: Arc<OnceLock<Option<Vec<String>>>>,
+    /// Don't check the scheme and that the workspace builds before asking the model for a fix
+    pub skip_preflight: bool,
+    /// Preflight builds by scheme, `None` if xcodebuild couldn't run; clones of these
+    /// options share them, so each scheme is built once per run
+    pub preflight_builds: Arc<Mutex<HashMap<String, Option<BuildResult>>>>,
+    /// Erase the simulator before every test run so no state bleeds in from other tests
+    pub isolate: bool,
+    /// Target or class prefixes the test_runner tool may run (`--allow-testing`); any when empty
+    pub allowed_tests: Vec<String>,
+    /// Longest a single test run may take; `None` uses the test runner's default
+    pub test_timeout: Option<Duration>,
+    /// Longest a test run waits for other runs to release the simulator; `None` uses the default
+    pub simulator_lock_timeout: Option<Duration>,
+    /// Largest tool result, in bytes, sent to the model; longer ones are cut and saved
+    /// for paged reading. `None` uses the default of 64 KB
+    pub max_tool_result_bytes: Option<usize>,
+    /// Longest edge, in pixels, snapshots are downscaled to; `None` uses the encoder's default
+    pub max_image_dimension: Option<u32>,
+    /// Request streaming responses from providers that support them
+    pub stream: bool,
+    /// Command-backed project tools offered next to the built-in ones (`--allow-custom-tools`)
+    pub custom_tools: Vec<CustomTool>,
+    /// Live token count shown after every response; clones of these options share it,
+    /// so it spans all tests of a run
+    pub token_counter: Arc<TokenCounter>,
+    /// Stops the run between iterations and drops the provider request in flight when
+    /// cancelled (Ctrl-C, a `--serve` client that disconnects); clones of these options
+    /// share it, so it also skips the remaining tests of a run
+    pub cancellation: CancellationToken,
+}
@@ -0,0 +1,124 @@
+use crate::report::Oscillation;
+use std::collections::BTreeMap;
+
+/// Longest cycle of repeated workspace states that counts as oscillation
+const MAX_CYCLE_LENGTH: usize = 4;
+
+/// Contents of the files edited so far, keyed by path
+pub type WorkspaceState = BTreeMap<String, String>;
+
+/// Detects edits that keep returning the edited files to an earlier state
+///
+/// Record the state after every iteration that edited something. Once a state
+/// reappears within `MAX_CYCLE_LENGTH` recorded states, the model is going in
+/// circles and further iterations only burn tokens.
+///
+/// Only edits reaching a state not seen before count as progress; iterations
+/// without edits, and edits back to an earlier state, count as stalled.
+#[derive(Debug, Default)]
+pub struct OscillationDetector {
+    states: Vec<WorkspaceState>,
+    /// Iterations since the edits last reached a new state
+    stalled_iterations: usize,
+}
+
+impl OscillationDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the state after an editing iteration and return the cycle it closes, if any
+    pub fn record(&mut self, state: WorkspaceState) -> Option<Oscillation> {
+        if self.states.contains(&state) {
+            self.stalled_iterations += 1;
+        } else {
+            self.stalled_iterations = 0;
+        }
+
+        // An edit that changed nothing is not a cycle
+        if self.states.last() == Some(&state) {
+            return None;
+        }
+
+        let cycle = self
+            .states
+            .iter()
+            .rev()
+            .take(MAX_CYCLE_LENGTH)
+            .position(|previous| *previous == state)
+            .map(|distance| Oscillation {
+                cycle_length: distance + 1,
+                states: [
+                    state.clone(),
+                    self.states.last().cloned().unwrap_or_default(),
+                ],
+            });
+
+        self.states.push(state);
+        cycle
+    }
+
+    /// Record an iteration that edited nothing
+    pub fn record_no_edits(&mut self) {
+        self.stalled_iterations += 1;
+    }
+
+    /// Iterations since the edits last brought the files to a state not seen before
+    pub fn stalled_iterations(&self) -> usize {
+        self.stalled_iterations
+    }
+
+    /// Start counting stalled iterations again, e.g. after switching models
+    pub fn restart_progress(&mut self) {
+        self.stalled_iterations = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(content: &str) -> WorkspaceState {
+        BTreeMap::from([("LoginTests.swift".to_string(), content.to_string())])
+    }
+
+    #[test]
+    fn test_detects_a_b_a_cycle() {
+        let mut detector = OscillationDetector::new();
+
+        assert_eq!(detector.record(state("A")), None);
+        assert_eq!(detector.record(state("B")), None);
+        let oscillation = detector.record(state("A")).unwrap();
+
+        assert_eq!(oscillation.cycle_length, 2);
+        assert_eq!(oscillation.states, [state("A"), state("B")]);
+    }
+
+    #[test]
+    fn test_only_new_states_count_as_progress() {
+        let mut detector = OscillationDetector::new();
+
+        detector.record_no_edits();
+        detector.record(state("A"));
+        assert_eq!(detector.stalled_iterations(), 0);
+        detector.record(state("A"));
+        detector.record_no_edits();
+        assert_eq!(detector.stalled_iterations(), 2);
+        // Back to an earlier state is no progress
+        detector.record(state("B"));
+        detector.record(state("A"));
+        assert_eq!(detector.stalled_iterations(), 1);
+
+        detector.restart_progress();
+        assert_eq!(detector.stalled_iterations(), 0);
+    }
+
+    #[test]
+    fn test_progress_and_no_op_edits_are_not_oscillation() {
+        let mut detector = OscillationDetector::new();
+
+        for content in ["A", "B", "B", "C", "D", "E", "F", "A"] {
+            assert_eq!(detector.record(state(content)), None);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::report::TestOutcome;
+use std::fmt;
+
+/// How the pipeline run for a single test ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineOutcome {
+    /// The model finished and its last test run passed
+    Fixed,
+    /// The model finished, but the last test run still failed
+    NotFixed,
+    /// The model finished without running the test, so the fix is unconfirmed
+    Unverified,
+    /// The model gave up; `file` and `line` point at the assertion a human should look at
+    GaveUp {
+        file: Option<String>,
+        line: Option<u32>,
+        reason: String,
+    },
+    /// The iteration limit was reached
+    MaxIterations,
+    /// The edits kept returning the files to an earlier state
+    OscillationDetected,
+    /// The model kept looking for paths that don't exist, even after being shown the workspace
+    StuckExploring,
+    /// The run used up its token budget (`--token-budget`)
+    BudgetExhausted,
+    /// The run was cancelled, e.g. by Ctrl-C
+    Cancelled,
+}
+
+impl PipelineOutcome {
+    /// Give-up outcome for the model's `GIVING UP:` message, with the location it names
+    pub fn gave_up(text: &str, file: Option<String>, line: Option<u32>) -> Self {
+        let reason = text
+            .split_once("GIVING UP:")
+            .map_or(text, |(_, reason)| reason)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        Self::GaveUp { file, line, reason }
+    }
+}
+
+impl fmt::Display for PipelineOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed => write!(f, "fixed"),
+            Self::NotFixed => write!(f, "not fixed, the test still fails"),
+            Self::Unverified => write!(f, "finished without running the test"),
+            Self::GaveUp {
+                file: Some(file),
+                line: Some(line),
+                reason,
+            } => write!(f, "gave up at {}:{}: {}", file, line, reason),
+            Self::GaveUp { reason, .. } => write!(f, "gave up: {}", reason),
+            Self::MaxIterations => write!(f, "reached the iteration limit"),
+            Self::OscillationDetected => write!(f, "stopped, the edits went in circles"),
+            Self::StuckExploring => write!(f, "stopped, stuck exploring the workspace"),
+            Self::BudgetExhausted => write!(f, "stopped, the token budget is used up"),
+            Self::Cancelled => write!(f, "stopped, the run was cancelled"),
+        }
+    }
+}
+
+impl From<&PipelineOutcome> for TestOutcome {
+    fn from(outcome: &PipelineOutcome) -> Self {
+        match outcome {
+            PipelineOutcome::Fixed => TestOutcome::Completed,
+            PipelineOutcome::NotFixed => TestOutcome::NotFixed,
+            PipelineOutcome::Unverified => TestOutcome::Unverified,
+            PipelineOutcome::GaveUp { .. } => TestOutcome::GaveUp,
+            PipelineOutcome::MaxIterations => TestOutcome::MaxIterations,
+            PipelineOutcome::OscillationDetected => TestOutcome::Oscillation,
+            PipelineOutcome::StuckExploring => TestOutcome::StuckExploring,
+            PipelineOutcome::BudgetExhausted => TestOutcome::BudgetExhausted,
+            PipelineOutcome::Cancelled => TestOutcome::Cancelled,
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::llm::{LLMRequest, MessageRole};
+use crate::redact::redact;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Write `request` as `<name>.json` and `<name>.md` into `dir`
+///
+/// The request is written as the pipeline assembled it, before any provider
+/// translation. Known secrets and anything that looks like an API key are
+/// redacted, and base64 blobs are replaced by a short placeholder.
+pub fn write_request(
+    dir: &Path,
+    name: &str,
+    request: &LLMRequest,
+    secrets: &[&str],
+) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(request).map_err(io::Error::other)?;
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(format!("{}.json", name)), redact(&json, secrets))?;
+    fs::write(
+        dir.join(format!("{}.md", name)),
+        redact(&render_request(name, request), secrets),
+    )
+}
+
+/// Render a request as readable markdown
+fn render_request(name: &str, request: &LLMRequest) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Autofix Prompt: {}\n", name);
+    let _ = writeln!(
+        out,
+        "max_tokens: {}, temperature: {}, stream: {}\n",
+        request
+            .max_tokens
+            .map_or_else(|| "default".to_string(), |tokens| tokens.to_string()),
+        request
+            .temperature
+            .map_or_else(|| "default".to_string(), |t| t.to_string()),
+        request.stream
+    );
+
+    if let Some(system_prompt) = &request.system_prompt {
+        let _ = writeln!(out, "## System\n\n{}\n", system_prompt);
+    }
+
+    let _ = writeln!(out, "## Messages\n");
+    for message in &request.messages {
+        let role = match message.role {
+            MessageRole::User => "User",
+            MessageRole::Assistant => "Assistant",
+            MessageRole::Tool => "Tool",
+        };
+        let _ = writeln!(out, "### {}\n\n{}\n", role, message.content);
+    }
+
+    let _ = writeln!(out, "## Tools\n");
+    for tool in &request.tools {
+        let _ = writeln!(
+            out,
+            "### `{}`\n\n{}\n\n```json\n{}\n```\n",
+            tool.name,
+            tool.description,
+            serde_json::to_string_pretty(&tool.input_schema).unwrap_or_default()
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{Message, ToolDefinition};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_request_is_written_redacted() {
+        let dir = std::env::temp_dir().join(format!("prompt-dump-{}", Uuid::new_v4()));
+        let request = LLMRequest {
+            system_prompt: None,
+            messages: vec![Message {
+                role: MessageRole::User,
+                content: format!("Key secret-key, screenshot {}", "A".repeat(600)),
+            }],
+            tools: vec![ToolDefinition {
+                name: "code_editor".to_string(),
+                description: "Edit a file".to_string(),
+                input_schema: serde_json::json!({"type": "object"}),
+            }],
+            max_tokens: Some(1024),
+            temperature: None,
+            stream: false,
+        };
+
+        write_request(&dir, "iteration-01", &request, &["secret-key"]).unwrap();
+
+        let json = fs::read_to_string(dir.join("iteration-01.json")).unwrap();
+        let dumped: LLMRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            dumped.messages[0].content,
+            "Key [REDACTED], screenshot [base64 data, 600 bytes omitted]"
+        );
+        let markdown = fs::read_to_string(dir.join("iteration-01.md")).unwrap();
+        assert!(markdown.contains("### User\n\nKey [REDACTED]"));
+        assert!(markdown.contains("### `code_editor`\n\nEdit a file"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
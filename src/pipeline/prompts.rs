@@ -1,10 +1,109 @@
+use super::FixTarget;
+use crate::source_language::SourceLanguage;
 use crate::xctestresultdetailparser::XCTestResultDetail;
 use std::path::Path;
 
+/// List every failed assertion so the model fixes all of them, not just the first
+fn failing_assertions(detail: &XCTestResultDetail) -> String {
+    if detail.failures.is_empty() {
+        return String::new();
+    }
+
+    let mut section = String::from("\n\n**Failing Assertions:**");
+    for (index, failure) in detail.failures.iter().enumerate() {
+        match failure.location() {
+            Some(location) => section.push_str(&format!(
+                "\n{}. {}: {}",
+                index + 1,
+                location,
+                failure.message
+            )),
+            None => section.push_str(&format!("\n{}. {}", index + 1, failure.message)),
+        }
+    }
+    if detail.failures.len() > 1 {
+        section.push_str("\n\nThe test has several failing assertions. Fix ALL of them; the test only passes once none of them fails.");
+    }
+    section
+}
+
+/// Rules that keep the model from fixing a test by weakening it
+const ASSERTION_RULES: &str = r#"CRITICAL RULES ABOUT TEST ASSERTIONS:
+- NEVER delete or comment out test assertions (XCTAssert*, XCTFail, etc.)
+- NEVER remove test expectations or verification code
+- You MAY update assertion values to match the current app behavior
+- Assertions validate important app behavior - they must remain active
+- Common assertion updates needed:
+  * Update expected text/labels if app copy changed (e.g., "Login" → "Sign In")
+  * Update expected counts if UI elements were reorganized
+  * Update expected properties if design changed (e.g., button placement)
+- If an assertion needs to be updated, make the change and explain why
+- The assertion itself must stay - only the expected VALUES can change"#;
+
+/// How to give up, in the format the pipeline parses into the unresolved assertion
+fn give_up_policy(workspace_path: &Path) -> String {
+    format!(
+        r#"GIVE UP POLICY:
+- If you attempt to fix the test/app code 2 times and the assertion still fails in unexpected ways
+- STOP and provide a final message with this exact format:
+
+  GIVING UP: Unable to fix assertion failure after 2 attempts
+  Failed assertion: [exact line of code from test file]
+  File: [absolute file path starting from workspace]
+  Line: [line number]
+  Reason: [brief explanation of what you tried]
+
+- If the test has several failing assertions, report the one that still fails
+- Provide the FULL absolute path to the test file (e.g., {}/path/to/TestFile.swift)
+- Provide the exact LINE NUMBER where the assertion appears
+- This will automatically open Xcode at the failing assertion for manual review
+- DO NOT make any more code changes after giving up
+- DO NOT try alternative approaches beyond the 2 attempts"#,
+        workspace_path.display()
+    )
+}
+
+/// Generate the prompt for a tool-using run that changes `fix_target`
+///
+/// `FixTarget::Auto` has to be resolved first; unresolved it gets the test prompt.
+pub fn generate_prompt(
+    fix_target: FixTarget,
+    detail: &XCTestResultDetail,
+    test_file_contents: &str,
+    language: SourceLanguage,
+    workspace_path: &Path,
+    has_snapshot: bool,
+) -> String {
+    match fix_target {
+        FixTarget::App => generate_knightrider_prompt(
+            detail,
+            test_file_contents,
+            language,
+            workspace_path,
+            has_snapshot,
+        ),
+        FixTarget::Test | FixTarget::Auto => generate_standard_prompt(
+            detail,
+            test_file_contents,
+            language,
+            workspace_path,
+            has_snapshot,
+        ),
+        FixTarget::Both => generate_combined_prompt(
+            detail,
+            test_file_contents,
+            language,
+            workspace_path,
+            has_snapshot,
+        ),
+    }
+}
+
 /// Generate the prompt for Knight Rider mode (autonomous fixing with tools)
 pub fn generate_knightrider_prompt(
     detail: &XCTestResultDetail,
     test_file_contents: &str,
+    language: SourceLanguage,
     workspace_path: &Path,
     has_snapshot: bool,
 ) -> String {
@@ -13,10 +112,10 @@ pub fn generate_knightrider_prompt(
 
 **Failed Test:** {}
 **Test Identifier:** {}
-**Workspace Path:** {}
+**Workspace Path:** {}{}
 
 **Test File Contents:**
-```swift
+```{}
 {}
 ```
 
@@ -33,7 +132,7 @@ YOUR TASK: Use the available tools to automatically fix the APPLICATION CODE. Yo
 2. Use `directory_inspector` to read the app source files that the test interacts with
 3. Analyze the test to understand what it expects from the application
 4. Identify what's missing or incorrect in the APPLICATION CODE
-5. Use `code_editor` to make necessary changes to APPLICATION SOURCE CODE ONLY
+5. Use `code_editor` to make necessary changes to APPLICATION SOURCE CODE ONLY (operation "create" adds a new file, e.g. a missing view)
 6. Use `test_runner` with operation "test" to verify the test now passes
 
 IMPORTANT INSTRUCTIONS:
@@ -44,7 +143,7 @@ IMPORTANT INSTRUCTIONS:
 - If the first fix doesn't work, iterate and try different approaches
 - Common fixes needed in app code:
   * Add missing UI elements that the test expects
-  * Add accessibility identifiers to UI elements so tests can find them
+  * Add accessibility identifiers to UI elements so tests can find them (`accessibilityIdentifier`, not the user-facing `accessibilityLabel`)
   * Fix incorrect labels, text, or button titles
   * Ensure proper view hierarchy and element visibility
   * Add missing navigation or view transitions
@@ -54,6 +153,8 @@ Use this full identifier when calling test_runner."#,
         detail.test_name,
         detail.test_identifier_url,
         workspace_path.display(),
+        failing_assertions(detail),
+        language.code_fence(),
         test_file_contents,
         if has_snapshot {
             "**Simulator Snapshot:** I've attached the latest simulator screenshot showing the state when the test failed."
@@ -68,6 +169,7 @@ Use this full identifier when calling test_runner."#,
 pub fn generate_standard_prompt(
     detail: &XCTestResultDetail,
     test_file_contents: &str,
+    language: SourceLanguage,
     workspace_path: &Path,
     has_snapshot: bool,
 ) -> String {
@@ -76,10 +178,10 @@ pub fn generate_standard_prompt(
 
 **Failed Test:** {}
 **Test Identifier:** {}
-**Workspace Path:** {}
+**Workspace Path:** {}{}
 
 **Test File Contents:**
-```swift
+```{}
 {}
 ```
 
@@ -119,50 +221,344 @@ IMPORTANT INSTRUCTIONS:
   * Design changes: Update expected properties (labels, button text, etc.)
   * ALWAYS explain what changed and why the assertion was updated
 - If adding accessibility to app:
-  * Use `.accessibilityIdentifier("...")` in SwiftUI
-  * Use `element.accessibilityIdentifier = "..."` in UIKit
+{}
   * Keep identifier names clear and test-friendly
+  * Don't confuse the two: `accessibilityIdentifier` is for tests and never shown, `accessibilityLabel` is the localized text VoiceOver reads. `.matching(identifier:)` only finds identifiers, a `label ==` predicate only finds labels, and subscripts like `app.buttons["..."]` find either
 
-CRITICAL RULES ABOUT TEST ASSERTIONS:
-- NEVER delete or comment out test assertions (XCTAssert*, XCTFail, etc.)
-- NEVER remove test expectations or verification code
-- You MAY update assertion values to match the current app behavior
-- Assertions validate important app behavior - they must remain active
-- Common assertion updates needed:
-  * Update expected text/labels if app copy changed (e.g., "Login" → "Sign In")
-  * Update expected counts if UI elements were reorganized
-  * Update expected properties if design changed (e.g., button placement)
-- If an assertion needs to be updated, make the change and explain why
-- The assertion itself must stay - only the expected VALUES can change
+{}
 
-GIVE UP POLICY:
-- If you attempt to fix the test/app code 2 times and the assertion still fails in unexpected ways
-- STOP and provide a final message with this exact format:
+{}
 
-  GIVING UP: Unable to fix assertion failure after 2 attempts
-  Failed assertion: [exact line of code from test file]
-  File: [absolute file path starting from workspace]
-  Line: [line number]
-  Reason: [brief explanation of what you tried]
+The test identifier format is: {}
+Use this full identifier when calling test_runner."#,
+        detail.test_name,
+        detail.test_identifier_url,
+        workspace_path.display(),
+        failing_assertions(detail),
+        language.code_fence(),
+        test_file_contents,
+        if has_snapshot {
+            "**Simulator Snapshot:** I've attached the latest simulator screenshot showing the state when the test failed."
+        } else {
+            "**Note:** No simulator snapshot was available for this test."
+        },
+        language.accessibility_guidance(),
+        ASSERTION_RULES,
+        give_up_policy(workspace_path),
+        detail.test_identifier_url
+    )
+}
 
-- Provide the FULL absolute path to the test file (e.g., {}/path/to/TestFile.swift)
-- Provide the exact LINE NUMBER where the assertion appears
-- This will automatically open Xcode at the failing assertion for manual review
-- DO NOT make any more code changes after giving up
-- DO NOT try alternative approaches beyond the 2 attempts
+/// Generate the prompt for combined mode (fix app and test code, within explicit boundaries)
+pub fn generate_combined_prompt(
+    detail: &XCTestResultDetail,
+    test_file_contents: &str,
+    language: SourceLanguage,
+    workspace_path: &Path,
+    has_snapshot: bool,
+) -> String {
+    format!(
+        r#"I am analyzing a failed iOS UI test and need you to AUTOMATICALLY FIX IT using the provided tools.
+
+**Failed Test:** {}
+**Test Identifier:** {}
+**Workspace Path:** {}{}
+
+**Test File Contents:**
+```{}
+{}
+```
+
+{}
+
+ASSUMPTION: THE FIX MAY NEED BOTH THE APP AND THE TEST
+- Some failures need an app change (e.g. a missing accessibility identifier) and a test change (e.g. an outdated assertion value)
+- You may edit application code and test code, but decide for each change which side is actually wrong
+- The app's current behavior is intended unless the test clearly expects something the app is missing
+
+YOUR TASK: Use the available tools to automatically fix the test failure. You should:
+
+1. Use `directory_inspector` to explore the codebase and locate the test file and the app code it exercises
+2. Use `directory_inspector` to read the test file and the relevant app source files
+3. Analyze the test to understand what it expects from the application
+4. Decide which parts of the failure are caused by the TEST CODE and which by the APPLICATION CODE
+5. Use `code_editor` to fix the test code and, where needed, the application code
+6. Use `test_runner` with operation "test" to verify the test now passes
+
+IMPORTANT INSTRUCTIONS:
+- Prefer minimal app changes: add identifiers, labels or missing elements, but don't change app behavior to suit the test
+- Never rewrite app logic just to make an assertion pass
+- Make targeted, minimal changes to fix the specific test failure
+- After each code change, test to verify (testing also compiles the code)
+- If the first fix doesn't work, iterate and try different approaches
+- If adding accessibility to app:
+{}
+  * Keep identifier names clear and test-friendly
+  * Don't confuse the two: `accessibilityIdentifier` is for tests and never shown, `accessibilityLabel` is the localized text VoiceOver reads. `.matching(identifier:)` only finds identifiers, a `label ==` predicate only finds labels, and subscripts like `app.buttons["..."]` find either
+
+{}
+
+{}
 
 The test identifier format is: {}
 Use this full identifier when calling test_runner."#,
         detail.test_name,
         detail.test_identifier_url,
         workspace_path.display(),
+        failing_assertions(detail),
+        language.code_fence(),
         test_file_contents,
         if has_snapshot {
             "**Simulator Snapshot:** I've attached the latest simulator screenshot showing the state when the test failed."
         } else {
             "**Note:** No simulator snapshot was available for this test."
         },
+        language.accessibility_guidance(),
+        ASSERTION_RULES,
+        give_up_policy(workspace_path),
+        detail.test_identifier_url
+    )
+}
+
+/// Generate the prompt for a test that crashed instead of failing an assertion
+///
+/// The assertion-focused prompts mislead the model here, so it is pointed at the crash
+/// messages and the stack trace of the crash log, if one was attached.
+pub fn generate_crash_prompt(
+    detail: &XCTestResultDetail,
+    test_file_contents: &str,
+    language: SourceLanguage,
+    workspace_path: &Path,
+    fix_target: FixTarget,
+    crash_log: Option<&str>,
+) -> String {
+    let mut crash_messages = String::new();
+    if !detail.failures.is_empty() {
+        crash_messages.push_str("\n\n**Crash Messages:**");
+        for failure in &detail.failures {
+            crash_messages.push_str(&format!("\n- {}", failure.message));
+        }
+    }
+    let crash_log = match crash_log {
+        Some(crash_log) => format!("**Crash Log:**\n```\n{}\n```", crash_log),
+        None => "**Note:** No crash log was attached; work from the crash messages and the code."
+            .to_string(),
+    };
+    let scope = match fix_target {
+        FixTarget::App => {
+            "- The test is the source of truth: fix the crash in APPLICATION code only, never in test files"
+        }
+        FixTarget::Test | FixTarget::Both | FixTarget::Auto => {
+            "- Fix the crash where it happens: in the test code if the crashing frame is there (e.g. a force unwrap of a missing element), otherwise with a minimal change to the application code"
+        }
+    };
+
+    format!(
+        r#"I am analyzing an iOS UI test that CRASHED and need you to AUTOMATICALLY FIX IT using the provided tools.
+
+**Crashed Test:** {}
+**Test Identifier:** {}
+**Workspace Path:** {}{}
+
+**Test File Contents:**
+```{}
+{}
+```
+
+{}
+
+THIS IS A CRASH, NOT AN ASSERTION FAILURE
+- The process was killed by a signal or an uncaught exception; no assertion decided the outcome
+- Do not change assertion values - they did not cause the failure
+
+YOUR TASK: Use the available tools to find and fix the crashing call site. You should:
+
+1. Read the exception reason and the stack trace of the crashing thread, starting at its topmost frame in the app's or test's own code
+2. Use `directory_inspector` to read the source file and function of that frame
+3. Work out why it crashes there, e.g. a force unwrap of nil, an index out of range, a failed cast, a precondition or an unhandled Objective-C exception
+4. Use `code_editor` to fix the cause with a minimal change
+5. Use `test_runner` with operation "test" to verify the test no longer crashes and passes
+
+IMPORTANT INSTRUCTIONS:
+{}
+- Fix the cause instead of hiding the crash, e.g. do not wrap the call in a `try?` that silently skips the tested behavior
+- After each code change, test to verify (testing also compiles the code)
+
+{}
+
+{}
+
+The test identifier format is: {}
+Use this full identifier when calling test_runner."#,
+        detail.test_name,
+        detail.test_identifier_url,
         workspace_path.display(),
+        crash_messages,
+        language.code_fence(),
+        test_file_contents,
+        crash_log,
+        scope,
+        ASSERTION_RULES,
+        give_up_policy(workspace_path),
         detail.test_identifier_url
     )
 }
+
+/// Generate the prompt for a performance test whose measurements exceeded their baseline
+///
+/// Nothing in the UI is wrong, so instead of assertions the model gets the measured
+/// and baseline values and is asked to find what made the measured code slower.
+pub fn generate_performance_prompt(
+    detail: &XCTestResultDetail,
+    test_file_contents: &str,
+    language: SourceLanguage,
+    workspace_path: &Path,
+    fix_target: FixTarget,
+) -> String {
+    let mut measurements = String::new();
+    if !detail.performance_metrics.is_empty() {
+        measurements.push_str("\n\n**Measurements:**");
+        for metric in &detail.performance_metrics {
+            measurements.push_str(&format!("\n- {}", metric.summary()));
+        }
+    }
+    let scope = match fix_target {
+        FixTarget::Test => {
+            "- The app is the source of truth: only change the test code, e.g. move setup out of the `measure` block when it measures more than the behavior it is meant to; if the slowdown is in the app, give up and say so"
+        }
+        FixTarget::App | FixTarget::Both | FixTarget::Auto => {
+            "- Fix the slowdown in the APPLICATION code that runs inside the measured block"
+        }
+    };
+
+    format!(
+        r#"I am analyzing an iOS performance test that EXCEEDED ITS BASELINE and need you to AUTOMATICALLY FIX IT using the provided tools.
+
+**Slow Test:** {}
+**Test Identifier:** {}
+**Workspace Path:** {}{}{}
+
+**Test File Contents:**
+```{}
+{}
+```
+
+THIS IS A PERFORMANCE REGRESSION, NOT A FUNCTIONAL FAILURE
+- The measured code got slower (or used more of a resource) than the recorded baseline allows
+- The UI may look entirely correct; there is no wrong element or value to fix
+
+YOUR TASK: Use the available tools to find and fix what made the measured code slower. You should:
+
+1. Find the code the `measure` block exercises, starting from the test above
+2. Use `directory_inspector` to read that code and look for recent or obvious costs: work repeated per item, synchronous I/O or network calls on the main thread, missing caching, excessive layout passes or needless allocations
+3. Use `code_editor` to remove the cost with a minimal change that keeps the behavior
+4. Use `test_runner` with operation "test" to measure again and verify the test passes
+
+IMPORTANT INSTRUCTIONS:
+{}
+- Never raise the baseline, loosen the allowed regression or shrink what the `measure` block covers to make the test pass
+- After each code change, test to verify (testing also compiles the code)
+
+{}
+
+The test identifier format is: {}
+Use this full identifier when calling test_runner."#,
+        detail.test_name,
+        detail.test_identifier_url,
+        workspace_path.display(),
+        failing_assertions(detail),
+        measurements,
+        language.code_fence(),
+        test_file_contents,
+        scope,
+        give_up_policy(workspace_path),
+        detail.test_identifier_url
+    )
+}
+
+/// Generate a single-shot prompt for models without tool support
+///
+/// The model cannot explore or edit the workspace, so it is asked for a diagnosis
+/// and a proposed change instead.
+pub fn generate_analysis_prompt(
+    detail: &XCTestResultDetail,
+    test_file_contents: &str,
+    language: SourceLanguage,
+    workspace_path: &Path,
+    has_snapshot: bool,
+) -> String {
+    format!(
+        r#"I am analyzing a failed iOS UI test. No tools are available, so you cannot read other files, edit code or run tests.
+
+**Failed Test:** {}
+**Test Identifier:** {}
+**Workspace Path:** {}{}
+
+**Test File Contents:**
+```{}
+{}
+```
+
+{}
+
+YOUR TASK: Based only on the information above, explain why the test most likely fails and propose a fix.
+
+Please answer with:
+1. Root cause: the most likely reason for the failure
+2. Proposed fix: the exact code change, as a before/after snippet of the test file (or the app code, if the test is correct)
+3. Confidence: how sure you are and what information would confirm the diagnosis"#,
+        detail.test_name,
+        detail.test_identifier_url,
+        workspace_path.display(),
+        failing_assertions(detail),
+        language.code_fence(),
+        test_file_contents,
+        if has_snapshot {
+            "**Simulator Snapshot:** I've attached the latest simulator screenshot showing the state when the test failed."
+        } else {
+            "**Note:** No simulator snapshot was available for this test."
+        },
+    )
+}
+
+/// Generate the request to summarize the conversation so far, replacing older turns
+pub fn generate_compaction_prompt() -> &'static str {
+    r#"The conversation is getting long. Summarize what you learned so far so the earlier turns can be dropped.
+
+List the facts you still need to fix the test, one per line, for example:
+- Already learned: the login button lives in LoginView.swift and its identifier is "loginButton"
+- Missing: no element with the identifier "submit" exists in the app
+- Tried: renaming the identifier in the test, the test still failed with the same assertion
+
+Include file paths, identifiers and test results verbatim. Leave out anything that no longer matters. Do not call any tools."#
+}
+
+/// Generate the request for a handoff note after the model gave up
+pub fn generate_handoff_prompt() -> &'static str {
+    r#"You gave up on fixing this test. A human engineer will take over from here.
+
+Write a concise handoff note for them with these sections:
+- Root-cause hypothesis: what you believe is causing the failure
+- What was tried: the changes and checks you made, and their results
+- Ruled out: causes you eliminated and why
+- Suggested next steps: concrete actions for the engineer
+
+Keep it short and specific. Do not call any tools."#
+}
+
+/// Generate the note on the destinations the test runs on, empty for a single one
+pub fn destinations_section(destinations: &[String]) -> String {
+    if destinations.len() < 2 {
+        return String::new();
+    }
+
+    let mut section = String::from(
+        "\n\n**Destinations:**\n\
+        The test runs on each of these destinations, and the failure may be device-specific, e.g. a layout that only breaks on a smaller screen. \
+        A failed test_runner result names the devices it still fails on; the fix must pass on all of them:",
+    );
+    for destination in destinations {
+        section.push_str(&format!("\n- {}", destination));
+    }
+    section
+}
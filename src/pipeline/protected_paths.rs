@@ -0,0 +1,94 @@
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Protected path globs committed with the project, relative to the workspace
+pub const PROTECTED_PATHS_FILE: &str = ".autofix/protected.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProtectedPathsError {
+    #[error("Failed to read protected paths from {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("Invalid protected paths file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Invalid protected path pattern \"{pattern}\" in {path}: {source}")]
+    Pattern {
+        path: PathBuf,
+        pattern: String,
+        source: glob::PatternError,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProtectedPathsFile {
+    paths: Vec<String>,
+}
+
+/// Load the globs of files the model must never edit from the workspace's
+/// `.autofix/protected.toml`; a workspace without one protects nothing
+pub fn load_protected_paths(workspace: &Path) -> Result<Vec<glob::Pattern>, ProtectedPathsError> {
+    let path = workspace.join(PROTECTED_PATHS_FILE);
+    let toml = match fs::read_to_string(&path) {
+        Ok(toml) => toml,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => return Err(ProtectedPathsError::Read { path, source }),
+    };
+    let file: ProtectedPathsFile =
+        toml::from_str(&toml).map_err(|source| ProtectedPathsError::Parse {
+            path: path.clone(),
+            source,
+        })?;
+
+    file.paths
+        .into_iter()
+        .map(|pattern| {
+            glob::Pattern::new(&pattern).map_err(|source| ProtectedPathsError::Pattern {
+                path: path.clone(),
+                pattern,
+                source,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_load_protected_paths() {
+        let workspace = std::env::temp_dir().join(format!("protected-paths-{}", Uuid::new_v4()));
+        assert!(load_protected_paths(&workspace).unwrap().is_empty());
+
+        fs::create_dir_all(workspace.join(".autofix")).unwrap();
+        fs::write(
+            workspace.join(PROTECTED_PATHS_FILE),
+            "# Generated and vendored code\npaths = [\"Generated/**\", \"Pods/**\"]\n",
+        )
+        .unwrap();
+        let patterns = load_protected_paths(&workspace).unwrap();
+        assert!(patterns[0].matches_path(Path::new("Generated/API/Client.swift")));
+        assert!(!patterns[1].matches_path(Path::new("App/LoginView.swift")));
+
+        fs::write(workspace.join(PROTECTED_PATHS_FILE), "paths = [\"[\"]\n").unwrap();
+        assert!(matches!(
+            load_protected_paths(&workspace),
+            Err(ProtectedPathsError::Pattern { .. })
+        ));
+        fs::write(workspace.join(PROTECTED_PATHS_FILE), "path = []\n").unwrap();
+        assert!(matches!(
+            load_protected_paths(&workspace),
+            Err(ProtectedPathsError::Parse { .. })
+        ));
+
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+}
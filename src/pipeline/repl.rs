@@ -0,0 +1,136 @@
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, LazyLock, Mutex};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Lines typed on the terminal, read by one thread for the whole process
+///
+/// A question given up on, e.g. when the run is cancelled, leaves its line
+/// queued for the next question instead of on a reader nobody waits for.
+static STDIN_LINES: LazyLock<tokio::sync::Mutex<UnboundedReceiver<String>>> = LazyLock::new(|| {
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        loop {
+            let mut line = String::new();
+            match std::io::stdin().lock().read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    tokio::sync::Mutex::new(receiver)
+});
+
+/// Next line typed on the terminal, or `None` once input ends
+///
+/// Safe to drop before it finishes: the line stays queued for the next call.
+pub async fn read_stdin_line() -> Option<String> {
+    STDIN_LINES.lock().await.recv().await
+}
+
+/// What the user answered after a model turn in `--interactive-repl`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplReply {
+    /// Send this message to the model
+    Message(String),
+    /// Go on without a message, sending only the tool results
+    Continue,
+    /// End the conversation
+    Exit,
+}
+
+type ReadLine = dyn FnMut() -> Option<String> + Send;
+
+/// Source of the user's messages in `--interactive-repl`
+///
+/// Clones share the source, so one terminal serves every test of a run.
+#[derive(Clone)]
+pub struct ReplInput {
+    source: Source,
+}
+
+#[derive(Clone)]
+enum Source {
+    Stdin,
+    Lines(Arc<Mutex<ReadLine>>),
+}
+
+impl fmt::Debug for ReplInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplInput").finish_non_exhaustive()
+    }
+}
+
+impl ReplInput {
+    /// Read lines with `read_line`, which returns `None` once input ends
+    pub fn new(read_line: impl FnMut() -> Option<String> + Send + 'static) -> Self {
+        Self {
+            source: Source::Lines(Arc::new(Mutex::new(read_line))),
+        }
+    }
+
+    /// Read the user's messages from the terminal, sharing its reader with other questions
+    pub fn stdin() -> Self {
+        Self {
+            source: Source::Stdin,
+        }
+    }
+
+    /// Ask for the next message; `tools_ran` offers going on with just the tool results
+    ///
+    /// `exit`, `quit` and the end of input end the conversation, as does an empty
+    /// line when there is nothing to go on with. The line is read off the runtime,
+    /// so it keeps running while the user types.
+    pub async fn ask(&self, tools_ran: bool) -> ReplReply {
+        if tools_ran {
+            print!("\n💬 You (Enter to continue, exit to stop): ");
+        } else {
+            print!("\n💬 You (exit to stop): ");
+        }
+        let _ = std::io::stdout().flush();
+
+        let line = match &self.source {
+            Source::Stdin => read_stdin_line().await,
+            Source::Lines(read_line) => {
+                let read_line = read_line.clone();
+                tokio::task::spawn_blocking(move || (read_line.lock().unwrap())())
+                    .await
+                    .ok()
+                    .flatten()
+            }
+        };
+        let Some(line) = line else {
+            return ReplReply::Exit;
+        };
+        match line.trim() {
+            "exit" | "quit" => ReplReply::Exit,
+            "" if tools_ran => ReplReply::Continue,
+            "" => ReplReply::Exit,
+            message => ReplReply::Message(message.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ask() {
+        let mut lines = vec!["  Check LoginView first\n", "\n", "\n", "quit\n"].into_iter();
+        let input = ReplInput::new(move || lines.next().map(str::to_string));
+
+        assert_eq!(
+            input.ask(false).await,
+            ReplReply::Message("Check LoginView first".to_string())
+        );
+        assert_eq!(input.ask(true).await, ReplReply::Continue);
+        assert_eq!(input.ask(false).await, ReplReply::Exit);
+        assert_eq!(input.ask(true).await, ReplReply::Exit);
+        assert_eq!(input.ask(true).await, ReplReply::Exit);
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Delete all but the `keep` most recently modified run directories in `base_dir`
+///
+/// Only directories named by a UUID are considered, so anything else a user
+/// put there survives. Returns the directories that were removed.
+pub fn prune_run_dirs(base_dir: &Path, keep: usize) -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(base_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut run_dirs: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| Uuid::parse_str(name).is_ok())
+        })
+        .map(|entry| {
+            let modified = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            (modified, entry.path())
+        })
+        .collect();
+
+    // Newest first
+    run_dirs.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+    let mut removed = Vec::new();
+    for (_, dir) in run_dirs.into_iter().skip(keep) {
+        fs::remove_dir_all(&dir)?;
+        removed.push(dir);
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_keeps_newest_run_dirs() {
+        let base_dir = std::env::temp_dir().join(format!("autofix-retention-{}", Uuid::new_v4()));
+        let now = SystemTime::now();
+
+        // Five run directories, each one minute older than the previous
+        let run_dirs: Vec<PathBuf> = (0..5)
+            .map(|age| {
+                let dir = base_dir.join(Uuid::new_v4().to_string());
+                fs::create_dir_all(&dir).unwrap();
+                fs::File::open(&dir)
+                    .unwrap()
+                    .set_modified(now - Duration::from_secs(60 * age))
+                    .unwrap();
+                dir
+            })
+            .collect();
+        let unrelated = base_dir.join("notes");
+        fs::create_dir_all(&unrelated).unwrap();
+
+        let mut removed = prune_run_dirs(&base_dir, 2).unwrap();
+        removed.sort();
+
+        let mut expected = run_dirs[2..].to_vec();
+        expected.sort();
+        assert_eq!(removed, expected);
+        assert!(run_dirs[0].exists() && run_dirs[1].exists());
+        assert!(unrelated.exists());
+
+        // Clean up
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_base_dir_is_not_an_error() {
+        let base_dir = std::env::temp_dir().join(format!("autofix-retention-{}", Uuid::new_v4()));
+        assert!(prune_run_dirs(&base_dir, 1).unwrap().is_empty());
+    }
+}
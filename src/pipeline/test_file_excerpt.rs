@@ -0,0 +1,139 @@
+use crate::xctestresultdetailparser::XCTestResultDetail;
+
+/// Test files larger than this are trimmed before they go into the prompt
+const MAX_TEST_FILE_BYTES: usize = 40_000;
+
+/// Lines kept before and after each failing line
+const CONTEXT_LINES: usize = 60;
+
+/// The parts of a huge test file worth sending, or `None` if the whole file fits
+///
+/// Keeps the lines around every failing assertion and replaces the rest with
+/// markers naming the omitted line ranges, so the model can read them with
+/// `directory_inspector` if needed. Without a failing line the start of the file
+/// is kept up to the size limit.
+pub fn excerpt(contents: &str, detail: &XCTestResultDetail) -> Option<String> {
+    if contents.len() <= MAX_TEST_FILE_BYTES {
+        return None;
+    }
+    let lines: Vec<&str> = contents.lines().collect();
+
+    // 0-based, inclusive ranges around the failing lines, merged where they overlap
+    let mut failing_lines: Vec<usize> = detail
+        .failures
+        .iter()
+        .filter_map(|failure| failure.line)
+        .map(|line| (line as usize).saturating_sub(1))
+        .filter(|&index| index < lines.len())
+        .collect();
+    failing_lines.sort_unstable();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for index in failing_lines {
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + CONTEXT_LINES).min(lines.len() - 1);
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end + 1 => *last_end = end.max(*last_end),
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    if ranges.is_empty() {
+        let mut size = 0;
+        let kept = lines
+            .iter()
+            .take_while(|line| {
+                size += line.len() + 1;
+                size <= MAX_TEST_FILE_BYTES
+            })
+            .count();
+        if kept > 0 {
+            ranges.push((0, kept - 1));
+        }
+    }
+
+    let omitted = |first: usize, last: usize| {
+        format!(
+            "// ... lines {}-{} omitted, the file is too large to include in full ...",
+            first + 1,
+            last + 1
+        )
+    };
+    let mut excerpt = Vec::new();
+    let mut next = 0;
+    for (start, end) in ranges {
+        if start > next {
+            excerpt.push(omitted(next, start - 1));
+        }
+        excerpt.extend(lines[start..=end].iter().map(|line| line.to_string()));
+        next = end + 1;
+    }
+    if next < lines.len() {
+        excerpt.push(omitted(next, lines.len() - 1));
+    }
+    Some(excerpt.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xctestresultdetailparser::FailureDetail;
+
+    fn detail_failing_at(lines: &[u32]) -> XCTestResultDetail {
+        XCTestResultDetail::failing(
+            lines
+                .iter()
+                .map(|&line| FailureDetail {
+                    message: "XCTAssertTrue failed".to_string(),
+                    file: Some("LoginTests.swift".to_string()),
+                    line: Some(line),
+                })
+                .collect(),
+        )
+    }
+
+    /// A generated test file with one assertion per line, numbered like the file
+    fn huge_file() -> String {
+        (1..=5000)
+            .map(|line| {
+                format!(
+                    "        XCTAssertTrue(app.buttons[\"Button{}\"].exists)",
+                    line
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_small_files_are_sent_whole() {
+        assert_eq!(
+            excerpt("func testLogin() {}\n", &detail_failing_at(&[1])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_huge_file_keeps_region_around_failing_line() {
+        let excerpt = excerpt(&huge_file(), &detail_failing_at(&[2500])).unwrap();
+
+        assert!(excerpt.len() < MAX_TEST_FILE_BYTES);
+        assert!(excerpt.contains("\"Button2500\""));
+        assert!(excerpt.contains("\"Button2440\""));
+        assert!(excerpt.contains("\"Button2560\""));
+        assert!(!excerpt.contains("\"Button2439\""));
+        assert!(!excerpt.contains("\"Button2561\""));
+        assert!(excerpt.starts_with("// ... lines 1-2439 omitted"));
+        assert!(excerpt.ends_with(
+            "// ... lines 2561-5000 omitted, the file is too large to include in full ..."
+        ));
+    }
+
+    #[test]
+    fn test_huge_file_without_failing_line_keeps_its_start() {
+        let excerpt = excerpt(&huge_file(), &detail_failing_at(&[])).unwrap();
+
+        assert!(excerpt.len() <= MAX_TEST_FILE_BYTES + 100);
+        assert!(excerpt.starts_with("        XCTAssertTrue(app.buttons[\"Button1\"].exists)"));
+        assert!(excerpt.contains("omitted, the file is too large"));
+    }
+}
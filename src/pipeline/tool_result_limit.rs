@@ -0,0 +1,88 @@
+use serde_json::Value;
+use std::path::Path;
+
+/// Largest tool result sent to the model unless `--max-tool-result-bytes` says otherwise
+pub const DEFAULT_MAX_TOOL_RESULT_BYTES: usize = 64 * 1024;
+
+/// Lines the truncation marker suggests reading per page
+const PAGE_LINES: usize = 200;
+
+/// `content` cut to at most `max_bytes`, followed by a marker saying how much was left
+/// out and, if the full result was saved to `full_result`, how to page through it
+pub fn truncate(content: &str, max_bytes: usize, full_result: Option<&Path>) -> String {
+    let mut end = max_bytes.min(content.len());
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let pointer = match full_result {
+        Some(path) => format!(
+            " The full result is saved to {path}; read it in pages with directory_inspector, e.g. {{\"operation\": \"read\", \"path\": \"{path}\", \"start_line\": 1, \"line_count\": {PAGE_LINES}}}.",
+            path = path.display(),
+        ),
+        None => String::new(),
+    };
+    format!(
+        "{}\n\n[TRUNCATED: showing the first {} of {} bytes.{}]",
+        &content[..end],
+        end,
+        content.len(),
+        pointer
+    )
+}
+
+/// The result as text for paged reading: every top-level string field written out with
+/// its real line breaks, so long output like xcodebuild's stdout spans many lines
+pub fn render_for_paging(result: &Value) -> String {
+    let Value::Object(fields) = result else {
+        return serde_json::to_string_pretty(result).unwrap_or_default();
+    };
+    fields
+        .iter()
+        .map(|(name, value)| match value {
+            Value::String(text) => format!("## {}\n{}\n", name, text),
+            value => format!(
+                "## {}\n{}\n",
+                name,
+                serde_json::to_string_pretty(value).unwrap_or_default()
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oversized_result_is_truncated_with_pointer() {
+        let result = serde_json::json!({
+            "success": false,
+            "stdout": "Compiling LoginView.swift\n".repeat(10_000),
+        });
+        let content = result.to_string();
+
+        let truncated = truncate(&content, 1000, Some(Path::new("/tmp/run/toolu_1.txt")));
+
+        assert!(truncated.len() < 1400);
+        assert!(truncated.starts_with(&content[..1000]));
+        assert!(truncated.contains(&format!(
+            "[TRUNCATED: showing the first 1000 of {} bytes.",
+            content.len()
+        )));
+        assert!(truncated.contains(r#""path": "/tmp/run/toolu_1.txt", "start_line": 1"#));
+
+        let rendered = render_for_paging(&result);
+        assert!(rendered.starts_with("## stdout\nCompiling LoginView.swift\n"));
+        assert!(rendered.lines().count() > 10_000);
+    }
+
+    #[test]
+    fn test_truncate_keeps_characters_whole() {
+        assert_eq!(
+            truncate("ab✓cd", 3, None),
+            "ab\n\n[TRUNCATED: showing the first 2 of 7 bytes.]"
+        );
+    }
+}
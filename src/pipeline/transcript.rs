@@ -0,0 +1,157 @@
+use crate::redact::redact;
+use anthropic_sdk::{ContentBlock, ContentBlockParam, ImageSource};
+use std::fmt::Write;
+
+/// One turn of the conversation: what was sent to the model and what it answered
+pub type ConversationTurn = (Vec<ContentBlockParam>, Vec<ContentBlock>);
+
+/// Render a conversation as a human-readable markdown transcript
+///
+/// Known secrets and anything that looks like an API key are redacted, and
+/// base64 blobs are replaced by a short placeholder.
+pub fn render_transcript(title: &str, history: &[ConversationTurn], secrets: &[&str]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Autofix Transcript: {}\n", title);
+
+    for (index, (user_content, assistant_content)) in history.iter().enumerate() {
+        let _ = writeln!(out, "## Turn {}\n", index + 1);
+
+        for block in user_content {
+            match block {
+                ContentBlockParam::Text { text } => {
+                    let _ = writeln!(out, "### User\n\n{}\n", text);
+                }
+                ContentBlockParam::Image { source } => {
+                    let _ = writeln!(out, "### User\n\n{}\n", image_placeholder(source));
+                }
+                ContentBlockParam::ToolUse { id, name, input } => {
+                    write_tool_call(&mut out, id, name, input);
+                }
+                ContentBlockParam::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error,
+                } => {
+                    let label = if *is_error == Some(true) {
+                        "Tool error"
+                    } else {
+                        "Tool result"
+                    };
+                    let _ = writeln!(
+                        out,
+                        "### {} (`{}`)\n\n````\n{}\n````\n",
+                        label,
+                        tool_use_id,
+                        content.as_deref().unwrap_or_default()
+                    );
+                }
+            }
+        }
+
+        for block in assistant_content {
+            match block {
+                ContentBlock::Text { text } => {
+                    let _ = writeln!(out, "### Assistant\n\n{}\n", text);
+                }
+                ContentBlock::Image { source } => {
+                    let _ = writeln!(out, "### Assistant\n\n{}\n", image_placeholder(source));
+                }
+                ContentBlock::ToolUse { id, name, input } => {
+                    write_tool_call(&mut out, id, name, input);
+                }
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    ..
+                } => {
+                    let _ = writeln!(
+                        out,
+                        "### Tool result (`{}`)\n\n````\n{}\n````\n",
+                        tool_use_id,
+                        content.as_deref().unwrap_or_default()
+                    );
+                }
+            }
+        }
+    }
+
+    redact(&out, secrets)
+}
+
+fn write_tool_call(out: &mut String, id: &str, name: &str, input: &serde_json::Value) {
+    let _ = writeln!(
+        out,
+        "### Tool call: `{}` (`{}`)\n\n```json\n{}\n```\n",
+        name,
+        id,
+        serde_json::to_string_pretty(input).unwrap_or_default()
+    );
+}
+
+fn image_placeholder(source: &ImageSource) -> String {
+    match source {
+        ImageSource::Base64 { media_type, data } => {
+            format!(
+                "_[{} image, {} bytes of base64 omitted]_",
+                media_type,
+                data.len()
+            )
+        }
+        ImageSource::Url { url } => format!("_[image: {}]_", url),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_transcript() {
+        let history = vec![(
+            vec![
+                ContentBlockParam::text("Fix the test"),
+                ContentBlockParam::image_base64("image/jpeg", "A".repeat(4096)),
+            ],
+            vec![
+                ContentBlock::Text {
+                    text: "Let me look around".to_string(),
+                },
+                ContentBlock::ToolUse {
+                    id: "toolu_1".to_string(),
+                    name: "directory_inspector".to_string(),
+                    input: serde_json::json!({"operation": "list", "path": "."}),
+                },
+            ],
+        )];
+
+        let transcript = render_transcript("testLogin", &history, &[]);
+
+        assert!(transcript.starts_with("# Autofix Transcript: testLogin"));
+        assert!(transcript.contains("## Turn 1"));
+        assert!(transcript.contains("Fix the test"));
+        assert!(transcript.contains("_[image/jpeg image, 4096 bytes of base64 omitted]_"));
+        assert!(transcript.contains("### Tool call: `directory_inspector` (`toolu_1`)"));
+        assert!(!transcript.contains(&"A".repeat(100)));
+    }
+
+    #[test]
+    fn test_redacts_secrets_and_blobs() {
+        let history = vec![(
+            vec![ContentBlockParam::ToolResult {
+                tool_use_id: "toolu_1".to_string(),
+                content: Some(format!(
+                    "key=my-secret-key token=sk-ant-api03-abcdef blob={}",
+                    "QUJD".repeat(200)
+                )),
+                is_error: None,
+            }],
+            vec![],
+        )];
+
+        let transcript = render_transcript("testLogin", &history, &["my-secret-key"]);
+
+        assert!(!transcript.contains("my-secret-key"));
+        assert!(!transcript.contains("sk-ant-api03"));
+        assert!(transcript.contains("[base64 data, 800 bytes omitted]"));
+    }
+}
@@ -0,0 +1,374 @@
+// Edits kept in memory for --patch-only
+//
+// With --patch-only the code editor writes to VirtualFiles instead of the
+// working tree. Reads fall through to disk until a file is first written, and
+// the first read content is kept as the original the patch is made against.
+// Test runs happen in a copy of the workspace with the edits written into it.
+
+use super::autofix_pipeline::PipelineError;
+use crate::tools::{DiskFiles, FileStore};
+use crate::workspace_path;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Lines of unchanged context around each hunk of the patch
+const CONTEXT_LINES: usize = 3;
+
+/// Directories left out of the workspace copy tests run in
+const UNCOPIED_DIRS: &[&str] = &[".git", ".autofix", "build", "DerivedData"];
+
+/// In-memory copies of the files edited with `--patch-only`
+#[derive(Debug, Default)]
+pub struct VirtualFiles {
+    /// Edited file by its absolute path
+    files: Mutex<BTreeMap<PathBuf, VirtualFile>>,
+}
+
+#[derive(Debug, Clone)]
+struct VirtualFile {
+    /// Content on disk before the first edit, `None` for a created file
+    original: Option<String>,
+    content: String,
+}
+
+/// State of `VirtualFiles` to go back to, taken with `VirtualFiles::checkpoint`
+pub struct Checkpoint(BTreeMap<PathBuf, VirtualFile>);
+
+impl FileStore for VirtualFiles {
+    fn exists(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().contains_key(&Self::key(path)) || path.exists()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        match self.files.lock().unwrap().get(&Self::key(path)) {
+            Some(file) => Ok(file.content.clone()),
+            None => fs::read_to_string(path),
+        }
+    }
+
+    fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        let key = Self::key(path);
+        if let Some(file) = files.get_mut(&key) {
+            file.content = content.to_string();
+            return Ok(());
+        }
+
+        let original = match fs::read_to_string(path) {
+            Ok(original) => Some(original),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        files.insert(
+            key,
+            VirtualFile {
+                original,
+                content: content.to_string(),
+            },
+        );
+        Ok(())
+    }
+}
+
+impl VirtualFiles {
+    /// Files are keyed by absolute path, so relative and absolute tool paths meet
+    fn key(path: &Path) -> PathBuf {
+        std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.files.lock().unwrap().clone())
+    }
+
+    /// Drop the edits made since `checkpoint` was taken
+    pub fn restore(&self, checkpoint: Checkpoint) {
+        *self.files.lock().unwrap() = checkpoint.0;
+    }
+
+    /// Write the edited files into `dir`, a copy of `workspace`
+    pub fn write_into(&self, workspace: &Path, dir: &Path) -> io::Result<()> {
+        for (relative, file) in self.relative_files(workspace) {
+            DiskFiles.write(&dir.join(relative), &file.content)?;
+        }
+        Ok(())
+    }
+
+    /// Diff of each changed file inside `workspace`
+    fn file_diffs(&self, workspace: &Path) -> Vec<String> {
+        self.relative_files(workspace)
+            .into_iter()
+            .filter(|(_, file)| file.original.as_deref() != Some(file.content.as_str()))
+            .map(|(relative, file)| {
+                unified_diff(&relative, file.original.as_deref(), &file.content)
+            })
+            .collect()
+    }
+
+    /// Write the unified diff of all edits against `workspace` to `path`, as `git apply`
+    /// takes it; returns the number of files it changes
+    ///
+    /// Files outside the workspace are left out.
+    pub fn write_patch(&self, workspace: &Path, path: &Path) -> Result<usize, PipelineError> {
+        let diffs = self.file_diffs(workspace);
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, diffs.concat())?;
+        Ok(diffs.len())
+    }
+
+    fn relative_files(&self, workspace: &Path) -> Vec<(String, VirtualFile)> {
+        self.files
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(path, file)| (workspace_path::relative(path, workspace), file.clone()))
+            .filter(|(relative, _)| !Path::new(relative).is_absolute())
+            .collect()
+    }
+}
+
+/// Copy `workspace` to `dest`, leaving out version control, autofix and build output
+pub fn copy_workspace(workspace: &Path, dest: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(workspace)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let target = dest.join(&name);
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &target)?;
+        } else if file_type.is_dir() {
+            if !UNCOPIED_DIRS.iter().any(|dir| name == *dir) {
+                copy_workspace(&entry.path(), &target)?;
+            }
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(link: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(link)?, target)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(link: &Path, target: &Path) -> io::Result<()> {
+    fs::copy(link, target).map(|_| ())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Line {
+    /// Unchanged, by its index in the original
+    Same(usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Diff of one file in git's format; `original` is `None` for a new file
+fn unified_diff(path: &str, original: Option<&str>, content: &str) -> String {
+    let old: Vec<&str> = original.unwrap_or_default().split_inclusive('\n').collect();
+    let new: Vec<&str> = content.split_inclusive('\n').collect();
+
+    let mut diff = format!("diff --git a/{path} b/{path}\n");
+    match original {
+        Some(_) => diff.push_str(&format!("--- a/{path}\n")),
+        None => diff.push_str("new file mode 100644\n--- /dev/null\n"),
+    }
+    diff.push_str(&format!("+++ b/{path}\n"));
+
+    let lines = diff_lines(&old, &new);
+    let changes: Vec<usize> = (0..lines.len())
+        .filter(|&i| !matches!(lines[i], Line::Same(..)))
+        .collect();
+    let mut remaining = changes.as_slice();
+    while let Some(&first) = remaining.first() {
+        // Extend the hunk while the next change is close enough to share context
+        let mut last = first;
+        let mut taken = 1;
+        for &change in &remaining[1..] {
+            if change - last > 2 * CONTEXT_LINES {
+                break;
+            }
+            last = change;
+            taken += 1;
+        }
+        remaining = &remaining[taken..];
+
+        let hunk = &lines
+            [first.saturating_sub(CONTEXT_LINES)..(last + CONTEXT_LINES + 1).min(lines.len())];
+        diff.push_str(&hunk_header(
+            hunk,
+            &lines[..first.saturating_sub(CONTEXT_LINES)],
+        ));
+        for line in hunk {
+            let (prefix, text) = match *line {
+                Line::Same(i) => (' ', old[i]),
+                Line::Removed(i) => ('-', old[i]),
+                Line::Added(j) => ('+', new[j]),
+            };
+            diff.push(prefix);
+            diff.push_str(text);
+            if !text.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    diff
+}
+
+/// `@@ -start,count +start,count @@` for `hunk`, which follows the lines of `before`
+fn hunk_header(hunk: &[Line], before: &[Line]) -> String {
+    let old_before = before
+        .iter()
+        .filter(|line| !matches!(line, Line::Added(_)))
+        .count();
+    let new_before = before
+        .iter()
+        .filter(|line| !matches!(line, Line::Removed(_)))
+        .count();
+    let old_count = hunk
+        .iter()
+        .filter(|line| !matches!(line, Line::Added(_)))
+        .count();
+    let new_count = hunk
+        .iter()
+        .filter(|line| !matches!(line, Line::Removed(_)))
+        .count();
+    // An empty range starts at the line before it
+    let start = |before: usize, count: usize| if count == 0 { before } else { before + 1 };
+    format!(
+        "@@ -{},{} +{},{} @@\n",
+        start(old_before, old_count),
+        old_count,
+        start(new_before, new_count),
+        new_count
+    )
+}
+
+/// Line-by-line diff from the longest common subsequence of the changed middle
+///
+/// The common start and end are matched first, so the quadratic part only
+/// covers the lines between the first and last change.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Line> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j]: length of the common subsequence of old_mid[i..] and new_mid[j..]
+    let mut lcs = vec![vec![0u32; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines: Vec<Line> = (0..prefix).map(Line::Same).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() || j < new_mid.len() {
+        if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+            lines.push(Line::Same(prefix + i));
+            i += 1;
+            j += 1;
+        } else if j == new_mid.len() || (i < old_mid.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(Line::Removed(prefix + i));
+            i += 1;
+        } else {
+            lines.push(Line::Added(prefix + j));
+            j += 1;
+        }
+    }
+    lines.extend((old.len() - suffix..old.len()).map(Line::Same));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_unified_diff() {
+        let original = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let content = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk";
+        assert_eq!(
+            unified_diff("App/View.swift", Some(original), content),
+            "diff --git a/App/View.swift b/App/View.swift\n\
+             --- a/App/View.swift\n\
+             +++ b/App/View.swift\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,3 +8,4 @@\n h\n i\n j\n+k\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            unified_diff("New.swift", None, "x\n"),
+            "diff --git a/New.swift b/New.swift\nnew file mode 100644\n--- /dev/null\n+++ b/New.swift\n@@ -0,0 +1,1 @@\n+x\n"
+        );
+    }
+
+    #[test]
+    fn test_patch_applies_and_leaves_workspace_unchanged() {
+        let workspace = std::env::temp_dir().join(format!("virtual-files-{}", Uuid::new_v4()));
+        fs::create_dir_all(workspace.join("App")).unwrap();
+        let original = (1..=20).map(|i| format!("line {i}\n")).collect::<String>();
+        fs::write(workspace.join("App/View.swift"), &original).unwrap();
+
+        let files = VirtualFiles::default();
+        let view = workspace.join("App/View.swift");
+        let edited = original
+            .replace("line 3\n", "line three\n")
+            .replace("line 18\n", "");
+        files.write(&view, &edited).unwrap();
+        files
+            .write(&workspace.join("App/New.swift"), "struct New {}\n")
+            .unwrap();
+        assert_eq!(files.read(&view).unwrap(), edited);
+        assert_eq!(fs::read_to_string(&view).unwrap(), original);
+        assert!(!workspace.join("App/New.swift").exists());
+
+        let patch_path = workspace.join("fix.patch");
+        assert_eq!(files.write_patch(&workspace, &patch_path).unwrap(), 2);
+
+        let clone = workspace.join("clone");
+        copy_workspace(&workspace.join("App"), &clone.join("App")).unwrap();
+        match Command::new("git")
+            .arg("apply")
+            .arg(&patch_path)
+            .current_dir(&clone)
+            .status()
+        {
+            Ok(status) => {
+                assert!(status.success());
+                assert_eq!(
+                    fs::read_to_string(clone.join("App/View.swift")).unwrap(),
+                    edited
+                );
+                assert_eq!(
+                    fs::read_to_string(clone.join("App/New.swift")).unwrap(),
+                    "struct New {}\n"
+                );
+            }
+            Err(_) => eprintln!("git not available, skipping git apply"),
+        }
+
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+}
@@ -0,0 +1,303 @@
+// Per-project defaults from `.autofix.toml`
+//
+// Settings committed with a repo travel with it, so everyone running autofix on
+// it uses the same provider, destinations and protected paths without flags.
+// Each setting comes from the first source that sets it: CLI flags, then
+// environment variables, then the file, then the built-in defaults.
+
+use crate::llm::ProviderType;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Project config file, searched for in the workspace and its parent directories
+pub const PROJECT_CONFIG_FILE: &str = ".autofix.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("Invalid project config {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Invalid protected path pattern \"{pattern}\" in {path}: {source}")]
+    Pattern {
+        path: PathBuf,
+        pattern: String,
+        source: glob::PatternError,
+    },
+
+    #[error("Invalid {var} '{value}'")]
+    Variable { var: &'static str, value: String },
+}
+
+/// Run settings that a project file, the environment or the CLI may set
+///
+/// Unset fields are `None` or empty, so layers can be merged with `merge`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub provider: Option<ProviderType>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_iterations: Option<u32>,
+    /// xcodebuild `-destination` specifiers
+    #[serde(default)]
+    pub destination: Vec<String>,
+    pub scheme: Option<String>,
+    /// Directory names the test file search skips
+    #[serde(default)]
+    pub ignore_dir: Vec<String>,
+    /// Globs of files the model must never edit, relative to the workspace even when
+    /// the file is found in one of its parents
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
+    /// File the settings were read from, if any
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Settings of the nearest `.autofix.toml` in `workspace` or one of its parents
+    ///
+    /// Without such a file nothing is set.
+    pub fn discover(workspace: &Path) -> Result<Config, ConfigError> {
+        let start = fs::canonicalize(workspace).unwrap_or_else(|_| workspace.to_path_buf());
+        match start
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .find(|path| path.is_file())
+        {
+            Some(path) => Self::load(&path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Settings of the config file at `path`
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let toml = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let config: Config = toml::from_str(&toml).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        // Reject bad globs now rather than when the first edit is checked
+        config.protected_path_patterns()?;
+        Ok(Config {
+            path: Some(path.to_path_buf()),
+            ..config
+        })
+    }
+
+    /// Settings from `AUTOFIX_PROVIDER`, `AUTOFIX_MODEL`, `AUTOFIX_TEMPERATURE` and
+    /// `AUTOFIX_MAX_ITERATIONS`
+    pub fn from_env() -> Result<Config, ConfigError> {
+        Self::from_vars(|var| env::var(var).ok())
+    }
+
+    /// Settings from the variables `lookup` finds, unset or empty ones left out
+    fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
+        let var = |name: &str| lookup(name).filter(|value| !value.is_empty());
+        let invalid = |var: &'static str, value: String| ConfigError::Variable { var, value };
+
+        Ok(Config {
+            provider: var("AUTOFIX_PROVIDER")
+                .map(|value| {
+                    ProviderType::from_str(&value).map_err(|_| invalid("AUTOFIX_PROVIDER", value))
+                })
+                .transpose()?,
+            model: var("AUTOFIX_MODEL"),
+            temperature: var("AUTOFIX_TEMPERATURE")
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|_| invalid("AUTOFIX_TEMPERATURE", value))
+                })
+                .transpose()?,
+            max_iterations: var("AUTOFIX_MAX_ITERATIONS")
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|_| invalid("AUTOFIX_MAX_ITERATIONS", value))
+                })
+                .transpose()?,
+            ..Config::default()
+        })
+    }
+
+    /// These settings with every setting of `over` replacing them
+    ///
+    /// A model is picked for its provider, so `over` switching to another provider
+    /// drops this model too.
+    pub fn merge(self, over: Config) -> Config {
+        fn or_vec<T>(over: Vec<T>, base: Vec<T>) -> Vec<T> {
+            if over.is_empty() { base } else { over }
+        }
+        let provider_changed = over
+            .provider
+            .is_some_and(|provider| provider != self.provider.unwrap_or_default());
+        Config {
+            provider: over.provider.or(self.provider),
+            model: over.model.or(self.model.filter(|_| !provider_changed)),
+            temperature: over.temperature.or(self.temperature),
+            max_iterations: over.max_iterations.or(self.max_iterations),
+            destination: or_vec(over.destination, self.destination),
+            scheme: over.scheme.or(self.scheme),
+            ignore_dir: or_vec(over.ignore_dir, self.ignore_dir),
+            protected_paths: or_vec(over.protected_paths, self.protected_paths),
+            path: over.path.or(self.path),
+        }
+    }
+
+    /// `protected_paths` as glob patterns
+    pub fn protected_path_patterns(&self) -> Result<Vec<glob::Pattern>, ConfigError> {
+        self.protected_paths
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|source| ConfigError::Pattern {
+                    path: self.path.clone().unwrap_or_default(),
+                    pattern: pattern.clone(),
+                    source,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_discover_walks_up_from_the_workspace() {
+        let root = std::env::temp_dir().join(format!("project-config-{}", Uuid::new_v4()));
+        let workspace = root.join("ios/App");
+        fs::create_dir_all(&workspace).unwrap();
+        assert_eq!(Config::discover(&workspace).unwrap(), Config::default());
+
+        fs::write(
+            root.join(PROJECT_CONFIG_FILE),
+            "provider = \"openai\"\nmodel = \"gpt-4o\"\nmax-iterations = 30\n\
+             destination = [\"platform=iOS Simulator,name=iPhone 16\"]\n\
+             ignore-dir = [\"Vendor\"]\nprotected-paths = [\"Generated/**\"]\n",
+        )
+        .unwrap();
+        let config = Config::discover(&workspace).unwrap();
+        assert_eq!(config.provider, Some(ProviderType::OpenAI));
+        assert_eq!(config.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(config.max_iterations, Some(30));
+        assert_eq!(config.ignore_dir, vec!["Vendor"]);
+        assert!(config.protected_path_patterns().unwrap()[0].matches("Generated/API.swift"));
+        assert_eq!(
+            config.path,
+            Some(fs::canonicalize(&root).unwrap().join(PROJECT_CONFIG_FILE))
+        );
+
+        fs::write(root.join(PROJECT_CONFIG_FILE), "max_iterations = 30\n").unwrap();
+        assert!(matches!(
+            Config::discover(&workspace),
+            Err(ConfigError::Parse { .. })
+        ));
+        fs::write(
+            root.join(PROJECT_CONFIG_FILE),
+            "protected-paths = [\"[\"]\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            Config::discover(&workspace),
+            Err(ConfigError::Pattern { .. })
+        ));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_defaults_file_env_cli_precedence() {
+        let file = Config {
+            provider: Some(ProviderType::OpenAI),
+            model: Some("gpt-4o".to_string()),
+            temperature: Some(0.2),
+            max_iterations: Some(30),
+            destination: vec!["name=iPhone 16".to_string()],
+            scheme: Some("App".to_string()),
+            ..Config::default()
+        };
+        let vars = HashMap::from([
+            ("AUTOFIX_MODEL", "gpt-4.1"),
+            ("AUTOFIX_TEMPERATURE", "0.5"),
+            ("AUTOFIX_MAX_ITERATIONS", ""),
+        ]);
+        let env = Config::from_vars(|var| vars.get(var).map(|value| value.to_string())).unwrap();
+        let cli = Config {
+            temperature: Some(0.9),
+            destination: vec!["name=iPhone SE (3rd generation)".to_string()],
+            ..Config::default()
+        };
+
+        let config = Config::default().merge(file).merge(env).merge(cli);
+
+        // The file sets what nothing else does, the environment beats it, flags beat both
+        assert_eq!(config.provider, Some(ProviderType::OpenAI));
+        assert_eq!(config.max_iterations, Some(30));
+        assert_eq!(config.scheme.as_deref(), Some("App"));
+        assert_eq!(config.model.as_deref(), Some("gpt-4.1"));
+        assert_eq!(config.temperature, Some(0.9));
+        assert_eq!(config.destination, vec!["name=iPhone SE (3rd generation)"]);
+        // Unset everywhere, so the built-in default applies
+        assert!(config.ignore_dir.is_empty());
+
+        // The file's gpt-4o was picked for OpenAI, so --provider claude gets its default
+        let file = Config {
+            provider: Some(ProviderType::OpenAI),
+            model: Some("gpt-4o".to_string()),
+            ..Config::default()
+        };
+        let cli = Config {
+            provider: Some(ProviderType::Claude),
+            ..Config::default()
+        };
+        let config = Config::default().merge(file.clone()).merge(cli);
+        assert_eq!(config.provider, Some(ProviderType::Claude));
+        assert_eq!(config.model, None);
+
+        // Naming the same provider again keeps the model
+        let same = Config {
+            provider: Some(ProviderType::OpenAI),
+            ..Config::default()
+        };
+        assert_eq!(file.merge(same).model.as_deref(), Some("gpt-4o"));
+
+        // A model without a provider is for the default provider, Claude
+        let file = Config {
+            model: Some("claude-opus-4".to_string()),
+            ..Config::default()
+        };
+        let cli = Config {
+            provider: Some(ProviderType::Claude),
+            ..Config::default()
+        };
+        assert_eq!(file.merge(cli).model.as_deref(), Some("claude-opus-4"));
+    }
+
+    #[test]
+    fn test_invalid_environment_variable() {
+        let vars = HashMap::from([("AUTOFIX_MAX_ITERATIONS", "many")]);
+        assert!(matches!(
+            Config::from_vars(|var| vars.get(var).map(|value| value.to_string())),
+            Err(ConfigError::Variable {
+                var: "AUTOFIX_MAX_ITERATIONS",
+                ..
+            })
+        ));
+    }
+}
@@ -65,8 +65,20 @@ impl RateLimiter {
     /// * `Ok(())` - Request can proceed immediately
     /// * `Err(Duration)` - Request should wait for the specified duration
     pub fn check_and_wait(&self, estimated_tokens: usize) -> Result<(), Duration> {
+        let wait = self.wait_duration_for(estimated_tokens);
+        if wait.is_zero() { Ok(()) } else { Err(wait) }
+    }
+
+    /// How long a request with the given token count has to wait before it fits the window
+    ///
+    /// # Arguments
+    /// * `estimated_tokens` - Estimated number of input tokens for the request
+    ///
+    /// # Returns
+    /// * `Duration::ZERO` if the request can proceed immediately, the exact wait otherwise
+    pub fn wait_duration_for(&self, estimated_tokens: usize) -> Duration {
         if !self.enabled {
-            return Ok(());
+            return Duration::ZERO;
         }
 
         let mut state = self.state.lock().unwrap();
@@ -86,38 +98,21 @@ impl RateLimiter {
         let tokens_in_window: usize = state.usage_history.iter().map(|(_, tokens)| tokens).sum();
 
         // Check if adding these estimated tokens would exceed the limit
-        if tokens_in_window + estimated_tokens > self.tokens_per_minute {
-            // Find the oldest entry to determine when it will expire
-            if let Some(&(oldest_timestamp, oldest_tokens)) = state.usage_history.front() {
-                // Calculate when enough tokens will be freed up
-                let time_until_oldest_expires = oldest_timestamp + Duration::from_secs(60) - now;
-
-                // If freeing the oldest entry would be enough, wait for it
-                if tokens_in_window - oldest_tokens + estimated_tokens <= self.tokens_per_minute {
-                    return Err(time_until_oldest_expires);
-                }
-
-                // Otherwise, we need to wait longer - find when enough tokens free up
-                let mut cumulative_freed = 0;
-                for &(timestamp, tokens) in state.usage_history.iter() {
-                    cumulative_freed += tokens;
-                    if tokens_in_window - cumulative_freed + estimated_tokens
-                        <= self.tokens_per_minute
-                    {
-                        let wait_time = timestamp + Duration::from_secs(60) - now;
-                        return Err(wait_time);
-                    }
-                }
-
-                // Worst case: wait 60 seconds for full window reset
-                return Err(Duration::from_secs(60));
-            }
+        if tokens_in_window + estimated_tokens <= self.tokens_per_minute {
+            return Duration::ZERO;
+        }
 
-            // No history but still over limit? Wait 60 seconds
-            return Err(Duration::from_secs(60));
+        // Find when enough of the oldest entries expire to make room
+        let mut cumulative_freed = 0;
+        for &(timestamp, tokens) in state.usage_history.iter() {
+            cumulative_freed += tokens;
+            if tokens_in_window - cumulative_freed + estimated_tokens <= self.tokens_per_minute {
+                return (timestamp + Duration::from_secs(60)).saturating_duration_since(now);
+            }
         }
 
-        Ok(())
+        // Worst case (or no history but still over limit): wait 60 seconds for full window reset
+        Duration::from_secs(60)
     }
 
     /// Record actual token usage from an API response
@@ -149,9 +144,12 @@ impl RateLimiter {
 
     /// Get current usage statistics
     ///
+    /// # Arguments
+    /// * `estimated_tokens` - Estimated number of input tokens for the next request
+    ///
     /// # Returns
-    /// * `(tokens_used, tokens_remaining, seconds_until_oldest_expires)`
-    pub fn get_stats(&self) -> (usize, usize, u64) {
+    /// * `(tokens_used, tokens_remaining, seconds_until_oldest_expires, wait_for_next_request)`
+    pub fn get_stats(&self, estimated_tokens: usize) -> (usize, usize, u64, Duration) {
         let mut state = self.state.lock().unwrap();
         let now = Instant::now();
         let window_start = now - Duration::from_secs(60);
@@ -178,7 +176,10 @@ impl RateLimiter {
             0
         };
 
-        (tokens_used, tokens_remaining, seconds_until_reset)
+        drop(state);
+        let wait = self.wait_duration_for(estimated_tokens);
+
+        (tokens_used, tokens_remaining, seconds_until_reset, wait)
     }
 
     /// Create a rate limiter from environment variables
@@ -245,7 +246,7 @@ mod tests {
         // Record second usage
         limiter.record_usage(400);
         // Verify stats
-        let (used, remaining, _) = limiter.get_stats();
+        let (used, remaining, _, _) = limiter.get_stats(0);
         assert_eq!(used, 900);
         assert_eq!(remaining, 100);
     }
@@ -274,7 +275,7 @@ mod tests {
         // Record some usage
         limiter.record_usage(500);
         // Verify current usage
-        let (used, remaining, _) = limiter.get_stats();
+        let (used, remaining, _, _) = limiter.get_stats(0);
         assert_eq!(used, 500);
         assert_eq!(remaining, 500);
 
@@ -285,4 +286,32 @@ mod tests {
         // Now at 900, can't use 200 more
         assert!(limiter.check_and_wait(200).is_err());
     }
+
+    #[test]
+    fn test_wait_duration_for() {
+        let limiter = RateLimiter::new(ProviderType::Claude, 1000, true, false);
+        assert_eq!(limiter.wait_duration_for(1000), Duration::ZERO);
+
+        limiter.record_usage(300);
+        limiter.record_usage(600);
+        assert_eq!(limiter.wait_duration_for(100), Duration::ZERO);
+
+        // Only once both entries expire is there room for 900 more tokens
+        let wait = limiter.wait_duration_for(900);
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+
+        // More than the whole budget never fits; wait for a full window reset
+        assert_eq!(limiter.wait_duration_for(2000), Duration::from_secs(60));
+
+        let (_, _, _, stats_wait) = limiter.get_stats(900);
+        assert!(stats_wait > Duration::from_secs(59));
+        assert_eq!(limiter.get_stats(100).3, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_wait_duration_for_disabled() {
+        let limiter = RateLimiter::new(ProviderType::Claude, 1000, false, false);
+        limiter.record_usage(5000);
+        assert_eq!(limiter.wait_duration_for(1000), Duration::ZERO);
+    }
 }
@@ -0,0 +1,28 @@
+// Removes secrets and embedded binary data from text that leaves the process,
+// such as transcripts and HTTP traces
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// API keys of the supported providers (Anthropic, OpenAI, Groq)
+static API_KEY_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(sk-ant-[A-Za-z0-9_\-]+|sk-[A-Za-z0-9_\-]{20,}|gsk_[A-Za-z0-9]{20,})").unwrap()
+});
+
+/// Long base64 runs, e.g. images embedded in tool results
+static BASE64_BLOB_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z0-9+/]{512,}={0,2}").unwrap());
+
+/// Remove secrets and base64 blobs from `text`
+pub fn redact(text: &str, secrets: &[&str]) -> String {
+    let mut redacted = text.to_string();
+    for secret in secrets.iter().filter(|secret| !secret.is_empty()) {
+        redacted = redacted.replace(secret, "[REDACTED]");
+    }
+    let redacted = API_KEY_PATTERN.replace_all(&redacted, "[REDACTED]");
+    BASE64_BLOB_PATTERN
+        .replace_all(&redacted, |caps: &regex::Captures| {
+            format!("[base64 data, {} bytes omitted]", caps[0].len())
+        })
+        .into_owned()
+}
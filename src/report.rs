@@ -0,0 +1,554 @@
+use crate::llm::{ProviderConfig, TokenUsage};
+use crate::pipeline::FixTarget;
+use crate::xcresultparser::TestFailure;
+use crate::xctestresultdetailparser::{FailureDetail, FailureKind};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReportError {
+    #[error("Failed to serialize report: {0}")]
+    Serialize(#[from] serde_json::Error),
+
+    #[error("Failed to write report to {path}: {source}")]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Structured result of an autofix run
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub provider: String,
+    pub model: String,
+    pub duration_secs: f64,
+    pub token_usage: TokenUsage,
+    pub timings: Timings,
+    /// Calls per tool across all tests
+    #[serde(skip_serializing_if = "ToolMetrics::is_empty")]
+    pub tool_metrics: ToolMetrics,
+    pub tests: Vec<TestReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline_delta: Option<BaselineDelta>,
+    /// Why the run stopped early; the tests above are the ones processed before it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RunReport {
+    pub fn new(provider_config: &ProviderConfig) -> Self {
+        Self {
+            provider: format!("{:?}", provider_config.provider_type).to_lowercase(),
+            model: provider_config.model.clone(),
+            duration_secs: 0.0,
+            token_usage: TokenUsage::zero(),
+            timings: Timings::default(),
+            tool_metrics: ToolMetrics::default(),
+            tests: Vec::new(),
+            baseline_delta: None,
+            error: None,
+        }
+    }
+
+    /// Add the report of a single test and accumulate its token usage
+    pub fn add_test(&mut self, test: TestReport) {
+        self.token_usage += test.token_usage;
+        self.timings.merge(&test.timings);
+        self.tool_metrics.merge(&test.tool_metrics);
+        self.tests.push(test);
+    }
+
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration_secs = duration.as_secs_f64();
+    }
+
+    /// Write the report as pretty-printed JSON, creating parent directories as needed
+    pub fn write_to(&self, path: &Path) -> Result<(), ReportError> {
+        let json = serde_json::to_string_pretty(self)?;
+        let write_error = |source| ReportError::Write {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent).map_err(write_error)?;
+        }
+        fs::write(path, json).map_err(write_error)
+    }
+}
+
+/// How the pipeline run for a single test ended
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestOutcome {
+    /// The model finished and its last test run passed
+    #[default]
+    Completed,
+    /// The model finished, but its last test run still failed
+    NotFixed,
+    /// The model finished without running the test
+    Unverified,
+    /// The model gave up on fixing the test
+    GaveUp,
+    /// The iteration limit was reached
+    MaxIterations,
+    /// The edits kept returning the files to an earlier state
+    Oscillation,
+    /// The model kept looking for paths that don't exist, even after being shown the workspace
+    StuckExploring,
+    /// The run used up its token budget (`--token-budget`)
+    BudgetExhausted,
+    /// The run was cancelled before the model finished, e.g. by Ctrl-C
+    Cancelled,
+    /// The pipeline failed with an error
+    Error,
+    /// The test was excluded from this run or skipped by the test runner
+    Skipped,
+}
+
+/// Edits that cycled back to an earlier state of the edited files
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Oscillation {
+    /// Number of editing iterations before the state repeated
+    pub cycle_length: usize,
+    /// The repeated state and the state right before it, as file path to content
+    pub states: [BTreeMap<String, String>; 2],
+}
+
+/// A successful edit made by the code editor tool
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileEdit {
+    pub file_path: String,
+    pub old_content: String,
+    pub new_content: String,
+}
+
+/// Result of the `build-for-testing` run before the model is involved
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Preflight {
+    pub passed: bool,
+    /// Compiler errors of a failed build
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Structured result of the pipeline run for a single test
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TestReport {
+    pub test_id: String,
+    pub outcome: TestOutcome,
+    /// Whether the test failed an assertion or crashed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_kind: Option<FailureKind>,
+    /// Side the model was asked to change, with `auto` already resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix_target: Option<FixTarget>,
+    /// Build checked before fixing; absent with `--skip-preflight` or when xcodebuild can't run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preflight: Option<Preflight>,
+    /// Result of the last test run triggered by the model, if any
+    pub test_passed: Option<bool>,
+    /// Limit each test run had before being killed (`--test-timeout`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_timeout_secs: Option<u64>,
+    /// Test runs killed for exceeding the test timeout
+    pub timed_out_runs: u32,
+    /// Test runs repeated because the simulator couldn't be acquired
+    pub simulator_retries: u32,
+    /// Test runs started on a freshly erased simulator (`--isolate`)
+    pub isolated: bool,
+    /// Simulator runtime of the last test run, e.g. `iOS Simulator 26.0 (23A339)`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<String>,
+    /// Devices the last test run failed on, with several `--destination`s
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failing_devices: Vec<String>,
+    pub iterations: u32,
+    pub edits: Vec<FileEdit>,
+    /// Sides the edits changed, `app` and/or `test`, in the order they were first edited
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub edited_sides: Vec<FixTarget>,
+    pub token_usage: TokenUsage,
+    pub duration_secs: f64,
+    pub timings: Timings,
+    /// Calls per tool and how many of them failed
+    #[serde(skip_serializing_if = "ToolMetrics::is_empty")]
+    pub tool_metrics: ToolMetrics,
+    /// Tool calls that repeated an earlier call of the same response and reused its result
+    pub duplicate_tool_calls: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Handoff note written by the model after giving up (`--summarize-on-giveup`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handoff: Option<String>,
+    /// Files that had uncommitted changes before the model edited them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dirty_files: Vec<String>,
+    /// Protected files (`.autofix/protected.toml`) the model tried to edit and was refused
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub protected_path_violations: Vec<String>,
+    /// Tests the model asked to run outside `--allow-testing`; these runs don't set `test_passed`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub refused_test_runs: Vec<String>,
+    /// The assertion the model could not fix when it gave up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unresolved_assertion: Option<FailureDetail>,
+    /// Set when the run stopped because the edits went in circles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oscillation: Option<Oscillation>,
+    /// Files restored from their snapshots because the test was not fixed (`--revert-unfixed`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reverted_files: Vec<String>,
+    /// Token usage per model when switching models (`--escalate`/`--deescalate`)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub model_usage: BTreeMap<String, TokenUsage>,
+    /// Model that was in use when the test got fixed, when switching models
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixed_by_model: Option<String>,
+}
+
+impl TestReport {
+    /// Report for a test whose pipeline could not produce one itself
+    pub fn failed(test_id: &str, error: String) -> Self {
+        Self {
+            test_id: test_id.to_string(),
+            outcome: TestOutcome::Error,
+            error: Some(error),
+            ..Self::default()
+        }
+    }
+
+    /// Report for a test that was excluded from the run or not fixable (skipped)
+    pub fn skipped(test_id: &str) -> Self {
+        Self {
+            test_id: test_id.to_string(),
+            outcome: TestOutcome::Skipped,
+            ..Self::default()
+        }
+    }
+
+    /// Whether the model completed and its last test run passed
+    pub fn is_fixed(&self) -> bool {
+        self.outcome == TestOutcome::Completed && self.test_passed == Some(true)
+    }
+
+    /// Accumulate the token usage of one LLM response
+    pub fn record_usage(&mut self, usage: &TokenUsage) {
+        self.token_usage += *usage;
+    }
+
+    /// Accumulate the token usage of one LLM response under the model that answered
+    pub fn record_model_usage(&mut self, model: &str, usage: &TokenUsage) {
+        *self.model_usage.entry(model.to_string()).or_default() += *usage;
+    }
+}
+
+/// Accumulated wall-clock time of one kind of work
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct TimingEntry {
+    pub count: u32,
+    pub total_secs: f64,
+}
+
+/// Wall-clock time spent per pipeline step, LLM call and tool, keyed by name
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Timings(BTreeMap<String, TimingEntry>);
+
+impl Timings {
+    /// Add one measurement for `name`
+    pub fn record(&mut self, name: &str, duration: Duration) {
+        let entry = self.0.entry(name.to_string()).or_default();
+        entry.count += 1;
+        entry.total_secs += duration.as_secs_f64();
+    }
+
+    /// Add all measurements of `other`
+    pub fn merge(&mut self, other: &Timings) {
+        for (name, other_entry) in &other.0 {
+            let entry = self.0.entry(name.clone()).or_default();
+            entry.count += other_entry.count;
+            entry.total_secs += other_entry.total_secs;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Print the timings, slowest first
+    pub fn print(&self) {
+        let mut entries: Vec<_> = self.0.iter().collect();
+        entries.sort_by(|a, b| b.1.total_secs.total_cmp(&a.1.total_secs));
+
+        println!("⏱️  Timing breakdown:");
+        for (name, entry) in entries {
+            println!(
+                "  {:<28} {:>8.1}s  ({} call{})",
+                name,
+                entry.total_secs,
+                entry.count,
+                if entry.count == 1 { "" } else { "s" }
+            );
+        }
+        println!();
+    }
+}
+
+/// Calls of one tool and how they went
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ToolStats {
+    pub calls: u32,
+    pub succeeded: u32,
+    /// Calls whose result reported an error, e.g. an edit whose old content wasn't found
+    pub errored: u32,
+    pub total_secs: f64,
+}
+
+/// Calls per tool, keyed by tool name
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ToolMetrics(BTreeMap<String, ToolStats>);
+
+impl ToolMetrics {
+    /// Add one call of `name`
+    pub fn record(&mut self, name: &str, succeeded: bool, duration: Duration) {
+        let stats = self.0.entry(name.to_string()).or_default();
+        stats.calls += 1;
+        if succeeded {
+            stats.succeeded += 1;
+        } else {
+            stats.errored += 1;
+        }
+        stats.total_secs += duration.as_secs_f64();
+    }
+
+    /// Add all calls of `other`
+    pub fn merge(&mut self, other: &ToolMetrics) {
+        for (name, other_stats) in &other.0 {
+            let stats = self.0.entry(name.clone()).or_default();
+            stats.calls += other_stats.calls;
+            stats.succeeded += other_stats.succeeded;
+            stats.errored += other_stats.errored;
+            stats.total_secs += other_stats.total_secs;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Print the calls per tool, most-used first
+    pub fn print(&self) {
+        let mut entries: Vec<_> = self.0.iter().collect();
+        entries.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.calls));
+
+        println!("🔧 Tool calls:");
+        for (name, stats) in entries {
+            println!(
+                "  {:<28} {:>4} call{}  {:>4} ok  {:>4} errored  {:>8.1}s",
+                name,
+                stats.calls,
+                if stats.calls == 1 { " " } else { "s" },
+                stats.succeeded,
+                stats.errored,
+                stats.total_secs
+            );
+        }
+        println!();
+    }
+}
+
+/// Net change of the test suite compared to the original xcresult
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BaselineDelta {
+    /// Tests that failed originally and pass now
+    pub fixed: Vec<String>,
+    /// Tests that did not fail originally but fail now
+    pub regressed: Vec<String>,
+    /// Tests that failed originally and still fail (or were never re-run)
+    pub still_failing: Vec<String>,
+    /// Whether the fresh runs included tests that passed originally, so regressions
+    /// could show up; runs limited to the failing tests can't reveal any
+    pub regressions_detectable: bool,
+}
+
+impl BaselineDelta {
+    /// Compare the original failures against fresh test results
+    ///
+    /// `fresh_results` holds `(test_identifier_url, passed)` pairs ordered from
+    /// oldest to newest; a later result for the same test wins.
+    pub fn compute(original_failures: &[TestFailure], fresh_results: &[(String, bool)]) -> Self {
+        let latest: BTreeMap<&str, bool> = fresh_results
+            .iter()
+            .map(|(test_id, passed)| (test_id.as_str(), *passed))
+            .collect();
+
+        let mut delta = Self::default();
+
+        for failure in original_failures {
+            let test_id = failure.test_identifier_url.as_str();
+            match latest.get(test_id) {
+                Some(true) => delta.fixed.push(test_id.to_string()),
+                _ => delta.still_failing.push(test_id.to_string()),
+            }
+        }
+
+        for (test_id, passed) in latest {
+            let failed_originally = original_failures
+                .iter()
+                .any(|failure| failure.test_identifier_url == test_id);
+            if !failed_originally {
+                delta.regressions_detectable = true;
+                if !passed {
+                    delta.regressed.push(test_id.to_string());
+                }
+            }
+        }
+
+        delta
+    }
+
+    /// Print the delta as a human-readable summary
+    pub fn print(&self) {
+        println!("Baseline Comparison:");
+        println!("  Fixed: {}", self.fixed.len());
+        for test_id in &self.fixed {
+            println!("    ✅ {}", test_id);
+        }
+        if !self.regressions_detectable {
+            println!("  Regressed: unknown, the fresh runs only ran the originally failing tests");
+        } else {
+            println!("  Regressed: {}", self.regressed.len());
+        }
+        for test_id in &self.regressed {
+            println!("    🔻 {}", test_id);
+        }
+        println!("  Still failing: {}", self.still_failing.len());
+        for test_id in &self.still_failing {
+            println!("    ❌ {}", test_id);
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(test_id: &str) -> TestFailure {
+        TestFailure {
+            test_identifier: 1,
+            test_identifier_string: test_id.to_string(),
+            test_identifier_url: test_id.to_string(),
+            test_name: test_id.to_string(),
+            target_name: "UITests".to_string(),
+            failure_text: "failed".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_compute_delta() {
+        let original = vec![
+            failure("test://a"),
+            failure("test://b"),
+            failure("test://c"),
+        ];
+        let fresh = vec![
+            ("test://a".to_string(), true),
+            ("test://b".to_string(), false),
+            ("test://d".to_string(), false),
+        ];
+
+        let delta = BaselineDelta::compute(&original, &fresh);
+
+        assert_eq!(delta.fixed, vec!["test://a"]);
+        assert_eq!(delta.regressed, vec!["test://d"]);
+        assert!(delta.regressions_detectable);
+        // test://c was never re-run, so it still counts as failing
+        assert_eq!(delta.still_failing, vec!["test://b", "test://c"]);
+    }
+
+    #[test]
+    fn test_regressions_undetectable_when_only_failures_ran() {
+        let original = vec![failure("test://a"), failure("test://b")];
+        let fresh = vec![("test://a".to_string(), true)];
+
+        let delta = BaselineDelta::compute(&original, &fresh);
+
+        assert!(delta.regressed.is_empty());
+        assert!(!delta.regressions_detectable);
+    }
+
+    #[test]
+    fn test_run_report_accumulates_tokens() {
+        let mut report = RunReport::new(&ProviderConfig::default());
+        for test_id in ["test://a", "test://b"] {
+            let mut test = TestReport {
+                test_id: test_id.to_string(),
+                ..TestReport::default()
+            };
+            test.record_usage(&TokenUsage::new(100, 20));
+            report.add_test(test);
+        }
+
+        assert_eq!(report.provider, "claude");
+        assert_eq!(report.token_usage.input_tokens, 200);
+        assert_eq!(report.token_usage.total_tokens, 240);
+    }
+
+    #[test]
+    fn test_write_report_creates_parent_dirs() {
+        let dir = std::env::temp_dir().join(format!("autofix-report-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("nested/report.json");
+
+        let mut report = RunReport::new(&ProviderConfig::default());
+        report.add_test(TestReport::failed("test://a", "boom".to_string()));
+        report.write_to(&path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["tests"][0]["outcome"], "error");
+        assert_eq!(json["tests"][0]["error"], "boom");
+        assert!(json.get("baseline_delta").is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_timings_accumulate_and_merge() {
+        let mut first = Timings::default();
+        first.record("tool:test_runner", Duration::from_secs(30));
+        first.record("tool:test_runner", Duration::from_secs(10));
+        first.record("llm", Duration::from_secs(2));
+
+        let mut total = Timings::default();
+        total.merge(&first);
+        total.merge(&first);
+
+        let json = serde_json::to_value(&total).unwrap();
+        assert_eq!(json["tool:test_runner"]["count"], 4);
+        assert_eq!(json["tool:test_runner"]["total_secs"], 80.0);
+        assert_eq!(json["llm"]["count"], 2);
+    }
+
+    #[test]
+    fn test_latest_result_wins() {
+        let original = vec![failure("test://a")];
+        let fresh = vec![
+            ("test://a".to_string(), false),
+            ("test://a".to_string(), true),
+        ];
+
+        let delta = BaselineDelta::compute(&original, &fresh);
+
+        assert_eq!(delta.fixed, vec!["test://a"]);
+        assert!(delta.still_failing.is_empty());
+    }
+}
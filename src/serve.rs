@@ -0,0 +1,425 @@
+// HTTP/SSE server streaming pipeline events for IDE integration
+//
+// POST /run with a JSON body starts an autofix run for a single test and
+// answers with a `text/event-stream` response. Every `PipelineEvent` is sent
+// as one SSE `data:` line containing the event as JSON. The stream ends when
+// the run finishes; a client that disconnects cancels its run. Runs are
+// processed one at a time since they edit the workspace.
+
+use crate::llm::ProviderConfig;
+use crate::pipeline::{CancellationToken, FixTarget, PipelineEvent, PipelineOptions};
+use crate::project_config::Config;
+use crate::test_command::TestCommand;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// Settings shared by every run started through the server
+pub struct ServeConfig {
+    pub test_result: Option<PathBuf>,
+    pub workspace: Option<PathBuf>,
+    pub fix_target: FixTarget,
+    pub verbose: bool,
+    pub provider_config: ProviderConfig,
+    pub options: PipelineOptions,
+    /// Settings from the environment and CLI flags, which beat those of the
+    /// `.autofix.toml` of each run's workspace
+    pub overrides: Config,
+}
+
+/// Body of a `POST /run` request
+#[derive(Debug, Deserialize)]
+struct RunRequest {
+    test_id: String,
+    #[serde(default)]
+    test_result: Option<PathBuf>,
+    #[serde(default)]
+    workspace: Option<PathBuf>,
+    #[serde(default)]
+    fix_target: Option<FixTarget>,
+    /// Deprecated alias: `true` means `fix_target: "app"`
+    #[serde(default)]
+    knightrider: Option<bool>,
+}
+
+impl RunRequest {
+    /// The requested fix target, falling back to the server's
+    fn fix_target(&self, default: FixTarget) -> FixTarget {
+        match (self.fix_target, self.knightrider) {
+            (Some(fix_target), _) => fix_target,
+            (None, Some(true)) => FixTarget::App,
+            (None, Some(false)) => FixTarget::Test,
+            (None, None) => default,
+        }
+    }
+}
+
+/// Largest request body accepted; a run request is a few hundred bytes
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// A parsed HTTP request (only what the server needs)
+#[derive(Debug)]
+struct HttpRequest {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+/// Why a request was rejected before its body was read
+#[derive(Debug, PartialEq)]
+enum BadRequest {
+    Malformed,
+    BodyTooLarge,
+}
+
+impl BadRequest {
+    /// Status line and message of the response
+    fn response(&self) -> (&'static str, &'static str) {
+        match self {
+            BadRequest::Malformed => ("400 Bad Request", "Malformed request"),
+            BadRequest::BodyTooLarge => ("413 Payload Too Large", "Request body too large"),
+        }
+    }
+}
+
+/// Listen on `addr` and serve autofix runs until the process is stopped
+pub async fn serve(addr: &str, config: ServeConfig) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    println!(
+        "🛰️  Serving autofix events on http://{}",
+        listener.local_addr()?
+    );
+    println!("   POST /run {{\"test_id\": \"...\"}} to start a run");
+    println!();
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        if config.verbose {
+            println!("  [DEBUG] Connection from {}", peer);
+        }
+        if let Err(e) = handle_connection(stream, &config).await {
+            eprintln!("⚠️  Connection error: {}", e);
+        }
+    }
+}
+
+/// Provider config and options of a run in `workspace`, with the settings of its
+/// `.autofix.toml` under those of the environment and CLI flags
+fn workspace_settings(
+    config: &ServeConfig,
+    workspace: &Path,
+) -> Result<(ProviderConfig, PipelineOptions), String> {
+    let settings = Config::discover(workspace)
+        .map_err(|e| e.to_string())?
+        .merge(config.overrides.clone());
+
+    // Switching providers would need another API key, which the server doesn't load
+    let provider_type = settings.provider.unwrap_or_default();
+    if provider_type != config.provider_config.provider_type {
+        return Err(format!(
+            "Runs in {} use provider {}, but the server was started for {}; start one with --provider {}",
+            workspace.display(),
+            provider_type.name(),
+            config.provider_config.provider_type.name(),
+            provider_type.name()
+        ));
+    }
+    let mut provider_config = config.provider_config.clone();
+    // The ladder picks the models itself
+    if config.options.model_ladder.is_none() {
+        provider_config.model = settings
+            .model
+            .clone()
+            .unwrap_or_else(|| ProviderConfig::default_for_provider(provider_type).model);
+    }
+
+    let protected_paths = settings
+        .protected_path_patterns()
+        .map_err(|e| e.to_string())?;
+    let options = PipelineOptions {
+        max_iterations: settings.max_iterations,
+        temperature: settings.temperature,
+        // A destination from the file would conflict with --os-version
+        destinations: if config.options.os_version.is_none() {
+            settings.destination
+        } else {
+            config.options.destinations.clone()
+        },
+        scheme: settings.scheme,
+        ignored_dirs: settings.ignore_dir,
+        protected_paths,
+        ..config.options.clone()
+    };
+    Ok((provider_config, options))
+}
+
+async fn handle_connection(mut stream: TcpStream, config: &ServeConfig) -> std::io::Result<()> {
+    let request = match read_request(&mut stream).await? {
+        Ok(request) => request,
+        Err(bad_request) => {
+            let (status, message) = bad_request.response();
+            return write_response(&mut stream, status, message).await;
+        }
+    };
+
+    if request.path != "/run" {
+        return write_response(&mut stream, "404 Not Found", "Not found").await;
+    }
+    if request.method != "POST" {
+        return write_response(&mut stream, "405 Method Not Allowed", "Use POST /run").await;
+    }
+
+    let run_request: RunRequest = match serde_json::from_slice(&request.body) {
+        Ok(run_request) => run_request,
+        Err(e) => {
+            let message = format!("Invalid run request: {}", e);
+            return write_response(&mut stream, "400 Bad Request", &message).await;
+        }
+    };
+
+    let fix_target = run_request.fix_target(config.fix_target);
+    let (Some(test_result), Some(workspace)) = (
+        run_request
+            .test_result
+            .or_else(|| config.test_result.clone()),
+        run_request.workspace.or_else(|| config.workspace.clone()),
+    ) else {
+        return write_response(
+            &mut stream,
+            "400 Bad Request",
+            "test_result and workspace are required (in the request or via CLI flags)",
+        )
+        .await;
+    };
+
+    let (provider_config, options) = match workspace_settings(config, &workspace) {
+        Ok(settings) => settings,
+        Err(message) => {
+            return write_response(&mut stream, "400 Bad Request", &message).await;
+        }
+    };
+
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\n\
+              Content-Type: text/event-stream\r\n\
+              Cache-Control: no-cache\r\n\
+              Connection: close\r\n\r\n",
+        )
+        .await?;
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let cancellation = CancellationToken::default();
+    let options = PipelineOptions {
+        event_sink: Some(sender.clone()),
+        // Nobody is at the terminal to answer prompts
+        assume_yes: true,
+        // Count tokens per run rather than over the server's lifetime
+        token_counter: Default::default(),
+        cancellation: cancellation.clone(),
+        // Each request may name a different workspace
+        available_schemes: Default::default(),
+        preflight_builds: Default::default(),
+        dirty_files: Default::default(),
+        ..options
+    };
+
+    let run = async move {
+        let cmd = TestCommand::new(
+            test_result,
+            workspace,
+            run_request.test_id,
+            fix_target,
+            config.verbose,
+            provider_config,
+        )
+        .with_options(options);
+
+        if let Err(e) = cmd.execute_ios().await {
+            let _ = sender.send(PipelineEvent::Failed {
+                error: e.to_string(),
+            });
+        }
+    };
+
+    let forward = async {
+        let mut client_connected = true;
+        while let Some(event) = receiver.recv().await {
+            // A disconnected client cancels the run; keep draining until it stops
+            if client_connected && stream.write_all(&sse_frame(&event)).await.is_err() {
+                client_connected = false;
+                cancellation.cancel();
+            }
+        }
+    };
+
+    tokio::join!(run, forward);
+    stream.shutdown().await
+}
+
+/// Encode an event as a single SSE message
+fn sse_frame(event: &PipelineEvent) -> Vec<u8> {
+    let json = serde_json::to_string(event).unwrap_or_default();
+    format!("data: {}\n\n", json).into_bytes()
+}
+
+/// Read the request line, headers and body (sized by Content-Length, at most `MAX_BODY_BYTES`)
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Result<HttpRequest, BadRequest>> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Ok(Err(BadRequest::Malformed));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(Err(BadRequest::Malformed));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+
+    // Checked before allocating, since the length comes from the client
+    if content_length > MAX_BODY_BYTES {
+        return Ok(Err(BadRequest::BodyTooLarge));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Ok(HttpRequest { method, path, body }))
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    message: &str,
+) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        message.len(),
+        message
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_frame_format() {
+        let frame = sse_frame(&PipelineEvent::IterationStarted { iteration: 2 });
+        assert_eq!(
+            String::from_utf8(frame).unwrap(),
+            "data: {\"type\":\"iteration_started\",\"iteration\":2}\n\n"
+        );
+    }
+
+    #[test]
+    fn test_run_request_defaults() {
+        let request: RunRequest = serde_json::from_str(r#"{"test_id": "test://x"}"#).unwrap();
+        assert_eq!(request.test_id, "test://x");
+        assert!(request.test_result.is_none());
+        assert!(request.workspace.is_none());
+        assert_eq!(request.fix_target(FixTarget::Auto), FixTarget::Auto);
+
+        let request: RunRequest =
+            serde_json::from_str(r#"{"test_id": "test://x", "knightrider": true}"#).unwrap();
+        assert_eq!(request.fix_target(FixTarget::Test), FixTarget::App);
+    }
+
+    #[test]
+    fn test_run_uses_the_settings_of_its_workspace() {
+        let workspace =
+            std::env::temp_dir().join(format!("autofix-serve-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&workspace).unwrap();
+        let config = ServeConfig {
+            test_result: None,
+            workspace: None,
+            fix_target: FixTarget::Test,
+            verbose: false,
+            provider_config: ProviderConfig::default(),
+            options: PipelineOptions::default(),
+            overrides: Config {
+                max_iterations: Some(5),
+                ..Config::default()
+            },
+        };
+
+        std::fs::write(
+            workspace.join(".autofix.toml"),
+            "model = \"claude-opus-4\"\nmax-iterations = 30\nscheme = \"App\"\n",
+        )
+        .unwrap();
+        let (provider_config, options) = workspace_settings(&config, &workspace).unwrap();
+        assert_eq!(provider_config.model, "claude-opus-4");
+        assert_eq!(options.scheme.as_deref(), Some("App"));
+        // The CLI and environment still win
+        assert_eq!(options.max_iterations, Some(5));
+
+        std::fs::write(workspace.join(".autofix.toml"), "provider = \"openai\"\n").unwrap();
+        let error = workspace_settings(&config, &workspace).unwrap_err();
+        assert!(error.contains("--provider openai"), "{}", error);
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_request_with_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(b"POST /run HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}")
+                .await
+                .unwrap();
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let request = read_request(&mut stream).await.unwrap().unwrap();
+        client.await.unwrap();
+
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/run");
+        assert_eq!(request.body, b"{}");
+    }
+
+    #[tokio::test]
+    async fn test_read_request_rejects_oversized_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(b"POST /run HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n{}")
+                .await
+                .unwrap();
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let result = read_request(&mut stream).await.unwrap();
+        client.await.unwrap();
+
+        assert_eq!(result.unwrap_err(), BadRequest::BodyTooLarge);
+    }
+}
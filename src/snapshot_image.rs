@@ -0,0 +1,463 @@
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageReader};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use uuid::Uuid;
+
+/// Longest edge a snapshot is sent with unless `--max-image-dimension` says otherwise;
+/// providers downscale anything larger themselves but still bill for the upload
+pub const DEFAULT_MAX_IMAGE_DIMENSION: u32 = 1568;
+
+/// Largest encoded image providers accept
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Smallest longest edge shrinking for `MAX_IMAGE_BYTES` goes down to
+const MIN_IMAGE_DIMENSION: u32 = 256;
+
+/// Edge of the grayscale thumbnails snapshots are compared at
+const COMPARISON_DIMENSION: u32 = 64;
+
+/// `similarity` at or above which two snapshots show the same UI; a blinking cursor or
+/// an updated clock in the status bar stays above it
+pub const UNCHANGED_SIMILARITY: f64 = 0.99;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotImageError {
+    #[error("Failed to read image: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Unrecognized image format: {0}")]
+    UnknownFormat(PathBuf),
+
+    #[error("Failed to transcode image: {0}")]
+    Transcode(#[from] image::ImageError),
+
+    #[error("sips failed to convert {path}: {message}")]
+    Sips { path: PathBuf, message: String },
+}
+
+/// A snapshot ready to be attached to an LLM request
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodedImage {
+    /// Media type matching the encoded bytes, e.g. `image/png`
+    pub media_type: &'static str,
+    /// Base64-encoded image data
+    pub data: String,
+    /// How the snapshot was shrunk to fit the limits, if it was
+    pub downscaled: Option<Downscale>,
+}
+
+/// Size of a snapshot before and after downscaling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Downscale {
+    pub original_dimensions: (u32, u32),
+    pub original_bytes: usize,
+    pub dimensions: (u32, u32),
+    pub bytes: usize,
+}
+
+/// Formats a simulator snapshot can arrive in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SnapshotFormat {
+    /// Sent as-is with the given media type
+    Supported(&'static str),
+    /// Decoded and re-encoded as PNG in-process
+    Decodable(ImageFormat),
+    /// Only readable via `sips` (HEIC, TIFF)
+    NeedsSips,
+}
+
+/// Encodes snapshots for providers, transcoding formats they don't accept
+///
+/// Providers only take PNG, JPEG, GIF and WebP, while simulators can hand out HEIC.
+/// HEIC and TIFF are converted with `sips`, which ships with macOS.
+///
+/// Snapshots larger than the maximum dimension or byte size are downscaled,
+/// keeping their aspect ratio, and sent as PNG.
+pub struct SnapshotImageEncoder {
+    sips_path: PathBuf,
+    max_dimension: u32,
+}
+
+impl SnapshotImageEncoder {
+    pub fn new() -> Self {
+        Self {
+            sips_path: PathBuf::from("sips"),
+            max_dimension: DEFAULT_MAX_IMAGE_DIMENSION,
+        }
+    }
+
+    /// Downscale snapshots whose width or height exceeds `max_dimension` pixels
+    pub fn with_max_dimension(mut self, max_dimension: u32) -> Self {
+        self.max_dimension = max_dimension;
+        self
+    }
+
+    /// Read `path`, detect its real format and return it base64-encoded with a supported media type
+    pub fn encode(&self, path: &Path) -> Result<EncodedImage, SnapshotImageError> {
+        let bytes = fs::read(path)?;
+
+        // Fall back to the extension for data the sniffing doesn't recognize
+        let format = Self::detect_format(&bytes)
+            .or_else(|| media_type_for_extension(path).map(SnapshotFormat::Supported))
+            .ok_or_else(|| SnapshotImageError::UnknownFormat(path.to_path_buf()))?;
+
+        let (media_type, bytes) = match format {
+            SnapshotFormat::Supported(media_type) => (media_type, bytes),
+            SnapshotFormat::Decodable(format) => {
+                let decoded = image::load_from_memory_with_format(&bytes, format)?;
+                let mut png = Vec::new();
+                decoded.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+                ("image/png", png)
+            }
+            SnapshotFormat::NeedsSips => ("image/png", self.convert_with_sips(path)?),
+        };
+
+        match self.downscale(&bytes)? {
+            Some((png, downscale)) => Ok(Self::base64("image/png", &png, Some(downscale))),
+            None => Ok(Self::base64(media_type, &bytes, None)),
+        }
+    }
+
+    /// Shrink the image to the maximum dimension and byte size as PNG, or `None`
+    /// if it already fits or can't be decoded
+    fn downscale(&self, bytes: &[u8]) -> Result<Option<(Vec<u8>, Downscale)>, SnapshotImageError> {
+        let Ok(Ok(original_dimensions)) = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .map(|reader| reader.into_dimensions())
+        else {
+            return Ok(None);
+        };
+        let (width, height) = original_dimensions;
+        if width.max(height) <= self.max_dimension && bytes.len() <= MAX_IMAGE_BYTES {
+            return Ok(None);
+        }
+
+        let decoded = image::load_from_memory(bytes)?;
+        let mut target = width.max(height).min(self.max_dimension);
+        loop {
+            let resized = decoded.resize(target, target, FilterType::Triangle);
+            let mut png = Vec::new();
+            resized.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            if png.len() <= MAX_IMAGE_BYTES || target <= MIN_IMAGE_DIMENSION {
+                let downscale = Downscale {
+                    original_dimensions,
+                    original_bytes: bytes.len(),
+                    dimensions: (resized.width(), resized.height()),
+                    bytes: png.len(),
+                };
+                return Ok(Some((png, downscale)));
+            }
+            target = (target * 3 / 4).max(MIN_IMAGE_DIMENSION);
+        }
+    }
+
+    fn base64(
+        media_type: &'static str,
+        bytes: &[u8],
+        downscaled: Option<Downscale>,
+    ) -> EncodedImage {
+        EncodedImage {
+            media_type,
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            downscaled,
+        }
+    }
+
+    /// Detect the image format from magic bytes; file extensions are not trusted
+    fn detect_format(bytes: &[u8]) -> Option<SnapshotFormat> {
+        if Self::is_heif(bytes) {
+            return Some(SnapshotFormat::NeedsSips);
+        }
+
+        match image::guess_format(bytes).ok()? {
+            ImageFormat::Png => Some(SnapshotFormat::Supported("image/png")),
+            ImageFormat::Jpeg => Some(SnapshotFormat::Supported("image/jpeg")),
+            ImageFormat::Gif => Some(SnapshotFormat::Supported("image/gif")),
+            format @ (ImageFormat::WebP | ImageFormat::Bmp) => {
+                Some(SnapshotFormat::Decodable(format))
+            }
+            ImageFormat::Tiff => Some(SnapshotFormat::NeedsSips),
+            _ => None,
+        }
+    }
+
+    /// HEIF containers start with an `ftyp` box naming a HEIC/HEIF brand
+    fn is_heif(bytes: &[u8]) -> bool {
+        bytes.len() >= 12
+            && &bytes[4..8] == b"ftyp"
+            && matches!(
+                &bytes[8..12],
+                b"heic" | b"heix" | b"hevc" | b"heim" | b"heis" | b"mif1" | b"msf1"
+            )
+    }
+
+    /// Convert `path` to PNG using `sips` and return the PNG bytes
+    fn convert_with_sips(&self, path: &Path) -> Result<Vec<u8>, SnapshotImageError> {
+        let output_path =
+            std::env::temp_dir().join(format!("autofix-snapshot-{}.png", Uuid::new_v4()));
+
+        let output = Command::new(&self.sips_path)
+            .arg("-s")
+            .arg("format")
+            .arg("png")
+            .arg(path)
+            .arg("--out")
+            .arg(&output_path)
+            .output()
+            .map_err(|e| SnapshotImageError::Sips {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            let _ = fs::remove_file(&output_path);
+            return Err(SnapshotImageError::Sips {
+                path: path.to_path_buf(),
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+
+        let png = fs::read(&output_path);
+        let _ = fs::remove_file(&output_path);
+        Ok(png?)
+    }
+}
+
+/// How alike two snapshots look, from 0.0 to 1.0, where 1.0 means pixel-identical
+///
+/// Differing snapshots are compared as small grayscale thumbnails, so the score reflects
+/// visible changes rather than compression noise, and never reaches 1.0. Formats the
+/// `image` crate can't decode, like HEIC, are an error.
+pub fn similarity(previous: &[u8], current: &[u8]) -> Result<f64, SnapshotImageError> {
+    if previous == current {
+        return Ok(1.0);
+    }
+    let previous = image::load_from_memory(previous)?;
+    let current = image::load_from_memory(current)?;
+    if previous.dimensions() == current.dimensions()
+        && previous.to_rgba8().as_raw() == current.to_rgba8().as_raw()
+    {
+        return Ok(1.0);
+    }
+
+    let thumbnail = |image: &DynamicImage| {
+        image
+            .resize_exact(
+                COMPARISON_DIMENSION,
+                COMPARISON_DIMENSION,
+                FilterType::Triangle,
+            )
+            .to_luma8()
+    };
+    let (previous, current) = (thumbnail(&previous), thumbnail(&current));
+    let difference: u64 = previous
+        .as_raw()
+        .iter()
+        .zip(current.as_raw())
+        .map(|(a, b)| a.abs_diff(*b) as u64)
+        .sum();
+    let mean_difference = difference as f64 / (previous.as_raw().len() as f64 * 255.0);
+    Ok((1.0 - mean_difference).min(0.999))
+}
+
+/// Media type providers expect for a snapshot file, derived from its extension
+pub fn media_type_for_extension(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+impl Default for SnapshotImageEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_bytes() -> Vec<u8> {
+        let mut png = Vec::new();
+        image::RgbImage::new(2, 2)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_similarity_separates_identical_from_changed_snapshots() {
+        let encode = |image: &image::RgbImage| {
+            let mut png = Vec::new();
+            image
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .unwrap();
+            png
+        };
+        let screen = image::RgbImage::from_pixel(40, 80, image::Rgb([255, 255, 255]));
+        let mut with_alert = screen.clone();
+        for x in 5..35 {
+            for y in 30..50 {
+                with_alert.put_pixel(x, y, image::Rgb([0, 0, 0]));
+            }
+        }
+
+        assert_eq!(similarity(&encode(&screen), &encode(&screen)).unwrap(), 1.0);
+        let changed = similarity(&encode(&screen), &encode(&with_alert)).unwrap();
+        assert!(changed > 0.7 && changed < 0.95, "similarity {}", changed);
+        let mut with_cursor = screen.clone();
+        with_cursor.put_pixel(20, 40, image::Rgb([0, 0, 0]));
+        let nearly = similarity(&encode(&screen), &encode(&with_cursor)).unwrap();
+        assert!(nearly >= UNCHANGED_SIMILARITY && nearly < 1.0);
+        assert!(similarity(&png_bytes(), b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_mislabeled_png_keeps_png_media_type() {
+        let temp_dir = std::env::temp_dir().join(format!("snapshot-image-{}", Uuid::new_v4()));
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("snapshot.jpg");
+        fs::write(&path, png_bytes()).unwrap();
+
+        let encoded = SnapshotImageEncoder::new().encode(&path).unwrap();
+
+        assert_eq!(encoded.media_type, "image/png");
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_heic_is_transcoded_to_png() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = std::env::temp_dir().join(format!("snapshot-image-{}", Uuid::new_v4()));
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        // Minimal HEIC header: ftyp box with the `heic` brand
+        let heic_path = temp_dir.join("snapshot.heic");
+        fs::write(&heic_path, b"\0\0\0\x18ftypheic\0\0\0\0mif1heic").unwrap();
+
+        // Stand-in for sips that writes a real PNG to the `--out` argument
+        let png_path = temp_dir.join("converted.png");
+        fs::write(&png_path, png_bytes()).unwrap();
+        let sips_path = temp_dir.join("sips");
+        fs::write(
+            &sips_path,
+            format!(
+                "#!/bin/sh\nwhile [ \"$1\" != \"--out\" ]; do shift; done\ncp '{}' \"$2\"\n",
+                png_path.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&sips_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let encoder = SnapshotImageEncoder {
+            sips_path,
+            ..SnapshotImageEncoder::new()
+        };
+        let encoded = encoder.encode(&heic_path).unwrap();
+
+        assert_eq!(encoded.media_type, "image/png");
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(&encoded.data)
+            .unwrap();
+        assert_eq!(image::guess_format(&decoded).unwrap(), ImageFormat::Png);
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_png_is_tagged_image_png() {
+        let temp_dir = std::env::temp_dir().join(format!("snapshot-image-{}", Uuid::new_v4()));
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("snapshot.png");
+        fs::write(&path, png_bytes()).unwrap();
+
+        let encoded = SnapshotImageEncoder::new().encode(&path).unwrap();
+
+        assert_eq!(encoded.media_type, "image/png");
+        assert_eq!(
+            media_type_for_extension(Path::new("Snapshot.PNG")),
+            Some("image/png")
+        );
+        assert_eq!(
+            media_type_for_extension(Path::new("snapshot.jpeg")),
+            Some("image/jpeg")
+        );
+        assert_eq!(media_type_for_extension(Path::new("snapshot.heic")), None);
+
+        // Unrecognized bytes still get a media type matching the extension
+        fs::write(&path, b"\x89PNG truncated").unwrap();
+        let encoded = SnapshotImageEncoder::new().encode(&path).unwrap();
+        assert_eq!(encoded.media_type, "image/png");
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_oversized_snapshot_is_downscaled() {
+        let temp_dir = std::env::temp_dir().join(format!("snapshot-image-{}", Uuid::new_v4()));
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("snapshot.jpg");
+        let mut jpeg = Vec::new();
+        image::RgbImage::new(1206, 2622)
+            .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .unwrap();
+        fs::write(&path, &jpeg).unwrap();
+
+        let encoded = SnapshotImageEncoder::new()
+            .with_max_dimension(800)
+            .encode(&path)
+            .unwrap();
+
+        assert_eq!(encoded.media_type, "image/png");
+        let downscaled = encoded.downscaled.unwrap();
+        assert_eq!(downscaled.original_dimensions, (1206, 2622));
+        // Aspect ratio is kept
+        assert_eq!(downscaled.dimensions, (368, 800));
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(&encoded.data)
+            .unwrap();
+        let image = image::load_from_memory(&decoded).unwrap();
+        assert_eq!((image.width(), image.height()), (368, 800));
+
+        // Snapshots within the limit are sent unchanged
+        let encoded = SnapshotImageEncoder::new()
+            .with_max_dimension(3000)
+            .encode(&path)
+            .unwrap();
+        assert_eq!(encoded.media_type, "image/jpeg");
+        assert_eq!(encoded.downscaled, None);
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_unknown_format_is_rejected() {
+        let temp_dir = std::env::temp_dir().join(format!("snapshot-image-{}", Uuid::new_v4()));
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("snapshot.bin");
+        fs::write(&path, b"not an image").unwrap();
+
+        let result = SnapshotImageEncoder::new().encode(&path);
+
+        assert!(matches!(result, Err(SnapshotImageError::UnknownFormat(_))));
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+/// Language of the UI tests, which decides the files searched and the prompt's code samples
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SourceLanguage {
+    #[default]
+    Swift,
+    /// Objective-C, as in legacy UI test targets
+    #[value(name = "objc")]
+    ObjectiveC,
+}
+
+impl SourceLanguage {
+    /// Every language, in the order the test file search tries them
+    pub const ALL: [SourceLanguage; 2] = [SourceLanguage::Swift, SourceLanguage::ObjectiveC];
+
+    /// Human-readable name for console output
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceLanguage::Swift => "Swift",
+            SourceLanguage::ObjectiveC => "Objective-C",
+        }
+    }
+
+    /// Language of a file, from its extension
+    pub fn from_path(path: &Path) -> Option<SourceLanguage> {
+        match path.extension()?.to_str()? {
+            "swift" => Some(SourceLanguage::Swift),
+            "m" | "mm" | "h" => Some(SourceLanguage::ObjectiveC),
+            _ => None,
+        }
+    }
+
+    /// Extensions of files declaring a test class; headers only declare the interface
+    pub fn test_file_extensions(&self) -> &'static [&'static str] {
+        match self {
+            SourceLanguage::Swift => &["swift"],
+            SourceLanguage::ObjectiveC => &["m", "mm"],
+        }
+    }
+
+    /// Extensions of app source files that may declare UI elements
+    pub fn source_file_extensions(&self) -> &'static [&'static str] {
+        match self {
+            SourceLanguage::Swift => &["swift"],
+            SourceLanguage::ObjectiveC => &["m", "mm", "h"],
+        }
+    }
+
+    /// Language name for markdown code fences
+    pub fn code_fence(&self) -> &'static str {
+        match self {
+            SourceLanguage::Swift => "swift",
+            SourceLanguage::ObjectiveC => "objectivec",
+        }
+    }
+
+    /// How to add an accessibility identifier to app code, as prompt bullet points
+    pub fn accessibility_guidance(&self) -> &'static str {
+        match self {
+            SourceLanguage::Swift => {
+                "  * Use `.accessibilityIdentifier(\"...\")` in SwiftUI\n  * Use `element.accessibilityIdentifier = \"...\"` in UIKit"
+            }
+            SourceLanguage::ObjectiveC => {
+                "  * Use `[element setAccessibilityIdentifier:@\"...\"];` or `element.accessibilityIdentifier = @\"...\";` in UIKit\n  * Use `.accessibilityIdentifier(\"...\")` only in SwiftUI files the app already has"
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_from_extension() {
+        assert_eq!(
+            SourceLanguage::from_path(Path::new("AppUITests/LoginTests.swift")),
+            Some(SourceLanguage::Swift)
+        );
+        assert_eq!(
+            SourceLanguage::from_path(Path::new("AppUITests/LoginTests.m")),
+            Some(SourceLanguage::ObjectiveC)
+        );
+        assert_eq!(
+            SourceLanguage::from_path(Path::new("App/LoginViewController.h")),
+            Some(SourceLanguage::ObjectiveC)
+        );
+        assert_eq!(SourceLanguage::from_path(Path::new("README.md")), None);
+    }
+}
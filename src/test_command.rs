@@ -1,24 +1,55 @@
+use crate::junitparser::{self, JUnitParserError, JUnitResultParser};
 use crate::llm::ProviderConfig;
-use crate::pipeline::{AutofixPipeline, PipelineError};
+use crate::pipeline::{
+    self, AutofixPipeline, FixTarget, PipelineError, PipelineOptions, PipelineOutcome,
+    ProtectedPathsError,
+};
+use crate::report::{ReportError, RunReport, TestReport};
 use crate::xctestresultdetailparser::{XCTestResultDetailParser, XCTestResultDetailParserError};
 use std::path::PathBuf;
+use std::time::Instant;
 
 #[derive(Debug, thiserror::Error)]
 pub enum TestCommandError {
     #[error("Failed to parse test details: {0}")]
     ParseError(#[from] XCTestResultDetailParserError),
 
-    #[error("Failed to run autofix pipeline: {0}")]
-    PipelineError(#[from] PipelineError),
+    #[error("Failed to parse test details: {0}")]
+    JUnitParseError(#[from] JUnitParserError),
+
+    #[error("Failed to run autofix pipeline: {source}")]
+    PipelineError {
+        source: PipelineError,
+        /// What the pipeline recorded until it failed, with the error set
+        report: Box<TestReport>,
+    },
+
+    #[error("Failed to write report: {0}")]
+    Report(#[from] ReportError),
+
+    #[error(transparent)]
+    ProtectedPaths(#[from] ProtectedPathsError),
+}
+
+impl TestCommandError {
+    /// Report of the failed test: the pipeline's partial report, or just the error
+    pub fn report(&self, test_id: &str) -> TestReport {
+        match self {
+            TestCommandError::PipelineError { report, .. } => (**report).clone(),
+            e => TestReport::failed(test_id, e.to_string()),
+        }
+    }
 }
 
 pub struct TestCommand {
     test_result_path: PathBuf,
     workspace_path: PathBuf,
     test_id: String,
-    knightrider_mode: bool,
+    fix_target: FixTarget,
     verbose: bool,
     provider_config: ProviderConfig,
+    options: PipelineOptions,
+    report_file: Option<PathBuf>,
 }
 
 impl TestCommand {
@@ -26,7 +57,7 @@ impl TestCommand {
         test_result_path: PathBuf,
         workspace_path: PathBuf,
         test_id: String,
-        knightrider_mode: bool,
+        fix_target: FixTarget,
         verbose: bool,
         provider_config: ProviderConfig,
     ) -> Self {
@@ -34,23 +65,59 @@ impl TestCommand {
             test_result_path,
             workspace_path,
             test_id,
-            knightrider_mode,
+            fix_target,
             verbose,
             provider_config,
+            options: PipelineOptions::default(),
+            report_file: None,
         }
     }
 
-    /// Execute the test command for iOS
-    pub async fn execute_ios(&self) -> Result<(), TestCommandError> {
-        self.execute_ios_internal(true).await
+    /// Write the structured run report as JSON to the given path at the end of the run
+    pub fn with_report_file(mut self, report_file: Option<PathBuf>) -> Self {
+        self.report_file = report_file;
+        self
+    }
+
+    /// Apply optional pipeline settings
+    pub fn with_options(mut self, options: PipelineOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Execute the test command for iOS, returning how the pipeline run ended
+    ///
+    /// `None` means the test was skipped, so there was nothing to fix.
+    pub async fn execute_ios(&self) -> Result<Option<PipelineOutcome>, TestCommandError> {
+        let started = Instant::now();
+        let result = self.execute_ios_internal(true).await;
+
+        if let Some(report_file) = &self.report_file {
+            let mut report = RunReport::new(&self.provider_config);
+            report.add_test(match &result {
+                Ok((_, test_report)) => test_report.clone(),
+                Err(e) => e.report(&self.test_id),
+            });
+            report.set_duration(started.elapsed());
+            report.write_to(report_file)?;
+            println!("📄 Report written to: {}", report_file.display());
+        }
+
+        result.map(|(outcome, _)| outcome)
     }
 
     /// Execute the test command for iOS without printing (for use by autofix command)
-    pub async fn execute_ios_silent(&self) -> Result<(), TestCommandError> {
-        self.execute_ios_internal(true).await
+    pub async fn execute_ios_silent(&self) -> Result<TestReport, TestCommandError> {
+        self.execute_ios_internal(true)
+            .await
+            .map(|(_, report)| report)
     }
 
-    async fn execute_ios_internal(&self, print_output: bool) -> Result<(), TestCommandError> {
+    /// Run the pipeline for the test, returning its outcome (`None` if skipped) and report
+    async fn execute_ios_internal(
+        &self,
+        print_output: bool,
+    ) -> Result<(Option<PipelineOutcome>, TestReport), TestCommandError> {
         if print_output {
             println!("Fetching test details for iOS...");
             println!("Test result path: {}", self.test_result_path.display());
@@ -60,24 +127,65 @@ impl TestCommand {
         }
 
         // Parse the test details
-        let parser = XCTestResultDetailParser::new();
-        let detail = parser.parse(&self.test_result_path, &self.test_id)?;
+        let parse_started = Instant::now();
+        let detail = if junitparser::is_junit(&self.test_result_path) {
+            JUnitResultParser::new().parse_detail(&self.test_result_path, &self.test_id)?
+        } else {
+            XCTestResultDetailParser::new().parse(&self.test_result_path, &self.test_id)?
+        };
+        let parse_duration = parse_started.elapsed();
 
         if print_output {
             Self::print_test_detail(&detail);
         }
 
+        // Skipped tests have nothing to fix
+        if detail.test_result == "Skipped" {
+            println!("⏭️  Test was skipped; nothing to fix");
+            let mut report = TestReport::skipped(&self.test_id);
+            report.timings.record("parse_test_details", parse_duration);
+            return Ok((None, report));
+        }
+
+        // Protected paths belong to the workspace being fixed, which --serve requests choose
+        let mut options = self.options.clone();
+        options
+            .protected_paths
+            .extend(pipeline::load_protected_paths(&self.workspace_path)?);
+
         // Run the autofix pipeline
         let pipeline = AutofixPipeline::new(
             &self.test_result_path,
             &self.workspace_path,
-            self.knightrider_mode,
+            self.fix_target,
             self.verbose,
             self.provider_config.clone(),
-        )?;
-        pipeline.run(&detail).await?;
+        )
+        .map_err(|source| TestCommandError::PipelineError {
+            report: Box::new(TestReport::failed(&self.test_id, source.to_string())),
+            source,
+        })?
+        .with_options(options);
+        let outcome = match pipeline.run(&detail).await {
+            Ok(outcome) => outcome,
+            Err(source) => {
+                let mut report = pipeline.report();
+                report.timings.record("parse_test_details", parse_duration);
+                return Err(TestCommandError::PipelineError {
+                    source,
+                    report: Box::new(report),
+                });
+            }
+        };
 
-        Ok(())
+        let mut report = pipeline.report();
+        report.timings.record("parse_test_details", parse_duration);
+        report.timings.print();
+        if self.verbose && !report.tool_metrics.is_empty() {
+            report.tool_metrics.print();
+        }
+
+        Ok((Some(outcome), report))
     }
 
     /// Print the test detail information
@@ -99,6 +207,18 @@ impl TestCommand {
         );
         println!();
 
+        // Print failed assertions
+        if !detail.failures.is_empty() {
+            println!("Failures:");
+            for failure in &detail.failures {
+                match failure.location() {
+                    Some(location) => println!("  - {}: {}", location, failure.message),
+                    None => println!("  - {}", failure.message),
+                }
+            }
+            println!();
+        }
+
         // Print devices
         if !detail.devices.is_empty() {
             println!("Devices:");
@@ -158,7 +278,7 @@ mod tests {
             PathBuf::from("tests/fixtures/sample.xcresult"),
             PathBuf::from("path/to/workspace"),
             "test://example".to_string(),
-            false,
+            FixTarget::Test,
             false,
             config,
         );
@@ -178,7 +298,7 @@ mod tests {
             PathBuf::from("tests/fixtures/sample.xcresult"),
             PathBuf::from("path/to/workspace"),
             "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/testExample".to_string(),
-            false,
+            FixTarget::Test,
             false,
             config,
         );
@@ -191,7 +311,10 @@ mod tests {
         if let Err(e) = result {
             match e {
                 TestCommandError::ParseError(_) => {}
-                TestCommandError::PipelineError(_) => {}
+                TestCommandError::JUnitParseError(_) => {}
+                TestCommandError::PipelineError { .. } => {}
+                TestCommandError::Report(_) => {}
+                TestCommandError::ProtectedPaths(_) => {}
             }
         }
     }
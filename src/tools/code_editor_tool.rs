@@ -1,16 +1,53 @@
+use crate::workspace_path;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where the code editor reads and writes the files it edits
+pub trait FileStore {
+    fn exists(&self, path: &Path) -> bool;
+    fn read(&self, path: &Path) -> io::Result<String>;
+    /// Replace the content of `path`, creating the file and its parent directories if needed
+    fn write(&self, path: &Path, content: &str) -> io::Result<()>;
+}
+
+/// The files on disk
+pub struct DiskFiles;
+
+impl FileStore for DiskFiles {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        path.parent().map_or(Ok(()), fs::create_dir_all)?;
+        fs::write(path, content)
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CodeEditorTool {
     name: String,
     description: String,
+    /// Edits applied during this run, keyed by (file_path, old_content, new_content)
+    #[serde(skip)]
+    applied_edits: Mutex<HashSet<(String, String, String)>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CodeEditorInput {
+    /// "edit" (default) or "create"
+    #[serde(default)]
+    pub operation: Option<String>,
     pub file_path: String,
+    #[serde(default)]
     pub old_content: String,
     pub new_content: String,
 }
@@ -26,24 +63,37 @@ impl CodeEditorTool {
     pub fn new() -> Self {
         Self {
             name: "code_editor".to_string(),
-            description: r#"A tool to edit source code files within the workspace.
-This tool performs exact string replacement in files.
+            description: r#"A tool to edit or create source code files within the workspace.
 
-Input format:
+Operations:
+- edit (default): exact string replacement in an existing file
+- create: write a new file, creating parent directories (never overwrites an existing file)
+
+Input format for edit:
 {
   "file_path": "relative/path/to/file.swift",
   "old_content": "exact string to replace",
   "new_content": "new string content"
 }
 
-The tool will:
+Input format for create:
+{
+  "operation": "create",
+  "file_path": "relative/path/to/NewView.swift",
+  "new_content": "full file content"
+}
+
+For edit, the tool will:
 1. Read the file
 2. Verify the old_content exists exactly as specified
 3. Replace it with new_content
 4. Write the file back
 
-IMPORTANT: The old_content must match exactly (including whitespace and indentation)."#
+IMPORTANT: The old_content should match exactly (including whitespace and indentation).
+If it only differs in whitespace and matches exactly one place in the file, that place is edited
+and the file's indentation is kept."#
                 .to_string(),
+            applied_edits: Mutex::new(HashSet::new()),
         }
     }
 
@@ -54,68 +104,341 @@ IMPORTANT: The old_content must match exactly (including whitespace and indentat
             "input_schema": {
                 "type": "object",
                 "properties": {
+                    "operation": {
+                        "type": "string",
+                        "enum": ["edit", "create"],
+                        "description": "edit (default) replaces old_content in an existing file; create writes a new file"
+                    },
                     "file_path": {
                         "type": "string",
                         "description": "Relative path to the file within the workspace"
                     },
                     "old_content": {
                         "type": "string",
-                        "description": "Exact content to be replaced"
+                        "description": "Exact content to be replaced (edit only)"
                     },
                     "new_content": {
                         "type": "string",
-                        "description": "New content to replace with"
+                        "description": "New content to replace with, or the full content of a created file"
                     }
                 },
-                "required": ["file_path", "old_content", "new_content"]
+                "required": ["file_path", "new_content"]
             }
         })
     }
 
-    pub fn execute(&self, input: CodeEditorInput, workspace_root: &Path) -> CodeEditorResult {
+    /// Apply `input` to the files of `store`, `DiskFiles` outside of `--patch-only`
+    pub fn execute(
+        &self,
+        input: CodeEditorInput,
+        workspace_root: &Path,
+        store: &dyn FileStore,
+    ) -> CodeEditorResult {
         let full_path = workspace_root.join(&input.file_path);
 
+        // An edit without old_content on a missing file can only mean "create"
+        match input.operation.as_deref() {
+            Some("create") => return Self::create(&input, workspace_root, store),
+            None | Some("edit") if input.old_content.is_empty() && !store.exists(&full_path) => {
+                return Self::create(&input, workspace_root, store);
+            }
+            // Replacing "" would insert new_content between every character
+            None | Some("edit") if input.old_content.is_empty() => {
+                return CodeEditorResult {
+                    success: false,
+                    message: format!(
+                        "No old_content given for existing file: {}",
+                        workspace_path::display(&full_path, workspace_root)
+                    ),
+                    error: Some("Pass the exact old_content to replace, or use the \"create\" operation for a new file.".to_string()),
+                };
+            }
+            None | Some("edit") => {}
+            Some(other) => {
+                return CodeEditorResult {
+                    success: false,
+                    message: format!("Unknown operation: {}", other),
+                    error: Some("Supported operations are \"edit\" and \"create\"".to_string()),
+                };
+            }
+        }
+
         // Read the current file content
-        let current_content = match fs::read_to_string(&full_path) {
+        let current_content = match store.read(&full_path) {
             Ok(content) => content,
             Err(e) => {
                 return CodeEditorResult {
                     success: false,
-                    message: format!("Failed to read file: {}", full_path.display()),
+                    message: format!(
+                        "Failed to read file: {}",
+                        workspace_path::display(&full_path, workspace_root)
+                    ),
                     error: Some(e.to_string()),
                 };
             }
         };
 
-        // Check if old_content exists in the file
-        if !current_content.contains(&input.old_content) {
+        // Short-circuit an identical edit that was already applied in this run
+        let edit_key = (
+            input.file_path.clone(),
+            input.old_content.clone(),
+            input.new_content.clone(),
+        );
+        if self.applied_edits.lock().unwrap().contains(&edit_key)
+            && current_content.contains(&input.new_content)
+        {
+            return CodeEditorResult {
+                success: true,
+                message: format!(
+                    "Edit already applied to file: {} (duplicate edit skipped)",
+                    workspace_path::display(&full_path, workspace_root)
+                ),
+                error: None,
+            };
+        }
+
+        // Check if old_content exists in the file, falling back to a whitespace-tolerant match
+        let (new_content, whitespace_tolerant) = if current_content.contains(&input.old_content) {
+            (
+                current_content.replace(&input.old_content, &input.new_content),
+                false,
+            )
+        } else if let Some(replaced) = Self::replace_ignoring_whitespace(
+            &current_content,
+            &input.old_content,
+            &input.new_content,
+        ) {
+            (replaced, true)
+        } else {
             return CodeEditorResult {
                 success: false,
                 message: format!(
                     "Old content not found in file: {}",
-                    full_path.display()
+                    workspace_path::display(&full_path, workspace_root)
                 ),
                 error: Some("The exact old_content string was not found in the file. Make sure it matches exactly including whitespace.".to_string()),
             };
+        };
+
+        // Write the new content back
+        match store.write(&full_path, &new_content) {
+            Ok(_) => {
+                self.applied_edits.lock().unwrap().insert(edit_key);
+                CodeEditorResult {
+                    success: true,
+                    message: if whitespace_tolerant {
+                        format!(
+                            "Successfully edited file: {} (matched ignoring whitespace differences)",
+                            workspace_path::display(&full_path, workspace_root)
+                        )
+                    } else {
+                        format!(
+                            "Successfully edited file: {}",
+                            workspace_path::display(&full_path, workspace_root)
+                        )
+                    },
+                    error: None,
+                }
+            }
+            Err(e) => CodeEditorResult {
+                success: false,
+                message: format!(
+                    "Failed to write file: {}",
+                    workspace_path::display(&full_path, workspace_root)
+                ),
+                error: Some(e.to_string()),
+            },
         }
+    }
+}
 
-        // Perform the replacement
-        let new_content = current_content.replace(&input.old_content, &input.new_content);
+impl CodeEditorTool {
+    /// Write `new_content` to a new file inside the workspace
+    fn create(
+        input: &CodeEditorInput,
+        workspace_root: &Path,
+        store: &dyn FileStore,
+    ) -> CodeEditorResult {
+        let full_path = match Self::resolve_in_workspace(&input.file_path, workspace_root) {
+            Some(path) => path,
+            None => {
+                return CodeEditorResult {
+                    success: false,
+                    message: format!(
+                        "Refusing to create file outside the workspace: {}",
+                        input.file_path
+                    ),
+                    error: Some(
+                        "file_path must be a relative path inside the workspace".to_string(),
+                    ),
+                };
+            }
+        };
 
-        // Write the new content back
-        match fs::write(&full_path, new_content) {
+        if store.exists(&full_path) {
+            return CodeEditorResult {
+                success: false,
+                message: format!("File already exists: {}", workspace_path::display(&full_path, workspace_root)),
+                error: Some(
+                    "create never overwrites files. Use the edit operation to change an existing file."
+                        .to_string(),
+                ),
+            };
+        }
+
+        match store.write(&full_path, &input.new_content) {
             Ok(_) => CodeEditorResult {
                 success: true,
-                message: format!("Successfully edited file: {}", full_path.display()),
+                message: format!(
+                    "Successfully created file: {}",
+                    workspace_path::display(&full_path, workspace_root)
+                ),
                 error: None,
             },
             Err(e) => CodeEditorResult {
                 success: false,
-                message: format!("Failed to write file: {}", full_path.display()),
+                message: format!(
+                    "Failed to create file: {}",
+                    workspace_path::display(&full_path, workspace_root)
+                ),
                 error: Some(e.to_string()),
             },
         }
     }
+
+    /// Join `file_path` onto the workspace, rejecting paths that could escape it
+    fn resolve_in_workspace(file_path: &str, workspace_root: &Path) -> Option<PathBuf> {
+        let path = Path::new(file_path);
+        let relative = if path.is_absolute() {
+            path.strip_prefix(workspace_root).ok()?
+        } else {
+            path
+        };
+
+        relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+            .then(|| workspace_root.join(relative))
+    }
+
+    /// Replace `old` in `content` when it only differs in whitespace
+    ///
+    /// Runs of whitespace (including newlines and indentation) are compared as a
+    /// single space. The replacement is applied only if exactly one such match
+    /// exists; `new` is re-indented to the file's indentation.
+    fn replace_ignoring_whitespace(content: &str, old: &str, new: &str) -> Option<String> {
+        let old_trimmed = old.trim();
+        if old_trimmed.is_empty() {
+            return None;
+        }
+        let (normalized_old, _) = Self::normalize_whitespace(old_trimmed);
+        let (normalized, byte_ranges) = Self::normalize_whitespace(content);
+
+        let mut matches = normalized.match_indices(&normalized_old);
+        let (index, _) = matches.next()?;
+        if matches.next().is_some() {
+            return None;
+        }
+        let start = byte_ranges[index].0;
+        let end = byte_ranges[index + normalized_old.len() - 1].1;
+
+        // The match starts at the first non-whitespace character, so the file keeps its own
+        // indentation for the first line; map the model's indentation for the following lines
+        let old_indent = Self::leading_whitespace(old.trim_start_matches(['\n', '\r']));
+        let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+        let file_indent = Self::leading_whitespace(&content[line_start..]);
+
+        let new_trimmed = new.trim_start_matches(['\n', '\r']);
+        let new_trimmed = new_trimmed.strip_prefix(old_indent).unwrap_or(new_trimmed);
+        let new_trimmed = if old.len() > old.trim_end().len() {
+            new_trimmed.trim_end()
+        } else {
+            new_trimmed
+        };
+        let reindented = new_trimmed
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| match line.strip_prefix(old_indent) {
+                Some(rest) if i > 0 && !old_indent.is_empty() => {
+                    let nested_indent = Self::leading_whitespace(rest);
+                    format!(
+                        "{}{}{}",
+                        file_indent,
+                        Self::convert_indent(nested_indent, old_indent, file_indent),
+                        &rest[nested_indent.len()..]
+                    )
+                }
+                _ => line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Some(format!(
+            "{}{}{}",
+            &content[..start],
+            reindented,
+            &content[end..]
+        ))
+    }
+
+    /// Collapse whitespace runs to a single space, keeping the original byte range of every
+    /// byte of the normalized string
+    fn normalize_whitespace(text: &str) -> (String, Vec<(usize, usize)>) {
+        let mut normalized = String::with_capacity(text.len());
+        let mut byte_ranges = Vec::with_capacity(text.len());
+        let mut chars = text.char_indices().peekable();
+
+        while let Some((start, c)) = chars.next() {
+            if c.is_whitespace() {
+                let mut end = start + c.len_utf8();
+                while let Some(&(next_start, next)) = chars.peek() {
+                    if !next.is_whitespace() {
+                        break;
+                    }
+                    end = next_start + next.len_utf8();
+                    chars.next();
+                }
+                normalized.push(' ');
+                byte_ranges.push((start, end));
+            } else {
+                normalized.push(c);
+                byte_ranges.extend(std::iter::repeat_n(
+                    (start, start + c.len_utf8()),
+                    c.len_utf8(),
+                ));
+            }
+        }
+
+        (normalized, byte_ranges)
+    }
+
+    /// Convert `indent` from the model's indentation style to the file's, using the width
+    /// implied by the two base indentations (e.g. four spaces quoted for one tab)
+    fn convert_indent(indent: &str, old_indent: &str, file_indent: &str) -> String {
+        let count = |text: &str, c: char| text.chars().filter(|&x| x == c).count();
+        let (old_tabs, old_spaces) = (count(old_indent, '\t'), count(old_indent, ' '));
+        let (file_tabs, file_spaces) = (count(file_indent, '\t'), count(file_indent, ' '));
+
+        if old_tabs == 0 && file_spaces == 0 && file_tabs > 0 && old_spaces % file_tabs == 0 {
+            // Spaces quoted for a tab-indented file
+            let width = old_spaces / file_tabs;
+            let spaces = count(indent, ' ');
+            format!(
+                "{}{}",
+                "\t".repeat(count(indent, '\t') + spaces / width),
+                " ".repeat(spaces % width)
+            )
+        } else if old_spaces == 0 && file_tabs == 0 && old_tabs > 0 && file_spaces % old_tabs == 0 {
+            // Tabs quoted for a space-indented file
+            indent.replace('\t', &" ".repeat(file_spaces / old_tabs))
+        } else {
+            indent.to_string()
+        }
+    }
+
+    fn leading_whitespace(line: &str) -> &str {
+        &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+    }
 }
 
 impl Default for CodeEditorTool {
@@ -123,3 +446,259 @@ impl Default for CodeEditorTool {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit_input(old_content: &str, new_content: &str) -> CodeEditorInput {
+        CodeEditorInput {
+            operation: None,
+            file_path: "View.swift".to_string(),
+            old_content: old_content.to_string(),
+            new_content: new_content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_edit_replaces_content() {
+        let temp_dir = std::env::temp_dir().join("test_code_editor_edit");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("View.swift"), "Button(\"Login\")").unwrap();
+
+        let tool = CodeEditorTool::new();
+        let result = tool.execute(
+            edit_input("\"Login\"", "\"Sign In\""),
+            &temp_dir,
+            &DiskFiles,
+        );
+
+        assert!(result.success);
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("View.swift")).unwrap(),
+            "Button(\"Sign In\")"
+        );
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_edit_matches_tabs_against_spaces() {
+        let temp_dir = std::env::temp_dir().join("test_code_editor_tabs");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(
+            temp_dir.join("View.swift"),
+            "var body: some View {\n\tVStack {\n\t\tText(\"Hello\")\n\t}\n}\n",
+        )
+        .unwrap();
+
+        // The model quotes the file with four-space indentation
+        let tool = CodeEditorTool::new();
+        let result = tool.execute(
+            edit_input(
+                "    VStack {\n        Text(\"Hello\")",
+                "    VStack {\n        Text(\"Hello\")\n        Text(\"World\")",
+            ),
+            &temp_dir,
+            &DiskFiles,
+        );
+
+        assert!(result.success);
+        assert!(result.message.contains("ignoring whitespace"));
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("View.swift")).unwrap(),
+            "var body: some View {\n\tVStack {\n\t\tText(\"Hello\")\n\t\tText(\"World\")\n\t}\n}\n"
+        );
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_edit_matches_despite_trailing_spaces() {
+        let temp_dir = std::env::temp_dir().join("test_code_editor_trailing");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(
+            temp_dir.join("View.swift"),
+            "let title = \"Login\"   \nlet subtitle = \"Welcome\"\n",
+        )
+        .unwrap();
+
+        let tool = CodeEditorTool::new();
+        let result = tool.execute(
+            edit_input(
+                "let title = \"Login\"\nlet subtitle",
+                "let title = \"Sign In\"\nlet subtitle",
+            ),
+            &temp_dir,
+            &DiskFiles,
+        );
+
+        assert!(result.success);
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("View.swift")).unwrap(),
+            "let title = \"Sign In\"\nlet subtitle = \"Welcome\"\n"
+        );
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_ambiguous_whitespace_match_is_rejected() {
+        let temp_dir = std::env::temp_dir().join("test_code_editor_ambiguous");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let original = "\tText(\"Hello\")\n\tText(\"Hello\")\n";
+        fs::write(temp_dir.join("View.swift"), original).unwrap();
+
+        let tool = CodeEditorTool::new();
+        let result = tool.execute(
+            edit_input("    Text(\"Hello\")  ", "    Text(\"Bye\")"),
+            &temp_dir,
+            &DiskFiles,
+        );
+
+        assert!(!result.success);
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("View.swift")).unwrap(),
+            original
+        );
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    fn create_input(file_path: &str, new_content: &str) -> CodeEditorInput {
+        CodeEditorInput {
+            operation: Some("create".to_string()),
+            file_path: file_path.to_string(),
+            old_content: String::new(),
+            new_content: new_content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_create_new_file() {
+        let temp_dir = std::env::temp_dir().join("test_code_editor_create");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let tool = CodeEditorTool::new();
+        let result = tool.execute(
+            create_input("ProfileView.swift", "struct ProfileView {}\n"),
+            &temp_dir,
+            &DiskFiles,
+        );
+
+        assert!(result.success);
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("ProfileView.swift")).unwrap(),
+            "struct ProfileView {}\n"
+        );
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_create_into_new_directory() {
+        let temp_dir = std::env::temp_dir().join("test_code_editor_create_dir");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        // Empty old_content on a missing file is treated as create as well
+        let tool = CodeEditorTool::new();
+        let result = tool.execute(
+            CodeEditorInput {
+                file_path: "App/Views/Settings/SettingsView.swift".to_string(),
+                ..edit_input("", "struct SettingsView {}\n")
+            },
+            &temp_dir,
+            &DiskFiles,
+        );
+
+        assert!(result.success);
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("App/Views/Settings/SettingsView.swift")).unwrap(),
+            "struct SettingsView {}\n"
+        );
+
+        // Paths leaving the workspace are refused
+        let escaped = tool.execute(create_input("../Escaped.swift", ""), &temp_dir, &DiskFiles);
+        assert!(!escaped.success);
+        assert!(!temp_dir.join("../Escaped.swift").exists());
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_create_refuses_overwrite() {
+        let temp_dir = std::env::temp_dir().join("test_code_editor_create_existing");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("View.swift"), "struct View {}\n").unwrap();
+
+        let tool = CodeEditorTool::new();
+        let result = tool.execute(
+            create_input("View.swift", "struct Other {}\n"),
+            &temp_dir,
+            &DiskFiles,
+        );
+
+        assert!(!result.success);
+        assert!(result.message.contains("already exists"));
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("View.swift")).unwrap(),
+            "struct View {}\n"
+        );
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_edit_without_old_content_leaves_existing_file_unchanged() {
+        let temp_dir = std::env::temp_dir().join("test_code_editor_empty_old_content");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("View.swift"), "struct View {}\n").unwrap();
+
+        let tool = CodeEditorTool::new();
+        let result = tool.execute(edit_input("", "struct Other {}\n"), &temp_dir, &DiskFiles);
+
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("old_content"));
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("View.swift")).unwrap(),
+            "struct View {}\n"
+        );
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_reissued_edit_is_skipped() {
+        let temp_dir = std::env::temp_dir().join("test_code_editor_reissue");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("View.swift"), "Text(\"Hello\")").unwrap();
+
+        let tool = CodeEditorTool::new();
+        let input = || edit_input("Text(\"Hello\")", "Text(\"Hello\")\n    .padding()");
+
+        let first = tool.execute(input(), &temp_dir, &DiskFiles);
+        assert!(first.success);
+
+        // The model re-issues the exact same edit after a test failure
+        let second = tool.execute(input(), &temp_dir, &DiskFiles);
+        assert!(second.success);
+        assert!(second.message.contains("already applied"));
+
+        // The edit must not be applied twice
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("View.swift")).unwrap(),
+            "Text(\"Hello\")\n    .padding()"
+        );
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,293 @@
+use super::test_runner_tool::output_with_timeout;
+use anthropic_sdk::Tool;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// Names of the built-in tools, which custom tools can't replace
+const BUILT_IN_TOOLS: &[&str] = &["directory_inspector", "code_editor", "test_runner"];
+
+/// Longest a custom tool's command may run
+const CUSTOM_TOOL_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Tool names providers accept
+static NAME_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z0-9_-]{1,64}$").unwrap());
+
+/// `{field}` placeholders in a command template
+static PLACEHOLDER_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+#[derive(Debug, thiserror::Error)]
+pub enum CustomToolError {
+    #[error("Failed to read custom tools from {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid custom tools file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error("Invalid custom tool name \"{0}\": use up to 64 letters, digits, '_' or '-'")]
+    InvalidName(String),
+
+    #[error("Custom tool \"{0}\" clashes with a built-in or another custom tool")]
+    DuplicateName(String),
+
+    #[error("Invalid input_schema of custom tool \"{name}\": {source}")]
+    InvalidSchema {
+        name: String,
+        source: serde_json::Error,
+    },
+}
+
+/// Contents of a custom tools file
+#[derive(Debug, Deserialize)]
+struct CustomToolsFile {
+    tools: Vec<CustomTool>,
+}
+
+/// A project-specific tool that runs a shell command in the workspace
+///
+/// `command` is a template: `{field}` is replaced by the shell-quoted value of
+/// that input field, so the model can't inject shell syntax.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CustomTool {
+    pub name: String,
+    pub description: String,
+    #[serde(default = "empty_input_schema")]
+    pub input_schema: Value,
+    pub command: String,
+}
+
+fn empty_input_schema() -> Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomToolResult {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CustomToolResult {
+    fn error(message: String) -> Self {
+        Self {
+            success: false,
+            exit_code: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            error: Some(message),
+        }
+    }
+}
+
+/// Load the custom tools defined in the JSON file at `path`
+pub fn load(path: &Path) -> Result<Vec<CustomTool>, CustomToolError> {
+    let json = fs::read_to_string(path).map_err(|source| CustomToolError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let file: CustomToolsFile =
+        serde_json::from_str(&json).map_err(|source| CustomToolError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+    let mut names: Vec<&str> = BUILT_IN_TOOLS.to_vec();
+    for tool in &file.tools {
+        if !NAME_PATTERN.is_match(&tool.name) {
+            return Err(CustomToolError::InvalidName(tool.name.clone()));
+        }
+        if names.contains(&tool.name.as_str()) {
+            return Err(CustomToolError::DuplicateName(tool.name.clone()));
+        }
+        // The pipeline converts every definition into a provider tool, so it must convert here
+        if let Err(source) = serde_json::from_value::<Tool>(tool.to_tool_definition()) {
+            return Err(CustomToolError::InvalidSchema {
+                name: tool.name.clone(),
+                source,
+            });
+        }
+        names.push(&tool.name);
+    }
+    Ok(file.tools)
+}
+
+impl CustomTool {
+    pub fn to_tool_definition(&self) -> Value {
+        serde_json::json!({
+            "name": self.name,
+            "description": self.description,
+            "input_schema": self.input_schema,
+        })
+    }
+
+    /// The command with every placeholder replaced by its shell-quoted input value
+    ///
+    /// Fails if a string value is a path leaving the workspace.
+    pub fn render_command(&self, input: &Value) -> Result<String, String> {
+        let mut violation = None;
+        let command =
+            PLACEHOLDER_PATTERN.replace_all(&self.command, |captures: &regex::Captures| {
+                let value = match input.get(&captures[1]) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                };
+                if escapes_workspace(&value) {
+                    violation.get_or_insert_with(|| {
+                        format!(
+                            "\"{}\" for {} is outside the workspace",
+                            value, &captures[1]
+                        )
+                    });
+                }
+                shell_quote(&value)
+            });
+        match violation {
+            Some(violation) => Err(violation),
+            None => Ok(command.into_owned()),
+        }
+    }
+
+    /// Run the command in `workspace_root` with the model-supplied `input`
+    pub fn execute(&self, input: &Value, workspace_root: &Path) -> CustomToolResult {
+        let command = match self.render_command(input) {
+            Ok(command) => command,
+            Err(violation) => return CustomToolResult::error(violation),
+        };
+
+        let output = output_with_timeout(
+            Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .current_dir(workspace_root),
+            CUSTOM_TOOL_TIMEOUT,
+        );
+        match output {
+            Ok(output) => CustomToolResult {
+                success: output.status.is_some_and(|status| status.success()),
+                exit_code: output.status.and_then(|status| status.code()),
+                stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                error: output.status.is_none().then(|| {
+                    format!(
+                        "Killed after running longer than {}s",
+                        CUSTOM_TOOL_TIMEOUT.as_secs()
+                    )
+                }),
+            },
+            Err(e) => CustomToolResult::error(format!("Failed to run {}: {}", self.name, e)),
+        }
+    }
+}
+
+/// Whether `value` is an absolute path or climbs out of the directory it's used in
+fn escapes_workspace(value: &str) -> bool {
+    let path = Path::new(value);
+    path.is_absolute()
+        || value.starts_with('~')
+        || path
+            .components()
+            .any(|component| component == Component::ParentDir)
+}
+
+/// Quote `value` as a single POSIX shell word
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn echo_tool() -> CustomTool {
+        CustomTool {
+            name: "echo".to_string(),
+            description: "Echo a message".to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": { "message": { "type": "string" } },
+                "required": ["message"]
+            }),
+            command: "echo {message}".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_load_rejects_built_in_names_and_invalid_schemas() {
+        let dir = std::env::temp_dir().join(format!("autofix-custom-tools-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tools.json");
+        fs::write(
+            &path,
+            r#"{"tools": [{"name": "lint", "description": "Run the linter", "command": "swiftlint"}]}"#,
+        )
+        .unwrap();
+        let tools = load(&path).unwrap();
+        assert_eq!(tools[0].name, "lint");
+        assert_eq!(tools[0].input_schema, empty_input_schema());
+
+        fs::write(
+            &path,
+            r#"{"tools": [{"name": "code_editor", "description": "", "command": "true"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            load(&path),
+            Err(CustomToolError::DuplicateName(name)) if name == "code_editor"
+        ));
+
+        fs::write(
+            &path,
+            r#"{"tools": [{"name": "lint", "description": "", "input_schema": "none", "command": "true"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            load(&path),
+            Err(CustomToolError::InvalidSchema { name, .. }) if name == "lint"
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_render_command_quotes_input() {
+        let tool = echo_tool();
+        assert_eq!(
+            tool.render_command(&serde_json::json!({ "message": "it's $(rm -rf .)" }))
+                .unwrap(),
+            r"echo 'it'\''s $(rm -rf .)'"
+        );
+        assert!(
+            tool.render_command(&serde_json::json!({ "message": "../../etc/passwd" }))
+                .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_runs_in_workspace() {
+        let result = echo_tool().execute(
+            &serde_json::json!({ "message": "hello; exit 1" }),
+            &std::env::temp_dir(),
+        );
+        assert!(result.success);
+        assert_eq!(result.stdout, "hello; exit 1\n");
+    }
+}
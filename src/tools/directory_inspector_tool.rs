@@ -1,11 +1,81 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path};
+
+/// Most paths a `find` returns; the total match count is reported alongside
+const MAX_FIND_RESULTS: usize = 50;
+
+/// Directories holding third-party or generated code, ranked last by `find`
+pub const VENDORED_DIRS: &[&str] = &[
+    "Pods",
+    "Carthage",
+    "SourcePackages",
+    "DerivedData",
+    ".build",
+    "node_modules",
+    ".autofix",
+];
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DirectoryInspectorTool {
     name: String,
     description: String,
+    #[serde(skip)]
+    ranking: FindRanking,
+}
+
+/// What makes a `find` match relevant for the test being fixed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FindRanking {
+    /// Names from the failing test, such as its target and class without the `Tests` suffix
+    pub keywords: Vec<String>,
+    /// Whether test code or app code is being fixed; the other side ranks lower
+    pub prefer_test_paths: Option<bool>,
+}
+
+impl FindRanking {
+    /// Sort key for `path`, relative to the searched directory; smaller is more relevant
+    ///
+    /// Vendored code comes last, then paths from the side not being fixed, then
+    /// paths without a keyword. Within each group shallower paths come first.
+    fn key(&self, relative: &Path) -> (bool, bool, bool, usize) {
+        let components: Vec<&str> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            })
+            .collect();
+        let lowercase_path = relative.to_string_lossy().to_lowercase();
+
+        let is_vendored = components
+            .iter()
+            .any(|component| VENDORED_DIRS.contains(component));
+        let is_other_side = self
+            .prefer_test_paths
+            .is_some_and(|prefer_tests| is_test_path(&components) != prefer_tests);
+        let misses_keywords = !self
+            .keywords
+            .iter()
+            .any(|keyword| lowercase_path.contains(&keyword.to_lowercase()));
+
+        (
+            is_vendored,
+            is_other_side,
+            misses_keywords,
+            components.len(),
+        )
+    }
+}
+
+/// Whether a path, given as its components, belongs to test code
+///
+/// Test bundles and files follow the `...Tests` naming convention.
+pub fn is_test_path(components: &[&str]) -> bool {
+    components.iter().any(|component| {
+        let stem = component.split('.').next().unwrap_or(component);
+        stem.ends_with("Tests") || stem.ends_with("Test")
+    })
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -14,6 +84,12 @@ pub struct DirectoryInspectorInput {
     pub path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pattern: Option<String>,
+    /// First line to read, 1-based; reads from the start when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<usize>,
+    /// Number of lines to read; reads to the end when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_count: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,14 +106,21 @@ impl DirectoryInspectorTool {
             description: r#"A tool to inspect the file system, read files, and search for content.
 Operations:
 - "list": List files and directories in a path. Returns array of {name, type, path}.
-- "read": Read the contents of a file. Returns {content: string}.
+- "read": Read the contents of a file. Returns {content: string}. Pass start_line (1-based) and line_count to read a page of a long file; the result then also has start_line, end_line and total_lines.
 - "search": Search for a pattern (regex) in files. Returns array of {file, line, content, line_number}.
-- "find": Find files by name pattern (glob). Returns array of file paths.
+- "find": Find files by name pattern (glob). Returns {total_matches, files}, the most relevant files first (at most 50).
 
-Input format: {"operation": "list|read|search|find", "path": "/path/to/dir", "pattern": "optional search pattern"}"#.to_string(),
+Input format: {"operation": "list|read|search|find", "path": "/path/to/dir", "pattern": "optional search pattern", "start_line": 1, "line_count": 200}"#.to_string(),
+            ranking: FindRanking::default(),
         }
     }
 
+    /// Rank `find` results by relevance for the test being fixed
+    pub fn with_find_ranking(mut self, ranking: FindRanking) -> Self {
+        self.ranking = ranking;
+        self
+    }
+
     pub fn to_tool_definition(&self) -> serde_json::Value {
         serde_json::json!({
             "name": self.name,
@@ -57,6 +140,16 @@ Input format: {"operation": "list|read|search|find", "path": "/path/to/dir", "pa
                     "pattern": {
                         "type": "string",
                         "description": "Optional search pattern (regex for search, glob for find)"
+                    },
+                    "start_line": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional first line to read, 1-based (read only)"
+                    },
+                    "line_count": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Optional number of lines to read (read only)"
                     }
                 },
                 "required": ["operation", "path"]
@@ -73,7 +166,7 @@ Input format: {"operation": "list|read|search|find", "path": "/path/to/dir", "pa
 
         match input.operation.as_str() {
             "list" => self.list_directory(&full_path),
-            "read" => self.read_file(&full_path),
+            "read" => self.read_file(&full_path, input.start_line, input.line_count),
             "search" => {
                 if let Some(pattern) = input.pattern {
                     self.search_files(&full_path, &pattern)
@@ -134,13 +227,38 @@ Input format: {"operation": "list|read|search|find", "path": "/path/to/dir", "pa
         }
     }
 
-    fn read_file(&self, path: &Path) -> DirectoryInspectorResult {
+    fn read_file(
+        &self,
+        path: &Path,
+        start_line: Option<usize>,
+        line_count: Option<usize>,
+    ) -> DirectoryInspectorResult {
         match fs::read_to_string(path) {
-            Ok(content) => DirectoryInspectorResult {
-                success: true,
-                data: Some(serde_json::json!({"content": content})),
-                error: None,
-            },
+            Ok(content) if start_line.is_none() && line_count.is_none() => {
+                DirectoryInspectorResult {
+                    success: true,
+                    data: Some(serde_json::json!({"content": content})),
+                    error: None,
+                }
+            }
+            Ok(content) => {
+                let lines: Vec<&str> = content.lines().collect();
+                let start = start_line.unwrap_or(1).max(1);
+                let end = line_count
+                    .map_or(lines.len(), |count| (start - 1).saturating_add(count))
+                    .min(lines.len());
+                let page = lines.get(start - 1..end).unwrap_or_default();
+                DirectoryInspectorResult {
+                    success: true,
+                    data: Some(serde_json::json!({
+                        "content": page.join("\n"),
+                        "start_line": start,
+                        "end_line": end.max(start - 1),
+                        "total_lines": lines.len(),
+                    })),
+                    error: None,
+                }
+            }
             Err(e) => DirectoryInspectorResult {
                 success: false,
                 data: None,
@@ -222,14 +340,25 @@ Input format: {"operation": "list|read|search|find", "path": "/path/to/dir", "pa
 
         match glob::glob(&glob_pattern) {
             Ok(paths) => {
-                let files: Vec<String> = paths
-                    .filter_map(|entry| entry.ok())
-                    .map(|path| path.to_string_lossy().to_string())
+                let mut files: Vec<_> = paths.filter_map(|entry| entry.ok()).collect();
+                let total_matches = files.len();
+                files.sort_by_cached_key(|file| {
+                    let relative = file.strip_prefix(path).unwrap_or(file);
+                    (self.ranking.key(relative), file.clone())
+                });
+                files.truncate(MAX_FIND_RESULTS);
+
+                let files: Vec<String> = files
+                    .iter()
+                    .map(|file| file.to_string_lossy().to_string())
                     .collect();
 
                 DirectoryInspectorResult {
                     success: true,
-                    data: Some(serde_json::json!(files)),
+                    data: Some(serde_json::json!({
+                        "total_matches": total_matches,
+                        "files": files,
+                    })),
                     error: None,
                 }
             }
@@ -247,3 +376,101 @@ impl Default for DirectoryInspectorTool {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_find_ranks_app_source_above_vendored_match() {
+        let workspace =
+            std::env::temp_dir().join(format!("directory-inspector-{}", Uuid::new_v4()));
+        for file in [
+            "Pods/LoginKit/Sources/LoginView.swift",
+            "App/LoginView.swift",
+            "App/Settings/SettingsView.swift",
+            "AppUITests/LoginViewTests.swift",
+        ] {
+            let path = workspace.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let tool = DirectoryInspectorTool::new().with_find_ranking(FindRanking {
+            keywords: vec!["Login".to_string()],
+            prefer_test_paths: Some(false),
+        });
+
+        let result = tool.execute(
+            DirectoryInspectorInput {
+                operation: "find".to_string(),
+                path: ".".to_string(),
+                pattern: Some("*.swift".to_string()),
+                start_line: None,
+                line_count: None,
+            },
+            &workspace,
+        );
+
+        let data = result.data.unwrap();
+        assert_eq!(data["total_matches"], 4);
+        let files: Vec<String> = data["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| {
+                let file = Path::new(file.as_str().unwrap());
+                file.strip_prefix(workspace.join("."))
+                    .unwrap()
+                    .display()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                "App/LoginView.swift",
+                "App/Settings/SettingsView.swift",
+                "AppUITests/LoginViewTests.swift",
+                "Pods/LoginKit/Sources/LoginView.swift",
+            ]
+        );
+
+        // Clean up
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[test]
+    fn test_read_returns_requested_page() {
+        let workspace =
+            std::env::temp_dir().join(format!("directory-inspector-{}", Uuid::new_v4()));
+        fs::create_dir_all(&workspace).unwrap();
+        let lines: Vec<String> = (1..=10).map(|n| format!("line {}", n)).collect();
+        fs::write(workspace.join("output.txt"), lines.join("\n")).unwrap();
+        let read = |start_line, line_count| {
+            DirectoryInspectorTool::new()
+                .execute(
+                    DirectoryInspectorInput {
+                        operation: "read".to_string(),
+                        path: "output.txt".to_string(),
+                        pattern: None,
+                        start_line,
+                        line_count,
+                    },
+                    &workspace,
+                )
+                .data
+                .unwrap()
+        };
+
+        let page = read(Some(4), Some(3));
+        assert_eq!(page["content"], "line 4\nline 5\nline 6");
+        assert_eq!(page["end_line"], 6);
+        assert_eq!(page["total_lines"], 10);
+        assert_eq!(read(Some(9), Some(5))["content"], "line 9\nline 10");
+        assert_eq!(read(Some(20), None)["content"], "");
+        assert_eq!(read(None, None)["content"], lines.join("\n"));
+
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+}
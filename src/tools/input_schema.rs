@@ -0,0 +1,112 @@
+use serde_json::Value;
+
+/// Check a tool call's input against the tool's `input_schema`
+///
+/// Covers the parts of JSON Schema the tool definitions use: an object with
+/// `required` fields and `properties` with a `type` and optional `enum`.
+/// Returns a description of the first violation found.
+pub fn validate(input: &Value, schema: &Value) -> Result<(), String> {
+    let Some(fields) = input.as_object() else {
+        return Err(format!("input must be an object, got {}", type_name(input)));
+    };
+
+    let required = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str);
+    for field in required {
+        if fields.get(field).is_none_or(Value::is_null) {
+            return Err(format!("missing required field \"{}\"", field));
+        }
+    }
+
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return Ok(());
+    };
+    for (field, value) in fields {
+        let Some(property) = properties.get(field) else {
+            continue;
+        };
+        if let Some(expected) = property.get("type").and_then(Value::as_str)
+            && !has_type(value, expected)
+        {
+            return Err(format!(
+                "field \"{}\" must be of type {}, got {}",
+                field,
+                expected,
+                type_name(value)
+            ));
+        }
+        if let Some(allowed) = property.get("enum").and_then(Value::as_array)
+            && !allowed.contains(value)
+        {
+            return Err(format!(
+                "field \"{}\" must be one of {}, got {}",
+                field,
+                Value::Array(allowed.clone()),
+                value
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::CodeEditorTool;
+    use serde_json::json;
+
+    #[test]
+    fn test_reports_schema_violations() {
+        let schema = CodeEditorTool::new().to_tool_definition()["input_schema"].clone();
+
+        assert_eq!(
+            validate(
+                &json!({"file_path": "A.swift", "new_content": "x"}),
+                &schema
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            validate(&json!({"new_content": "x"}), &schema),
+            Err("missing required field \"file_path\"".to_string())
+        );
+        assert_eq!(
+            validate(&json!({"file_path": 3, "new_content": "x"}), &schema),
+            Err("field \"file_path\" must be of type string, got number".to_string())
+        );
+        assert_eq!(
+            validate(
+                &json!({"operation": "delete", "file_path": "A.swift", "new_content": ""}),
+                &schema
+            ),
+            Err(
+                "field \"operation\" must be one of [\"edit\",\"create\"], got \"delete\""
+                    .to_string()
+            )
+        );
+        assert!(validate(&json!("edit"), &schema).is_err());
+    }
+}
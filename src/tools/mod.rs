@@ -1,7 +1,12 @@
 pub mod code_editor_tool;
+pub mod custom_tool;
 pub mod directory_inspector_tool;
+pub mod input_schema;
+pub mod scheme_list;
+pub mod simulator_lock;
 pub mod test_runner_tool;
 
-pub use code_editor_tool::{CodeEditorInput, CodeEditorTool};
-pub use directory_inspector_tool::{DirectoryInspectorInput, DirectoryInspectorTool};
+pub use code_editor_tool::{CodeEditorInput, CodeEditorTool, DiskFiles, FileStore};
+pub use custom_tool::CustomTool;
+pub use directory_inspector_tool::{DirectoryInspectorInput, DirectoryInspectorTool, FindRanking};
 pub use test_runner_tool::{TestRunnerInput, TestRunnerTool};
@@ -0,0 +1,187 @@
+use crate::developer_dir;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchemeError {
+    #[error("Failed to list schemes: {0}")]
+    ListFailed(String),
+
+    #[error("Scheme '{scheme}' not found. Available schemes: {}", available.join(", "))]
+    UnknownScheme {
+        scheme: String,
+        available: Vec<String>,
+    },
+
+    #[error("No scheme matches test target '{target}'. Pass one of these with --scheme: {}", available.join(", "))]
+    NoMatchingScheme {
+        target: String,
+        available: Vec<String>,
+    },
+}
+
+/// Output of `xcodebuild -list -json`, for a workspace or a project
+#[derive(Debug, Deserialize)]
+struct ListOutput {
+    workspace: Option<SchemeContainer>,
+    project: Option<SchemeContainer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemeContainer {
+    #[serde(default)]
+    schemes: Vec<String>,
+}
+
+/// Schemes of the workspace or project in `workspace_root`, per `xcodebuild -list`
+pub fn list_schemes(workspace_root: &Path) -> Result<Vec<String>, SchemeError> {
+    let output = developer_dir::command("xcodebuild")
+        .arg("-list")
+        .arg("-json")
+        .current_dir(workspace_root)
+        .output()
+        .map_err(|e| SchemeError::ListFailed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(SchemeError::ListFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    parse_scheme_list(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Scheme names from `xcodebuild -list -json` output
+fn parse_scheme_list(json: &str) -> Result<Vec<String>, SchemeError> {
+    // xcodebuild may log warnings before the JSON
+    let json = json.find('{').map_or(json, |start| &json[start..]);
+    let output: ListOutput =
+        serde_json::from_str(json).map_err(|e| SchemeError::ListFailed(e.to_string()))?;
+    Ok(output
+        .workspace
+        .or(output.project)
+        .map(|container| container.schemes)
+        .unwrap_or_default())
+}
+
+/// Scheme to run the test `test://com.apple.xcode/{scheme}/{target}/...` with
+///
+/// A `requested` scheme (`--scheme`) must exist. Otherwise the scheme named in the
+/// identifier is used if it exists, else the scheme containing the target's name,
+/// else the longest scheme the target's name starts with, e.g. `App` for `AppUITests`.
+pub fn resolve_scheme(
+    available: &[String],
+    requested: Option<&str>,
+    test_identifier: &str,
+) -> Result<String, SchemeError> {
+    if let Some(scheme) = requested {
+        if !available.iter().any(|known| known == scheme) {
+            return Err(SchemeError::UnknownScheme {
+                scheme: scheme.to_string(),
+                available: available.to_vec(),
+            });
+        }
+        return Ok(scheme.to_string());
+    }
+
+    let parts: Vec<&str> = test_identifier
+        .trim_start_matches("test://")
+        .split('/')
+        .collect();
+    let derived = parts.get(1).copied().unwrap_or_default();
+    let target = parts.get(2).copied().unwrap_or_default();
+    if available.iter().any(|known| known == derived) {
+        return Ok(derived.to_string());
+    }
+
+    let containing_target = available
+        .iter()
+        .filter(|scheme| !target.is_empty() && scheme.contains(target))
+        .min_by_key(|scheme| scheme.len());
+    let target_prefix = available
+        .iter()
+        .filter(|scheme| !scheme.is_empty() && target.starts_with(scheme.as_str()))
+        .max_by_key(|scheme| scheme.len());
+    containing_target
+        .or(target_prefix)
+        .cloned()
+        .ok_or_else(|| SchemeError::NoMatchingScheme {
+            target: target.to_string(),
+            available: available.to_vec(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST_OUTPUT: &str = r#"2025-10-14 10:12:01.123 xcodebuild[123:456] Requested but did not find extension point
+{
+  "workspace" : {
+    "name" : "Shop",
+    "schemes" : [
+      "Shop",
+      "Shop-Staging",
+      "ShopKit"
+    ]
+  }
+}"#;
+
+    fn schemes() -> Vec<String> {
+        parse_scheme_list(LIST_OUTPUT).unwrap()
+    }
+
+    #[test]
+    fn test_parse_scheme_list() {
+        assert_eq!(schemes(), vec!["Shop", "Shop-Staging", "ShopKit"]);
+        assert_eq!(
+            parse_scheme_list(r#"{"project": {"name": "App", "schemes": ["App"], "targets": []}}"#)
+                .unwrap(),
+            vec!["App"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_scheme_picks_best_match() {
+        // The scheme in the identifier exists
+        assert_eq!(
+            resolve_scheme(
+                &schemes(),
+                None,
+                "test://com.apple.xcode/ShopKit/ShopKitTests/CartTests/testTotal"
+            )
+            .unwrap(),
+            "ShopKit"
+        );
+        // Identifier names a scheme that doesn't exist; the target's prefix does
+        assert_eq!(
+            resolve_scheme(
+                &schemes(),
+                None,
+                "test://com.apple.xcode/Shop (Debug)/ShopUITests/LoginTests/testLogin"
+            )
+            .unwrap(),
+            "Shop"
+        );
+        assert!(matches!(
+            resolve_scheme(
+                &schemes(),
+                None,
+                "test://com.apple.xcode/Other/OtherTests/LoginTests/testLogin"
+            ),
+            Err(SchemeError::NoMatchingScheme { target, .. }) if target == "OtherTests"
+        ));
+    }
+
+    #[test]
+    fn test_resolve_scheme_validates_requested_scheme() {
+        let identifier = "test://com.apple.xcode/Shop/ShopUITests/LoginTests/testLogin";
+        assert_eq!(
+            resolve_scheme(&schemes(), Some("Shop-Staging"), identifier).unwrap(),
+            "Shop-Staging"
+        );
+        let error = resolve_scheme(&schemes(), Some("Shop-Prod"), identifier).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Scheme 'Shop-Prod' not found. Available schemes: Shop, Shop-Staging, ShopKit"
+        );
+    }
+}
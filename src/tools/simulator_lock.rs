@@ -0,0 +1,116 @@
+use std::fs::{self, File, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest a test run waits for another one to release the simulator unless
+/// `--simulator-lock-timeout` says otherwise
+pub const DEFAULT_SIMULATOR_LOCK_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// How often a held lock is tried again
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SimulatorLockError {
+    #[error("Failed to open simulator lock {path}: {source}")]
+    Open { path: PathBuf, source: io::Error },
+
+    #[error(
+        "Timed out after {}s waiting for another test run to release the simulator",
+        .0.as_secs()
+    )]
+    Timeout(Duration),
+}
+
+/// Lock file of `simulator` (a name or UDID); kept outside any workspace because
+/// runs on different workspaces still compete for the same simulator
+pub fn lock_path(simulator: &str) -> PathBuf {
+    let file_name: String = simulator
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    std::env::temp_dir()
+        .join("autofix-simulators")
+        .join(format!("{}.lock", file_name))
+}
+
+/// Exclusive use of the test simulator, shared by all threads and autofix processes
+/// using the same lock file; released when dropped
+pub struct SimulatorLock {
+    _file: File,
+}
+
+impl SimulatorLock {
+    /// Wait up to `timeout` for the lock at `path`, creating the file as needed
+    pub fn acquire(path: &Path, timeout: Duration) -> Result<Self, SimulatorLockError> {
+        let open_error = |source| SimulatorLockError::Open {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(open_error)?;
+        }
+        let file = File::create(path).map_err(open_error)?;
+
+        let started = Instant::now();
+        let mut announced = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::Error(e)) => return Err(open_error(e)),
+                Err(TryLockError::WouldBlock) if started.elapsed() >= timeout => {
+                    return Err(SimulatorLockError::Timeout(timeout));
+                }
+                Err(TryLockError::WouldBlock) => {
+                    if !announced {
+                        println!("   🔒 Waiting for another test run to release the simulator...");
+                        announced = true;
+                    }
+                    thread::sleep(RETRY_INTERVAL);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_held_lock_times_out() {
+        let dir = std::env::temp_dir().join(format!("simulator-lock-{}", Uuid::new_v4()));
+        let path = dir.join("simulator.lock");
+
+        let held = SimulatorLock::acquire(&path, Duration::ZERO).unwrap();
+        assert!(matches!(
+            SimulatorLock::acquire(&path, Duration::from_millis(250)),
+            Err(SimulatorLockError::Timeout(timeout)) if timeout == Duration::from_millis(250)
+        ));
+        drop(held);
+        assert!(SimulatorLock::acquire(&path, Duration::ZERO).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lock_path_is_per_simulator() {
+        assert_eq!(
+            lock_path("iPhone 17 Pro").file_name().unwrap(),
+            "iPhone_17_Pro.lock"
+        );
+        assert_ne!(lock_path("iPhone 17 Pro"), lock_path("iPad Air"));
+        assert_eq!(
+            lock_path("iPad Air").parent(),
+            lock_path("iPhone 17 Pro").parent()
+        );
+    }
+}
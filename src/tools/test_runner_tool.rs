@@ -1,14 +1,421 @@
+use super::simulator_lock::{
+    self, DEFAULT_SIMULATOR_LOCK_TIMEOUT, SimulatorLock, SimulatorLockError,
+};
+use crate::developer_dir;
+use crate::xcresultparser::XCResultParser;
 use crate::xctestresultdetailparser::{XCTestResultDetail, XCTestResultDetailParser};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+/// Simulator used for all test runs
+const SIMULATOR_NAME: &str = "iPhone 17 Pro";
+
+/// Longest a single test run may take unless `--test-timeout` says otherwise
+pub const DEFAULT_TEST_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How often a running xcodebuild is checked for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Extra attempts at a test run the simulator couldn't be acquired for
+const MAX_SIMULATOR_RETRIES: u32 = 2;
+
+/// Wait before retrying a test run the simulator couldn't be acquired for
+const SIMULATOR_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The simulator an xcodebuild `-destination` runs on
+#[derive(Debug, PartialEq)]
+enum DestinationSimulator<'a> {
+    /// Given by its UDID (`id=`)
+    Id(&'a str),
+    /// Given by its name (`name=`) and, if pinned, runtime version (`OS=`)
+    Name(&'a str, Option<&'a str>),
+}
+
+/// Simulators of `xcrun simctl list devices --json`, by runtime identifier such as
+/// `com.apple.CoreSimulator.SimRuntime.iOS-18-5`
+#[derive(Debug, Default, Deserialize)]
+struct SimctlDeviceList {
+    devices: HashMap<String, Vec<SimctlDevice>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimctlDevice {
+    udid: String,
+    name: String,
+    #[serde(default)]
+    is_available: bool,
+}
+
+impl SimctlDeviceList {
+    /// The simulators installed on this machine; `None` if simctl can't list them
+    fn load() -> Option<Self> {
+        let output = developer_dir::command("xcrun")
+            .args(["simctl", "list", "devices", "--json"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        serde_json::from_slice(&output.stdout).ok()
+    }
+
+    /// UDID of the available simulator named `name` on the iOS runtime with
+    /// `os_version`, or on the newest runtime that has one without a version
+    fn udid(&self, name: &str, os_version: Option<&str>) -> Option<&str> {
+        let wanted = os_version.map(|version| {
+            version
+                .split('.')
+                .map(|part| part.parse().unwrap_or_default())
+                .collect::<Vec<u32>>()
+        });
+        self.devices
+            .iter()
+            .filter_map(|(runtime, devices)| Some((ios_runtime_version(runtime)?, devices)))
+            .filter(|(version, _)| {
+                wanted
+                    .as_ref()
+                    .is_none_or(|wanted| version.starts_with(wanted))
+            })
+            .flat_map(|(version, devices)| {
+                devices
+                    .iter()
+                    .filter(|device| device.is_available && device.name == name)
+                    .map(move |device| (version.clone(), device))
+            })
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, device)| device.udid.as_str())
+    }
+}
+
+/// `[18, 5]` for the runtime identifier `com.apple.CoreSimulator.SimRuntime.iOS-18-5`
+fn ios_runtime_version(runtime: &str) -> Option<Vec<u32>> {
+    let (_, version) = runtime.rsplit_once(".iOS-")?;
+    version.split('-').map(|part| part.parse().ok()).collect()
+}
+
+/// Whether xcodebuild's output reports the test as skipped (`XCTSkip`, Swift Testing's
+/// `.enabled(if:)`), which exits successfully although the test never ran
+pub fn was_skipped(stdout: &str) -> bool {
+    stdout.lines().any(|line| {
+        (line.starts_with("Test Case '") && line.contains("' skipped ("))
+            || (line.contains("Test ") && line.contains("() skipped"))
+    })
+}
+
+/// How the test did on one of several destinations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DestinationResult {
+    /// Device name, e.g. `iPhone SE (3rd generation)`
+    pub device: String,
+    pub passed: bool,
+}
+
+/// Per-device outcomes of a run on several destinations, in the order devices first report
+///
+/// xcodebuild names the device in `Test case '…' passed on '<device>' (…)` lines,
+/// either by name or as a parallel clone like `Clone 1 of iPhone 17 Pro - AppUITests-Runner (4321)`.
+/// A device's last outcome counts, so a test that passes on retry passes.
+pub fn destination_results(stdout: &str) -> Vec<DestinationResult> {
+    let mut results: Vec<DestinationResult> = Vec::new();
+    for line in stdout.lines().map(str::trim) {
+        if !line.starts_with("Test case '") && !line.starts_with("Test Case '") {
+            continue;
+        }
+        let Some((passed, rest)) = [(true, "' passed on '"), (false, "' failed on '")]
+            .iter()
+            .find_map(|(passed, marker)| line.split_once(marker).map(|(_, rest)| (*passed, rest)))
+        else {
+            continue;
+        };
+        let Some((device, _)) = rest.rsplit_once("' (") else {
+            continue;
+        };
+
+        let device = device_name(device);
+        match results.iter_mut().find(|result| result.device == device) {
+            Some(result) => result.passed = passed,
+            None => results.push(DestinationResult { device, passed }),
+        }
+    }
+    results
+}
+
+/// `Clone 1 of iPhone 17 Pro - AppUITests-Runner (4321)` as `iPhone 17 Pro`
+fn device_name(device: &str) -> String {
+    let device = device
+        .strip_prefix("Clone ")
+        .and_then(|rest| rest.split_once(" of "))
+        .map_or(device, |(_, name)| name);
+    device
+        .split_once(" - ")
+        .map_or(device, |(name, _)| name)
+        .to_string()
+}
+
+/// Which devices a failed multi-destination run failed and passed on, for the model
+fn device_failure_note(results: &[DestinationResult]) -> Option<String> {
+    let devices = |passed: bool| {
+        results
+            .iter()
+            .filter(|result| result.passed == passed)
+            .map(|result| result.device.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let (failing, passing) = (devices(false), devices(true));
+    if failing.is_empty() {
+        None
+    } else if passing.is_empty() {
+        Some(format!("It fails on every destination: {}.", failing))
+    } else {
+        Some(format!(
+            "It fails only on {} and passes on {} - look for device-specific causes such as layouts that break or elements that need scrolling on smaller screens.",
+            failing, passing
+        ))
+    }
+}
+
+/// Problems with the test infrastructure (simulator/device), as opposed to
+/// failures caused by the code under test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InfrastructureError {
+    /// No simulator matches the requested destination
+    SimulatorNotFound,
+    /// The simulator exists but is not booted
+    SimulatorNotBooted,
+    /// The simulator could not be booted
+    SimulatorBootFailed,
+    /// The device is busy with another operation
+    DeviceBusy,
+    /// Another test run kept the simulator locked past the lock timeout
+    SimulatorLocked,
+}
+
+impl InfrastructureError {
+    /// Human-readable explanation including a suggestion for the user
+    pub fn description(&self) -> String {
+        match self {
+            InfrastructureError::SimulatorNotFound => format!(
+                "No simulator named '{}' is available. Create it in Xcode or via `xcrun simctl create`.",
+                SIMULATOR_NAME
+            ),
+            InfrastructureError::SimulatorNotBooted => format!(
+                "The '{}' simulator is not booted. Boot it with `xcrun simctl boot \"{}\"`.",
+                SIMULATOR_NAME, SIMULATOR_NAME
+            ),
+            InfrastructureError::SimulatorBootFailed => format!(
+                "The '{}' simulator failed to boot. Try `xcrun simctl shutdown all` and run again.",
+                SIMULATOR_NAME
+            ),
+            InfrastructureError::DeviceBusy => {
+                "The simulator is busy with another operation. Wait for it to finish and run again."
+                    .to_string()
+            }
+            InfrastructureError::SimulatorLocked => {
+                "Another test run kept the simulator locked for longer than the lock timeout. Wait for it to finish or raise --simulator-lock-timeout."
+                    .to_string()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for InfrastructureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+/// Detect known xcodebuild infrastructure errors in the process output
+pub fn detect_infrastructure_error(stdout: &str, stderr: &str) -> Option<InfrastructureError> {
+    const PATTERNS: &[(&str, InfrastructureError)] = &[
+        (
+            "Unable to find a destination matching the provided destination specifier",
+            InfrastructureError::SimulatorNotFound,
+        ),
+        ("Invalid device:", InfrastructureError::SimulatorNotFound),
+        (
+            "Unable to lookup in current state: Shutdown",
+            InfrastructureError::SimulatorNotBooted,
+        ),
+        (
+            "device is not booted",
+            InfrastructureError::SimulatorNotBooted,
+        ),
+        ("Unable to boot", InfrastructureError::SimulatorBootFailed),
+        ("Failed to boot", InfrastructureError::SimulatorBootFailed),
+        ("device is busy", InfrastructureError::DeviceBusy),
+        ("Device is busy", InfrastructureError::DeviceBusy),
+    ];
+
+    PATTERNS
+        .iter()
+        .find(|(pattern, _)| stderr.contains(pattern) || stdout.contains(pattern))
+        .map(|(_, error)| *error)
+}
+
+/// Whether xcodebuild failed to get hold of the simulator in a way that often
+/// resolves itself, e.g. while it's still booting or busy with a parallel run
+pub fn is_transient_simulator_error(stdout: &str, stderr: &str) -> bool {
+    const PATTERNS: &[&str] = &[
+        "Unable to find a destination",
+        "The request to open",
+        "device is busy",
+        "Device is busy",
+    ];
+
+    PATTERNS
+        .iter()
+        .any(|pattern| stderr.contains(pattern) || stdout.contains(pattern))
+}
+
+/// Outcome of `xcodebuild build-for-testing`
+#[derive(Debug, Clone)]
+pub struct BuildResult {
+    pub success: bool,
+    pub exit_code: i32,
+    /// Compiler and build errors, in the order xcodebuild reported them
+    pub errors: Vec<String>,
+    pub infrastructure_error: Option<InfrastructureError>,
+}
+
+/// Error lines of xcodebuild output, e.g. `LoginTests.swift:12:5: error: cannot find 'app' in scope`
+///
+/// xcodebuild repeats errors in its summary, so each is returned once.
+pub fn compiler_errors(output: &str) -> Vec<String> {
+    let mut errors: Vec<String> = Vec::new();
+    for line in output.lines().map(str::trim) {
+        if (line.contains(": error: ") || line.starts_with("error: "))
+            && !errors.iter().any(|error| error == line)
+        {
+            errors.push(line.to_string());
+        }
+    }
+    errors
+}
+
+/// Output of a process that may have been killed on timeout
+pub struct ProcessOutput {
+    /// `None` when the process was killed for running too long
+    pub status: Option<ExitStatus>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Run `command` like `Command::output`, killing it once it runs longer than `timeout`
+///
+/// Output is collected as it arrives, so a killed process still returns what it printed.
+pub fn output_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<ProcessOutput> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = collect(child.stdout.take());
+    let stderr = collect(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(POLL_INTERVAL.min(timeout));
+    };
+
+    // Processes spawned by a killed xcodebuild can keep its pipes open, so
+    // only wait for the readers when it exited by itself
+    let (stdout, stderr) = match status {
+        Some(_) => (join(stdout), join(stderr)),
+        None => (snapshot(&stdout), snapshot(&stderr)),
+    };
+    Ok(ProcessOutput {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+type Collected = (Arc<Mutex<Vec<u8>>>, Option<thread::JoinHandle<()>>);
+
+/// Read `pipe` on a background thread into a shared buffer
+fn collect(pipe: Option<impl Read + Send + 'static>) -> Collected {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let handle = pipe.map(|mut pipe| {
+        let buffer = buffer.clone();
+        thread::spawn(move || {
+            let mut chunk = [0u8; 8192];
+            while let Ok(read) = pipe.read(&mut chunk) {
+                if read == 0 {
+                    break;
+                }
+                buffer.lock().unwrap().extend_from_slice(&chunk[..read]);
+            }
+        })
+    });
+    (buffer, handle)
+}
+
+fn join((buffer, handle): Collected) -> Vec<u8> {
+    if let Some(handle) = handle {
+        let _ = handle.join();
+    }
+    buffer.lock().unwrap().clone()
+}
+
+fn snapshot((buffer, _): &Collected) -> Vec<u8> {
+    buffer.lock().unwrap().clone()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TestRunnerTool {
     name: String,
     description: String,
+    /// Simulator runtime to run on, e.g. `18.5`; xcodebuild picks one when unset
+    #[serde(default)]
+    os_version: Option<String>,
+    /// Raw arguments appended after the built-in xcodebuild arguments
+    #[serde(default)]
+    extra_args: Vec<String>,
+    /// Longest a test run may take before xcodebuild is killed
+    #[serde(default = "default_test_timeout")]
+    timeout: Duration,
+    /// Scheme to run tests with instead of the one named in the test identifier
+    #[serde(default)]
+    scheme: Option<String>,
+    /// Erase the simulator before every run and run without parallel simulator clones
+    #[serde(default)]
+    isolate: bool,
+    /// Target or class prefixes of the tests that may run, e.g. `AppUITests/LoginTests`;
+    /// any test may run when empty
+    #[serde(default)]
+    allowed_tests: Vec<String>,
+    /// Longest a test run waits for another one to release the simulator
+    #[serde(default = "default_simulator_lock_timeout")]
+    lock_timeout: Duration,
+    /// xcodebuild `-destination` specifiers to run on (`--destination`); the default
+    /// simulator when empty
+    #[serde(default)]
+    destinations: Vec<String>,
+}
+
+fn default_simulator_lock_timeout() -> Duration {
+    DEFAULT_SIMULATOR_LOCK_TIMEOUT
+}
+
+fn default_test_timeout() -> Duration {
+    DEFAULT_TEST_TIMEOUT
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,9 +435,36 @@ pub struct TestRunnerResult {
     pub test_detail: Option<XCTestResultDetail>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub xcresult_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub infrastructure_error: Option<InfrastructureError>,
+    /// Runtime the test ran on according to the result bundle, e.g. `iOS Simulator 26.0 (23A339)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<String>,
+    /// xcodebuild was killed after running longer than the test timeout
+    #[serde(default)]
+    pub timed_out: bool,
+    /// Times the run was repeated because the simulator couldn't be acquired
+    #[serde(default)]
+    pub simulator_retries: u32,
+    /// The test was skipped, e.g. by `XCTSkip`; not counted as a pass
+    #[serde(default)]
+    pub skipped: bool,
+    /// The test is outside `--allow-testing` and was not run
+    #[serde(default)]
+    pub refused: bool,
+    /// Outcome per device when the test ran on several destinations
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destination_results: Vec<DestinationResult>,
 }
 
 impl TestRunnerTool {
+    /// Directory, relative to the workspace, holding one UUID directory per test run
+    pub const RUNS_DIR: &str = ".autofix/test-runner-tool";
+
+    /// Derived data of the preflight build, relative to the workspace; kept between
+    /// runs so that later preflights build incrementally
+    pub const PREFLIGHT_DIR: &str = ".autofix/preflight";
+
     pub fn new() -> Self {
         Self {
             name: "test_runner".to_string(),
@@ -48,13 +482,178 @@ Input format:
 The test_identifier format is: test://com.apple.xcode/{scheme}/{target}/{class}/{method}
 
 Returns exit code, stdout, stderr, success status, and detailed test failure information if the test fails."#.to_string(),
+            os_version: None,
+            extra_args: Vec::new(),
+            timeout: DEFAULT_TEST_TIMEOUT,
+            scheme: None,
+            isolate: false,
+            allowed_tests: Vec::new(),
+            lock_timeout: DEFAULT_SIMULATOR_LOCK_TIMEOUT,
+            destinations: Vec::new(),
+        }
+    }
+
+    /// Kill test runs that take longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Run tests on the simulator runtime with this OS version instead of letting xcodebuild pick one
+    pub fn with_os_version(mut self, os_version: Option<String>) -> Self {
+        self.os_version = os_version;
+        self
+    }
+
+    /// Run tests with this scheme instead of the one named in the test identifier
+    pub fn with_scheme(mut self, scheme: Option<String>) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Run every test on a freshly erased simulator, so no state from earlier tests bleeds in
+    pub fn with_isolation(mut self, isolate: bool) -> Self {
+        self.isolate = isolate;
+        self
+    }
+
+    /// Append raw arguments to every xcodebuild invocation, e.g. `CODE_SIGNING_ALLOWED=NO`
+    pub fn with_extra_args(mut self, extra_args: Vec<String>) -> Self {
+        self.extra_args = extra_args;
+        self
+    }
+
+    /// Only run tests under these target or class prefixes (`--allow-testing`), refusing all others
+    pub fn with_allowed_tests(mut self, allowed_tests: Vec<String>) -> Self {
+        self.allowed_tests = allowed_tests;
+        self
+    }
+
+    /// Run tests on these xcodebuild `-destination` specifiers instead of the default
+    /// simulator, e.g. `platform=iOS Simulator,name=iPhone SE (3rd generation)`
+    pub fn with_destinations(mut self, destinations: Vec<String>) -> Self {
+        self.destinations = destinations;
+        self
+    }
+
+    /// Wait at most `lock_timeout` for other test runs to release the simulator
+    pub fn with_lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /// Run `simulator_work` while holding the locks of all simulators it runs on, so
+    /// test runs of other threads and processes on them don't overlap with it
+    fn with_simulator<T>(
+        &self,
+        simulator_work: impl FnOnce() -> T,
+    ) -> Result<T, SimulatorLockError> {
+        let mut simulators = self.simulators();
+        // Taken in a fixed order, so runs sharing some of the simulators can't deadlock
+        simulators.sort();
+        simulators.dedup();
+        let _locks = simulators
+            .iter()
+            .map(|simulator| {
+                SimulatorLock::acquire(&simulator_lock::lock_path(simulator), self.lock_timeout)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(simulator_work())
+    }
+
+    /// Whether `full_test` (`<target>/<class>/<method>`) lies under an allowed prefix,
+    /// matched on whole components like xcodebuild's `-only-testing`
+    fn is_allowed(&self, full_test: &str) -> bool {
+        self.allowed_tests.is_empty()
+            || self.allowed_tests.iter().any(|prefix| {
+                let prefix = prefix.trim_matches('/');
+                full_test == prefix
+                    || full_test
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
+
+    /// xcodebuild `-destination` arguments for all destinations
+    fn destination_args(&self) -> Vec<OsString> {
+        if self.destinations.is_empty() {
+            return vec!["-destination".into(), self.destination().into()];
+        }
+        self.destinations
+            .iter()
+            .flat_map(|destination| ["-destination".into(), destination.into()])
+            .collect()
+    }
+
+    /// Simulators the destinations run on; the default simulator only without
+    /// `--destination`, as destinations that aren't simulators have none
+    fn destination_simulators(&self) -> Vec<DestinationSimulator<'_>> {
+        fn value<'a>(destination: &'a str, key: &str) -> Option<&'a str> {
+            destination
+                .split(',')
+                .find_map(|pair| pair.trim().strip_prefix(key))
+        }
+        if self.destinations.is_empty() {
+            return vec![DestinationSimulator::Name(
+                SIMULATOR_NAME,
+                self.os_version.as_deref(),
+            )];
+        }
+        self.destinations
+            .iter()
+            .filter_map(|destination| match value(destination, "id=") {
+                Some(udid) => Some(DestinationSimulator::Id(udid)),
+                None => Some(DestinationSimulator::Name(
+                    value(destination, "name=")?,
+                    value(destination, "OS="),
+                )),
+            })
+            .collect()
+    }
+
+    /// Simulators that are booted, erased and shut down: the ones the destinations
+    /// run on, by UDID where several runtimes have a simulator of that name
+    fn simulators(&self) -> Vec<String> {
+        let devices = SimctlDeviceList::load().unwrap_or_default();
+        self.destination_simulators()
+            .into_iter()
+            .map(|simulator| match simulator {
+                DestinationSimulator::Id(udid) => udid.to_string(),
+                DestinationSimulator::Name(name, os_version) => {
+                    devices.udid(name, os_version).unwrap_or(name).to_string()
+                }
+            })
+            .collect()
+    }
+
+    /// xcodebuild `-destination` for the default test simulator
+    fn destination(&self) -> String {
+        match &self.os_version {
+            Some(os_version) => format!(
+                "platform=iOS Simulator,name={},OS={}",
+                SIMULATOR_NAME, os_version
+            ),
+            None => format!("platform=iOS Simulator,name={}", SIMULATOR_NAME),
         }
     }
 
     pub fn to_tool_definition(&self) -> serde_json::Value {
+        let description = if self.allowed_tests.is_empty() {
+            self.description.clone()
+        } else {
+            format!(
+                "{}\n\nOnly tests under these targets or classes may be run: {}",
+                self.description,
+                self.allowed_tests.join(", ")
+            )
+        };
         serde_json::json!({
             "name": self.name,
-            "description": self.description,
+            "description": description,
             "input_schema": {
                 "type": "object",
                 "properties": {
@@ -87,6 +686,13 @@ Returns exit code, stdout, stderr, success status, and detailed test failure inf
                 ),
                 test_detail: None,
                 xcresult_path: None,
+                infrastructure_error: None,
+                runtime: None,
+                timed_out: false,
+                simulator_retries: 0,
+                skipped: false,
+                refused: false,
+                destination_results: Vec::new(),
             },
         }
     }
@@ -108,7 +714,10 @@ Returns exit code, stdout, stderr, success status, and detailed test failure inf
         }
 
         // Skip "com.apple.xcode" and get scheme, rest
-        let scheme = parts.get(1)?.to_string();
+        let scheme = match &self.scheme {
+            Some(scheme) => scheme.clone(),
+            None => parts.get(1)?.to_string(),
+        };
         let full_test = parts[2..].join("/");
 
         Some((scheme, full_test))
@@ -126,15 +735,43 @@ Returns exit code, stdout, stderr, success status, and detailed test failure inf
                     message: format!("Invalid test identifier format: {}", test_identifier),
                     test_detail: None,
                     xcresult_path: None,
+                    infrastructure_error: None,
+                    runtime: None,
+                    timed_out: false,
+                    simulator_retries: 0,
+                    skipped: false,
+                    refused: false,
+                    destination_results: Vec::new(),
                 };
             }
         };
 
+        if !self.is_allowed(&full_test) {
+            return TestRunnerResult {
+                success: false,
+                exit_code: -1,
+                stdout: String::new(),
+                stderr: String::new(),
+                message: format!(
+                    "Refused to run {}: only tests under {} may be run in this session",
+                    full_test,
+                    self.allowed_tests.join(", ")
+                ),
+                test_detail: None,
+                xcresult_path: None,
+                infrastructure_error: None,
+                runtime: None,
+                timed_out: false,
+                simulator_retries: 0,
+                skipped: false,
+                refused: true,
+                destination_results: Vec::new(),
+            };
+        }
+
         // Create temporary directories for this test run
         let uuid = Uuid::new_v4();
-        let temp_base = workspace_root
-            .join(".autofix/test-runner-tool")
-            .join(uuid.to_string());
+        let temp_base = workspace_root.join(Self::RUNS_DIR).join(uuid.to_string());
         let build_dir = temp_base.join("build");
         let test_dir = temp_base.join("test");
 
@@ -148,6 +785,13 @@ Returns exit code, stdout, stderr, success status, and detailed test failure inf
                 message: format!("Failed to create build directory: {}", e),
                 test_detail: None,
                 xcresult_path: None,
+                infrastructure_error: None,
+                runtime: None,
+                timed_out: false,
+                simulator_retries: 0,
+                skipped: false,
+                refused: false,
+                destination_results: Vec::new(),
             };
         }
 
@@ -160,51 +804,161 @@ Returns exit code, stdout, stderr, success status, and detailed test failure inf
                 message: format!("Failed to create test directory: {}", e),
                 test_detail: None,
                 xcresult_path: None,
+                infrastructure_error: None,
+                runtime: None,
+                timed_out: false,
+                simulator_retries: 0,
+                skipped: false,
+                refused: false,
+                destination_results: Vec::new(),
             };
         }
 
         let result_bundle_path = test_dir.join("result.xcresult");
+        let xcodebuild_args =
+            self.xcodebuild_args(&scheme, &full_test, &build_dir, &result_bundle_path);
 
-        let output = Command::new("xcodebuild")
-            .arg("test")
-            .arg("-scheme")
-            .arg(&scheme)
-            .arg("-destination")
-            .arg("platform=iOS Simulator,name=iPhone 17 Pro")
-            .arg(format!("-only-testing:{}", full_test))
-            .arg("-derivedDataPath")
-            .arg(&build_dir)
-            .arg("-resultBundlePath")
-            .arg(&result_bundle_path)
-            .current_dir(workspace_root)
-            .output();
+        let run_xcodebuild = || {
+            output_with_timeout(
+                developer_dir::command("xcodebuild")
+                    .args(&xcodebuild_args)
+                    .current_dir(workspace_root),
+                self.timeout,
+            )
+        };
+
+        // Only the simulator work is serialized; parsing the result bundle happens outside
+        let locked = self.with_simulator(|| {
+            if self.isolate {
+                self.erase_simulator();
+            }
+            let mut output = run_xcodebuild();
+
+            // A shut down simulator is booted automatically and the test is retried once
+            if let Ok(ref failed) = output
+                && failed.status.is_some_and(|status| !status.success())
+                && detect_infrastructure_error(
+                    &String::from_utf8_lossy(&failed.stdout),
+                    &String::from_utf8_lossy(&failed.stderr),
+                ) == Some(InfrastructureError::SimulatorNotBooted)
+                && self.boot_simulator()
+            {
+                // xcodebuild refuses to overwrite an existing result bundle
+                let _ = fs::remove_dir_all(&result_bundle_path);
+                output = run_xcodebuild();
+            }
+
+            // Right after booting or with parallel runs xcodebuild may fail to get hold
+            // of the simulator; that's not a test failure, so try again a few times
+            let mut simulator_retries = 0;
+            while simulator_retries < MAX_SIMULATOR_RETRIES
+                && let Ok(ref failed) = output
+                && failed.status.is_some_and(|status| !status.success())
+                && is_transient_simulator_error(
+                    &String::from_utf8_lossy(&failed.stdout),
+                    &String::from_utf8_lossy(&failed.stderr),
+                )
+            {
+                simulator_retries += 1;
+                println!(
+                    "   ⏳ Simulator not available yet, retrying ({}/{})...",
+                    simulator_retries, MAX_SIMULATOR_RETRIES
+                );
+                thread::sleep(SIMULATOR_RETRY_DELAY);
+                let _ = fs::remove_dir_all(&result_bundle_path);
+                output = run_xcodebuild();
+            }
+
+            // The UI test runner outlives the killed xcodebuild on the simulator
+            if let Ok(ref timed_out) = output
+                && timed_out.status.is_none()
+            {
+                self.shutdown_simulator();
+            }
+            (output, simulator_retries)
+        });
+        let (output, simulator_retries) = match locked {
+            Ok(locked) => locked,
+            Err(SimulatorLockError::Timeout(_)) => {
+                let error = InfrastructureError::SimulatorLocked;
+                return TestRunnerResult {
+                    success: false,
+                    exit_code: -1,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    message: format!("Test infrastructure error: {}", error),
+                    test_detail: None,
+                    xcresult_path: None,
+                    infrastructure_error: Some(error),
+                    runtime: None,
+                    timed_out: false,
+                    simulator_retries: 0,
+                    skipped: false,
+                    refused: false,
+                    destination_results: Vec::new(),
+                };
+            }
+            Err(e) => {
+                return TestRunnerResult {
+                    success: false,
+                    exit_code: -1,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    message: e.to_string(),
+                    test_detail: None,
+                    xcresult_path: None,
+                    infrastructure_error: None,
+                    runtime: None,
+                    timed_out: false,
+                    simulator_retries: 0,
+                    skipped: false,
+                    refused: false,
+                    destination_results: Vec::new(),
+                };
+            }
+        };
 
         match output {
             Ok(output) => {
                 let stdout = String::from_utf8_lossy(&output.stdout).to_string();
                 let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let exit_code = output.status.code().unwrap_or(-1);
-                let success = output.status.success();
+                let timed_out = output.status.is_none();
+                let exit_code = output.status.and_then(|status| status.code()).unwrap_or(-1);
+                let exited_successfully = output.status.is_some_and(|status| status.success());
+                let skipped = exited_successfully && was_skipped(&stdout);
+                let success = exited_successfully && !skipped;
+                let infrastructure_error = if exited_successfully || timed_out {
+                    None
+                } else {
+                    detect_infrastructure_error(&stdout, &stderr)
+                };
 
                 // If test failed, parse the xcresult to get detailed failure information
-                let (test_detail, xcresult_path) = if !success && result_bundle_path.exists() {
-                    let parser = XCTestResultDetailParser::new();
-                    match parser.parse(&result_bundle_path, test_identifier) {
-                        Ok(detail) => (Some(detail), Some(result_bundle_path.clone())),
-                        Err(e) => {
-                            eprintln!("Failed to parse xcresult: {}", e);
-                            (None, Some(result_bundle_path.clone()))
+                let (test_detail, xcresult_path) =
+                    if !exited_successfully && result_bundle_path.exists() {
+                        let parser = XCTestResultDetailParser::new();
+                        match parser.parse(&result_bundle_path, test_identifier) {
+                            Ok(detail) => (Some(detail), Some(result_bundle_path.clone())),
+                            Err(e) => {
+                                eprintln!("Failed to parse xcresult: {}", e);
+                                (None, Some(result_bundle_path.clone()))
+                            }
                         }
-                    }
+                    } else {
+                        (
+                            None,
+                            if result_bundle_path.exists() {
+                                Some(result_bundle_path.clone())
+                            } else {
+                                None
+                            },
+                        )
+                    };
+                let runtime = Self::resolved_runtime(test_detail.as_ref(), &result_bundle_path);
+                let destination_results = if self.destinations.len() > 1 {
+                    destination_results(&stdout)
                 } else {
-                    (
-                        None,
-                        if result_bundle_path.exists() {
-                            Some(result_bundle_path.clone())
-                        } else {
-                            None
-                        },
-                    )
+                    Vec::new()
                 };
 
                 TestRunnerResult {
@@ -214,11 +968,36 @@ Returns exit code, stdout, stderr, success status, and detailed test failure inf
                     stderr: stderr.clone(),
                     message: if success {
                         format!("Test passed: {}", full_test)
+                    } else if skipped {
+                        format!(
+                            "Test was skipped and did not run: {}. A skipped test is not a pass - check the XCTSkip or precondition it skips on, and whether the simulator state it expects is there.",
+                            full_test
+                        )
+                    } else if timed_out {
+                        format!(
+                            "Test timed out after {}s and was stopped: {}. A UI test that never finishes is usually waiting for an element or expectation that never appears - check waitForExistence timeouts, XCTestExpectation fulfillment and whether the app reaches the expected screen.",
+                            self.timeout.as_secs(),
+                            full_test
+                        )
+                    } else if let Some(error) = infrastructure_error {
+                        format!("Test infrastructure error: {}", error)
                     } else {
-                        format!("Test failed: {} (exit code: {})", full_test, exit_code)
+                        let message =
+                            format!("Test failed: {} (exit code: {})", full_test, exit_code);
+                        match device_failure_note(&destination_results) {
+                            Some(note) => format!("{}. {}", message, note),
+                            None => message,
+                        }
                     },
                     test_detail,
                     xcresult_path,
+                    infrastructure_error,
+                    runtime,
+                    timed_out,
+                    simulator_retries,
+                    skipped,
+                    refused: false,
+                    destination_results,
                 }
             }
             Err(e) => TestRunnerResult {
@@ -229,8 +1008,161 @@ Returns exit code, stdout, stderr, success status, and detailed test failure inf
                 message: format!("Failed to execute xcodebuild: {}", e),
                 test_detail: None,
                 xcresult_path: None,
+                infrastructure_error: None,
+                runtime: None,
+                timed_out: false,
+                simulator_retries,
+                skipped: false,
+                refused: false,
+                destination_results: Vec::new(),
+            },
+        }
+    }
+
+    /// Scheme `test_identifier` runs with: `--scheme`, or the one the identifier names
+    pub fn scheme_for(&self, test_identifier: &str) -> Option<String> {
+        self.parse_test_identifier(test_identifier)
+            .map(|(scheme, _)| scheme)
+    }
+
+    /// Build the scheme of `test_identifier` and its tests without running them
+    ///
+    /// Fails only when xcodebuild cannot be started; a broken build is a
+    /// `BuildResult` listing the errors.
+    pub fn build_for_testing(
+        &self,
+        test_identifier: &str,
+        workspace_root: &Path,
+    ) -> Result<BuildResult, String> {
+        let (scheme, _) = self
+            .parse_test_identifier(test_identifier)
+            .ok_or_else(|| format!("Invalid test identifier format: {}", test_identifier))?;
+
+        let output = developer_dir::command("xcodebuild")
+            .arg("build-for-testing")
+            .arg("-scheme")
+            .arg(&scheme)
+            .args(self.destination_args())
+            .arg("-derivedDataPath")
+            .arg(workspace_root.join(Self::PREFLIGHT_DIR))
+            .args(&self.extra_args)
+            .current_dir(workspace_root)
+            .output()
+            .map_err(|e| format!("Failed to execute xcodebuild: {}", e))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let success = output.status.success();
+        Ok(BuildResult {
+            success,
+            exit_code: output.status.code().unwrap_or(-1),
+            errors: if success {
+                Vec::new()
+            } else {
+                compiler_errors(&format!("{}\n{}", stdout, stderr))
             },
+            infrastructure_error: if success {
+                None
+            } else {
+                detect_infrastructure_error(&stdout, &stderr)
+            },
+        })
+    }
+
+    /// Arguments of the xcodebuild call running `full_test`, with the extra arguments last
+    fn xcodebuild_args(
+        &self,
+        scheme: &str,
+        full_test: &str,
+        build_dir: &Path,
+        result_bundle_path: &Path,
+    ) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec!["test".into(), "-scheme".into(), scheme.into()];
+        args.extend(self.destination_args());
+        args.extend([
+            format!("-only-testing:{}", full_test).into(),
+            "-derivedDataPath".into(),
+            build_dir.into(),
+            "-resultBundlePath".into(),
+            result_bundle_path.into(),
+        ]);
+        if self.isolate {
+            // Parallel testing runs on clones that don't share the erased simulator's state
+            args.extend(["-parallel-testing-enabled".into(), "NO".into()]);
+        }
+        args.extend(self.extra_args.iter().map(OsString::from));
+        args
+    }
+
+    /// Runtime the test ran on, read from the failure detail or else the bundle summary
+    fn resolved_runtime(
+        test_detail: Option<&XCTestResultDetail>,
+        result_bundle_path: &Path,
+    ) -> Option<String> {
+        if let Some(device) = test_detail.and_then(|detail| detail.devices.first()) {
+            return Some(device.runtime());
+        }
+        if !result_bundle_path.exists() {
+            return None;
         }
+        let summary = XCResultParser::new().parse(result_bundle_path).ok()?;
+        summary
+            .devices_and_configurations
+            .first()
+            .map(|configuration| configuration.device.runtime())
+    }
+
+    /// Shut down the test simulators, stopping a hung UI test runner
+    fn shutdown_simulator(&self) {
+        self.shutdown(&self.simulators());
+    }
+
+    fn shutdown(&self, simulators: &[String]) {
+        for simulator in simulators {
+            println!("   📱 Shutting down simulator '{}'...", simulator);
+            let _ = developer_dir::command("xcrun")
+                .arg("simctl")
+                .arg("shutdown")
+                .arg(simulator)
+                .output();
+        }
+    }
+
+    /// Shut down and erase the test simulators, resetting apps, data and settings
+    fn erase_simulator(&self) {
+        let simulators = self.simulators();
+        self.shutdown(&simulators);
+        for simulator in &simulators {
+            println!(
+                "   🧼 Erasing simulator '{}' for an isolated run...",
+                simulator
+            );
+            let _ = developer_dir::command("xcrun")
+                .arg("simctl")
+                .arg("erase")
+                .arg(simulator)
+                .output();
+        }
+    }
+
+    /// Boot the test simulators, returning whether all of them booted
+    fn boot_simulator(&self) -> bool {
+        // Every one is booted, even after one failed
+        let booted: Vec<bool> = self
+            .simulators()
+            .into_iter()
+            .map(|simulator| {
+                println!("   📱 Booting simulator '{}'...", simulator);
+                developer_dir::command("xcrun")
+                    .arg("simctl")
+                    .arg("boot")
+                    .arg(&simulator)
+                    .output()
+                    .map(|output| output.status.success())
+                    .unwrap_or(false)
+            })
+            .collect();
+        booted.into_iter().all(|booted| booted)
     }
 }
 
@@ -239,3 +1171,361 @@ impl Default for TestRunnerTool {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destination_pins_os_version() {
+        assert_eq!(
+            TestRunnerTool::new().destination(),
+            "platform=iOS Simulator,name=iPhone 17 Pro"
+        );
+        assert_eq!(
+            TestRunnerTool::new()
+                .with_os_version(Some("18.5".to_string()))
+                .destination(),
+            "platform=iOS Simulator,name=iPhone 17 Pro,OS=18.5"
+        );
+    }
+
+    #[test]
+    fn test_simulator_resolves_to_udid_of_requested_runtime() {
+        let devices: SimctlDeviceList = serde_json::from_value(serde_json::json!({
+            "devices": {
+                "com.apple.CoreSimulator.SimRuntime.iOS-18-5": [
+                    { "udid": "A-18-5", "name": "iPhone 17 Pro", "isAvailable": true },
+                    { "udid": "B-18-5", "name": "iPhone SE (3rd generation)", "isAvailable": true }
+                ],
+                "com.apple.CoreSimulator.SimRuntime.iOS-26-0": [
+                    { "udid": "A-26-0", "name": "iPhone 17 Pro", "isAvailable": true },
+                    { "udid": "B-26-0", "name": "iPhone SE (3rd generation)", "isAvailable": false }
+                ],
+                "com.apple.CoreSimulator.SimRuntime.watchOS-11-0": [
+                    { "udid": "W", "name": "iPhone 17 Pro", "isAvailable": true }
+                ]
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(devices.udid("iPhone 17 Pro", Some("18.5")), Some("A-18-5"));
+        assert_eq!(devices.udid("iPhone 17 Pro", Some("26")), Some("A-26-0"));
+        // Without a version, the newest runtime wins
+        assert_eq!(devices.udid("iPhone 17 Pro", None), Some("A-26-0"));
+        // Unavailable simulators are left out
+        assert_eq!(
+            devices.udid("iPhone SE (3rd generation)", None),
+            Some("B-18-5")
+        );
+        assert_eq!(devices.udid("iPhone 17 Pro", Some("17.0")), None);
+
+        let tool = TestRunnerTool::new().with_os_version(Some("18.5".to_string()));
+        assert_eq!(
+            tool.destination_simulators(),
+            [DestinationSimulator::Name("iPhone 17 Pro", Some("18.5"))]
+        );
+    }
+
+    #[test]
+    fn test_multi_destination_results() {
+        let tool = TestRunnerTool::new().with_destinations(vec![
+            "platform=iOS Simulator,name=iPhone SE (3rd generation)".to_string(),
+            "platform=iOS Simulator,name=iPhone 17 Pro,OS=26.0".to_string(),
+        ]);
+        let args = tool.xcodebuild_args(
+            "App",
+            "AppUITests/LoginTests/testLogin",
+            Path::new("/tmp/build"),
+            Path::new("/tmp/result.xcresult"),
+        );
+        assert_eq!(args[3], "-destination");
+        assert_eq!(
+            args[4],
+            "platform=iOS Simulator,name=iPhone SE (3rd generation)"
+        );
+        assert_eq!(args[5], "-destination");
+        assert_eq!(
+            tool.destination_simulators(),
+            [
+                DestinationSimulator::Name("iPhone SE (3rd generation)", None),
+                DestinationSimulator::Name("iPhone 17 Pro", Some("26.0"))
+            ]
+        );
+
+        // UDIDs are taken as they are, and destinations without a simulator have none
+        let tool = TestRunnerTool::new().with_destinations(vec![
+            "platform=iOS Simulator,id=8A1B2C3D-0000-4000-8000-000000000001".to_string(),
+            "platform=macOS,arch=arm64".to_string(),
+        ]);
+        assert_eq!(
+            tool.destination_simulators(),
+            [DestinationSimulator::Id(
+                "8A1B2C3D-0000-4000-8000-000000000001"
+            )]
+        );
+        assert_eq!(tool.simulators(), ["8A1B2C3D-0000-4000-8000-000000000001"]);
+
+        let stdout = "\
+Test suite 'LoginTests' started on 'Clone 1 of iPhone SE (3rd generation) - AppUITests-Runner (4321)'
+Test case 'LoginTests.testLogin()' failed on 'Clone 1 of iPhone SE (3rd generation) - AppUITests-Runner (4321)' (4.120 seconds)
+Test case 'LoginTests.testLogin()' passed on 'iPhone 17 Pro' (3.456 seconds)
+Test case 'LoginTests.testLogout()' failed on 'iPhone 17 Pro' (1.000 seconds)
+Test case 'LoginTests.testLogout()' passed on 'iPhone 17 Pro' (1.100 seconds)
+** TEST FAILED **
+";
+        let results = destination_results(stdout);
+        assert_eq!(
+            results,
+            vec![
+                DestinationResult {
+                    device: "iPhone SE (3rd generation)".to_string(),
+                    passed: false,
+                },
+                DestinationResult {
+                    device: "iPhone 17 Pro".to_string(),
+                    passed: true,
+                },
+            ]
+        );
+        assert_eq!(
+            device_failure_note(&results).unwrap(),
+            "It fails only on iPhone SE (3rd generation) and passes on iPhone 17 Pro - look for device-specific causes such as layouts that break or elements that need scrolling on smaller screens."
+        );
+        assert!(
+            destination_results(
+                "Test Case '-[AppUITests.LoginTests testLogin]' failed (4.1 seconds)."
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_concurrent_runs_do_not_overlap_on_simulator() {
+        // Runs on different workspaces, but the same simulator
+        let destination = format!("platform=iOS Simulator,name=Shared {}", Uuid::new_v4());
+        let tools = [
+            TestRunnerTool::new().with_destinations(vec![destination.clone()]),
+            TestRunnerTool::new().with_destinations(vec![destination]),
+        ];
+        let intervals = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for tool in &tools {
+                scope.spawn(|| {
+                    tool.with_simulator(|| {
+                        let started = Instant::now();
+                        thread::sleep(Duration::from_millis(200));
+                        intervals.lock().unwrap().push((started, Instant::now()));
+                    })
+                    .unwrap();
+                });
+            }
+        });
+
+        let mut intervals = intervals.into_inner().unwrap();
+        intervals.sort();
+        assert_eq!(intervals.len(), 2);
+        assert!(
+            intervals[0].1 <= intervals[1].0,
+            "critical sections overlapped"
+        );
+    }
+
+    #[test]
+    fn test_runs_on_different_simulators_do_not_wait() {
+        let first = TestRunnerTool::new()
+            .with_destinations(vec![format!(
+                "platform=iOS Simulator,name=First {}",
+                Uuid::new_v4()
+            )])
+            .with_lock_timeout(Duration::ZERO);
+        let second = TestRunnerTool::new()
+            .with_destinations(vec![format!(
+                "platform=iOS Simulator,name=Second {}",
+                Uuid::new_v4()
+            )])
+            .with_lock_timeout(Duration::ZERO);
+
+        first
+            .with_simulator(|| second.with_simulator(|| ()).unwrap())
+            .unwrap();
+        // The same simulator is still exclusive
+        assert!(matches!(
+            first.with_simulator(|| first.with_simulator(|| ())),
+            Ok(Err(SimulatorLockError::Timeout(_)))
+        ));
+    }
+
+    #[test]
+    fn test_disallowed_target_is_refused() {
+        let tool = TestRunnerTool::new().with_allowed_tests(vec![
+            "AppUITests/LoginTests".to_string(),
+            "AppUnitTests/".to_string(),
+        ]);
+        assert!(tool.is_allowed("AppUITests/LoginTests/testLogin"));
+        assert!(tool.is_allowed("AppUnitTests/CartTests/testTotal"));
+        assert!(!tool.is_allowed("AppUITests/LoginTestsE2E/testLogin"));
+
+        // Refused before anything is built, so no workspace is needed
+        let result = tool.execute(
+            TestRunnerInput {
+                operation: "test".to_string(),
+                test_identifier:
+                    "test://com.apple.xcode/App/AppE2ETests/CheckoutTests/testPurchase".to_string(),
+            },
+            Path::new("/nonexistent"),
+        );
+        assert!(!result.success);
+        assert!(result.message.starts_with(
+            "Refused to run AppE2ETests/CheckoutTests/testPurchase: only tests under AppUITests/LoginTests"
+        ));
+        assert!(result.refused);
+        assert!(result.xcresult_path.is_none());
+        assert!(!Path::new("/nonexistent").exists());
+    }
+
+    #[test]
+    fn test_extra_args_follow_built_in_args() {
+        let tool = TestRunnerTool::new().with_extra_args(vec![
+            "CODE_SIGNING_ALLOWED=NO".to_string(),
+            "-skipPackagePluginValidation".to_string(),
+        ]);
+
+        let args = tool.xcodebuild_args(
+            "App",
+            "AppUITests/LoginTests/testLogin",
+            Path::new("/tmp/build"),
+            Path::new("/tmp/result.xcresult"),
+        );
+
+        assert_eq!(args.len(), 12);
+        assert_eq!(args[0], "test");
+        assert_eq!(args[9], "/tmp/result.xcresult");
+        assert_eq!(args[10], "CODE_SIGNING_ALLOWED=NO");
+        assert_eq!(args[11], "-skipPackagePluginValidation");
+    }
+
+    #[test]
+    fn test_isolated_run_disables_parallel_testing() {
+        let tool = TestRunnerTool::new()
+            .with_isolation(true)
+            .with_extra_args(vec!["CODE_SIGNING_ALLOWED=NO".to_string()]);
+
+        let args = tool.xcodebuild_args(
+            "App",
+            "AppUITests/LoginTests/testLogin",
+            Path::new("/tmp/build"),
+            Path::new("/tmp/result.xcresult"),
+        );
+
+        assert_eq!(args[10], "-parallel-testing-enabled");
+        assert_eq!(args[11], "NO");
+        assert_eq!(args[12], "CODE_SIGNING_ALLOWED=NO");
+    }
+
+    #[test]
+    fn test_skipped_test_is_detected() {
+        assert!(was_skipped(
+            "Test Case '-[AppUITests.LoginTests testLogin]' started.\n/App/LoginTests.swift:8: Test skipped - Requires a camera\nTest Case '-[AppUITests.LoginTests testLogin]' skipped (0.012 seconds).\n** TEST SUCCEEDED **"
+        ));
+        assert!(was_skipped(
+            "◇ Test testCamera() started.\n➜ Test testCamera() skipped: \"No camera\""
+        ));
+        assert!(!was_skipped(
+            "Test Case '-[AppUITests.LoginTests testLogin]' passed (1.2 seconds).\n** TEST SUCCEEDED **"
+        ));
+    }
+
+    #[test]
+    fn test_compiler_errors_are_collected_once() {
+        let output = "CompileSwift normal arm64 /App/LoginTests.swift
+/App/LoginTests.swift:12:5: error: cannot find 'app' in scope
+        app.buttons[\"Login\"].tap()
+/App/LoginTests.swift:20:9: warning: variable 'x' was never used
+error: Swift compiler failed
+** TEST BUILD FAILED **
+
+The following build commands failed:
+\t/App/LoginTests.swift:12:5: error: cannot find 'app' in scope
+";
+
+        assert_eq!(
+            compiler_errors(output),
+            vec![
+                "/App/LoginTests.swift:12:5: error: cannot find 'app' in scope",
+                "error: Swift compiler failed",
+            ]
+        );
+        assert!(compiler_errors("** TEST BUILD SUCCEEDED **").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_with_timeout_kills_hung_process() {
+        let output = output_with_timeout(
+            Command::new("sh").args(["-c", "echo started; sleep 30"]),
+            Duration::from_millis(300),
+        )
+        .unwrap();
+        assert!(output.status.is_none());
+        assert_eq!(output.stdout, b"started\n");
+
+        let output =
+            output_with_timeout(Command::new("echo").arg("done"), Duration::from_secs(10)).unwrap();
+        assert!(output.status.unwrap().success());
+        assert_eq!(output.stdout, b"done\n");
+    }
+
+    #[test]
+    fn test_detect_simulator_not_found() {
+        let stderr = "xcodebuild: error: Unable to find a destination matching the provided destination specifier:\n\t\t{ platform:iOS Simulator, name:iPhone 17 Pro }";
+        assert_eq!(
+            detect_infrastructure_error("", stderr),
+            Some(InfrastructureError::SimulatorNotFound)
+        );
+    }
+
+    #[test]
+    fn test_detect_simulator_not_booted() {
+        let stdout = "An error was encountered processing the command (domain=com.apple.CoreSimulator.SimError, code=405):\nUnable to lookup in current state: Shutdown";
+        assert_eq!(
+            detect_infrastructure_error(stdout, ""),
+            Some(InfrastructureError::SimulatorNotBooted)
+        );
+    }
+
+    #[test]
+    fn test_detect_boot_failure_and_busy_device() {
+        assert_eq!(
+            detect_infrastructure_error("", "Failed to boot simulator"),
+            Some(InfrastructureError::SimulatorBootFailed)
+        );
+        assert_eq!(
+            detect_infrastructure_error("", "The operation couldn't be completed. Device is busy"),
+            Some(InfrastructureError::DeviceBusy)
+        );
+    }
+
+    #[test]
+    fn test_detect_transient_simulator_errors() {
+        assert!(is_transient_simulator_error(
+            "",
+            "xcodebuild: error: Unable to find a destination matching the provided destination specifier"
+        ));
+        assert!(is_transient_simulator_error(
+            "Testing failed:\n\tThe request to open \"com.example.App\" failed. The request was denied by service delegate (SBMainWorkspace).",
+            ""
+        ));
+        assert!(!is_transient_simulator_error(
+            "error: -[AppUITests testExample] : XCTAssertTrue failed",
+            ""
+        ));
+    }
+
+    #[test]
+    fn test_assertion_failure_is_not_infrastructure_error() {
+        let stdout = "error: -[AppUITests testExample] : XCTAssertTrue failed\n** TEST FAILED **";
+        assert_eq!(detect_infrastructure_error(stdout, ""), None);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::tools::scheme_list::{self, SchemeError};
+use crate::tools::test_runner_tool::compiler_errors;
+use crate::tools::{TestRunnerInput, TestRunnerTool};
+use crate::workspace_path;
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VerifyError {
+    #[error(
+        "Test '{0}' needs --scheme; or pass the full test://com.apple.xcode/<scheme>/<target>/<class>/<method> identifier"
+    )]
+    MissingScheme(String),
+
+    #[error(transparent)]
+    Scheme(#[from] SchemeError),
+
+    #[error("Test infrastructure error: {0}")]
+    Infrastructure(String),
+}
+
+/// Runs a single test once and reports whether it passes, e.g. to confirm a manual fix
+///
+/// Needs no provider: nothing is sent to a model.
+pub struct VerifyCommand {
+    workspace_path: PathBuf,
+    test_id: String,
+    scheme: Option<String>,
+    test_runner: TestRunnerTool,
+}
+
+impl VerifyCommand {
+    pub fn new(workspace_path: PathBuf, test_id: String) -> Self {
+        Self {
+            workspace_path,
+            test_id,
+            scheme: None,
+            test_runner: TestRunnerTool::new(),
+        }
+    }
+
+    /// Run the test with this scheme (`--scheme`) instead of the one in its identifier
+    pub fn with_scheme(mut self, scheme: Option<String>) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Run the test with this runner, configured with the destination, timeout and extra arguments
+    pub fn with_test_runner(mut self, test_runner: TestRunnerTool) -> Self {
+        self.test_runner = test_runner;
+        self
+    }
+
+    /// Run the test and print the outcome, returning whether it passed
+    pub fn execute_ios(self) -> Result<bool, VerifyError> {
+        let test_identifier = test_identifier_url(&self.test_id, self.scheme.as_deref())?;
+
+        // Check the scheme like the preflight does; a listing failure leaves it to xcodebuild
+        let scheme = match scheme_list::list_schemes(&self.workspace_path) {
+            Ok(available) => Some(scheme_list::resolve_scheme(
+                &available,
+                self.scheme.as_deref(),
+                &test_identifier,
+            )?),
+            Err(_) => self.scheme,
+        };
+
+        println!("🧪 Running {}...", test_identifier);
+        let result = self.test_runner.with_scheme(scheme).execute(
+            TestRunnerInput {
+                operation: "test".to_string(),
+                test_identifier,
+            },
+            &self.workspace_path,
+        );
+
+        if let Some(infrastructure_error) = result.infrastructure_error {
+            return Err(VerifyError::Infrastructure(
+                infrastructure_error.to_string(),
+            ));
+        }
+        if !result.success && !result.timed_out && result.xcresult_path.is_none() {
+            return Err(VerifyError::Infrastructure(result.message));
+        }
+
+        if let Some(runtime) = &result.runtime {
+            println!("📱 Runtime: {}", runtime);
+        }
+        if result.success {
+            println!("✅ {}", result.message);
+            return Ok(true);
+        }
+
+        println!("❌ {}", result.message);
+        match &result.test_detail {
+            Some(detail) => {
+                for failure in &detail.failures {
+                    println!(
+                        "   • {}{}",
+                        failure
+                            .location()
+                            .map(|location| format!("{}: ", location))
+                            .unwrap_or_default(),
+                        failure.message
+                    );
+                }
+            }
+            None => {
+                for error in compiler_errors(&result.stdout) {
+                    println!("   • {}", error);
+                }
+            }
+        }
+        if let Some(xcresult_path) = &result.xcresult_path {
+            println!(
+                "📦 Result bundle: {}",
+                workspace_path::display(xcresult_path, &self.workspace_path)
+            );
+        }
+        Ok(false)
+    }
+}
+
+/// Full test identifier for `test_id`, which may leave out the `test://com.apple.xcode/<scheme>/`
+/// prefix when a scheme is given
+fn test_identifier_url(test_id: &str, scheme: Option<&str>) -> Result<String, VerifyError> {
+    if test_id.starts_with("test://") {
+        return Ok(test_id.to_string());
+    }
+    match scheme {
+        Some(scheme) => Ok(format!(
+            "test://com.apple.xcode/{}/{}",
+            scheme,
+            test_id.trim_matches('/')
+        )),
+        None => Err(VerifyError::MissingScheme(test_id.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier_is_completed_with_scheme() {
+        let url = "test://com.apple.xcode/Shop/ShopUITests/LoginTests/testLogin";
+        assert_eq!(test_identifier_url(url, None).unwrap(), url);
+        assert_eq!(
+            test_identifier_url("ShopUITests/LoginTests/testLogin", Some("Shop")).unwrap(),
+            url
+        );
+        assert!(matches!(
+            test_identifier_url("ShopUITests/LoginTests/testLogin", None),
+            Err(VerifyError::MissingScheme(_))
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+// Paths as shown in output and stored in reports
+//
+// Reports always hold paths relative to the workspace root, so they compare
+// across machines and checkouts. Console output does the same unless
+// --absolute-paths asks for absolute ones.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+static ABSOLUTE_PATHS: OnceLock<()> = OnceLock::new();
+
+/// Show absolute paths in console output instead of workspace-relative ones
+pub fn show_absolute() {
+    let _ = ABSOLUTE_PATHS.set(());
+}
+
+/// `path` relative to `workspace`, the form stored in reports
+///
+/// Relative paths are taken to be relative to the workspace already, as the tools
+/// resolve them. Paths outside the workspace are returned unchanged.
+pub fn relative(path: &Path, workspace: &Path) -> String {
+    relative_path(path, workspace).display().to_string()
+}
+
+/// `path` for console output: relative to `workspace` unless `--absolute-paths` is set
+pub fn display(path: &Path, workspace: &Path) -> String {
+    let relative = relative_path(path, workspace);
+    if ABSOLUTE_PATHS.get().is_none() || relative.is_absolute() {
+        return relative.display().to_string();
+    }
+    let workspace = std::path::absolute(workspace).unwrap_or_else(|_| workspace.to_path_buf());
+    workspace.join(relative).display().to_string()
+}
+
+fn relative_path(path: &Path, workspace: &Path) -> PathBuf {
+    if let Ok(relative) = path.strip_prefix(workspace) {
+        return clean(relative);
+    }
+    if !path.is_absolute() {
+        return clean(path);
+    }
+    // The workspace may be given relative to the current directory or through a symlink
+    let canonical_workspace = fs::canonicalize(workspace).ok();
+    let canonical_path = fs::canonicalize(path).ok();
+    if let Some(workspace) = &canonical_workspace {
+        for path in [Some(path), canonical_path.as_deref()]
+            .into_iter()
+            .flatten()
+        {
+            if let Ok(relative) = path.strip_prefix(workspace) {
+                return clean(relative);
+            }
+        }
+    }
+    path.to_path_buf()
+}
+
+/// `path` with `.` and `..` components resolved lexically, e.g. `./App/View.swift`
+/// or `Sources/../App/View.swift` as `App/View.swift`
+fn clean(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(cleaned.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                cleaned.pop();
+            }
+            component => cleaned.push(component),
+        }
+    }
+    cleaned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_paths_are_relative_to_workspace() {
+        let workspace = std::env::temp_dir().join(format!("workspace-path-{}", Uuid::new_v4()));
+        fs::create_dir_all(workspace.join("App")).unwrap();
+        let file = workspace.join("App/LoginView.swift");
+        fs::write(&file, "").unwrap();
+
+        assert_eq!(relative(&file, &workspace), "App/LoginView.swift");
+        assert_eq!(
+            relative(Path::new("./App/LoginView.swift"), &workspace),
+            "App/LoginView.swift"
+        );
+        // Through a non-canonical spelling of the workspace
+        assert_eq!(
+            relative(&file, &workspace.join("App/..")),
+            "App/LoginView.swift"
+        );
+        assert_eq!(
+            relative(Path::new("Sources/../App/LoginView.swift"), &workspace),
+            "App/LoginView.swift"
+        );
+        assert_eq!(
+            relative(
+                &workspace.join("Sources/../App/LoginView.swift"),
+                &workspace
+            ),
+            "App/LoginView.swift"
+        );
+        assert_eq!(relative(Path::new("/etc/hosts"), &workspace), "/etc/hosts");
+
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+}
@@ -1,6 +1,8 @@
+use crate::xcresulttool::{XcresultTool, XcresultToolError};
+use crate::xctestresultdetailparser;
+use serde::Deserialize;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 #[derive(Debug, thiserror::Error)]
 pub enum AttachmentHandlerError {
@@ -20,56 +22,172 @@ pub enum AttachmentHandlerError {
     NoImageAttachmentsFound,
 }
 
+impl From<XcresultToolError> for AttachmentHandlerError {
+    fn from(error: XcresultToolError) -> Self {
+        match error {
+            XcresultToolError::NonZeroExitCode(code) => Self::NonZeroExitCode(code),
+            _ => Self::ExecutionError(error.to_string()),
+        }
+    }
+}
+
+/// File `xcresulttool export attachments` describes the exported files in
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Attachments exported for a single test
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestAttachments {
+    /// Identifier of the test the attachments belong to
+    pub test_id: String,
+    /// Directory containing the exported attachments
+    pub directory: PathBuf,
+    /// Every exported attachment according to the manifest, empty without one
+    pub attachments: Vec<Attachment>,
+}
+
+/// Entry of the export manifest: the attachments of one test
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestEntry {
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+}
+
+/// An exported attachment as described by the export manifest
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    /// Name of the file in the export directory
+    pub exported_file_name: String,
+    /// Name given in the test, e.g. `Failure screenshot_0_<uuid>.png` or
+    /// `kXCTAttachmentLegacyScreenImageData_1_<uuid>.jpeg`
+    #[serde(default)]
+    pub suggested_human_readable_name: Option<String>,
+    /// Type of the attachment, e.g. `public.png`
+    #[serde(default)]
+    pub uniform_type_identifier: Option<String>,
+    /// Whether XCTest recorded the attachment for a failure
+    #[serde(default)]
+    pub is_associated_with_failure: bool,
+    /// Seconds since 1970 the attachment was recorded at
+    #[serde(default)]
+    pub timestamp: Option<f64>,
+}
+
+impl Attachment {
+    /// Name given in the test, or the exported file name
+    pub fn name(&self) -> &str {
+        self.suggested_human_readable_name
+            .as_deref()
+            .unwrap_or(&self.exported_file_name)
+    }
+
+    /// Whether this is the screenshot XCTest takes when an assertion fails
+    pub fn is_failure_screenshot(&self) -> bool {
+        self.is_associated_with_failure || self.name().to_lowercase().starts_with("failure")
+    }
+}
+
+/// Attachments listed in the export manifest, across all tests in it
+pub fn parse_manifest(json: &str) -> Result<Vec<Attachment>, serde_json::Error> {
+    let entries: Vec<ManifestEntry> = serde_json::from_str(json)?;
+    Ok(entries
+        .into_iter()
+        .flat_map(|entry| entry.attachments)
+        .collect())
+}
+
 pub struct XCTestResultAttachmentHandler {
-    xcresulttool_path: PathBuf,
+    tool: XcresultTool,
 }
 
 impl XCTestResultAttachmentHandler {
     pub fn new() -> Self {
         Self {
-            xcresulttool_path: PathBuf::from("xcrun"),
+            tool: XcresultTool::new(),
         }
     }
 
-    /// Fetch attachments for a test and keep only the newest image file
+    /// Directory that attachments of `test_id` are exported to below `output_path`
+    ///
+    /// Each test gets its own subdirectory so that several failures sharing one
+    /// output directory don't mix up their snapshots.
+    pub fn attachments_dir_for<P: AsRef<Path>>(output_path: P, test_id: &str) -> PathBuf {
+        output_path
+            .as_ref()
+            .join("attachments")
+            .join(format!("{:016x}", Self::hash_test_id(test_id)))
+    }
+
+    /// Stable FNV-1a hash of a test identifier, used for directory names
+    fn hash_test_id(test_id: &str) -> u64 {
+        test_id.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /// Fetch attachments for a test and keep only its failure screenshot
+    ///
+    /// The screenshot is picked using the export manifest; without one, or without
+    /// an image in it, the newest image file is kept.
     pub fn fetch_attachments<P: AsRef<Path>>(
         &self,
         test_id: &str,
         xcresult_path: P,
         output_path: P,
-    ) -> Result<PathBuf, AttachmentHandlerError> {
-        let output_dir = output_path.as_ref().join("attachments");
+    ) -> Result<TestAttachments, AttachmentHandlerError> {
+        let output_dir = Self::attachments_dir_for(output_path, test_id);
 
         // Create the attachments directory
         fs::create_dir_all(&output_dir)?;
 
         // Execute xcresulttool to export attachments
-        let output = Command::new(&self.xcresulttool_path)
-            .arg("xcresulttool")
-            .arg("export")
-            .arg("attachments")
-            .arg("--test-id")
-            .arg(test_id)
-            .arg("--path")
-            .arg(xcresult_path.as_ref())
-            .arg("--output-path")
-            .arg(&output_dir)
-            .output()
-            .map_err(|e| AttachmentHandlerError::ExecutionError(e.to_string()))?;
-
-        if !output.status.success() {
-            let exit_code = output.status.code().unwrap_or(-1);
-            return Err(AttachmentHandlerError::NonZeroExitCode(exit_code));
-        }
-
-        // Find and keep only the newest image attachment
-        self.keep_newest_image_attachment(&output_dir)?;
+        self.tool
+            .export_attachments(xcresult_path.as_ref(), test_id, &output_dir)?;
+
+        // Read the manifest before the cleanup removes it along with other non-images
+        let attachments = fs::read_to_string(output_dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|json| parse_manifest(&json).ok())
+            .unwrap_or_default();
+
+        self.keep_screenshot(&output_dir, &attachments)?;
+
+        Ok(TestAttachments {
+            test_id: test_id.to_string(),
+            directory: output_dir,
+            attachments,
+        })
+    }
 
-        Ok(output_dir)
+    /// The image attachment to keep: a failure screenshot if there is one, else the latest
+    fn preferred_screenshot(&self, dir: &Path, attachments: &[Attachment]) -> Option<PathBuf> {
+        attachments
+            .iter()
+            .filter(|attachment| {
+                let path = dir.join(&attachment.exported_file_name);
+                self.is_image_file(&path) && path.is_file()
+            })
+            .max_by(|a, b| {
+                a.is_failure_screenshot()
+                    .cmp(&b.is_failure_screenshot())
+                    .then(
+                        a.timestamp
+                            .unwrap_or_default()
+                            .total_cmp(&b.timestamp.unwrap_or_default()),
+                    )
+            })
+            .map(|attachment| dir.join(&attachment.exported_file_name))
     }
 
-    /// Keep only the newest image attachment in the directory
-    fn keep_newest_image_attachment(&self, dir: &Path) -> Result<(), AttachmentHandlerError> {
+    /// Keep only the screenshot picked from the manifest, or else the newest image, in the directory
+    ///
+    /// Crash logs are kept too, since the crash prompt quotes them.
+    fn keep_screenshot(
+        &self,
+        dir: &Path,
+        attachments: &[Attachment],
+    ) -> Result<(), AttachmentHandlerError> {
         let entries: Vec<_> = fs::read_dir(dir)?
             .filter_map(|entry| entry.ok())
             .filter(|entry| entry.path().is_file())
@@ -105,11 +223,17 @@ impl XCTestResultAttachmentHandler {
                 }
         }
 
-        // Delete all files except the newest image
-        if let Some((newest_path, _)) = newest_image {
+        // Delete all files except crash logs and the screenshot from the manifest, or else the
+        // newest image
+        let kept = self
+            .preferred_screenshot(dir, attachments)
+            .or(newest_image.map(|(path, _)| path));
+        if let Some(kept_path) = kept {
             for entry in entries {
                 let path = entry.path();
-                if path != newest_path {
+                let is_crash_log =
+                    xctestresultdetailparser::is_crash_log(&entry.file_name().to_string_lossy());
+                if path != kept_path && !is_crash_log {
                     fs::remove_file(&path)?;
                 }
             }
@@ -147,7 +271,7 @@ mod tests {
     #[test]
     fn test_handler_creation() {
         let handler = XCTestResultAttachmentHandler::new();
-        assert_eq!(handler.xcresulttool_path, PathBuf::from("xcrun"));
+        assert_eq!(handler.tool.xcrun_path(), Path::new("xcrun"));
     }
 
     #[test]
@@ -172,7 +296,7 @@ mod tests {
         File::create(&non_image).unwrap().write_all(b"{}").unwrap();
 
         let handler = XCTestResultAttachmentHandler::new();
-        handler.keep_newest_image_attachment(&temp_dir).unwrap();
+        handler.keep_screenshot(&temp_dir, &[]).unwrap();
 
         // Only the newest image file should remain
         assert!(!file1.exists());
@@ -198,6 +322,140 @@ mod tests {
         assert!(!handler.is_image_file(Path::new("noextension")));
     }
 
+    #[test]
+    fn test_attachments_namespaced_per_test() {
+        let base_dir = std::env::temp_dir().join("test_attachments_per_test");
+        let first_id = "test://com.apple.xcode/App/UITests/LoginTests/testLogin";
+        let second_id = "test://com.apple.xcode/App/UITests/LoginTests/testLogout";
+
+        let first_dir = XCTestResultAttachmentHandler::attachments_dir_for(&base_dir, first_id);
+        let second_dir = XCTestResultAttachmentHandler::attachments_dir_for(&base_dir, second_id);
+
+        // Same base dir, distinct and stable per-test subdirectories
+        assert_ne!(first_dir, second_dir);
+        assert!(first_dir.starts_with(base_dir.join("attachments")));
+        assert_eq!(
+            first_dir,
+            XCTestResultAttachmentHandler::attachments_dir_for(&base_dir, first_id)
+        );
+
+        fs::create_dir_all(&first_dir).unwrap();
+        fs::create_dir_all(&second_dir).unwrap();
+        File::create(first_dir.join("login.png"))
+            .unwrap()
+            .write_all(b"login")
+            .unwrap();
+        File::create(second_dir.join("logout.png"))
+            .unwrap()
+            .write_all(b"logout")
+            .unwrap();
+
+        // Pruning one test's attachments must not touch the other's
+        let handler = XCTestResultAttachmentHandler::new();
+        handler.keep_screenshot(&first_dir, &[]).unwrap();
+        handler.keep_screenshot(&second_dir, &[]).unwrap();
+
+        assert!(first_dir.join("login.png").exists());
+        assert!(second_dir.join("logout.png").exists());
+
+        // Clean up
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+
+    const MANIFEST: &str = r#"[
+      {
+        "attachments": [
+          {
+            "configurationName": "Test Scheme Action",
+            "deviceName": "iPhone 17 Pro",
+            "exportedFileName": "5B2F1A40.png",
+            "isAssociatedWithFailure": true,
+            "suggestedHumanReadableName": "Failure screenshot_0_5B2F1A40.png",
+            "timestamp": 1760384503.12
+          },
+          {
+            "exportedFileName": "9C81D2E7.jpeg",
+            "isAssociatedWithFailure": false,
+            "suggestedHumanReadableName": "kXCTAttachmentLegacyScreenImageData_1_9C81D2E7.jpeg",
+            "timestamp": 1760384504.5,
+            "uniformTypeIdentifier": "public.jpeg"
+          }
+        ],
+        "testIdentifier": "AutoFixSamplerUITests/testExample()",
+        "testIdentifierURL": "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/testExample"
+      }
+    ]"#;
+
+    #[test]
+    fn test_parse_manifest() {
+        let attachments = parse_manifest(MANIFEST).unwrap();
+
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].name(), "Failure screenshot_0_5B2F1A40.png");
+        assert!(attachments[0].is_failure_screenshot());
+        assert_eq!(
+            attachments[1].uniform_type_identifier.as_deref(),
+            Some("public.jpeg")
+        );
+        assert!(!attachments[1].is_failure_screenshot());
+    }
+
+    #[test]
+    fn test_failure_screenshot_is_kept_over_newer_image() {
+        let temp_dir = std::env::temp_dir().join("test_manifest_attachments");
+        fs::create_dir_all(&temp_dir).unwrap();
+        File::create(temp_dir.join("5B2F1A40.png"))
+            .unwrap()
+            .write_all(b"failure")
+            .unwrap();
+        File::create(temp_dir.join("9C81D2E7.jpeg"))
+            .unwrap()
+            .write_all(b"later")
+            .unwrap();
+        File::create(temp_dir.join(MANIFEST_FILE))
+            .unwrap()
+            .write_all(MANIFEST.as_bytes())
+            .unwrap();
+
+        let handler = XCTestResultAttachmentHandler::new();
+        handler
+            .keep_screenshot(&temp_dir, &parse_manifest(MANIFEST).unwrap())
+            .unwrap();
+
+        assert!(temp_dir.join("5B2F1A40.png").exists());
+        assert!(!temp_dir.join("9C81D2E7.jpeg").exists());
+        assert!(!temp_dir.join(MANIFEST_FILE).exists());
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_crash_log_is_kept_next_to_screenshot() {
+        let temp_dir = std::env::temp_dir().join("test_crash_log_attachments");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let screenshot = temp_dir.join("failure.png");
+        let crash_log = temp_dir.join("AutoFixSampler-2025-10-13-214329.ips");
+        let older = temp_dir.join("older.png");
+        File::create(&older).unwrap().write_all(b"older").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        File::create(&screenshot)
+            .unwrap()
+            .write_all(b"failure")
+            .unwrap();
+        File::create(&crash_log).unwrap().write_all(b"{}").unwrap();
+
+        let handler = XCTestResultAttachmentHandler::new();
+        handler.keep_screenshot(&temp_dir, &[]).unwrap();
+
+        assert!(screenshot.exists());
+        assert!(crash_log.exists());
+        assert!(!older.exists());
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
     #[test]
     fn test_no_image_attachments() {
         let temp_dir = std::env::temp_dir().join("test_no_images");
@@ -210,7 +468,7 @@ mod tests {
         File::create(&file2).unwrap().write_all(b"text").unwrap();
 
         let handler = XCTestResultAttachmentHandler::new();
-        let result = handler.keep_newest_image_attachment(&temp_dir);
+        let result = handler.keep_screenshot(&temp_dir, &[]);
 
         assert!(result.is_err());
         match result {
@@ -1,30 +1,85 @@
+use crate::file_locator::{FileLocator, FileLocatorError, test_class_name};
+use crate::source_language::SourceLanguage;
+use crate::tools::directory_inspector_tool::VENDORED_DIRS;
+use regex::Regex;
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
-
-#[derive(Debug, thiserror::Error)]
-pub enum FileLocatorError {
-    #[error("Invalid test identifier URL: {0}")]
-    InvalidTestIdentifierUrl(String),
-
-    #[error("File not found for class: {0}")]
-    FileNotFound(String),
-
-    #[error("IO error: {0}")]
-    IoError(#[from] std::io::Error),
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// Build output directories never searched, besides hidden ones such as `.git`
+/// and the `VENDORED_DIRS` that `directory_inspector` ranks last
+///
+/// They are slow to walk and may contain copies of the test file.
+const IGNORED_DIRS: &[&str] = &["build"];
+
+/// Deepest directory nesting below the workspace that is searched
+const MAX_SEARCH_DEPTH: usize = 32;
+
+/// Longest a single search may take before it gives up
+const SEARCH_TIME_BUDGET: Duration = Duration::from_secs(30);
+
+/// Identifier URLs of XCTest and Swift Testing tests, whose files must look like tests
+const XCODE_TEST_URL_PREFIX: &str = "test://com.apple.xcode/";
+
+/// Constructs only a test file has: a test framework import (also Objective-C's
+/// `#import <XCTest/XCTest.h>`), an `XCTestCase` subclass or a Swift Testing `@Test`/`@Suite`
+static TEST_FILE_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?m)^\s*(@testable\s+)?import\s+(XCTest|Testing)\b|^\s*#import\s+<XCTest/|^\s*@import\s+XCTest\b|:\s*XCTestCase\b|@(Test|Suite)\b",
+    )
+    .unwrap()
+});
+
+/// Whether `contents` of a source file look like a test rather than a same-named app file
+fn looks_like_test_file(contents: &str) -> bool {
+    TEST_FILE_PATTERN.is_match(contents)
 }
 
 pub struct XCWorkspaceFileLocator {
     workspace_path: PathBuf,
+    /// Directory names skipped in addition to `IGNORED_DIRS` and `VENDORED_DIRS`
+    extra_ignored_dirs: Vec<String>,
+    /// Language of the test files; `None` tries Swift, then Objective-C
+    language: Option<SourceLanguage>,
 }
 
 impl XCWorkspaceFileLocator {
     pub fn new<P: AsRef<Path>>(workspace_path: P) -> Self {
         Self {
             workspace_path: workspace_path.as_ref().to_path_buf(),
+            extra_ignored_dirs: Vec::new(),
+            language: None,
         }
     }
 
-    /// Locate the Swift file for a given test identifier URL by searching for the class name
+    /// Only search for test files of `language` (`--language`)
+    pub fn with_language(mut self, language: Option<SourceLanguage>) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Also skip directories with these names
+    pub fn with_ignored_dirs(mut self, dirs: Vec<String>) -> Self {
+        self.extra_ignored_dirs = dirs;
+        self
+    }
+
+    /// Whether the search skips a directory named `name`
+    fn is_ignored_dir(&self, name: &str) -> bool {
+        name.starts_with('.')
+            || IGNORED_DIRS.contains(&name)
+            || VENDORED_DIRS.contains(&name)
+            || self.extra_ignored_dirs.iter().any(|dir| dir == name)
+    }
+
+    /// Locate the source file for a given test identifier URL by searching for the class name
+    ///
+    /// Files of that name that don't look like tests, e.g. an app model named like
+    /// the test class, are skipped in favor of the next match. Without a language one
+    /// search looks for `<Class>.swift` and the Objective-C `<Class>.m`/`.mm`, preferring
+    /// the Swift file when there are both.
     ///
     /// Examples:
     /// - test_identifier_url: "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/testExample"
@@ -38,59 +93,179 @@ impl XCWorkspaceFileLocator {
         // Extract the class name from the test identifier URL
         let class_name = self.extract_class_name(test_identifier_url)?;
 
-        // Search for the file in the workspace
-        let file_name = format!("{}.swift", class_name);
+        let languages = match self.language {
+            Some(language) => vec![language],
+            None => SourceLanguage::ALL.to_vec(),
+        };
+        let extensions: Vec<&str> = languages
+            .iter()
+            .flat_map(|language| language.test_file_extensions())
+            .copied()
+            .collect();
+        let file_name = match extensions.as_slice() {
+            [extension] => format!("{}.{}", class_name, extension),
+            _ => format!("{}.{{{}}}", class_name, extensions.join(",")),
+        };
+        // Position of a file name's extension in `extensions`, earlier being preferred
+        let rank = |name: &str| {
+            let extension = name.strip_prefix(class_name.as_str())?.strip_prefix('.')?;
+            extensions
+                .iter()
+                .position(|candidate| *candidate == extension)
+        };
+        let matches = |name: &str| rank(name).is_some();
+
+        // Search the workspace once for all candidate names
+        let must_be_test = test_identifier_url.starts_with(XCODE_TEST_URL_PREFIX);
+        let mut not_tests = Vec::new();
+        let mut fallback: Option<(usize, PathBuf)> = None;
+        let mut accept = |path: &Path| {
+            let is_test = !must_be_test
+                || fs::read_to_string(path).is_ok_and(|contents| looks_like_test_file(&contents));
+            if !is_test {
+                not_tests.push(path.to_path_buf());
+                return false;
+            }
+            let rank = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(rank)
+                .unwrap_or_default();
+            if rank == 0 {
+                return true;
+            }
+            // A less preferred file is used only if the search ends without a better one
+            if fallback.as_ref().is_none_or(|(best, _)| rank < *best) {
+                fallback = Some((rank, path.to_path_buf()));
+            }
+            false
+        };
+        let found =
+            self.search_for_file(&self.workspace_path, &file_name, &matches, &mut accept)?;
+        if let Some(path) = found.or(fallback.map(|(_, path)| path)) {
+            return Ok(path);
+        }
 
-        match self.search_for_file(&self.workspace_path, &file_name)? {
-            Some(path) => Ok(path),
-            None => Err(FileLocatorError::FileNotFound(class_name)),
+        if not_tests.is_empty() {
+            Err(FileLocatorError::FileNotFound(class_name))
+        } else {
+            Err(FileLocatorError::NotATestFile {
+                class_name,
+                candidates: not_tests,
+            })
         }
     }
 
-    /// Extract the class name from a test identifier URL
-    /// The class name is the second-to-last component (before the test method name)
+    /// Files below the workspace with one of `extensions`, e.g. the app's sources
     ///
-    /// Example: "test://com.apple.xcode/MyApp/MyUITests/Features/Login/Screens/LoginScreenTests/testLoginFlow"
-    /// Returns: "LoginScreenTests"
-    fn extract_class_name(&self, test_identifier_url: &str) -> Result<String, FileLocatorError> {
-        let parts = self.parse_test_identifier_url(test_identifier_url)?;
-
-        // We need at least: scheme, project, target, class, testMethod = 5 parts
-        if parts.len() < 5 {
-            return Err(FileLocatorError::InvalidTestIdentifierUrl(
-                test_identifier_url.to_string(),
-            ));
-        }
-
-        // The class name is the second-to-last component (before the test method)
-        let class_name = &parts[parts.len() - 2];
+    /// Skips and bounds directories like the test file search; a search cut short
+    /// by the bounds returns the files found until then.
+    pub fn source_files(&self, extensions: &[&str]) -> Vec<PathBuf> {
+        let label = format!("*.{{{}}}", extensions.join(","));
+        let matches = |name: &str| {
+            Path::new(name)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| extensions.contains(&extension))
+        };
+        let mut files = Vec::new();
+        let mut collect = |path: &Path| {
+            files.push(path.to_path_buf());
+            // Keep searching
+            false
+        };
+        let _ = self.search_for_file(&self.workspace_path, &label, &matches, &mut collect);
+        files
+    }
 
-        Ok(class_name.to_string())
+    /// Extract the class name from a test identifier URL
+    fn extract_class_name(&self, test_identifier_url: &str) -> Result<String, FileLocatorError> {
+        test_class_name(test_identifier_url)
     }
 
-    /// Recursively search for a file with the given name in the directory
-    /// Uses case-sensitive matching
+    /// Recursively search the directory for a file whose name `matches` and that `accept` accepts
+    ///
+    /// `file_name` describes the files searched for in errors.
     fn search_for_file(
         &self,
         dir: &Path,
         file_name: &str,
+        matches: &dyn Fn(&str) -> bool,
+        accept: &mut dyn FnMut(&Path) -> bool,
+    ) -> Result<Option<PathBuf>, FileLocatorError> {
+        let mut search = Search {
+            file_name,
+            matches,
+            accept,
+            started: Instant::now(),
+            searched_dirs: 0,
+            visited: HashSet::new(),
+            too_deep: None,
+        };
+        let found = self.search_dir(dir, 0, &mut search)?;
+
+        // Not finding the file says nothing when part of the tree was left out
+        match (found, search.too_deep) {
+            (None, Some(too_deep)) => Err(FileLocatorError::SearchTimedOut {
+                file_name: file_name.to_string(),
+                searched_dirs: search.searched_dirs,
+                reason: format!(
+                    "directories nested deeper than {} levels were skipped, e.g. {}",
+                    MAX_SEARCH_DEPTH,
+                    too_deep.display()
+                ),
+            }),
+            (found, _) => Ok(found),
+        }
+    }
+
+    fn search_dir(
+        &self,
+        dir: &Path,
+        depth: usize,
+        search: &mut Search,
     ) -> Result<Option<PathBuf>, FileLocatorError> {
         if !dir.exists() || !dir.is_dir() {
             return Ok(None);
         }
+        if search.started.elapsed() > SEARCH_TIME_BUDGET {
+            return Err(FileLocatorError::SearchTimedOut {
+                file_name: search.file_name.to_string(),
+                searched_dirs: search.searched_dirs,
+                reason: format!(
+                    "exceeded the {}s time budget while in {}",
+                    SEARCH_TIME_BUDGET.as_secs(),
+                    dir.display()
+                ),
+            });
+        }
+        // Symlinks can lead back into a directory already searched
+        if !search.visited.insert(fs::canonicalize(dir)?) {
+            return Ok(None);
+        }
+        search.searched_dirs += 1;
 
         for entry in fs::read_dir(dir)? {
             let entry = entry?;
             let path = entry.path();
 
             if path.is_file() {
-                if let Some(name) = path.file_name()
-                    && name == file_name {
-                        return Ok(Some(path));
-                    }
+                if let Some(name) = path.file_name().and_then(|name| name.to_str())
+                    && (search.matches)(name)
+                    && (search.accept)(&path)
+                {
+                    return Ok(Some(path));
+                }
             } else if path.is_dir() {
+                if self.is_ignored_dir(&entry.file_name().to_string_lossy()) {
+                    continue;
+                }
+                if depth == MAX_SEARCH_DEPTH {
+                    search.too_deep.get_or_insert(path);
+                    continue;
+                }
                 // Recursively search subdirectories
-                if let Some(found) = self.search_for_file(&path, file_name)? {
+                if let Some(found) = self.search_dir(&path, depth + 1, search)? {
                     return Ok(Some(found));
                 }
             }
@@ -98,22 +273,26 @@ impl XCWorkspaceFileLocator {
 
         Ok(None)
     }
+}
 
-    /// Parse the test identifier URL into parts
-    fn parse_test_identifier_url(&self, url: &str) -> Result<Vec<String>, FileLocatorError> {
-        // Remove the "test://" prefix
-        let without_prefix = url
-            .strip_prefix("test://")
-            .ok_or_else(|| FileLocatorError::InvalidTestIdentifierUrl(url.to_string()))?;
-
-        // Split by '/' and collect parts
-        let parts: Vec<String> = without_prefix.split('/').map(|s| s.to_string()).collect();
-
-        if parts.is_empty() {
-            return Err(FileLocatorError::InvalidTestIdentifierUrl(url.to_string()));
-        }
+/// Progress of one file search, kept to bound it
+struct Search<'a> {
+    file_name: &'a str,
+    /// Whether a file name is one searched for
+    matches: &'a dyn Fn(&str) -> bool,
+    /// Decides whether a file with the right name is the one searched for
+    accept: &'a mut dyn FnMut(&Path) -> bool,
+    started: Instant,
+    searched_dirs: usize,
+    /// Canonical paths of searched directories
+    visited: HashSet<PathBuf>,
+    /// First directory left out for being nested too deep
+    too_deep: Option<PathBuf>,
+}
 
-        Ok(parts)
+impl FileLocator for XCWorkspaceFileLocator {
+    fn locate_file(&self, test_identifier_url: &str) -> Result<PathBuf, FileLocatorError> {
+        XCWorkspaceFileLocator::locate_file(self, test_identifier_url)
     }
 }
 
@@ -122,30 +301,6 @@ mod tests {
     use super::*;
     use std::fs;
 
-    #[test]
-    fn test_parse_test_identifier_url() {
-        let locator = XCWorkspaceFileLocator::new("/tmp/workspace");
-        let url = "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/testExample";
-
-        let parts = locator.parse_test_identifier_url(url).unwrap();
-
-        assert_eq!(parts.len(), 5);
-        assert_eq!(parts[0], "com.apple.xcode");
-        assert_eq!(parts[1], "AutoFixSampler");
-        assert_eq!(parts[2], "AutoFixSamplerUITests");
-        assert_eq!(parts[3], "AutoFixSamplerUITests");
-        assert_eq!(parts[4], "testExample");
-    }
-
-    #[test]
-    fn test_parse_invalid_url() {
-        let locator = XCWorkspaceFileLocator::new("/tmp/workspace");
-        let url = "invalid://url";
-
-        let result = locator.parse_test_identifier_url(url);
-        assert!(result.is_err());
-    }
-
     #[test]
     fn test_locate_file() {
         // Create a temporary workspace structure
@@ -154,7 +309,7 @@ mod tests {
         fs::create_dir_all(&target_dir).unwrap();
 
         let test_file = target_dir.join("AutoFixSamplerUITests.swift");
-        fs::write(&test_file, "// Test file").unwrap();
+        fs::write(&test_file, "import XCTest").unwrap();
 
         let locator = XCWorkspaceFileLocator::new(&temp_dir);
         let url = "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/testExample";
@@ -207,7 +362,11 @@ mod tests {
         fs::create_dir_all(&nested_path).unwrap();
 
         let test_file = nested_path.join("LoginScreenTests.swift");
-        fs::write(&test_file, "class LoginScreenTests { }").unwrap();
+        fs::write(
+            &test_file,
+            "import XCTest\n\nclass LoginScreenTests: XCTestCase { }",
+        )
+        .unwrap();
 
         let locator = XCWorkspaceFileLocator::new(&temp_dir);
         let url = "test://com.apple.xcode/MyApp/MyUITests/Features/Login/Screens/LoginScreenTests/testLoginFlow";
@@ -227,7 +386,11 @@ mod tests {
         fs::create_dir_all(&deep_path).unwrap();
 
         let test_file = deep_path.join("MyTestClass.swift");
-        fs::write(&test_file, "class MyTestClass { }").unwrap();
+        fs::write(
+            &test_file,
+            "import XCTest\n\nclass MyTestClass: XCTestCase { }",
+        )
+        .unwrap();
 
         let locator = XCWorkspaceFileLocator::new(&temp_dir);
         let url = "test://com.apple.xcode/Project/Target/MyTestClass/testSomething";
@@ -238,4 +401,219 @@ mod tests {
         // Clean up
         fs::remove_dir_all(&temp_dir).unwrap();
     }
+
+    #[test]
+    fn test_search_skips_vendored_and_ignored_dirs() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("test_workspace_ignored_{}", uuid::Uuid::new_v4()));
+        let url = "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin";
+        for dir in ["Pods/Kit/Tests", "Carthage/Kit", "Vendor/Kit", "AppUITests"] {
+            fs::create_dir_all(temp_dir.join(dir)).unwrap();
+        }
+        fs::write(
+            temp_dir.join("Pods/Kit/Tests/LoginTests.swift"),
+            "// vendored",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.join("Carthage/Kit/LoginTests.swift"),
+            "// vendored",
+        )
+        .unwrap();
+        fs::write(temp_dir.join("Vendor/Kit/LoginTests.swift"), "// vendored").unwrap();
+        let test_file = temp_dir.join("AppUITests/LoginTests.swift");
+        fs::write(&test_file, "class LoginTests: XCTestCase { }").unwrap();
+
+        let locator =
+            XCWorkspaceFileLocator::new(&temp_dir).with_ignored_dirs(vec!["Vendor".to_string()]);
+        assert_eq!(locator.locate_file(url).unwrap(), test_file);
+
+        // Without the workspace copy only ignored copies are left, which are never returned
+        fs::remove_file(&test_file).unwrap();
+        assert!(matches!(
+            locator.locate_file(url),
+            Err(FileLocatorError::FileNotFound(_))
+        ));
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_decoy_with_test_class_name_is_skipped() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("test_workspace_decoy_{}", uuid::Uuid::new_v4()));
+        let url = "test://com.apple.xcode/App/AppUITests/LoginScreenTests/testLogin";
+        for dir in ["App/Models", "AppUITests"] {
+            fs::create_dir_all(temp_dir.join(dir)).unwrap();
+        }
+        // Whichever copy the search reaches first, the app model is never returned
+        let decoy = temp_dir.join("App/Models/LoginScreenTests.swift");
+        fs::write(
+            &decoy,
+            "import Foundation\n\nstruct LoginScreenTests { let count: Int }",
+        )
+        .unwrap();
+        let test_file = temp_dir.join("AppUITests/LoginScreenTests.swift");
+        fs::write(
+            &test_file,
+            "@testable import App\nimport XCTest\n\nfinal class LoginScreenTests: XCTestCase { }",
+        )
+        .unwrap();
+
+        let locator = XCWorkspaceFileLocator::new(&temp_dir);
+        assert_eq!(locator.locate_file(url).unwrap(), test_file);
+
+        // With only the decoy left, the error names it instead of claiming nothing matched
+        fs::remove_file(&test_file).unwrap();
+        match locator.locate_file(url) {
+            Err(FileLocatorError::NotATestFile {
+                class_name,
+                candidates,
+            }) => {
+                assert_eq!(class_name, "LoginScreenTests");
+                assert_eq!(candidates, vec![decoy]);
+            }
+            result => panic!("Expected NotATestFile, got {:?}", result),
+        }
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_locate_objective_c_test_file() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("test_workspace_objc_{}", uuid::Uuid::new_v4()));
+        let url = "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin";
+        fs::create_dir_all(temp_dir.join("AppUITests")).unwrap();
+        fs::write(
+            temp_dir.join("AppUITests/LoginTests.h"),
+            "@interface LoginTests : XCTestCase\n@end",
+        )
+        .unwrap();
+        let test_file = temp_dir.join("AppUITests/LoginTests.m");
+        fs::write(
+            &test_file,
+            "#import <XCTest/XCTest.h>\n\n@implementation LoginTests\n@end",
+        )
+        .unwrap();
+
+        let locator = XCWorkspaceFileLocator::new(&temp_dir);
+        assert_eq!(locator.locate_file(url).unwrap(), test_file);
+
+        // A Swift hint doesn't look at Objective-C files
+        let locator = locator.with_language(Some(SourceLanguage::Swift));
+        assert!(matches!(
+            locator.locate_file(url),
+            Err(FileLocatorError::FileNotFound(_))
+        ));
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_swift_file_is_preferred_over_objective_c() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("test_workspace_mixed_{}", uuid::Uuid::new_v4()));
+        let url = "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin";
+        fs::create_dir_all(temp_dir.join("A")).unwrap();
+        fs::create_dir_all(temp_dir.join("Z")).unwrap();
+        fs::write(
+            temp_dir.join("A/LoginTests.m"),
+            "#import <XCTest/XCTest.h>\n\n@implementation LoginTests\n@end",
+        )
+        .unwrap();
+        let test_file = temp_dir.join("Z/LoginTests.swift");
+        fs::write(
+            &test_file,
+            "import XCTest\n\nclass LoginTests: XCTestCase {}",
+        )
+        .unwrap();
+
+        let locator = XCWorkspaceFileLocator::new(&temp_dir);
+        assert_eq!(locator.locate_file(url).unwrap(), test_file);
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_looks_like_test_file() {
+        assert!(looks_like_test_file(
+            "import XCTest\n\nclass LoginTests: BaseUITestCase {}"
+        ));
+        assert!(looks_like_test_file(
+            "import Testing\n\n@Test func login() {}"
+        ));
+        assert!(looks_like_test_file("class LoginTests: XCTestCase {}"));
+        assert!(looks_like_test_file(
+            "#import <XCTest/XCTest.h>\n\n@implementation LoginTests\n@end"
+        ));
+        assert!(looks_like_test_file("@import XCTest;"));
+        assert!(!looks_like_test_file(
+            "import Foundation\n\n// Mirrors XCTest naming\nclass LoginTests {}"
+        ));
+    }
+
+    #[test]
+    fn test_search_stops_at_max_depth() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("test_workspace_depth_{}", uuid::Uuid::new_v4()));
+        let url = "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin";
+        let mut deep_path = temp_dir.clone();
+        for _ in 0..=MAX_SEARCH_DEPTH {
+            deep_path = deep_path.join("d");
+        }
+        fs::create_dir_all(&deep_path).unwrap();
+        fs::write(
+            deep_path.join("LoginTests.swift"),
+            "class LoginTests: XCTestCase { }",
+        )
+        .unwrap();
+
+        let locator = XCWorkspaceFileLocator::new(&temp_dir);
+        match locator.locate_file(url) {
+            Err(FileLocatorError::SearchTimedOut {
+                file_name,
+                searched_dirs,
+                reason,
+            }) => {
+                assert_eq!(file_name, "LoginTests.swift");
+                assert_eq!(searched_dirs, MAX_SEARCH_DEPTH + 1);
+                assert!(reason.contains(&deep_path.display().to_string()));
+            }
+            result => panic!("Expected SearchTimedOut, got {:?}", result),
+        }
+
+        // A file within the depth cap is still found
+        let shallow_file = temp_dir.join("d/LoginTests.swift");
+        fs::write(&shallow_file, "class LoginTests: XCTestCase { }").unwrap();
+        assert_eq!(locator.locate_file(url).unwrap(), shallow_file);
+
+        // Listing source files is bounded the same way
+        assert_eq!(locator.source_files(&["swift"]), vec![shallow_file]);
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_search_survives_symlink_cycle() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("test_workspace_cycle_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(temp_dir.join("App")).unwrap();
+        std::os::unix::fs::symlink(&temp_dir, temp_dir.join("App/Loop")).unwrap();
+
+        let locator = XCWorkspaceFileLocator::new(&temp_dir);
+        let result =
+            locator.locate_file("test://com.apple.xcode/App/AppUITests/LoginTests/testLogin");
+
+        assert!(matches!(result, Err(FileLocatorError::FileNotFound(_))));
+
+        // Clean up
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
 }
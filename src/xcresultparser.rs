@@ -1,50 +1,85 @@
+use crate::xcresulttool::{XcresultTool, XcresultToolError};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct XCResultSummary {
     pub title: String,
     pub result: String,
+    // Fields below are missing from some Xcode versions' output
+    #[serde(default)]
     pub environment_description: String,
+    #[serde(default)]
     pub start_time: f64,
+    #[serde(default)]
     pub finish_time: f64,
+    #[serde(default)]
     pub total_test_count: u32,
+    #[serde(default)]
     pub passed_tests: u32,
+    #[serde(default)]
     pub failed_tests: u32,
+    #[serde(default)]
     pub skipped_tests: u32,
+    #[serde(default)]
     pub expected_failures: u32,
+    #[serde(default)]
     pub devices_and_configurations: Vec<DeviceConfiguration>,
+    #[serde(default)]
     pub test_failures: Vec<TestFailure>,
+    #[serde(default)]
     pub statistics: Vec<serde_json::Value>,
+    #[serde(default)]
     pub top_insights: Vec<serde_json::Value>,
+    /// Fields of newer xcresulttool versions that aren't modeled here
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceConfiguration {
     pub device: Device,
+    #[serde(default)]
     pub test_plan_configuration: TestPlanConfiguration,
+    #[serde(default)]
     pub passed_tests: u32,
+    #[serde(default)]
     pub failed_tests: u32,
+    #[serde(default)]
     pub skipped_tests: u32,
+    #[serde(default)]
     pub expected_failures: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Device {
+    #[serde(default)]
     pub device_id: String,
     pub device_name: String,
+    #[serde(default)]
     pub model_name: String,
+    #[serde(default)]
     pub architecture: String,
     pub platform: String,
     pub os_version: String,
+    #[serde(default)]
     pub os_build_number: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+impl Device {
+    /// Platform and OS the tests ran on, e.g. `iOS Simulator 26.0 (23A339)`
+    pub fn runtime(&self) -> String {
+        format!(
+            "{} {} ({})",
+            self.platform, self.os_version, self.os_build_number
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct TestPlanConfiguration {
     pub configuration_id: String,
@@ -63,6 +98,88 @@ pub struct TestFailure {
     pub failure_text: String,
 }
 
+/// Messages XCTest and Swift Testing record when an expected failure no longer fails
+const UNEXPECTED_PASS_MARKERS: [&str; 2] = [
+    "expected failure but none recorded",
+    "known issue was not recorded",
+];
+
+impl TestFailure {
+    /// Whether this failure only reports that an expected failure (`XCTExpectFailure`) now passes
+    pub fn is_unexpected_pass(&self) -> bool {
+        let text = self.failure_text.to_lowercase();
+        UNEXPECTED_PASS_MARKERS
+            .iter()
+            .any(|marker| text.contains(marker))
+    }
+}
+
+/// A failure together with the bundle it was recorded in
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleFailure {
+    pub failure: TestFailure,
+    pub bundle: PathBuf,
+}
+
+/// Union of the failures of several bundles, e.g. from sharded CI runs
+///
+/// A test failing in more than one bundle is listed once, with the first
+/// bundle it failed in, so that its details and attachments are read from there.
+pub fn merge_failures(summaries: &[(PathBuf, XCResultSummary)]) -> Vec<BundleFailure> {
+    let mut merged: Vec<BundleFailure> = Vec::new();
+    for (bundle, summary) in summaries {
+        for failure in &summary.test_failures {
+            if merged
+                .iter()
+                .all(|known| known.failure.test_identifier_url != failure.test_identifier_url)
+            {
+                merged.push(BundleFailure {
+                    failure: failure.clone(),
+                    bundle: bundle.clone(),
+                });
+            }
+        }
+    }
+    merged
+}
+
+/// Per-test results of a bundle (`xcresulttool get test-results tests`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct XCTestResults {
+    pub test_nodes: Vec<XCTestNode>,
+}
+
+/// A node of the test tree: test plan, bundle, suite, test case or message
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct XCTestNode {
+    pub name: String,
+    pub node_type: String,
+    pub node_identifier: Option<String>,
+    #[serde(rename = "nodeIdentifierURL")]
+    pub node_identifier_url: Option<String>,
+    /// `Passed`, `Failed`, `Skipped` or `Expected Failure`
+    pub result: Option<String>,
+    #[serde(default)]
+    pub children: Vec<XCTestNode>,
+}
+
+impl XCTestResults {
+    /// All test cases whose result equals `result`, e.g. `Skipped`
+    pub fn test_cases_with_result(&self, result: &str) -> Vec<&XCTestNode> {
+        let mut matches = Vec::new();
+        let mut pending: Vec<&XCTestNode> = self.test_nodes.iter().collect();
+        while let Some(node) = pending.pop() {
+            if node.node_type == "Test Case" && node.result.as_deref() == Some(result) {
+                matches.push(node);
+            }
+            pending.extend(node.children.iter().rev());
+        }
+        matches
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum XCResultParserError {
     #[error("Failed to execute xcresulttool: {0}")]
@@ -81,15 +198,29 @@ pub enum XCResultParserError {
     Utf8Error(#[from] std::string::FromUtf8Error),
 }
 
+impl From<XcresultToolError> for XCResultParserError {
+    fn from(error: XcresultToolError) -> Self {
+        match error {
+            XcresultToolError::NonZeroExitCode(code) => Self::NonZeroExitCode(code),
+            XcresultToolError::JsonParseError(e) => Self::JsonParseError(e),
+            XcresultToolError::PathNotFound(path) => Self::PathNotFound(path),
+            XcresultToolError::Utf8Error(e) => Self::Utf8Error(e),
+            XcresultToolError::ExecutionError(_) | XcresultToolError::UnsupportedVersion(_) => {
+                Self::ExecutionError(error.to_string())
+            }
+        }
+    }
+}
+
 pub struct XCResultParser {
-    xcresulttool_path: PathBuf,
+    tool: XcresultTool,
 }
 
 impl XCResultParser {
     /// Create a new XCResultParser using the default xcresulttool path
     pub fn new() -> Self {
         Self {
-            xcresulttool_path: PathBuf::from("xcrun"),
+            tool: XcresultTool::new(),
         }
     }
 
@@ -98,31 +229,15 @@ impl XCResultParser {
         &self,
         xcresult_path: P,
     ) -> Result<XCResultSummary, XCResultParserError> {
-        let path = xcresult_path.as_ref();
-
-        if !path.exists() {
-            return Err(XCResultParserError::PathNotFound(path.to_path_buf()));
-        }
-
-        let output = Command::new(&self.xcresulttool_path)
-            .arg("xcresulttool")
-            .arg("get")
-            .arg("test-results")
-            .arg("summary")
-            .arg("--path")
-            .arg(path)
-            .output()
-            .map_err(|e| XCResultParserError::ExecutionError(e.to_string()))?;
-
-        if !output.status.success() {
-            let exit_code = output.status.code().unwrap_or(-1);
-            return Err(XCResultParserError::NonZeroExitCode(exit_code));
-        }
-
-        let json_str = String::from_utf8(output.stdout)?;
-        let result: XCResultSummary = serde_json::from_str(&json_str)?;
+        Ok(self.tool.summary(xcresult_path.as_ref())?)
+    }
 
-        Ok(result)
+    /// Parse the per-test results of a .xcresult bundle, including skipped tests
+    pub fn parse_tests<P: AsRef<Path>>(
+        &self,
+        xcresult_path: P,
+    ) -> Result<XCTestResults, XCResultParserError> {
+        Ok(self.tool.tests(xcresult_path.as_ref())?)
     }
 }
 
@@ -227,7 +342,161 @@ mod tests {
         assert_eq!(summary.result, "Failed");
         assert_eq!(summary.title, "Test - AutoFixSampler");
         assert_eq!(summary.devices_and_configurations.len(), 1);
+        assert_eq!(
+            summary.devices_and_configurations[0].device.runtime(),
+            "iOS Simulator 26.0 (23A339)"
+        );
         assert_eq!(summary.test_failures.len(), 1);
         assert_eq!(summary.test_failures[0].test_name, "testExample()");
     }
+
+    #[test]
+    fn test_summary_tolerates_missing_and_new_fields() {
+        // Trimmed output of an older Xcode plus fields of a newer one
+        let json = r#"{
+            "devicesAndConfigurations": [
+                {
+                    "device": {
+                        "deviceName": "iPhone 17 Pro",
+                        "osVersion": "26.1",
+                        "platform": "iOS Simulator",
+                        "simulatorRuntimeIdentifier": "com.apple.CoreSimulator.SimRuntime.iOS-26-1"
+                    }
+                }
+            ],
+            "result": "Failed",
+            "testFailures": [
+                {
+                    "failureText": "Failed to tap button",
+                    "targetName": "AutoFixSamplerUITests",
+                    "testIdentifier": 1,
+                    "testIdentifierString": "AutoFixSamplerUITests/testExample()",
+                    "testIdentifierURL": "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/testExample",
+                    "testName": "testExample()"
+                }
+            ],
+            "title": "Test - AutoFixSampler",
+            "topInsights": [{ "category": "Common Failure Patterns", "impact": "1 test" }],
+            "retriedTests": 0
+        }"#;
+
+        let summary: XCResultSummary = serde_json::from_str(json).unwrap();
+        assert_eq!(summary.test_failures.len(), 1);
+        assert_eq!(summary.total_test_count, 0);
+        assert!(summary.statistics.is_empty());
+        assert_eq!(
+            summary.devices_and_configurations[0].device.os_build_number,
+            ""
+        );
+        assert_eq!(summary.extra["retriedTests"], 0);
+    }
+
+    #[test]
+    fn test_tests_fixture_categorizes_results() {
+        let json = std::fs::read_to_string("tests/fixtures/test_results_tests.json").unwrap();
+        let results: XCTestResults = serde_json::from_str(&json).unwrap();
+
+        let names = |result: &str| -> Vec<String> {
+            results
+                .test_cases_with_result(result)
+                .iter()
+                .map(|node| node.name.clone())
+                .collect()
+        };
+
+        assert_eq!(names("Failed"), vec!["testExample()", "testOnboarding()"]);
+        assert_eq!(names("Skipped"), vec!["testCamera()"]);
+        assert_eq!(names("Expected Failure"), vec!["testLegacyLogin()"]);
+        assert_eq!(
+            results.test_cases_with_result("Skipped")[0]
+                .node_identifier_url
+                .as_deref(),
+            Some(
+                "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/testCamera"
+            )
+        );
+    }
+
+    #[test]
+    fn test_unexpected_pass_detection() {
+        let failure = |failure_text: &str| TestFailure {
+            test_identifier: 1,
+            test_identifier_string: "LoginTests/testLogin()".to_string(),
+            test_identifier_url: "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin"
+                .to_string(),
+            test_name: "testLogin()".to_string(),
+            target_name: "AppUITests".to_string(),
+            failure_text: failure_text.to_string(),
+        };
+
+        assert!(failure("Expected failure but none recorded").is_unexpected_pass());
+        assert!(failure("Known issue was not recorded").is_unexpected_pass());
+        assert!(!failure("Failed to tap button").is_unexpected_pass());
+    }
+
+    fn summary_with_failures(tests: &[&str]) -> XCResultSummary {
+        XCResultSummary {
+            title: "Test - App".to_string(),
+            result: "Failed".to_string(),
+            environment_description: String::new(),
+            start_time: 0.0,
+            finish_time: 0.0,
+            total_test_count: tests.len() as u32,
+            passed_tests: 0,
+            failed_tests: tests.len() as u32,
+            skipped_tests: 0,
+            expected_failures: 0,
+            devices_and_configurations: vec![],
+            test_failures: tests
+                .iter()
+                .enumerate()
+                .map(|(index, test)| TestFailure {
+                    test_identifier: index as u32,
+                    test_identifier_string: format!("AppUITests/{}()", test),
+                    test_identifier_url: format!(
+                        "test://com.apple.xcode/App/AppUITests/AppUITests/{}",
+                        test
+                    ),
+                    test_name: format!("{}()", test),
+                    target_name: "AppUITests".to_string(),
+                    failure_text: "Failed".to_string(),
+                })
+                .collect(),
+            statistics: vec![],
+            top_insights: vec![],
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    #[test]
+    fn test_merge_failures_across_bundles() {
+        let shard1 = PathBuf::from("shard1.xcresult");
+        let shard2 = PathBuf::from("shard2.xcresult");
+        let summaries = vec![
+            (
+                shard1.clone(),
+                summary_with_failures(&["testLogin", "testLogout"]),
+            ),
+            (
+                shard2.clone(),
+                summary_with_failures(&["testLogout", "testCheckout"]),
+            ),
+        ];
+
+        let merged = merge_failures(&summaries);
+
+        let names: Vec<(&str, &PathBuf)> = merged
+            .iter()
+            .map(|merged| (merged.failure.test_name.as_str(), &merged.bundle))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("testLogin()", &shard1),
+                ("testLogout()", &shard1),
+                ("testCheckout()", &shard2),
+            ]
+        );
+        assert!(merge_failures(&[]).is_empty());
+    }
 }
@@ -0,0 +1,231 @@
+// Typed client for `xcrun xcresulttool`
+//
+// Every read of an .xcresult bundle goes through `XcresultTool`, which owns
+// the xcrun path and developer directory and checks once per process and
+// developer directory that the tool is new enough for the `test-results`
+// subcommands. The parsers and
+// the attachment handler are thin wrappers around it.
+
+use crate::developer_dir;
+use crate::xcresultparser::{XCResultSummary, XCTestResults};
+use crate::xctestresultdetailparser::{TestWithMetrics, XCTestResultDetail};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+/// First xcresulttool version (Xcode 16) with `get test-results` and `export attachments`
+pub const MIN_XCRESULTTOOL_VERSION: u32 = 23000;
+
+/// Versions detected so far by developer directory; `None` when the version could
+/// not be determined. Shared by all clients, so each is probed once per process.
+static VERSIONS: LazyLock<Mutex<HashMap<Option<PathBuf>, Option<u32>>>> =
+    LazyLock::new(Default::default);
+
+#[derive(Debug, thiserror::Error)]
+pub enum XcresultToolError {
+    #[error("Failed to execute xcresulttool: {0}")]
+    ExecutionError(String),
+
+    #[error("xcresulttool returned non-zero exit code: {0}")]
+    NonZeroExitCode(i32),
+
+    #[error("Failed to parse JSON output: {0}")]
+    JsonParseError(#[from] serde_json::Error),
+
+    #[error("Path does not exist: {0}")]
+    PathNotFound(PathBuf),
+
+    #[error("Invalid UTF-8 in xcresulttool output")]
+    Utf8Error(#[from] std::string::FromUtf8Error),
+
+    #[error(
+        "xcresulttool version {0} is too old, Xcode 16 or later is required (select it with --developer-dir)"
+    )]
+    UnsupportedVersion(u32),
+}
+
+pub struct XcresultTool {
+    xcrun_path: PathBuf,
+    developer_dir: Option<PathBuf>,
+}
+
+impl XcresultTool {
+    /// Create a client using `xcrun` and the selected developer directory
+    pub fn new() -> Self {
+        Self {
+            xcrun_path: PathBuf::from("xcrun"),
+            developer_dir: developer_dir::get(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn xcrun_path(&self) -> &Path {
+        &self.xcrun_path
+    }
+
+    /// Version reported by `xcresulttool version`, e.g. 23500
+    pub fn version(&self) -> Option<u32> {
+        *VERSIONS
+            .lock()
+            .unwrap()
+            .entry(self.developer_dir.clone())
+            .or_insert_with(|| {
+                let output = self.command(["version"]).output().ok()?;
+                parse_version(&String::from_utf8_lossy(&output.stdout))
+            })
+    }
+
+    /// Summary of a bundle (`get test-results summary`)
+    pub fn summary(&self, xcresult_path: &Path) -> Result<XCResultSummary, XcresultToolError> {
+        self.get_test_results("summary", xcresult_path, &[])
+    }
+
+    /// Per-test results of a bundle, including skipped tests (`get test-results tests`)
+    pub fn tests(&self, xcresult_path: &Path) -> Result<XCTestResults, XcresultToolError> {
+        self.get_test_results("tests", xcresult_path, &[])
+    }
+
+    /// Run tree and failures of one test (`get test-results test-details`)
+    pub fn test_details(
+        &self,
+        xcresult_path: &Path,
+        test_id: &str,
+    ) -> Result<XCTestResultDetail, XcresultToolError> {
+        self.get_test_results("test-details", xcresult_path, &["--test-id", test_id])
+    }
+
+    /// Performance measurements of one test (`get test-results metrics`)
+    pub fn test_metrics(
+        &self,
+        xcresult_path: &Path,
+        test_id: &str,
+    ) -> Result<Vec<TestWithMetrics>, XcresultToolError> {
+        self.get_test_results("metrics", xcresult_path, &["--test-id", test_id])
+    }
+
+    /// Export the attachments of one test into `output_dir` (`export attachments`)
+    pub fn export_attachments(
+        &self,
+        xcresult_path: &Path,
+        test_id: &str,
+        output_dir: &Path,
+    ) -> Result<(), XcresultToolError> {
+        self.run(
+            [
+                OsStr::new("export"),
+                OsStr::new("attachments"),
+                OsStr::new("--test-id"),
+                OsStr::new(test_id),
+                OsStr::new("--path"),
+                xcresult_path.as_os_str(),
+                OsStr::new("--output-path"),
+                output_dir.as_os_str(),
+            ],
+            xcresult_path,
+        )?;
+        Ok(())
+    }
+
+    /// Run `get test-results <report>` and deserialize its JSON output
+    fn get_test_results<T: DeserializeOwned>(
+        &self,
+        report: &str,
+        xcresult_path: &Path,
+        extra_args: &[&str],
+    ) -> Result<T, XcresultToolError> {
+        let mut args = vec![
+            OsStr::new("get"),
+            OsStr::new("test-results"),
+            OsStr::new(report),
+        ];
+        args.extend(extra_args.iter().map(OsStr::new));
+        args.extend([OsStr::new("--path"), xcresult_path.as_os_str()]);
+
+        let json_str = self.run(args, xcresult_path)?;
+        Ok(serde_json::from_str(&json_str)?)
+    }
+
+    /// Run xcresulttool on the bundle at `xcresult_path` and return its stdout
+    fn run<I, S>(&self, args: I, xcresult_path: &Path) -> Result<String, XcresultToolError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        if !xcresult_path.exists() {
+            return Err(XcresultToolError::PathNotFound(xcresult_path.to_path_buf()));
+        }
+        if let Some(version) = self.version()
+            && version < MIN_XCRESULTTOOL_VERSION
+        {
+            return Err(XcresultToolError::UnsupportedVersion(version));
+        }
+
+        let output = self
+            .command(args)
+            .output()
+            .map_err(|e| XcresultToolError::ExecutionError(e.to_string()))?;
+
+        if !output.status.success() {
+            let exit_code = output.status.code().unwrap_or(-1);
+            return Err(XcresultToolError::NonZeroExitCode(exit_code));
+        }
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    fn command<I, S>(&self, args: I) -> std::process::Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command =
+            developer_dir::command_with(&self.xcrun_path, self.developer_dir.as_deref());
+        command.arg("xcresulttool").args(args);
+        command
+    }
+}
+
+impl Default for XcresultTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Version number from output like `xcresulttool version 23500, format version 3.53 (current)`
+fn parse_version(output: &str) -> Option<u32> {
+    let rest = output.trim().strip_prefix("xcresulttool version ")?;
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("xcresulttool version 23500, format version 3.53 (current)\n"),
+            Some(23500)
+        );
+        assert_eq!(parse_version("xcresulttool version 22608"), Some(22608));
+        assert_eq!(parse_version("xcrun: error: unable to find utility"), None);
+    }
+
+    #[test]
+    fn test_missing_bundle_is_reported_before_running() {
+        let tool = XcresultTool::new();
+        let path = Path::new("/nonexistent/path/to/test.xcresult");
+
+        assert!(matches!(
+            tool.summary(path),
+            Err(XcresultToolError::PathNotFound(_))
+        ));
+        assert!(matches!(
+            tool.export_attachments(path, "test-id", Path::new("/tmp")),
+            Err(XcresultToolError::PathNotFound(_))
+        ));
+    }
+}
@@ -1,6 +1,48 @@
+use crate::xcresulttool::{XcresultTool, XcresultToolError};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::sync::LazyLock;
+
+/// `File.swift:28: message`, the name xcresulttool gives failure message nodes
+static FAILURE_LOCATION_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([^:\s][^:]*\.swift):(\d+): (.*)$").unwrap());
+
+/// Failure messages of a crashed test, e.g. `Crash: App at ContentView.body.getter`,
+/// `testLogin() crashed with signal SIGABRT` or an uncaught exception
+static CRASH_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^crash:|\bcrashed\b|\buncaught exception\b|\bsignal (SIG[A-Z]+|\d+)\b")
+        .unwrap()
+});
+
+/// Failure messages of a measurement that exceeded its baseline, e.g.
+/// `Clock Monotonic Time average is 24% worse than baseline (max allowed regression: 10%)`
+static PERFORMANCE_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\bbaseline\b.*\b(worse|regression)\b|\b(worse|regression)\b.*\bbaseline\b")
+        .unwrap()
+});
+
+/// Regression XCTest allows when a baseline sets no limit of its own
+const DEFAULT_MAX_PERCENT_REGRESSION: f64 = 10.0;
+
+/// Whether a file name is a crash log, e.g. `AutoFixSampler-2025-10-13-214329.ips`
+pub fn is_crash_log(name: &str) -> bool {
+    Path::new(name).extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("crash") || extension.eq_ignore_ascii_case("ips")
+    })
+}
+
+/// How a test failed, which decides the guidance the model gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// An assertion failed
+    Assertion,
+    /// The app or test process crashed, e.g. on a signal or an uncaught exception
+    Crash,
+    /// A measurement (`XCTMetric`) exceeded its baseline
+    PerformanceRegression,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -9,30 +51,290 @@ pub struct XCTestResultDetail {
     #[serde(rename = "testIdentifierURL")]
     pub test_identifier_url: String,
     pub test_name: String,
-    pub test_description: String,
     pub test_result: String,
+    // Fields below are missing from some Xcode versions' output
+    #[serde(default)]
+    pub test_description: String,
+    #[serde(default)]
     pub start_time: f64,
+    #[serde(default)]
     pub duration: String,
+    #[serde(default)]
     pub duration_in_seconds: f64,
+    #[serde(default)]
     pub has_media_attachments: bool,
+    #[serde(default)]
     pub has_performance_metrics: bool,
+    #[serde(default)]
     pub devices: Vec<Device>,
+    #[serde(default)]
     pub test_plan_configurations: Vec<TestPlanConfiguration>,
+    #[serde(default)]
     pub test_runs: Vec<TestRun>,
+    /// Every failed assertion of the test, filled in by `XCTestResultDetailParser::parse`
+    #[serde(default)]
+    pub failures: Vec<FailureDetail>,
+    /// Measurements of a performance test, filled in by `XCTestResultDetailParser::parse`
+    #[serde(default)]
+    pub performance_metrics: Vec<PerformanceMetric>,
+    /// Fields of newer xcresulttool versions that aren't modeled here
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A single failed assertion within a test
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FailureDetail {
+    pub message: String,
+    /// Source file name as reported by XCTest, e.g. `LoginTests.swift`
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl FailureDetail {
+    /// `File.swift:28` if the location is known
+    pub fn location(&self) -> Option<String> {
+        Some(format!("{}:{}", self.file.as_ref()?, self.line?))
+    }
+
+    /// Split a failure node name into location and message
+    fn from_message(name: &str) -> Self {
+        match FAILURE_LOCATION_PATTERN.captures(name) {
+            Some(captures) => Self {
+                message: captures[3].to_string(),
+                file: Some(captures[1].to_string()),
+                line: captures[2].parse().ok(),
+            },
+            None => Self {
+                message: name.to_string(),
+                file: None,
+                line: None,
+            },
+        }
+    }
+}
+
+impl XCTestResultDetail {
+    /// Collect the failed assertions from the test run tree, across all runs and devices
+    ///
+    /// Failure message nodes carry the source location. Older bundles only name
+    /// the failure on the failed test case run, which is used as a fallback.
+    pub fn collect_failures(&self) -> Vec<FailureDetail> {
+        let mut nodes = Vec::new();
+        for run in &self.test_runs {
+            Self::flatten(&run.children, &mut nodes);
+        }
+
+        let mut failures: Vec<FailureDetail> = nodes
+            .iter()
+            .filter(|node| node.node_type == "Failure Message")
+            .map(|node| FailureDetail::from_message(&node.name))
+            .collect();
+        if failures.is_empty() {
+            failures = nodes
+                .iter()
+                .filter(|node| {
+                    node.node_type == "Test Case Run" && node.result.as_deref() == Some("Failed")
+                })
+                .map(|node| FailureDetail::from_message(&node.name))
+                .collect();
+        }
+
+        // Retries and multiple devices repeat the same assertion
+        let mut unique: Vec<FailureDetail> = Vec::new();
+        for failure in failures {
+            if !unique.contains(&failure) {
+                unique.push(failure);
+            }
+        }
+        unique
+    }
+
+    /// Whether the test crashed or exceeded a performance baseline rather than failed
+    /// an assertion
+    ///
+    /// A crash leaves a crash or exception failure message instead of an assertion,
+    /// often next to a `.crash`/`.ips` crash log attachment. A performance regression
+    /// shows in the measurements or in the failure message naming the baseline.
+    pub fn failure_kind(&self) -> FailureKind {
+        let mut nodes = Vec::new();
+        for run in &self.test_runs {
+            Self::flatten(&run.children, &mut nodes);
+        }
+
+        let crashed = nodes.iter().any(|node| match node.node_type.as_str() {
+            "Failure Message" => CRASH_PATTERN.is_match(&node.name),
+            "Attachment" => is_crash_log(&node.name),
+            _ => false,
+        });
+        let regressed = self
+            .performance_metrics
+            .iter()
+            .any(PerformanceMetric::is_regression)
+            || nodes.iter().any(|node| {
+                node.node_type == "Failure Message" && PERFORMANCE_PATTERN.is_match(&node.name)
+            })
+            || self
+                .failures
+                .iter()
+                .any(|failure| PERFORMANCE_PATTERN.is_match(&failure.message));
+        if crashed {
+            FailureKind::Crash
+        } else if regressed {
+            FailureKind::PerformanceRegression
+        } else {
+            FailureKind::Assertion
+        }
+    }
+
+    fn flatten<'a>(nodes: &'a [TestNode], out: &mut Vec<&'a TestNode>) {
+        for node in nodes {
+            out.push(node);
+            Self::flatten(&node.children, out);
+        }
+    }
+}
+
+#[cfg(test)]
+impl XCTestResultDetail {
+    /// `LoginTests/testLogin()` failing with the given assertions, for tests across the crate
+    pub fn failing(failures: Vec<FailureDetail>) -> Self {
+        Self {
+            test_identifier: "LoginTests/testLogin()".to_string(),
+            test_identifier_url: "test://com.apple.xcode/App/AppUITests/LoginTests/testLogin"
+                .to_string(),
+            test_name: "testLogin()".to_string(),
+            test_description: String::new(),
+            test_result: "Failed".to_string(),
+            start_time: 0.0,
+            duration: "1s".to_string(),
+            duration_in_seconds: 1.0,
+            has_media_attachments: false,
+            has_performance_metrics: false,
+            devices: vec![],
+            test_plan_configurations: vec![],
+            test_runs: vec![],
+            failures,
+            performance_metrics: vec![],
+            extra: serde_json::Map::new(),
+        }
+    }
+}
+
+/// Measurements of one test, as printed by `xcresulttool get test-results metrics`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestWithMetrics {
+    #[serde(default)]
+    pub test_runs: Vec<TestRunWithMetrics>,
+}
+
+/// Measurements of a test on one device and test plan configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestRunWithMetrics {
+    #[serde(default)]
+    pub metrics: Vec<PerformanceMetric>,
+}
+
+/// One `XCTMetric` of a performance test, e.g. Clock Monotonic Time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PerformanceMetric {
+    pub display_name: String,
+    #[serde(default)]
+    pub unit_of_measurement: String,
+    #[serde(default)]
+    pub measurements: Vec<f64>,
+    #[serde(default)]
+    pub baseline_average: Option<f64>,
+    #[serde(default)]
+    pub max_percent_regression: Option<f64>,
+    /// `prefersLarger` for metrics where more is better, e.g. throughput
+    #[serde(default)]
+    pub polarity: Option<String>,
+}
+
+impl PerformanceMetric {
+    /// Mean of the measured iterations
+    pub fn average(&self) -> Option<f64> {
+        if self.measurements.is_empty() {
+            return None;
+        }
+        Some(self.measurements.iter().sum::<f64>() / self.measurements.len() as f64)
+    }
+
+    /// How much worse than the baseline the average is, in percent; negative when better
+    pub fn regression_percent(&self) -> Option<f64> {
+        let baseline = self.baseline_average.filter(|baseline| *baseline != 0.0)?;
+        let change = (self.average()? - baseline) / baseline * 100.0;
+        Some(if self.polarity.as_deref() == Some("prefersLarger") {
+            -change
+        } else {
+            change
+        })
+    }
+
+    /// Whether the average is worse than the baseline allows
+    pub fn is_regression(&self) -> bool {
+        self.regression_percent().is_some_and(|regression| {
+            regression
+                > self
+                    .max_percent_regression
+                    .unwrap_or(DEFAULT_MAX_PERCENT_REGRESSION)
+        })
+    }
+
+    /// Measured and baseline values, e.g.
+    /// `Clock Monotonic Time: average 1.250 s, baseline 1.000 s (25.0% worse, 10% allowed)`
+    pub fn summary(&self) -> String {
+        let unit = |value: f64| format!("{:.3} {}", value, self.unit_of_measurement);
+        let average = self.average().map_or_else(
+            || "no measurements".to_string(),
+            |average| format!("average {}", unit(average)),
+        );
+        let baseline = match (self.baseline_average, self.regression_percent()) {
+            (Some(baseline), Some(regression)) => format!(
+                ", baseline {} ({:.1}% {}, {}% allowed)",
+                unit(baseline),
+                regression.abs(),
+                if regression > 0.0 { "worse" } else { "better" },
+                self.max_percent_regression
+                    .unwrap_or(DEFAULT_MAX_PERCENT_REGRESSION)
+            ),
+            (Some(baseline), None) => format!(", baseline {}", unit(baseline)),
+            (None, _) => ", no baseline".to_string(),
+        };
+        format!("{}: {}{}", self.display_name, average, baseline)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Device {
+    #[serde(default)]
     pub device_id: String,
     pub device_name: String,
+    #[serde(default)]
     pub model_name: String,
+    #[serde(default)]
     pub architecture: String,
     pub platform: String,
     pub os_version: String,
+    #[serde(default)]
     pub os_build_number: String,
 }
 
+impl Device {
+    /// Platform and OS the test ran on, e.g. `iOS Simulator 26.0 (23A339)`
+    pub fn runtime(&self) -> String {
+        format!(
+            "{} {} ({})",
+            self.platform, self.os_version, self.os_build_number
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct TestPlanConfiguration {
@@ -47,10 +349,13 @@ pub struct TestRun {
     pub node_identifier: String,
     pub node_type: String,
     pub result: String,
+    #[serde(default)]
     pub duration: String,
+    #[serde(default)]
     pub duration_in_seconds: f64,
     #[serde(default)]
     pub details: Option<String>,
+    #[serde(default)]
     pub children: Vec<TestNode>,
 }
 
@@ -94,15 +399,29 @@ pub enum XCTestResultDetailParserError {
     EmptyTestId,
 }
 
+impl From<XcresultToolError> for XCTestResultDetailParserError {
+    fn from(error: XcresultToolError) -> Self {
+        match error {
+            XcresultToolError::NonZeroExitCode(code) => Self::NonZeroExitCode(code),
+            XcresultToolError::JsonParseError(e) => Self::JsonParseError(e),
+            XcresultToolError::PathNotFound(path) => Self::PathNotFound(path),
+            XcresultToolError::Utf8Error(e) => Self::Utf8Error(e),
+            XcresultToolError::ExecutionError(_) | XcresultToolError::UnsupportedVersion(_) => {
+                Self::ExecutionError(error.to_string())
+            }
+        }
+    }
+}
+
 pub struct XCTestResultDetailParser {
-    xcresulttool_path: PathBuf,
+    tool: XcresultTool,
 }
 
 impl XCTestResultDetailParser {
     /// Create a new XCTestResultDetailParser using the default xcresulttool path
     pub fn new() -> Self {
         Self {
-            xcresulttool_path: PathBuf::from("xcrun"),
+            tool: XcresultTool::new(),
         }
     }
 
@@ -124,26 +443,23 @@ impl XCTestResultDetailParser {
             return Err(XCTestResultDetailParserError::EmptyTestId);
         }
 
-        let output = Command::new(&self.xcresulttool_path)
-            .arg("xcresulttool")
-            .arg("get")
-            .arg("test-results")
-            .arg("test-details")
-            .arg("--test-id")
-            .arg(test_id)
-            .arg("--path")
-            .arg(path)
-            .output()
-            .map_err(|e| XCTestResultDetailParserError::ExecutionError(e.to_string()))?;
-
-        if !output.status.success() {
-            let exit_code = output.status.code().unwrap_or(-1);
-            return Err(XCTestResultDetailParserError::NonZeroExitCode(exit_code));
+        let mut result = self.tool.test_details(path, test_id)?;
+        result.failures = result.collect_failures();
+        if result.has_performance_metrics {
+            // The measurements only sharpen the guidance; the failure is known without them
+            result.performance_metrics = self
+                .tool
+                .test_metrics(path, test_id)
+                .map(|tests| {
+                    tests
+                        .into_iter()
+                        .flat_map(|test| test.test_runs)
+                        .flat_map(|run| run.metrics)
+                        .collect()
+                })
+                .unwrap_or_default();
         }
 
-        let json_str = String::from_utf8(output.stdout)?;
-        let result: XCTestResultDetail = serde_json::from_str(&json_str)?;
-
         Ok(result)
     }
 }
@@ -233,4 +549,130 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_detail_tolerates_missing_and_new_fields() {
+        let json = r#"{
+            "testIdentifier": "AutoFixSamplerUITests/testExample()",
+            "testIdentifierURL": "test://com.apple.xcode/AutoFixSampler/AutoFixSamplerUITests/AutoFixSamplerUITests/testExample",
+            "testName": "testExample()",
+            "testResult": "Failed",
+            "testRuns": [
+                {
+                    "name": "iPhone 17 Pro",
+                    "nodeIdentifier": "1",
+                    "nodeType": "Device",
+                    "result": "Failed"
+                }
+            ],
+            "insights": [{ "text": "Failed in 1 of 1 runs" }]
+        }"#;
+
+        let detail: XCTestResultDetail = serde_json::from_str(json).unwrap();
+        assert_eq!(detail.test_result, "Failed");
+        assert!(detail.devices.is_empty());
+        assert!(detail.test_runs[0].children.is_empty());
+        assert!(detail.extra.contains_key("insights"));
+    }
+
+    #[test]
+    fn test_collect_multiple_failures() {
+        let json =
+            std::fs::read_to_string("tests/fixtures/test_detail_multiple_failures.json").unwrap();
+        let detail: XCTestResultDetail = serde_json::from_str(&json).unwrap();
+
+        let failures = detail.collect_failures();
+
+        assert_eq!(
+            failures,
+            vec![
+                FailureDetail {
+                    message: "XCTAssertEqual failed: (\"Login\") is not equal to (\"Sign In\")"
+                        .to_string(),
+                    file: Some("AutoFixSamplerUITests.swift".to_string()),
+                    line: Some(34),
+                },
+                FailureDetail {
+                    message: "XCTAssertTrue failed - Welcome label does not exist".to_string(),
+                    file: Some("AutoFixSamplerUITests.swift".to_string()),
+                    line: Some(41),
+                },
+            ]
+        );
+        assert_eq!(
+            failures[1].location().as_deref(),
+            Some("AutoFixSamplerUITests.swift:41")
+        );
+    }
+
+    #[test]
+    fn test_collect_failures_falls_back_to_test_case_run() {
+        let json = std::fs::read_to_string("tests/fixtures/test_detail.json").unwrap();
+        let detail: XCTestResultDetail = serde_json::from_str(&json).unwrap();
+
+        let failures = detail.collect_failures();
+
+        assert_eq!(failures.len(), 1);
+        assert!(
+            failures[0]
+                .message
+                .starts_with("Failed to tap \"press me\" Button")
+        );
+        assert_eq!(failures[0].location(), None);
+    }
+
+    #[test]
+    fn test_crash_is_told_apart_from_assertion() {
+        let json = std::fs::read_to_string("tests/fixtures/test_detail_crash.json").unwrap();
+        let detail: XCTestResultDetail = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(detail.failure_kind(), FailureKind::Crash);
+        assert_eq!(
+            detail.collect_failures()[0].message,
+            "Crash: AutoFixSampler (4242) at LoginViewModel.submit() + 84"
+        );
+
+        let json =
+            std::fs::read_to_string("tests/fixtures/test_detail_multiple_failures.json").unwrap();
+        let detail: XCTestResultDetail = serde_json::from_str(&json).unwrap();
+        assert_eq!(detail.failure_kind(), FailureKind::Assertion);
+
+        assert!(is_crash_log("AutoFixSampler-2025-10-13-214329.ips"));
+        assert!(is_crash_log("AutoFixSampler.crash"));
+        assert!(!is_crash_log("Failure screenshot_0.png"));
+    }
+
+    #[test]
+    fn test_performance_metrics_and_regression() {
+        let json = std::fs::read_to_string("tests/fixtures/test_metrics.json").unwrap();
+        let tests: Vec<TestWithMetrics> = serde_json::from_str(&json).unwrap();
+        let metrics: Vec<PerformanceMetric> = tests
+            .into_iter()
+            .flat_map(|test| test.test_runs)
+            .flat_map(|run| run.metrics)
+            .collect();
+
+        assert_eq!(metrics.len(), 2);
+        let launch = &metrics[0];
+        assert_eq!(launch.average(), Some(1.25));
+        assert!((launch.regression_percent().unwrap() - 25.0).abs() < 1e-9);
+        assert!(launch.is_regression());
+        assert_eq!(
+            launch.summary(),
+            "Duration (AppLaunch): average 1.250 s, baseline 1.000 s (25.0% worse, 10% allowed)"
+        );
+        // Within the default allowance of 10%
+        assert!(!metrics[1].is_regression());
+
+        let json = std::fs::read_to_string("tests/fixtures/test_detail_performance.json").unwrap();
+        let mut detail: XCTestResultDetail = serde_json::from_str(&json).unwrap();
+        assert!(detail.has_performance_metrics);
+        assert_eq!(detail.failure_kind(), FailureKind::PerformanceRegression);
+
+        // The measurements alone tell, even without a failure message naming the baseline
+        detail.test_runs.clear();
+        assert_eq!(detail.failure_kind(), FailureKind::Assertion);
+        detail.performance_metrics = metrics;
+        assert_eq!(detail.failure_kind(), FailureKind::PerformanceRegression);
+    }
 }